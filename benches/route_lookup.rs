@@ -0,0 +1,46 @@
+//! Route lookup benchmark
+//!
+//! Compares a linear scan over the route table with the indexed lookup used
+//! by the proxy, for a table of prefix routes.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use open_gateway::proxy::{ProxyRoute, ProxyService, RouteTable};
+use open_gateway::GatewayConfig;
+use std::collections::HashMap;
+
+/// Build `count` prefix routes like `/service-42/*`
+fn routes(count: usize) -> Vec<ProxyRoute> {
+    let toml: String = (0..count)
+        .map(|i| {
+            format!("[[routes]]\npath = \"/service-{i}/*\"\ntarget = \"http://localhost:8081\"\n\n")
+        })
+        .collect();
+    let config = GatewayConfig::parse(&toml).unwrap();
+    ProxyService::routes_from_config(&config.routes, &HashMap::new()).unwrap()
+}
+
+fn bench_route_lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("route_lookup");
+    for count in [10, 100, 1000] {
+        let routes = routes(count);
+        let table = RouteTable::new(routes.clone());
+        // Worst case for the linear scan: the last route matches
+        let path = format!("/service-{}/items/1", count - 1);
+
+        group.bench_with_input(BenchmarkId::new("linear", count), &path, |b, path| {
+            b.iter(|| {
+                routes
+                    .iter()
+                    .find(|r| r.matches(black_box(path), "GET", None))
+                    .is_some()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("indexed", count), &path, |b, path| {
+            b.iter(|| table.find(black_box(path), "GET", None).is_some())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_route_lookup);
+criterion_main!(benches);
@@ -0,0 +1,580 @@
+//! Admin API module
+//!
+//! This module serves operator endpoints on each gateway server:
+//! - `POST /admin/drain` takes the gateway out of rotation: readiness fails
+//!   while liveness, in-flight requests and new proxied requests keep working
+//! - `POST /admin/undrain` puts it back in rotation
+//! - `GET /gateway/info` describes the running configuration (including
+//!   runtime toggles), API key pool usage and readiness, with secrets masked
+//! - `GET /gateway/config` returns the effective configuration (after
+//!   profiles, defaults and runtime toggles) as JSON, or as TOML for
+//!   `Accept: application/toml`, with secrets masked
+//! - `GET /admin/pools/{name}/sample?n=1000` runs the pool's key selection
+//!   `n` times without forwarding and returns how often each key was picked
+//! - Endpoints sit under `routing.base_path` and behind the master access
+//!   token guard, which must be enabled for `[admin]` to be enabled
+
+use crate::api_key::{create_selector, mask_key};
+use crate::config::GatewayConfig;
+use crate::health::{HealthChecker, HealthResponse};
+use crate::metrics::GatewayMetrics;
+use crate::tui::{build_pool_summaries, PoolSummary};
+use arc_swap::ArcSwap;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+
+/// Path of the info endpoint, relative to the base path
+pub const INFO_PATH: &str = "/gateway/info";
+
+/// Path of the effective configuration endpoint, relative to the base path
+pub const CONFIG_PATH: &str = "/gateway/config";
+
+/// Content type of TOML responses
+const TOML_CONTENT_TYPE: &str = "application/toml";
+
+/// Selections made by a pool sample when `n` is not given
+const DEFAULT_SAMPLES: usize = 1000;
+
+/// Most selections a single pool sample may make
+const MAX_SAMPLES: usize = 100_000;
+
+/// State shared by the admin handlers
+#[derive(Clone)]
+pub struct AdminState {
+    /// Active configuration, after runtime toggles were applied; replaced
+    /// when routes are reloaded in place
+    pub config: Arc<ArcSwap<GatewayConfig>>,
+    /// Shared gateway metrics
+    pub metrics: Arc<GatewayMetrics>,
+    /// Shared health checker
+    pub health: Arc<HealthChecker>,
+}
+
+/// Description of a running gateway served at `/gateway/info`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewayInfo {
+    /// Gateway version
+    pub version: String,
+    /// Active configuration with secrets masked
+    pub config: GatewayConfig,
+    /// API key pools with usage counts
+    pub pools: Vec<PoolSummary>,
+    /// Readiness, including self-check results
+    pub health: HealthResponse,
+}
+
+impl GatewayInfo {
+    /// Describe the gateway from its shared state
+    pub fn collect(state: &AdminState) -> Self {
+        let config = state.config.load();
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            config: redact(&config),
+            pools: build_pool_summaries(&config, &state.metrics),
+            health: state.health.readiness(),
+        }
+    }
+}
+
+/// Distribution of a pool's key selection served at
+/// `/admin/pools/{name}/sample`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolSample {
+    /// Pool name
+    pub pool: String,
+    /// Selection strategy name
+    pub strategy: String,
+    /// Number of selections made
+    pub samples: usize,
+    /// Selections that returned no key
+    pub unselected: usize,
+    /// Enabled keys in configured order, with how often each was picked
+    pub keys: Vec<KeySample>,
+}
+
+/// How often one key was picked in a pool sample
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeySample {
+    /// Masked key
+    pub key: String,
+    /// Configured weight
+    pub weight: u32,
+    /// Times the key was selected
+    pub count: usize,
+}
+
+/// Query parameters of the pool sample endpoint
+#[derive(Debug, Deserialize)]
+struct SampleParams {
+    /// Number of selections to make
+    n: Option<usize>,
+}
+
+/// Mask API keys, access tokens, header values and webhook URLs in a
+/// configuration, including the route defaults
+pub fn redact(config: &GatewayConfig) -> GatewayConfig {
+    let mut config = config.clone();
+    config.master_access_token.tokens.clear();
+    config.alerts.webhook_url = config.alerts.webhook_url.as_deref().map(mask_key);
+    if let Some(source) = &mut config.routes_source {
+        source.auth_header = source.auth_header.as_deref().map(mask_key);
+    }
+    for pool in config.api_key_pools.values_mut() {
+        for key in &mut pool.keys {
+            key.key = mask_key(&key.key);
+        }
+        if let Some(oauth2) = &mut pool.oauth2 {
+            oauth2.client_secret = mask_key(&oauth2.client_secret);
+        }
+    }
+    for route in &mut config.routes {
+        for value in route.headers.values_mut() {
+            *value = mask_key(value);
+        }
+    }
+    redact_route_defaults(&mut config.defaults.route);
+    config
+}
+
+/// Keys whose string values are masked wherever they appear in `[defaults.route]`
+const SECRET_FIELDS: [&str; 2] = ["key", "client_secret"];
+
+/// Mask the header values and secret fields of `[defaults.route]`, which is
+/// merged into every route and so may carry the same secrets
+fn redact_route_defaults(table: &mut toml::Table) {
+    for (name, value) in table.iter_mut() {
+        match value {
+            toml::Value::Table(headers) if name == "headers" => {
+                for (_, value) in headers.iter_mut() {
+                    if let toml::Value::String(header) = value {
+                        *header = mask_key(header);
+                    }
+                }
+            }
+            toml::Value::String(secret) if SECRET_FIELDS.contains(&name.as_str()) => {
+                *secret = mask_key(secret);
+            }
+            toml::Value::Table(nested) => redact_route_defaults(nested),
+            toml::Value::Array(items) => {
+                for item in items {
+                    if let toml::Value::Table(nested) = item {
+                        redact_route_defaults(nested);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Build the admin routes for a server
+pub fn admin_router<S>(state: AdminState) -> Router<S> {
+    let config = state.config.load();
+    let routing = &config.routing;
+    Router::new()
+        .route(&routing.endpoint_path("/admin/drain"), post(drain_handler))
+        .route(
+            &routing.endpoint_path("/admin/undrain"),
+            post(undrain_handler),
+        )
+        .route(&routing.endpoint_path(INFO_PATH), get(info_handler))
+        .route(&routing.endpoint_path(CONFIG_PATH), get(config_handler))
+        .route(
+            &routing.endpoint_path("/admin/pools/:name/sample"),
+            get(pool_sample_handler),
+        )
+        .with_state(state)
+}
+
+/// Drain handler
+async fn drain_handler(State(state): State<AdminState>) -> Json<serde_json::Value> {
+    state.health.set_draining(true);
+    info!("Draining: readiness now reports not ready");
+    Json(serde_json::json!({ "draining": true }))
+}
+
+/// Undrain handler
+async fn undrain_handler(State(state): State<AdminState>) -> Json<serde_json::Value> {
+    state.health.set_draining(false);
+    info!("Drain cancelled: readiness restored");
+    Json(serde_json::json!({ "draining": false }))
+}
+
+/// Info handler
+async fn info_handler(State(state): State<AdminState>) -> Json<GatewayInfo> {
+    Json(GatewayInfo::collect(&state))
+}
+
+/// Pool sample handler
+///
+/// Selections run on a fresh selector built from the pool's configuration,
+/// so the live pool's strategy state and usage counts are untouched.
+async fn pool_sample_handler(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+    Query(params): Query<SampleParams>,
+) -> Response {
+    let config = state.config.load();
+    let Some(pool) = config.api_key_pools.get(&name) else {
+        return (
+            StatusCode::NOT_FOUND,
+            format!("Unknown API key pool '{}'", name),
+        )
+            .into_response();
+    };
+    let samples = params.n.unwrap_or(DEFAULT_SAMPLES);
+    if samples == 0 || samples > MAX_SAMPLES {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("n must be between 1 and {}", MAX_SAMPLES),
+        )
+            .into_response();
+    }
+
+    let selector = create_selector(pool);
+    let enabled: Vec<_> = pool.keys.iter().filter(|k| k.enabled).collect();
+    let mut counts = vec![0; enabled.len()];
+    let mut unselected = 0;
+    for _ in 0..samples {
+        match selector
+            .get_key()
+            .and_then(|key| enabled.iter().position(|k| k.key == key))
+        {
+            Some(index) => counts[index] += 1,
+            None => unselected += 1,
+        }
+    }
+
+    Json(PoolSample {
+        pool: name,
+        strategy: selector.strategy_name().to_string(),
+        samples,
+        unselected,
+        keys: enabled
+            .iter()
+            .zip(counts)
+            .map(|(key, count)| KeySample {
+                key: mask_key(&key.key),
+                weight: key.weight,
+                count,
+            })
+            .collect(),
+    })
+    .into_response()
+}
+
+/// Effective configuration handler
+async fn config_handler(State(state): State<AdminState>, headers: HeaderMap) -> Response {
+    let config = redact(&state.config.load());
+    let wants_toml = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains(TOML_CONTENT_TYPE));
+    if !wants_toml {
+        return Json(config).into_response();
+    }
+    match toml::to_string_pretty(&config) {
+        Ok(body) => ([(header::CONTENT_TYPE, TOML_CONTENT_TYPE)], body).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to encode configuration: {}", e),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RouteConfig;
+    use crate::proxy::ProxyService;
+    use axum::body::Body;
+    use axum::http::{Method, Request, StatusCode};
+    use std::collections::HashMap;
+    use tower::ServiceExt;
+
+    /// Build a server router with probes, admin routes and a proxied route
+    async fn gateway(health: Arc<HealthChecker>) -> Router {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, Router::new().fallback(|| async { "upstream" }))
+                .await
+                .unwrap();
+        });
+
+        let route: RouteConfig = toml::from_str(&format!(
+            "path = \"/api/*\"\ntarget = \"http://{}\"",
+            upstream
+        ))
+        .unwrap();
+        let proxy = Arc::new(
+            ProxyService::new(
+                ProxyService::routes_from_config(&[route], &HashMap::new()).unwrap(),
+                Arc::new(GatewayMetrics::new()),
+            )
+            .unwrap(),
+        );
+
+        let liveness = health.clone();
+        let readiness = health.clone();
+        Router::new()
+            .route(
+                "/health",
+                get(move || async move { liveness.liveness().status_code() }),
+            )
+            .route(
+                "/ready",
+                get(move || async move { readiness.readiness().status_code() }),
+            )
+            .merge(admin_router(AdminState {
+                config: Arc::new(ArcSwap::from_pointee(GatewayConfig::default())),
+                metrics: Arc::new(GatewayMetrics::new()),
+                health,
+            }))
+            .fallback(move |req: Request<Body>| async move {
+                match proxy.forward(req).await {
+                    Ok(response) => response,
+                    Err(err) => axum::response::IntoResponse::into_response(err.status_code()),
+                }
+            })
+    }
+
+    async fn send(app: &Router, method: Method, uri: &str) -> StatusCode {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(req).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_drain_fails_readiness_only() {
+        let health = Arc::new(HealthChecker::new());
+        let app = gateway(health.clone()).await;
+        assert_eq!(send(&app, Method::GET, "/ready").await, StatusCode::OK);
+
+        assert_eq!(
+            send(&app, Method::POST, "/admin/drain").await,
+            StatusCode::OK
+        );
+        assert!(health.is_draining());
+        assert_eq!(
+            send(&app, Method::GET, "/ready").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(send(&app, Method::GET, "/health").await, StatusCode::OK);
+        assert_eq!(send(&app, Method::GET, "/api/users").await, StatusCode::OK);
+
+        // Self-checks completing during the drain don't end it
+        health.set_ready(true);
+        assert_eq!(
+            send(&app, Method::GET, "/ready").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        assert_eq!(
+            send(&app, Method::POST, "/admin/undrain").await,
+            StatusCode::OK
+        );
+        assert_eq!(send(&app, Method::GET, "/ready").await, StatusCode::OK);
+    }
+
+    #[test]
+    fn test_info_masks_secrets() {
+        let config = GatewayConfig::parse(
+            r#"
+[master_access_token]
+enabled = true
+tokens = ["master-secret"]
+
+[[routes]]
+name = "users"
+path = "/users/*"
+target = "http://localhost:8081"
+headers = { "X-Internal-Token" = "internal-secret-1234" }
+enabled = false
+
+[api_key_pools.default]
+keys = [{ key = "sk-pool-secret-0001" }]
+
+[api_key_pools.oauth]
+oauth2 = { token_url = "http://auth/token", client_id = "gateway", client_secret = "oauth-secret-0002" }
+"#,
+        )
+        .unwrap();
+        let info = GatewayInfo::collect(&AdminState {
+            config: Arc::new(ArcSwap::from_pointee(config)),
+            metrics: Arc::new(GatewayMetrics::new()),
+            health: Arc::new(HealthChecker::new()),
+        });
+
+        let json = serde_json::to_string(&info).unwrap();
+        assert!(!json.replace("\"client_secret\"", "").contains("secret"));
+
+        // Clients read the same description back
+        let info: GatewayInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(info.config.routes[0].name.as_deref(), Some("users"));
+        assert!(!info.config.routes[0].enabled);
+        assert_eq!(info.pools[0].keys[0].masked_key, "****0001");
+    }
+
+    #[test]
+    fn test_redact_masks_route_defaults() {
+        let config = GatewayConfig::parse(
+            r#"
+[defaults.route]
+timeout_ms = 5000
+headers = { "X-Internal-Token" = "internal-secret-1234" }
+"#,
+        )
+        .unwrap();
+        assert!(serde_json::to_string(&config.defaults)
+            .unwrap()
+            .contains("secret"));
+
+        let redacted = redact(&config);
+        let json = serde_json::to_string(&redacted.defaults).unwrap();
+        assert!(!json.contains("secret"), "{}", json);
+        let route = &redacted.defaults.route;
+        assert_eq!(
+            route["headers"]["X-Internal-Token"].as_str(),
+            Some("****1234")
+        );
+        assert_eq!(route["timeout_ms"].as_integer(), Some(5000));
+    }
+
+    #[tokio::test]
+    async fn test_pool_sample_follows_weights() {
+        let config = GatewayConfig::parse(
+            r#"
+[api_key_pools.weighted]
+strategy = "weight"
+keys = [
+    { key = "sk-weighted-key-0001", weight = 1 },
+    { key = "sk-weighted-key-0002", weight = 3 },
+    { key = "sk-weighted-key-0003", weight = 5, enabled = false },
+]
+"#,
+        )
+        .unwrap();
+        let app = admin_router::<()>(AdminState {
+            config: Arc::new(ArcSwap::from_pointee(config)),
+            metrics: Arc::new(GatewayMetrics::new()),
+            health: Arc::new(HealthChecker::new()),
+        });
+        let fetch = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+                let response = app.oneshot(req).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (status, body)
+            }
+        };
+
+        let (status, body) = fetch("/admin/pools/weighted/sample?n=8000").await;
+        assert_eq!(status, StatusCode::OK);
+        let sample: PoolSample = serde_json::from_slice(&body).unwrap();
+        assert_eq!(sample.strategy, "weight");
+        assert_eq!(sample.samples, 8000);
+        assert_eq!(sample.unselected, 0);
+        assert_eq!(sample.keys.len(), 2);
+        assert_eq!(sample.keys[0].key, "****0001");
+        assert_eq!(sample.keys[1].weight, 3);
+        // Expected 2000 and 6000; the bounds are several standard deviations wide
+        let light = sample.keys[0].count;
+        assert!((1700..=2300).contains(&light), "{:?}", sample.keys);
+        assert_eq!(light + sample.keys[1].count, 8000);
+
+        let (status, body) = fetch("/admin/pools/weighted/sample").await;
+        assert_eq!(status, StatusCode::OK);
+        let sample: PoolSample = serde_json::from_slice(&body).unwrap();
+        assert_eq!(sample.samples, DEFAULT_SAMPLES);
+
+        let (status, _) = fetch("/admin/pools/missing/sample").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = fetch("/admin/pools/weighted/sample?n=0").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = fetch("/admin/pools/weighted/sample?n=1000000").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_config_reflects_profile_and_masks_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_path = dir.path().join("config.toml");
+        std::fs::write(
+            &base_path,
+            r#"
+[server]
+port = 8080
+
+[[routes]]
+name = "api"
+path = "/api/*"
+target = "http://localhost:3001"
+headers = { "X-Internal-Token" = "internal-secret-1234" }
+
+[api_key_pools.default]
+keys = [{ key = "sk-pool-secret-0001" }]
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("config.prod.toml"),
+            "[server]\nport = 80\n\n[[routes]]\nname = \"api\"\npath = \"/api/*\"\ntarget = \"https://api.prod.example.com\"\nheaders = { \"X-Internal-Token\" = \"internal-secret-1234\" }\n",
+        )
+        .unwrap();
+        let config = GatewayConfig::from_file_with_profile(&base_path, Some("prod")).unwrap();
+        let app = admin_router::<()>(AdminState {
+            config: Arc::new(ArcSwap::from_pointee(config)),
+            metrics: Arc::new(GatewayMetrics::new()),
+            health: Arc::new(HealthChecker::new()),
+        });
+        let fetch = |accept: &'static str| {
+            let app = app.clone();
+            async move {
+                let req = Request::builder()
+                    .uri(CONFIG_PATH)
+                    .header(header::ACCEPT, accept)
+                    .body(Body::empty())
+                    .unwrap();
+                let response = app.oneshot(req).await.unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                String::from_utf8(body.to_vec()).unwrap()
+            }
+        };
+
+        let json = fetch("application/json").await;
+        assert!(!json.contains("secret"), "{}", json);
+        let config: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(config["server"]["port"], 80);
+        assert_eq!(
+            config["routes"][0]["target"],
+            "https://api.prod.example.com"
+        );
+        assert_eq!(
+            config["api_key_pools"]["default"]["keys"][0]["key"],
+            "****0001"
+        );
+
+        let toml_body = fetch(TOML_CONTENT_TYPE).await;
+        assert!(!toml_body.contains("secret"), "{}", toml_body);
+        let config: toml::Value = toml::from_str(&toml_body).unwrap();
+        assert_eq!(config["server"]["port"].as_integer(), Some(80));
+    }
+}
@@ -0,0 +1,299 @@
+//! Error-rate alerting module
+//!
+//! This module notifies a webhook when a route's error rate is too high:
+//! - Per-route request and 5xx counts are sampled from the gateway metrics,
+//!   and the error rate is computed over a rolling window
+//! - An alert fires once a route with enough traffic reaches
+//!   `error_rate_threshold`, and recovers when the rate drops below the
+//!   (lower) recovery threshold, so a rate hovering around the threshold
+//!   doesn't flap
+//! - Notifications are POSTed as JSON with a 10s deadline; failed deliveries
+//!   are logged
+
+use crate::config::AlertsConfig;
+use crate::metrics::{GatewayMetrics, RouteTotals};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Longest interval between two evaluations
+const MAX_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Deadline for connecting to the webhook
+const WEBHOOK_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Deadline for a webhook delivery, so a stalled receiver can't hold up
+/// the next evaluations
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// State of an alert notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertStatus {
+    /// The route's error rate reached the threshold
+    Firing,
+    /// The route's error rate dropped below the recovery threshold
+    Resolved,
+}
+
+/// Notification POSTed to the webhook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertNotification {
+    /// Whether the alert fired or recovered
+    pub status: AlertStatus,
+    /// Route label (name, or path pattern)
+    pub route: String,
+    /// Error rate over the window
+    pub error_rate: f64,
+    /// Requests over the window
+    pub requests: u64,
+    /// 5xx responses over the window
+    pub errors: u64,
+    /// Configured firing threshold
+    pub threshold: f64,
+    /// Window length in seconds
+    pub window_secs: u64,
+}
+
+/// Evaluates route error rates and notifies the webhook
+pub struct Alerter {
+    config: AlertsConfig,
+    metrics: Arc<GatewayMetrics>,
+    client: reqwest::Client,
+    samples: VecDeque<(Instant, HashMap<String, RouteTotals>)>,
+    firing: HashSet<String>,
+}
+
+impl Alerter {
+    /// Create an alerter reading `metrics`
+    pub fn new(config: AlertsConfig, metrics: Arc<GatewayMetrics>) -> Self {
+        Self {
+            config,
+            metrics,
+            client: reqwest::Client::builder()
+                .connect_timeout(WEBHOOK_CONNECT_TIMEOUT)
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            samples: VecDeque::new(),
+            firing: HashSet::new(),
+        }
+    }
+
+    /// Sample the metrics at `now` and get the notifications to send
+    fn evaluate(&mut self, now: Instant) -> Vec<AlertNotification> {
+        let window = Duration::from_secs(self.config.window_secs);
+        let current = self.metrics.route_totals();
+
+        // Keep the newest sample that is at least a window old as baseline
+        while self
+            .samples
+            .get(1)
+            .is_some_and(|(at, _)| now.duration_since(*at) >= window)
+        {
+            self.samples.pop_front();
+        }
+        let baseline = self
+            .samples
+            .front()
+            .map(|(_, totals)| totals.clone())
+            .unwrap_or_else(|| current.clone());
+        self.samples.push_back((now, current.clone()));
+
+        let threshold = self.config.error_rate_threshold;
+        let recovery = self.config.recovery_threshold();
+        let mut routes: Vec<&String> = current.keys().chain(self.firing.iter()).collect();
+        routes.sort();
+        routes.dedup();
+
+        let mut notifications = Vec::new();
+        for route in routes {
+            let now_totals = current.get(route).copied().unwrap_or_default();
+            let before = baseline.get(route).copied().unwrap_or_default();
+            let requests = now_totals.requests.saturating_sub(before.requests);
+            let errors = now_totals.errors.saturating_sub(before.errors);
+            let error_rate = if requests == 0 {
+                0.0
+            } else {
+                errors as f64 / requests as f64
+            };
+
+            let status = if self.firing.contains(route) {
+                (error_rate < recovery).then_some(AlertStatus::Resolved)
+            } else {
+                (requests >= self.config.min_requests && error_rate >= threshold)
+                    .then_some(AlertStatus::Firing)
+            };
+            if let Some(status) = status {
+                notifications.push(AlertNotification {
+                    status,
+                    route: route.clone(),
+                    error_rate,
+                    requests,
+                    errors,
+                    threshold,
+                    window_secs: self.config.window_secs,
+                });
+            }
+        }
+
+        for notification in &notifications {
+            match notification.status {
+                AlertStatus::Firing => self.firing.insert(notification.route.clone()),
+                AlertStatus::Resolved => self.firing.remove(&notification.route),
+            };
+        }
+        notifications
+    }
+
+    /// Evaluate the error rates at `now` and send any notifications
+    pub async fn check(&mut self, now: Instant) {
+        let Some(url) = self.config.webhook_url.clone() else {
+            return;
+        };
+        for notification in self.evaluate(now) {
+            info!(
+                "Alert {:?} for route '{}': error rate {:.1}% over {}s",
+                notification.status,
+                notification.route,
+                notification.error_rate * 100.0,
+                notification.window_secs
+            );
+            let result = self
+                .client
+                .post(&url)
+                .json(&notification)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(e) = result {
+                warn!("Failed to send alert to webhook: {}", e);
+            }
+        }
+    }
+}
+
+/// Evaluate error rates periodically until `shutdown_rx` turns `true`
+pub async fn run_alerts(
+    config: AlertsConfig,
+    metrics: Arc<GatewayMetrics>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    let interval = Duration::from_secs(config.window_secs).min(MAX_CHECK_INTERVAL);
+    let mut alerter = Alerter::new(config, metrics);
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => alerter.check(Instant::now()).await,
+            changed = shutdown_rx.changed() => {
+                if changed.is_err() || *shutdown_rx.borrow() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Json, Router};
+
+    /// Spawn a webhook receiver forwarding the notifications it gets
+    async fn spawn_webhook() -> (String, tokio::sync::mpsc::Receiver<AlertNotification>) {
+        let (tx, rx) = tokio::sync::mpsc::channel(8);
+        let app = Router::new().route(
+            "/hook",
+            post(move |Json(notification): Json<AlertNotification>| {
+                let tx = tx.clone();
+                async move {
+                    tx.send(notification).await.unwrap();
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (format!("http://{}/hook", addr), rx)
+    }
+
+    fn record(metrics: &GatewayMetrics, status: u16, count: usize) {
+        for _ in 0..count {
+            metrics.record_route_request("users", "default", status);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fire_and_recovery_notifications() {
+        let (url, mut notifications) = spawn_webhook().await;
+        let metrics = Arc::new(GatewayMetrics::new());
+        let config = AlertsConfig {
+            webhook_url: Some(url),
+            window_secs: 60,
+            ..Default::default()
+        };
+        let mut alerter = Alerter::new(config, metrics.clone());
+        let start = Instant::now();
+        alerter.check(start).await;
+
+        // 6 of 10 requests fail within the window
+        record(&metrics, 200, 4);
+        record(&metrics, 502, 6);
+        alerter.check(start + Duration::from_secs(10)).await;
+        let fired = notifications.recv().await.unwrap();
+        assert_eq!(fired.status, AlertStatus::Firing);
+        assert_eq!(fired.route, "users");
+        assert_eq!((fired.requests, fired.errors), (10, 6));
+
+        // Still above the recovery threshold: no repeated notification
+        record(&metrics, 200, 2);
+        alerter.check(start + Duration::from_secs(20)).await;
+        assert!(notifications.try_recv().is_err());
+
+        // Once the failures leave the window, the alert recovers
+        record(&metrics, 200, 20);
+        alerter.check(start + Duration::from_secs(75)).await;
+        let resolved = notifications.recv().await.unwrap();
+        assert_eq!(resolved.status, AlertStatus::Resolved);
+        assert_eq!(resolved.route, "users");
+        assert_eq!(resolved.errors, 0);
+    }
+
+    #[tokio::test]
+    async fn test_hysteresis_and_min_requests() {
+        let metrics = Arc::new(GatewayMetrics::new());
+        let mut alerter = Alerter::new(
+            AlertsConfig {
+                error_rate_threshold: 0.5,
+                recovery_threshold: Some(0.2),
+                min_requests: 10,
+                ..Default::default()
+            },
+            metrics.clone(),
+        );
+        let start = Instant::now();
+        alerter.evaluate(start);
+
+        // Too little traffic to fire
+        record(&metrics, 500, 5);
+        assert!(alerter.evaluate(start + Duration::from_secs(1)).is_empty());
+
+        record(&metrics, 500, 5);
+        let fired = alerter.evaluate(start + Duration::from_secs(2));
+        assert_eq!(fired[0].status, AlertStatus::Firing);
+
+        // 10 errors out of 40 (25%) is below the threshold but not recovered
+        record(&metrics, 200, 30);
+        assert!(alerter.evaluate(start + Duration::from_secs(3)).is_empty());
+
+        // 4xx responses aren't errors
+        record(&metrics, 404, 20);
+        let resolved = alerter.evaluate(start + Duration::from_secs(4));
+        assert_eq!(resolved[0].status, AlertStatus::Resolved);
+    }
+}
@@ -4,89 +4,280 @@
 //! - Round Robin: Cycles through keys in order
 //! - Random: Selects a random key
 //! - Weight: Selects keys based on configured weights
+//!
+//! Strategies implement [`KeySelectionStrategy`] and are looked up by name in a
+//! [`StrategyRegistry`], so custom algorithms can be plugged in without forking.
 
-use crate::config::{ApiKeyConfig, ApiKeyPool, ApiKeyStrategy};
+use crate::config::{ApiKeyConfig, ApiKeyPool};
+use crate::oauth2::TokenSource;
 use rand::Rng;
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use tracing::warn;
+
+/// A key selection algorithm
+///
+/// Implementations receive the enabled keys of a pool and return the index of
+/// the key to use, or `None` if no key can be selected.
+pub trait KeySelectionStrategy: Send + Sync {
+    /// Select a key from the given list of enabled keys
+    fn select(&self, keys: &[ApiKeyConfig]) -> Option<usize>;
+}
+
+/// Round-robin selection: cycles through keys in order
+#[derive(Debug, Default)]
+pub struct RoundRobinStrategy {
+    index: AtomicUsize,
+}
+
+impl KeySelectionStrategy for RoundRobinStrategy {
+    fn select(&self, keys: &[ApiKeyConfig]) -> Option<usize> {
+        if keys.is_empty() {
+            return None;
+        }
+        Some(self.index.fetch_add(1, Ordering::SeqCst) % keys.len())
+    }
+}
+
+/// Random selection: picks a uniformly random key
+#[derive(Debug, Default)]
+pub struct RandomStrategy;
+
+impl KeySelectionStrategy for RandomStrategy {
+    fn select(&self, keys: &[ApiKeyConfig]) -> Option<usize> {
+        if keys.is_empty() {
+            return None;
+        }
+        Some(rand::thread_rng().gen_range(0..keys.len()))
+    }
+}
+
+/// Weighted selection: picks keys proportionally to their configured weight
+///
+/// Only enabled keys with a non-zero weight are candidates; without any,
+/// no key is selected.
+#[derive(Debug, Default)]
+pub struct WeightedStrategy;
+
+impl KeySelectionStrategy for WeightedStrategy {
+    fn select(&self, keys: &[ApiKeyConfig]) -> Option<usize> {
+        let candidates = || {
+            keys.iter()
+                .enumerate()
+                .filter(|(_, k)| k.enabled && k.weight > 0)
+        };
+        let total_weight: u64 = candidates().map(|(_, k)| u64::from(k.weight)).sum();
+        if total_weight == 0 {
+            return None;
+        }
+
+        let random_weight = rand::thread_rng().gen_range(0..total_weight);
+        let mut cumulative_weight = 0u64;
+        candidates()
+            .find(|(_, key)| {
+                cumulative_weight += u64::from(key.weight);
+                random_weight < cumulative_weight
+            })
+            .map(|(index, _)| index)
+    }
+}
+
+/// Factory creating a fresh strategy instance for each selector
+///
+/// Each pool gets its own instance so stateful strategies (like round-robin)
+/// keep independent state per pool.
+pub type StrategyFactory = Arc<dyn Fn() -> Box<dyn KeySelectionStrategy> + Send + Sync>;
+
+/// Registry mapping strategy names to their implementations
+pub struct StrategyRegistry {
+    factories: RwLock<HashMap<String, StrategyFactory>>,
+}
+
+impl StrategyRegistry {
+    /// Create a registry containing the built-in strategies
+    pub fn new() -> Self {
+        let registry = Self {
+            factories: RwLock::new(HashMap::new()),
+        };
+        registry.register("round_robin", || {
+            Box::new(RoundRobinStrategy::default()) as Box<dyn KeySelectionStrategy>
+        });
+        registry.register("random", || {
+            Box::new(RandomStrategy) as Box<dyn KeySelectionStrategy>
+        });
+        registry.register("weight", || {
+            Box::new(WeightedStrategy) as Box<dyn KeySelectionStrategy>
+        });
+        registry
+    }
+
+    /// Get the process-wide registry used when loading configuration
+    pub fn global() -> &'static StrategyRegistry {
+        static GLOBAL: OnceLock<StrategyRegistry> = OnceLock::new();
+        GLOBAL.get_or_init(StrategyRegistry::new)
+    }
+
+    /// Register a strategy under the given name, replacing any existing one
+    pub fn register<F>(&self, name: &str, factory: F)
+    where
+        F: Fn() -> Box<dyn KeySelectionStrategy> + Send + Sync + 'static,
+    {
+        self.factories
+            .write()
+            .expect("strategy registry lock poisoned")
+            .insert(name.to_string(), Arc::new(factory));
+    }
+
+    /// Check if a strategy with the given name is registered
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories
+            .read()
+            .expect("strategy registry lock poisoned")
+            .contains_key(name)
+    }
+
+    /// Create a new instance of the named strategy
+    pub fn create(&self, name: &str) -> Option<Box<dyn KeySelectionStrategy>> {
+        let factory = self
+            .factories
+            .read()
+            .expect("strategy registry lock poisoned")
+            .get(name)
+            .cloned();
+        factory.map(|f| f())
+    }
+}
+
+impl Default for StrategyRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Register a custom strategy in the global registry
+///
+/// Pools can then reference it by name via `strategy = "<name>"`.
+pub fn register_strategy<F>(name: &str, factory: F)
+where
+    F: Fn() -> Box<dyn KeySelectionStrategy> + Send + Sync + 'static,
+{
+    StrategyRegistry::global().register(name, factory);
+}
 
 /// API Key selector that manages a pool of API keys
-#[derive(Debug)]
 pub struct ApiKeySelector {
     /// List of enabled API keys
     keys: Vec<ApiKeyConfig>,
-    /// Selection strategy
-    strategy: ApiKeyStrategy,
+    /// Selection strategy implementation
+    strategy: Box<dyn KeySelectionStrategy>,
+    /// Selection strategy name
+    strategy_name: String,
     /// Header name for the API key
     pub header_name: String,
     /// Query parameter name for the API key (optional)
     pub query_param_name: Option<String>,
-    /// Current index for round-robin selection
-    round_robin_index: AtomicUsize,
-    /// Total weight for weighted selection
-    total_weight: u32,
+    /// OAuth2 token source used instead of the keys (optional)
+    token_source: Option<TokenSource>,
+    /// Instances of the strategies forced by name, kept so their state
+    /// carries over between requests
+    forced: Mutex<HashMap<String, Arc<dyn KeySelectionStrategy>>>,
+}
+
+impl std::fmt::Debug for ApiKeySelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiKeySelector")
+            .field("keys", &self.keys.len())
+            .field("strategy", &self.strategy_name)
+            .field("header_name", &self.header_name)
+            .field("query_param_name", &self.query_param_name)
+            .field("token_source", &self.token_source)
+            .finish()
+    }
 }
 
 impl ApiKeySelector {
     /// Create a new API key selector from a pool configuration
     pub fn new(pool: &ApiKeyPool) -> Self {
+        Self::with_registry(pool, StrategyRegistry::global())
+    }
+
+    /// Create a new API key selector resolving the strategy from the given registry
+    ///
+    /// Unknown strategy names fall back to round-robin.
+    pub fn with_registry(pool: &ApiKeyPool, registry: &StrategyRegistry) -> Self {
         let keys: Vec<ApiKeyConfig> = pool.keys.iter().filter(|k| k.enabled).cloned().collect();
-        let total_weight: u32 = keys.iter().map(|k| k.weight).sum();
+        let strategy_name = pool.strategy.as_str().to_string();
+        let strategy = registry.create(&strategy_name).unwrap_or_else(|| {
+            warn!(
+                "Unknown API key strategy '{}', falling back to round_robin",
+                strategy_name
+            );
+            Box::new(RoundRobinStrategy::default())
+        });
 
         Self {
             keys,
-            strategy: pool.strategy.clone(),
+            strategy,
+            strategy_name,
             header_name: pool.header_name.clone(),
             query_param_name: pool.query_param_name.clone(),
-            round_robin_index: AtomicUsize::new(0),
-            total_weight,
+            token_source: pool.oauth2.clone().map(TokenSource::new),
+            forced: Mutex::new(HashMap::new()),
         }
     }
 
     /// Get the next API key based on the configured strategy
+    ///
+    /// Returns `None` when the strategy has no key to offer; a disabled key
+    /// is never returned.
     pub fn get_key(&self) -> Option<&str> {
-        if self.keys.is_empty() {
-            return None;
-        }
-
-        match self.strategy {
-            ApiKeyStrategy::RoundRobin => self.get_round_robin(),
-            ApiKeyStrategy::Random => self.get_random(),
-            ApiKeyStrategy::Weight => self.get_weighted(),
-        }
+        self.select(self.strategy.as_ref())
     }
 
-    /// Round-robin selection
-    fn get_round_robin(&self) -> Option<&str> {
-        let index = self.round_robin_index.fetch_add(1, Ordering::SeqCst) % self.keys.len();
-        Some(&self.keys[index].key)
-    }
-
-    /// Random selection
-    fn get_random(&self) -> Option<&str> {
-        let index = rand::thread_rng().gen_range(0..self.keys.len());
-        Some(&self.keys[index].key)
+    /// Get a key chosen by the named strategy, leaving the configured
+    /// strategy's state untouched
+    ///
+    /// Each name gets its own instance, created on first use and kept, so a
+    /// forced `round_robin` keeps cycling. Strategy names missing from the
+    /// global registry are logged and fall back to the configured strategy.
+    pub fn get_key_with(&self, strategy_name: &str) -> Option<&str> {
+        let strategy = {
+            let mut forced = self.forced.lock().unwrap();
+            match forced.get(strategy_name) {
+                Some(strategy) => strategy.clone(),
+                None => {
+                    let Some(strategy) = StrategyRegistry::global().create(strategy_name) else {
+                        warn!(
+                            "Unknown API key strategy '{}' requested, using {}",
+                            strategy_name, self.strategy_name
+                        );
+                        return self.get_key();
+                    };
+                    let strategy: Arc<dyn KeySelectionStrategy> = Arc::from(strategy);
+                    forced.insert(strategy_name.to_string(), strategy.clone());
+                    strategy
+                }
+            }
+        };
+        self.select(strategy.as_ref())
     }
 
-    /// Weighted selection
-    fn get_weighted(&self) -> Option<&str> {
-        if self.total_weight == 0 {
-            return self.get_random();
+    fn select(&self, strategy: &dyn KeySelectionStrategy) -> Option<&str> {
+        if self.keys.is_empty() {
+            return None;
         }
 
-        let mut rng = rand::thread_rng();
-        let random_weight = rng.gen_range(0..self.total_weight);
-        let mut cumulative_weight = 0u32;
-
-        for key in &self.keys {
-            cumulative_weight += key.weight;
-            if random_weight < cumulative_weight {
-                return Some(&key.key);
-            }
-        }
+        strategy
+            .select(&self.keys)
+            .and_then(|index| self.keys.get(index))
+            .filter(|k| k.enabled)
+            .map(|k| k.key.as_str())
+    }
 
-        // Fallback to last key (should not happen)
-        self.keys.last().map(|k| k.key.as_str())
+    /// Get the OAuth2 token source, for pools that inject access tokens
+    pub fn token_source(&self) -> Option<&TokenSource> {
+        self.token_source.as_ref()
     }
 
     /// Get the number of keys in the pool
@@ -100,13 +291,21 @@ impl ApiKeySelector {
     }
 
     /// Get the strategy name
-    pub fn strategy_name(&self) -> &'static str {
-        match self.strategy {
-            ApiKeyStrategy::RoundRobin => "round_robin",
-            ApiKeyStrategy::Random => "random",
-            ApiKeyStrategy::Weight => "weight",
-        }
+    pub fn strategy_name(&self) -> &str {
+        &self.strategy_name
+    }
+}
+
+/// Mask an API key for display, keeping only its last four characters
+///
+/// Keys of eight characters or fewer are fully masked.
+pub fn mask_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 8 {
+        return "****".to_string();
     }
+    let suffix: String = chars[chars.len() - 4..].iter().collect();
+    format!("****{}", suffix)
 }
 
 /// Thread-safe wrapper for ApiKeySelector
@@ -120,6 +319,7 @@ pub fn create_selector(pool: &ApiKeyPool) -> SharedApiKeySelector {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::ApiKeyStrategy;
 
     fn create_test_pool(strategy: ApiKeyStrategy) -> ApiKeyPool {
         ApiKeyPool {
@@ -143,6 +343,7 @@ mod tests {
             strategy,
             header_name: "X-API-Key".to_string(),
             query_param_name: None,
+            oauth2: None,
         }
     }
 
@@ -161,6 +362,24 @@ mod tests {
         assert_eq!(selector.get_key(), Some("key2"));
     }
 
+    #[test]
+    fn test_forced_strategy_keeps_state() {
+        let pool = create_test_pool(ApiKeyStrategy::Weight);
+        let selector = ApiKeySelector::new(&pool);
+
+        let keys: Vec<_> = (0..4)
+            .map(|_| selector.get_key_with("round_robin"))
+            .collect();
+        assert_eq!(
+            keys,
+            [Some("key1"), Some("key2"), Some("key1"), Some("key2")]
+        );
+
+        // Unknown names use the configured strategy
+        assert!(selector.get_key_with("missing").is_some());
+        assert_eq!(selector.forced.lock().unwrap().len(), 1);
+    }
+
     #[test]
     fn test_random() {
         let pool = create_test_pool(ApiKeyStrategy::Random);
@@ -198,6 +417,38 @@ mod tests {
         assert!(ratio > 1.5 && ratio < 2.5, "Weighted ratio: {}", ratio);
     }
 
+    #[test]
+    fn test_weighted_without_candidates() {
+        let key = |name: &str, weight: u32, enabled: bool| ApiKeyConfig {
+            key: name.to_string(),
+            weight,
+            enabled,
+        };
+
+        // All enabled keys have weight 0
+        let pool = ApiKeyPool {
+            keys: vec![key("key1", 0, true), key("key2", 0, true)],
+            ..create_test_pool(ApiKeyStrategy::Weight)
+        };
+        let selector = ApiKeySelector::new(&pool);
+        assert_eq!(selector.len(), 2);
+        assert_eq!(selector.get_key(), None);
+
+        // All keys disabled
+        let keys = vec![key("key1", 1, false), key("key2", 3, false)];
+        assert_eq!(WeightedStrategy.select(&keys), None);
+
+        // Zero-weight and disabled keys are never picked
+        let keys = vec![
+            key("key1", 0, true),
+            key("key2", 5, false),
+            key("key3", 1, true),
+        ];
+        for _ in 0..50 {
+            assert_eq!(WeightedStrategy.select(&keys), Some(2));
+        }
+    }
+
     #[test]
     fn test_empty_pool() {
         let pool = ApiKeyPool {
@@ -205,10 +456,40 @@ mod tests {
             strategy: ApiKeyStrategy::RoundRobin,
             header_name: "X-API-Key".to_string(),
             query_param_name: None,
+            oauth2: None,
         };
         let selector = ApiKeySelector::new(&pool);
 
         assert!(selector.is_empty());
         assert_eq!(selector.get_key(), None);
     }
+
+    #[test]
+    fn test_mask_key() {
+        assert_eq!(mask_key("short"), "****");
+        assert_eq!(mask_key("Bearer sk-abcdef1234"), "****1234");
+    }
+
+    /// Always picks the last enabled key
+    struct LastKeyStrategy;
+
+    impl KeySelectionStrategy for LastKeyStrategy {
+        fn select(&self, keys: &[ApiKeyConfig]) -> Option<usize> {
+            keys.len().checked_sub(1)
+        }
+    }
+
+    #[test]
+    fn test_custom_strategy() {
+        register_strategy("last_key", || Box::new(LastKeyStrategy));
+        assert!(StrategyRegistry::global().contains("last_key"));
+
+        let pool = create_test_pool(ApiKeyStrategy::Custom("last_key".to_string()));
+        let selector = create_selector(&pool);
+
+        assert_eq!(selector.strategy_name(), "last_key");
+        for _ in 0..5 {
+            assert_eq!(selector.get_key(), Some("key2"));
+        }
+    }
 }
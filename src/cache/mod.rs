@@ -0,0 +1,570 @@
+//! Response cache module
+//!
+//! This module provides the per-route response cache:
+//! - Successful GET responses are stored in memory for a configured TTL
+//! - Requests with credentials (`Authorization`, `Cookie`) bypass the cache,
+//!   and responses are stored per variant of the headers named by their
+//!   `Vary`
+//! - Concurrent identical requests during a cache miss are coalesced so only
+//!   one of them reaches the upstream (single-flight); waiting requests only
+//!   get the response if it is cacheable and they select the same variant
+//! - Clients sending an `If-None-Match` that matches a cached `ETag` get a
+//!   `304 Not Modified` without the upstream being called
+
+use crate::config::CacheConfig;
+use axum::body::Body;
+use axum::http::{
+    header, HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode,
+};
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// A buffered upstream response
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    /// Response status
+    pub status: StatusCode,
+    /// Response headers
+    pub headers: HeaderMap,
+    /// Response body
+    pub body: Bytes,
+}
+
+impl CachedResponse {
+    /// Build a response to send to the client
+    pub fn to_response(&self) -> Response<Body> {
+        let mut response = Response::new(Body::from(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        response
+    }
+
+    /// Build a response for a client that sent `if_none_match`
+    ///
+    /// Answers `304 Not Modified` without a body when the client already has
+    /// the current representation, and the full response otherwise.
+    pub fn to_response_for(&self, if_none_match: Option<&HeaderValue>) -> Response<Body> {
+        let not_modified = self.status == StatusCode::OK
+            && self
+                .etag()
+                .zip(if_none_match)
+                .is_some_and(|(etag, header)| etag_matches(header, etag));
+        if !not_modified {
+            return self.to_response();
+        }
+
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::NOT_MODIFIED;
+        for name in NOT_MODIFIED_HEADERS {
+            for value in self.headers.get_all(&name) {
+                response.headers_mut().append(&name, value.clone());
+            }
+        }
+        response
+    }
+
+    /// Get the entity tag of the response
+    pub fn etag(&self) -> Option<&HeaderValue> {
+        self.headers.get(header::ETAG)
+    }
+
+    /// Check whether the response may be stored
+    pub fn is_cacheable(&self) -> bool {
+        self.status == StatusCode::OK
+            && !has_cache_directive(&self.headers, &["no-store", "private"])
+            && self.vary().is_some()
+    }
+
+    /// Get the request headers named by the response's `Vary`, or `None`
+    /// for `Vary: *` (a different response for every request)
+    pub fn vary(&self) -> Option<Vec<HeaderName>> {
+        let mut names = Vec::new();
+        for name in self
+            .headers
+            .get_all(header::VARY)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            if name == "*" {
+                return None;
+            }
+            if let Ok(name) = HeaderName::from_bytes(name.as_bytes()) {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        Some(names)
+    }
+}
+
+/// Values of the `vary` request headers, identifying a response variant
+fn variant(vary: &[HeaderName], headers: &HeaderMap) -> String {
+    vary.iter()
+        .map(|name| {
+            let values: Vec<&[u8]> = headers.get_all(name).iter().map(|v| v.as_bytes()).collect();
+            format!(
+                "{}: {}",
+                name,
+                String::from_utf8_lossy(&values.join(&b", "[..]))
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Cache key of the variant of `key` selected by `headers`
+fn variant_key(key: &str, vary: &[HeaderName], headers: &HeaderMap) -> String {
+    if vary.is_empty() {
+        return key.to_string();
+    }
+    format!("{}\n{}", key, variant(vary, headers))
+}
+
+/// Headers a `304 Not Modified` repeats from the full response (RFC 9110)
+const NOT_MODIFIED_HEADERS: [header::HeaderName; 6] = [
+    header::ETAG,
+    header::CACHE_CONTROL,
+    header::CONTENT_LOCATION,
+    header::DATE,
+    header::EXPIRES,
+    header::VARY,
+];
+
+/// Result shared with requests waiting on an in-flight fetch: the response
+/// and the variant the leading request selected
+type FlightResult = Option<(Arc<CachedResponse>, String)>;
+
+/// Role of a request in a single-flight group
+pub enum Flight {
+    /// This request fetches from the upstream and completes the flight
+    Leader(FlightGuard),
+    /// Another request is already fetching; wait for its response
+    Follower(watch::Receiver<FlightResult>),
+}
+
+/// Completes an in-flight fetch
+///
+/// Dropping the guard without calling [`FlightGuard::complete`] (e.g. when
+/// the upstream request failed) releases waiting requests so they fetch on
+/// their own.
+pub struct FlightGuard {
+    cache: Arc<ResponseCache>,
+    key: String,
+    tx: watch::Sender<FlightResult>,
+}
+
+impl FlightGuard {
+    /// Store the response to the request with `headers` if cacheable and
+    /// hand it to waiting requests
+    ///
+    /// Responses that can't be cached are kept from waiting requests too,
+    /// which then fetch on their own.
+    pub fn complete(self, headers: &HeaderMap, response: CachedResponse) {
+        let response = Arc::new(response);
+        let Some(vary) = response.vary().filter(|_| response.is_cacheable()) else {
+            return;
+        };
+        self.cache.insert(&self.key, headers, response.clone());
+        self.tx
+            .send_replace(Some((response, variant(&vary, headers))));
+    }
+}
+
+impl Drop for FlightGuard {
+    fn drop(&mut self) {
+        self.cache.inflight.lock().unwrap().remove(&self.key);
+    }
+}
+
+struct Entry {
+    response: Arc<CachedResponse>,
+    expires_at: Instant,
+}
+
+/// In-memory response cache for a route
+pub struct ResponseCache {
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<String, Entry>>,
+    /// Request headers named by the last stored response's `Vary`, per key
+    vary: Mutex<HashMap<String, Vec<HeaderName>>>,
+    inflight: Mutex<HashMap<String, watch::Sender<FlightResult>>>,
+}
+
+impl ResponseCache {
+    /// Create an empty cache
+    pub fn new(config: &CacheConfig) -> Self {
+        Self {
+            ttl: Duration::from_secs(config.ttl_secs),
+            max_entries: config.max_entries,
+            entries: Mutex::new(HashMap::new()),
+            vary: Mutex::new(HashMap::new()),
+            inflight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get a fresh cached response to a request with `headers`
+    pub fn get(&self, key: &str, headers: &HeaderMap) -> Option<Arc<CachedResponse>> {
+        let vary = self
+            .vary
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .unwrap_or_default();
+        let key = variant_key(key, &vary, headers);
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(&key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.response.clone()),
+            Some(_) => {
+                entries.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Store the response to a request with `headers`, evicting the entry
+    /// closest to expiry when full
+    ///
+    /// A response varying on request headers is stored for the variant
+    /// `headers` select.
+    pub fn insert(&self, key: &str, headers: &HeaderMap, response: Arc<CachedResponse>) {
+        let vary = response.vary().unwrap_or_default();
+        let mut entries = self.entries.lock().unwrap();
+        {
+            let mut known = self.vary.lock().unwrap();
+            if !known.contains_key(key) && known.len() >= self.max_entries {
+                // Forget the variants of keys without stored responses
+                let stored: std::collections::HashSet<&str> = entries
+                    .keys()
+                    .filter_map(|k| k.split('\n').next())
+                    .collect();
+                known.retain(|k, _| stored.contains(k.as_str()));
+            }
+            known.insert(key.to_string(), vary.clone());
+        }
+        let key = &variant_key(key, &vary, headers);
+        if !entries.contains_key(key) && entries.len() >= self.max_entries {
+            let now = Instant::now();
+            entries.retain(|_, e| e.expires_at > now);
+            if entries.len() >= self.max_entries {
+                if let Some(oldest) = entries
+                    .iter()
+                    .min_by_key(|(_, e)| e.expires_at)
+                    .map(|(k, _)| k.clone())
+                {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        entries.insert(
+            key.to_string(),
+            Entry {
+                response,
+                expires_at: Instant::now() + self.ttl,
+            },
+        );
+    }
+
+    /// Join the in-flight fetch for `key`, or become its leader
+    pub fn begin(self: &Arc<Self>, key: &str) -> Flight {
+        let mut inflight = self.inflight.lock().unwrap();
+        if let Some(tx) = inflight.get(key) {
+            return Flight::Follower(tx.subscribe());
+        }
+        let (tx, _) = watch::channel(None);
+        inflight.insert(key.to_string(), tx.clone());
+        Flight::Leader(FlightGuard {
+            cache: self.clone(),
+            key: key.to_string(),
+            tx,
+        })
+    }
+
+    /// Number of stored entries
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Check if the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Wait for the leader of a flight to finish, for a request with `headers`
+///
+/// Returns `None` if the leader gave up without a cacheable response, or
+/// its response is a different variant than `headers` select.
+pub async fn wait_for_leader(
+    mut rx: watch::Receiver<FlightResult>,
+    headers: &HeaderMap,
+) -> Option<Arc<CachedResponse>> {
+    let (response, leader_variant) = rx.wait_for(Option::is_some).await.ok()?.clone()?;
+    let vary = response.vary()?;
+    (variant(&vary, headers) == leader_variant).then_some(response)
+}
+
+/// Get the cache key for a request, or `None` if it must not be cached
+///
+/// Only GET requests without credentials (`Authorization` or `Cookie`) or
+/// a `no-store` directive are cacheable. Requests are keyed by method and
+/// path with query.
+pub fn cache_key<B>(req: &Request<B>) -> Option<String> {
+    if req.method() != Method::GET
+        || req.headers().contains_key(header::AUTHORIZATION)
+        || req.headers().contains_key(header::COOKIE)
+        || has_cache_directive(req.headers(), &["no-store"])
+    {
+        return None;
+    }
+    let path_and_query = req
+        .uri()
+        .path_and_query()
+        .map(|pq| pq.as_str())
+        .unwrap_or("/");
+    Some(format!("{} {}", req.method(), path_and_query))
+}
+
+/// Check whether an `If-None-Match` header matches `etag`
+///
+/// Uses the weak comparison required for `If-None-Match`, so `W/"a"` matches
+/// `"a"`. `*` matches any entity tag.
+pub fn etag_matches(if_none_match: &HeaderValue, etag: &HeaderValue) -> bool {
+    let (Ok(if_none_match), Ok(etag)) = (if_none_match.to_str(), etag.to_str()) else {
+        return false;
+    };
+    let etag = opaque_tag(etag);
+    if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque_tag(tag) == etag)
+}
+
+/// Strip the weakness indicator from an entity tag
+fn opaque_tag(tag: &str) -> &str {
+    let tag = tag.trim();
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+/// Check whether `Cache-Control` contains any of the given directives
+fn has_cache_directive(headers: &HeaderMap, directives: &[&str]) -> bool {
+    headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|d| {
+            directives
+                .iter()
+                .any(|wanted| d.trim().eq_ignore_ascii_case(wanted))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(ttl_secs: u64, max_entries: usize) -> CacheConfig {
+        CacheConfig {
+            ttl_secs,
+            max_entries,
+        }
+    }
+
+    fn ok_response(body: &'static str) -> CachedResponse {
+        CachedResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Bytes::from(body),
+        }
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let req = Request::get("/api/items?page=2").body(()).unwrap();
+        assert_eq!(cache_key(&req).as_deref(), Some("GET /api/items?page=2"));
+
+        let req = Request::post("/api/items").body(()).unwrap();
+        assert_eq!(cache_key(&req), None);
+
+        let req = Request::get("/api/items")
+            .header("Authorization", "Bearer token")
+            .body(())
+            .unwrap();
+        assert_eq!(cache_key(&req), None);
+
+        let req = Request::get("/api/items")
+            .header("Cookie", "session=a")
+            .body(())
+            .unwrap();
+        assert_eq!(cache_key(&req), None);
+
+        let req = Request::get("/api/items")
+            .header("Cache-Control", "no-store")
+            .body(())
+            .unwrap();
+        assert_eq!(cache_key(&req), None);
+    }
+
+    #[test]
+    fn test_etag_matches() {
+        let etag = HeaderValue::from_static("\"v1\"");
+        let matches = |header: &'static str| etag_matches(&HeaderValue::from_static(header), &etag);
+        assert!(matches("\"v1\""));
+        assert!(matches("W/\"v1\""));
+        assert!(matches("\"v0\", \"v1\""));
+        assert!(matches("*"));
+        assert!(!matches("\"v2\""));
+    }
+
+    #[test]
+    fn test_not_modified_response() {
+        let mut cached = ok_response("body");
+        cached
+            .headers
+            .insert(header::ETAG, HeaderValue::from_static("\"v1\""));
+        cached
+            .headers
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+
+        let response = cached.to_response_for(Some(&HeaderValue::from_static("\"v1\"")));
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], "\"v1\"");
+        assert!(!response.headers().contains_key(header::CONTENT_TYPE));
+
+        let response = cached.to_response_for(Some(&HeaderValue::from_static("\"v0\"")));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(cached.to_response_for(None).status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_insert_and_expire() {
+        let cache = ResponseCache::new(&config(60, 10));
+        cache.insert("GET /a", &HeaderMap::new(), Arc::new(ok_response("a")));
+        assert_eq!(
+            cache.get("GET /a", &HeaderMap::new()).unwrap().body,
+            Bytes::from("a")
+        );
+
+        let cache = ResponseCache::new(&config(0, 10));
+        cache.insert("GET /a", &HeaderMap::new(), Arc::new(ok_response("a")));
+        assert!(cache.get("GET /a", &HeaderMap::new()).is_none());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_max_entries() {
+        let cache = ResponseCache::new(&config(60, 2));
+        cache.insert("GET /a", &HeaderMap::new(), Arc::new(ok_response("a")));
+        cache.insert("GET /b", &HeaderMap::new(), Arc::new(ok_response("b")));
+        cache.insert("GET /c", &HeaderMap::new(), Arc::new(ok_response("c")));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("GET /a", &HeaderMap::new()).is_none());
+        assert!(cache.get("GET /c", &HeaderMap::new()).is_some());
+    }
+
+    #[test]
+    fn test_uncacheable_responses() {
+        let mut response = ok_response("a");
+        response.headers.insert(
+            header::CACHE_CONTROL,
+            "private, max-age=60".parse().unwrap(),
+        );
+        assert!(!response.is_cacheable());
+
+        let response = CachedResponse {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            ..ok_response("a")
+        };
+        assert!(!response.is_cacheable());
+    }
+
+    #[tokio::test]
+    async fn test_single_flight() {
+        let cache = Arc::new(ResponseCache::new(&config(60, 10)));
+
+        let Flight::Leader(guard) = cache.begin("GET /a") else {
+            panic!("first request should lead");
+        };
+        let Flight::Follower(rx) = cache.begin("GET /a") else {
+            panic!("second request should follow");
+        };
+
+        guard.complete(&HeaderMap::new(), ok_response("a"));
+        let shared = wait_for_leader(rx, &HeaderMap::new()).await.unwrap();
+        assert_eq!(shared.body, Bytes::from("a"));
+        assert!(cache.get("GET /a", &HeaderMap::new()).is_some());
+
+        // The flight is over; a new miss gets a new leader
+        assert!(matches!(cache.begin("GET /a"), Flight::Leader(_)));
+    }
+
+    #[test]
+    fn test_vary_selects_variant() {
+        let cache = ResponseCache::new(&config(60, 10));
+        let language = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT_LANGUAGE, HeaderValue::from_static(value));
+            headers
+        };
+        let mut response = ok_response("hallo");
+        response
+            .headers
+            .insert(header::VARY, HeaderValue::from_static("Accept-Language"));
+        cache.insert("GET /a", &language("de"), Arc::new(response));
+
+        assert_eq!(
+            cache.get("GET /a", &language("de")).unwrap().body,
+            Bytes::from("hallo")
+        );
+        assert!(cache.get("GET /a", &language("en")).is_none());
+        assert!(cache.get("GET /a", &HeaderMap::new()).is_none());
+
+        let mut response = ok_response("a");
+        response
+            .headers
+            .insert(header::VARY, HeaderValue::from_static("*"));
+        assert!(!response.is_cacheable());
+    }
+
+    #[tokio::test]
+    async fn test_uncacheable_response_not_shared() {
+        let cache = Arc::new(ResponseCache::new(&config(60, 10)));
+
+        let Flight::Leader(guard) = cache.begin("GET /a") else {
+            panic!("first request should lead");
+        };
+        let Flight::Follower(rx) = cache.begin("GET /a") else {
+            panic!("second request should follow");
+        };
+
+        let mut response = ok_response("mine");
+        response
+            .headers
+            .insert(header::CACHE_CONTROL, HeaderValue::from_static("private"));
+        guard.complete(&HeaderMap::new(), response);
+        assert!(wait_for_leader(rx, &HeaderMap::new()).await.is_none());
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_flight_releases_followers() {
+        let cache = Arc::new(ResponseCache::new(&config(60, 10)));
+
+        let leader = cache.begin("GET /a");
+        let Flight::Follower(rx) = cache.begin("GET /a") else {
+            panic!("second request should follow");
+        };
+
+        drop(leader);
+        assert!(wait_for_leader(rx, &HeaderMap::new()).await.is_none());
+    }
+}
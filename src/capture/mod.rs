@@ -0,0 +1,170 @@
+//! Request capture module
+//!
+//! This module writes sampled request/response pairs to disk for routes with
+//! a `capture` section, to debug intermittent upstream failures:
+//! - Each capture is one JSON file named after the time and route
+//! - Credential headers (`Authorization`, cookies, the route's API key
+//!   header and static headers) and the API key query parameter are masked
+//! - Request and response bodies are truncated to `max_bytes`
+
+use crate::api_key::mask_key;
+use crate::config::CaptureConfig;
+use axum::http::{HeaderMap, HeaderName, StatusCode};
+use serde_json::json;
+use std::path::PathBuf;
+
+/// Headers masked in every capture
+const SENSITIVE_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+];
+
+/// Capture settings of one route
+#[derive(Debug)]
+pub struct Capture {
+    config: CaptureConfig,
+    /// Headers carrying the route's API keys or static values, masked as well
+    key_headers: Vec<HeaderName>,
+    /// Query parameters carrying the route's API keys, masked in the URI
+    key_params: Vec<String>,
+}
+
+/// Request half of a capture, recorded before the request is forwarded
+pub struct CapturedRequest {
+    method: String,
+    uri: String,
+    headers: HeaderMap,
+    body: bytes::Bytes,
+}
+
+impl CapturedRequest {
+    /// Record a request as sent to the upstream
+    pub fn new(method: &str, uri: &str, headers: HeaderMap, body: bytes::Bytes) -> Self {
+        Self {
+            method: method.to_string(),
+            uri: uri.to_string(),
+            headers,
+            body,
+        }
+    }
+}
+
+impl Capture {
+    /// Create the capture settings for a route whose secrets travel in
+    /// `key_headers` (the injected key, a client's own, the route's static
+    /// headers) and `key_params`
+    pub fn new(config: CaptureConfig, key_headers: &[&str], key_params: &[&str]) -> Self {
+        Self {
+            config,
+            key_headers: key_headers
+                .iter()
+                .filter_map(|name| name.parse().ok())
+                .collect(),
+            key_params: key_params.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    /// Decide whether to capture the current request
+    pub fn sampled(&self) -> bool {
+        let rate = self.config.sample_rate;
+        rate >= 1.0 || (rate > 0.0 && rand::random::<f64>() < rate)
+    }
+
+    /// Write a request and its response to a new file in the capture
+    /// directory, returning the file's path
+    ///
+    /// `response_body` is `None` for streamed responses.
+    pub async fn write(
+        &self,
+        route: &str,
+        request: &CapturedRequest,
+        status: StatusCode,
+        response_headers: &HeaderMap,
+        response_body: Option<&[u8]>,
+    ) -> std::io::Result<PathBuf> {
+        let now = chrono::Utc::now();
+        let mut response = json!({
+            "status": status.as_u16(),
+            "headers": self.headers_json(response_headers),
+        });
+        if let Some(body) = response_body {
+            response["body"] = self.body_json(body);
+            response["body_truncated"] = json!(body.len() > self.config.max_bytes);
+        }
+        let capture = json!({
+            "captured_at": now.to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+            "route": route,
+            "request": {
+                "method": request.method,
+                "uri": self.uri_json(&request.uri),
+                "headers": self.headers_json(&request.headers),
+                "body": self.body_json(&request.body),
+                "body_truncated": request.body.len() > self.config.max_bytes,
+            },
+            "response": response,
+        });
+
+        let route: String = route
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+                _ => '_',
+            })
+            .collect();
+        let file_name = format!(
+            "{}-{}-{:08x}.json",
+            now.format("%Y%m%dT%H%M%S%.6fZ"),
+            route,
+            rand::random::<u32>()
+        );
+        tokio::fs::create_dir_all(&self.config.dir).await?;
+        let path = self.config.dir.join(file_name);
+        let contents = serde_json::to_vec_pretty(&capture).map_err(std::io::Error::other)?;
+        tokio::fs::write(&path, contents).await?;
+        Ok(path)
+    }
+
+    /// Headers as a JSON object, with credentials masked and repeated
+    /// headers joined
+    fn headers_json(&self, headers: &HeaderMap) -> serde_json::Value {
+        let mut object = serde_json::Map::new();
+        for name in headers.keys() {
+            let sensitive =
+                SENSITIVE_HEADERS.contains(&name.as_str()) || self.key_headers.contains(name);
+            let values: Vec<String> = headers
+                .get_all(name)
+                .iter()
+                .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+                .map(|v| if sensitive { mask_key(&v) } else { v })
+                .collect();
+            object.insert(name.as_str().to_string(), json!(values.join(", ")));
+        }
+        serde_json::Value::Object(object)
+    }
+
+    /// URI with the values of the key query parameters masked
+    fn uri_json(&self, uri: &str) -> String {
+        let Some((path, query)) = uri.split_once('?') else {
+            return uri.to_string();
+        };
+        let query: Vec<String> = query
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((name, value)) if self.key_params.iter().any(|p| p == name) => {
+                    format!("{}={}", name, mask_key(value))
+                }
+                _ => pair.to_string(),
+            })
+            .collect();
+        format!("{}?{}", path, query.join("&"))
+    }
+
+    /// Body as a (lossy UTF-8) string cut at `max_bytes`
+    fn body_json(&self, body: &[u8]) -> serde_json::Value {
+        let kept = &body[..body.len().min(self.config.max_bytes)];
+        json!(String::from_utf8_lossy(kept))
+    }
+}
@@ -0,0 +1,321 @@
+//! Circuit breaker module
+//!
+//! This module protects upstreams that are failing or responding slowly:
+//! - Outcomes of the most recent calls are tracked in a rolling window
+//! - The circuit opens when the failure rate or slow call rate is too high
+//! - While open, requests are rejected without reaching the upstream
+//! - After the open duration a single trial call decides whether to close
+//! - Calls abandoned before their outcome is recorded (timeouts, clients
+//!   going away) count as failed, so a lost trial can't keep the circuit
+//!   half-open
+
+use crate::config::CircuitBreakerConfig;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Circuit breaker state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls flow through normally
+    Closed,
+    /// Calls are rejected until the open duration elapses
+    Open,
+    /// A single trial call is allowed through
+    HalfOpen,
+}
+
+/// Outcome of a single upstream call
+#[derive(Debug, Clone, Copy)]
+struct CallOutcome {
+    failed: bool,
+    slow: bool,
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: CircuitState,
+    opened_at: Option<Instant>,
+    trial_in_flight: bool,
+    window: VecDeque<CallOutcome>,
+}
+
+/// Circuit breaker for a single route
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    /// Create a closed circuit breaker
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                state: CircuitState::Closed,
+                opened_at: None,
+                trial_in_flight: false,
+                window: VecDeque::with_capacity(config.window_size),
+            }),
+            config,
+        }
+    }
+
+    /// Get the current state
+    pub fn state(&self) -> CircuitState {
+        self.inner.lock().unwrap().state
+    }
+
+    /// Check whether a call may be made, returning the call to settle with
+    /// its outcome
+    ///
+    /// Moves an open circuit to half-open once the open duration has elapsed
+    /// and lets exactly one trial call through.
+    pub fn try_call(&self) -> Option<BreakerCall<'_>> {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.state {
+            CircuitState::Closed => {}
+            CircuitState::Open => {
+                let open_duration = Duration::from_secs(self.config.open_duration_secs);
+                if inner.opened_at.is_none_or(|t| t.elapsed() < open_duration) {
+                    return None;
+                }
+                inner.state = CircuitState::HalfOpen;
+                inner.trial_in_flight = true;
+            }
+            CircuitState::HalfOpen => {
+                if inner.trial_in_flight {
+                    return None;
+                }
+                inner.trial_in_flight = true;
+            }
+        }
+        Some(BreakerCall {
+            breaker: self,
+            started: Instant::now(),
+            settled: false,
+        })
+    }
+
+    /// Check whether a call would currently be rejected
+    ///
+    /// Unlike [`CircuitBreaker::try_call`] this doesn't claim the
+    /// half-open trial, so it can be used to refuse work early.
+    pub fn is_rejecting(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+        match inner.state {
+            CircuitState::Closed => false,
+            CircuitState::Open => {
+                let open_duration = Duration::from_secs(self.config.open_duration_secs);
+                inner.opened_at.is_none_or(|t| t.elapsed() < open_duration)
+            }
+            CircuitState::HalfOpen => inner.trial_in_flight,
+        }
+    }
+
+    /// Record the outcome of a call
+    pub fn record(&self, success: bool, latency: Duration) {
+        let slow = self
+            .config
+            .slow_call_threshold_ms
+            .is_some_and(|ms| latency > Duration::from_millis(ms));
+        let outcome = CallOutcome {
+            failed: !success,
+            slow,
+        };
+
+        let mut inner = self.inner.lock().unwrap();
+        match inner.state {
+            CircuitState::HalfOpen => {
+                inner.trial_in_flight = false;
+                if outcome.failed || outcome.slow {
+                    Self::open(&mut inner);
+                } else {
+                    inner.state = CircuitState::Closed;
+                    inner.opened_at = None;
+                    inner.window.clear();
+                }
+            }
+            CircuitState::Closed => {
+                if inner.window.len() == self.config.window_size {
+                    inner.window.pop_front();
+                }
+                inner.window.push_back(outcome);
+                if self.should_open(&inner.window) {
+                    Self::open(&mut inner);
+                }
+            }
+            // Calls started before the circuit opened
+            CircuitState::Open => {}
+        }
+    }
+
+    fn should_open(&self, window: &VecDeque<CallOutcome>) -> bool {
+        if window.len() < self.config.minimum_calls {
+            return false;
+        }
+        let total = window.len() as f64;
+        let failed = window.iter().filter(|o| o.failed).count() as f64;
+        let slow = window.iter().filter(|o| o.slow).count() as f64;
+        failed / total >= self.config.failure_rate || slow / total >= self.config.slow_call_rate
+    }
+
+    fn open(inner: &mut Inner) {
+        inner.state = CircuitState::Open;
+        inner.opened_at = Some(Instant::now());
+        inner.window.clear();
+    }
+}
+
+/// A call let through by a [`CircuitBreaker`]
+///
+/// Settle it with [`BreakerCall::record`]. Dropping it without an outcome
+/// (e.g. on a timeout, or when the client went away) records it as failed,
+/// once; a dropped half-open trial reopens the circuit instead of leaving it
+/// waiting for a trial that never finishes.
+#[must_use]
+pub struct BreakerCall<'a> {
+    breaker: &'a CircuitBreaker,
+    started: Instant,
+    settled: bool,
+}
+
+impl BreakerCall<'_> {
+    /// Record the outcome of the call
+    pub fn record(mut self, success: bool, latency: Duration) {
+        self.settled = true;
+        self.breaker.record(success, latency);
+    }
+}
+
+impl Drop for BreakerCall<'_> {
+    fn drop(&mut self) {
+        if !self.settled {
+            self.breaker.record(false, self.started.elapsed());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAST: Duration = Duration::from_millis(10);
+    const SLOW: Duration = Duration::from_millis(500);
+
+    fn slow_call_config() -> CircuitBreakerConfig {
+        CircuitBreakerConfig {
+            window_size: 10,
+            minimum_calls: 5,
+            slow_call_threshold_ms: Some(100),
+            slow_call_rate: 0.5,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fast_calls_keep_circuit_closed() {
+        let breaker = CircuitBreaker::new(slow_call_config());
+        for _ in 0..20 {
+            breaker.try_call().unwrap().record(true, FAST);
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_slow_burst_opens_circuit() {
+        let breaker = CircuitBreaker::new(slow_call_config());
+        for _ in 0..10 {
+            breaker.record(true, FAST);
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        // Four slow calls in a window of ten stay under the rate
+        for _ in 0..4 {
+            breaker.record(true, SLOW);
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        breaker.record(true, SLOW);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(breaker.try_call().is_none());
+    }
+
+    #[test]
+    fn test_slow_calls_ignored_without_threshold() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            slow_call_threshold_ms: None,
+            ..slow_call_config()
+        });
+        for _ in 0..20 {
+            breaker.record(true, SLOW);
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_failures_open_circuit() {
+        let breaker = CircuitBreaker::new(slow_call_config());
+        for _ in 0..5 {
+            breaker.record(false, FAST);
+        }
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[test]
+    fn test_half_open_trial() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            open_duration_secs: 0,
+            ..slow_call_config()
+        });
+        for _ in 0..5 {
+            breaker.record(true, SLOW);
+        }
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // One trial call is allowed, concurrent calls are rejected
+        assert!(!breaker.is_rejecting());
+        let trial = breaker.try_call().unwrap();
+        assert!(breaker.is_rejecting());
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(breaker.try_call().is_none());
+
+        // A slow trial reopens the circuit
+        trial.record(true, SLOW);
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // A fast trial closes it
+        breaker.try_call().unwrap().record(true, FAST);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.try_call().is_some());
+    }
+
+    #[test]
+    fn test_dropped_trial_reopens_circuit() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            open_duration_secs: 0,
+            ..slow_call_config()
+        });
+        for _ in 0..5 {
+            breaker.record(false, FAST);
+        }
+
+        // The trial's request was abandoned without an outcome
+        let trial = breaker.try_call().unwrap();
+        assert!(breaker.try_call().is_none());
+        drop(trial);
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // The next trial goes through rather than the circuit staying stuck
+        breaker.try_call().unwrap().record(true, FAST);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        // Abandoned calls through a closed circuit count as failures
+        for _ in 0..4 {
+            drop(breaker.try_call().unwrap());
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        drop(breaker.try_call().unwrap());
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+}
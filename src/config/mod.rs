@@ -5,11 +5,14 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// API key selection strategy
+///
+/// Built-in strategies have dedicated variants; any other name refers to a
+/// strategy registered at runtime through the API key strategy registry.
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
-#[serde(rename_all = "snake_case")]
+#[serde(from = "String", into = "String")]
 pub enum ApiKeyStrategy {
     /// Round-robin selection
     #[default]
@@ -18,6 +21,37 @@ pub enum ApiKeyStrategy {
     Random,
     /// Weighted selection based on configured weights
     Weight,
+    /// Custom strategy registered under the given name
+    Custom(String),
+}
+
+impl ApiKeyStrategy {
+    /// Get the configuration name of the strategy
+    pub fn as_str(&self) -> &str {
+        match self {
+            ApiKeyStrategy::RoundRobin => "round_robin",
+            ApiKeyStrategy::Random => "random",
+            ApiKeyStrategy::Weight => "weight",
+            ApiKeyStrategy::Custom(name) => name,
+        }
+    }
+}
+
+impl From<String> for ApiKeyStrategy {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "round_robin" => ApiKeyStrategy::RoundRobin,
+            "random" => ApiKeyStrategy::Random,
+            "weight" => ApiKeyStrategy::Weight,
+            _ => ApiKeyStrategy::Custom(name),
+        }
+    }
+}
+
+impl From<ApiKeyStrategy> for String {
+    fn from(strategy: ApiKeyStrategy) -> Self {
+        strategy.as_str().to_string()
+    }
 }
 
 /// API key configuration with optional weight
@@ -41,6 +75,14 @@ fn default_enabled() -> bool {
     true
 }
 
+fn default_max_decompressed_bytes() -> usize {
+    crate::decompress::DEFAULT_MAX_DECOMPRESSED_BYTES
+}
+
+fn default_body_schema_max_bytes() -> usize {
+    1024 * 1024
+}
+
 /// API key pool configuration
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ApiKeyPool {
@@ -56,6 +98,23 @@ pub struct ApiKeyPool {
     /// Query parameter name to inject the API key (optional, used when injecting as query param)
     #[serde(default)]
     pub query_param_name: Option<String>,
+    /// Fetch OAuth2 access tokens instead of using `keys` (optional)
+    #[serde(default)]
+    pub oauth2: Option<OAuth2Config>,
+}
+
+/// OAuth2 client credentials for a pool that injects access tokens
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuth2Config {
+    /// Token endpoint URL
+    pub token_url: String,
+    /// Client ID
+    pub client_id: String,
+    /// Client secret
+    pub client_secret: String,
+    /// Requested scope (optional, space-separated)
+    #[serde(default)]
+    pub scope: Option<String>,
 }
 
 fn default_header_name() -> String {
@@ -70,7 +129,8 @@ pub struct RouteConfig {
     pub name: Option<String>,
     /// Path pattern to match (e.g., "/api/v1/*")
     pub path: String,
-    /// Target URL to forward requests to
+    /// Target URL to forward requests to (not used by aggregate routes)
+    #[serde(default)]
     pub target: String,
     /// Optional methods to match (if empty, all methods are matched)
     #[serde(default)]
@@ -78,16 +138,575 @@ pub struct RouteConfig {
     /// Whether to strip the matched prefix from the path
     #[serde(default)]
     pub strip_prefix: bool,
+    /// Path forwarded when `strip_prefix` leaves nothing of the request
+    /// path: `"/"` (the default), `""` for the bare target, or a literal
+    /// path such as `"/index"`
+    #[serde(default)]
+    pub empty_path: Option<String>,
     /// API key pool name to use for this route
     pub api_key_pool: Option<String>,
-    /// Additional headers to add to the request
+    /// Header in which clients may present their own key; requests carrying
+    /// it are forwarded untouched, without a key from `api_key_pool`
+    #[serde(default)]
+    pub client_key_header: Option<String>,
+    /// Additional headers to add to the request; values may use the
+    /// `{path}`, `{method}`, `{host}`, `{route_name}` and `{now_rfc3339}`
+    /// templates (see [`expand_header_template`])
     #[serde(default)]
     pub headers: HashMap<String, String>,
+    /// Request headers moved into the upstream query string, as
+    /// header name -> query parameter name
+    #[serde(default)]
+    pub header_to_query: HashMap<String, String>,
+    /// Query parameters moved into upstream request headers, as
+    /// query parameter name -> header name
+    #[serde(default)]
+    pub query_to_header: HashMap<String, String>,
+    /// Client request headers forwarded to the upstream; others are dropped,
+    /// except `Content-Type`, `Content-Length` and `Content-Encoding`
+    /// (empty = forward all)
+    #[serde(default)]
+    pub forward_headers: Vec<String>,
+    /// Query parameters a request must carry to match the route, as
+    /// parameter name -> required value (`"*"` = any value)
+    #[serde(default)]
+    pub match_query: HashMap<String, String>,
     /// Route description
     pub description: Option<String>,
     /// Whether the route is enabled
     #[serde(default = "default_enabled")]
     pub enabled: bool,
+    /// Isolated upstream connection pool name (routes sharing a name share a client)
+    #[serde(default)]
+    pub upstream_pool: Option<String>,
+    /// Maximum number of concurrent upstream requests (default: unlimited)
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+    /// Deadline in milliseconds for the whole request, from reading the
+    /// client body to reading the upstream response (default: none)
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Stream the response body to the client, closing both connections
+    /// once no data arrives for this many milliseconds; `timeout_ms` then
+    /// only covers the wait for the response headers
+    #[serde(default)]
+    pub stream_idle_timeout_ms: Option<u64>,
+    /// Longest upstream request path and query in bytes, overriding
+    /// `limits.max_uri_bytes` for this route
+    #[serde(default)]
+    pub max_uri_bytes: Option<usize>,
+    /// Circuit breaker settings (default: disabled)
+    #[serde(default)]
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// Response cache settings (default: disabled)
+    #[serde(default)]
+    pub cache: Option<CacheConfig>,
+    /// Response headers removed from this route's responses
+    #[serde(default)]
+    pub strip_response_headers: Vec<String>,
+    /// Whether readiness requires this route's upstream to be reachable
+    #[serde(default)]
+    pub critical: bool,
+    /// Group used as the `group` label on this route's metrics; must be
+    /// listed in `metrics.route_groups` (default: "default")
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Talk HTTP/1.1 to the upstream and send header names title-cased
+    /// (`X-Api-Key`) for upstreams that require exact casing
+    #[serde(default)]
+    pub title_case_headers: bool,
+    /// Server name presented in the TLS handshake with an `https` target,
+    /// for targets addressed by IP or by a name the certificate doesn't cover
+    #[serde(default)]
+    pub tls_sni: Option<String>,
+    /// Request `Content-Type`s accepted for POST/PUT/PATCH requests, e.g.
+    /// `application/json` or `text/*` (empty = accept all)
+    #[serde(default)]
+    pub accept_content_types: Vec<String>,
+    /// Decode gzip, deflate and br request bodies before forwarding them
+    #[serde(default)]
+    pub decompress_request: bool,
+    /// Limit in bytes on a decoded request body; larger bodies get 413
+    #[serde(default = "default_max_decompressed_bytes")]
+    pub max_decompressed_bytes: usize,
+    /// JSON Schema file request bodies are validated against (optional)
+    #[serde(default)]
+    pub body_schema: Option<PathBuf>,
+    /// Limit in bytes on a body validated against `body_schema`; larger
+    /// bodies get 413
+    #[serde(default = "default_body_schema_max_bytes")]
+    pub body_schema_max_bytes: usize,
+    /// Sampled capture of request/response pairs to disk (optional)
+    #[serde(default)]
+    pub capture: Option<CaptureConfig>,
+    /// Sampled replay to a second upstream whose responses are compared
+    /// with the route's (optional)
+    #[serde(default)]
+    pub compare: Option<CompareConfig>,
+    /// Idempotency key header forwarded with every request, generated when
+    /// the client sends none (optional)
+    #[serde(default)]
+    pub idempotency_key: Option<IdempotencyKeyConfig>,
+    /// Sub-requests issued concurrently and merged into one JSON response
+    #[serde(default)]
+    pub aggregate: Vec<AggregateTarget>,
+    /// Fixed response served without contacting an upstream
+    #[serde(default)]
+    pub mock: Option<MockConfig>,
+    /// Precedence over overlapping routes with `routing.match_mode =
+    /// "most_specific"`; higher wins (default: 0)
+    #[serde(default)]
+    pub priority: i32,
+    /// Idle upstream connections opened at startup and kept open, so the
+    /// first requests don't pay for connection setup (default: 0)
+    #[serde(default)]
+    pub prewarm_connections: usize,
+    /// Responses whose latency is observed in the histogram: `"all"`,
+    /// `"errors_only"`, or the fraction of non-error responses (default: all)
+    #[serde(default)]
+    pub latency_sampling: LatencySampling,
+}
+
+/// Responses of a route whose latency is observed
+///
+/// Accepts `"all"`, `"errors_only"` or a fraction such as `0.1`. Error
+/// responses (4xx/5xx) are always observed, and every request is counted.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LatencySampling {
+    /// Observe all responses, or only errors
+    Mode(LatencySamplingMode),
+    /// Fraction of non-error responses observed, between 0.0 and 1.0
+    Fraction(f64),
+}
+
+/// Latency sampling given by name
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LatencySamplingMode {
+    /// Every response is observed
+    #[default]
+    All,
+    /// Only error responses are observed
+    ErrorsOnly,
+}
+
+impl Default for LatencySampling {
+    fn default() -> Self {
+        Self::Mode(LatencySamplingMode::All)
+    }
+}
+
+impl LatencySampling {
+    /// Decide whether to observe the latency of a response with `status`
+    pub fn observes(&self, status: u16) -> bool {
+        if status >= 400 {
+            return true;
+        }
+        match *self {
+            Self::Mode(LatencySamplingMode::All) => true,
+            Self::Mode(LatencySamplingMode::ErrorsOnly) => false,
+            Self::Fraction(rate) => rate >= 1.0 || (rate > 0.0 && rand::random::<f64>() < rate),
+        }
+    }
+}
+
+/// Fixed response of a mock route
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MockConfig {
+    /// Response status code
+    #[serde(default = "default_mock_status")]
+    pub status: u16,
+    /// Response headers
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Inline response body
+    #[serde(default)]
+    pub body: Option<String>,
+    /// File whose contents are the response body (read at load)
+    #[serde(default)]
+    pub body_file: Option<PathBuf>,
+}
+
+fn default_mock_status() -> u16 {
+    200
+}
+
+/// A sub-request of an aggregate route
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AggregateTarget {
+    /// Key of this sub-response in the merged body
+    pub name: String,
+    /// URL requested with GET
+    pub target: String,
+    /// Timeout for this sub-request in milliseconds
+    #[serde(default = "default_aggregate_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_aggregate_timeout_ms() -> u64 {
+    10_000
+}
+
+/// Response cache settings for a route
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CacheConfig {
+    /// Seconds a cached response stays fresh
+    #[serde(default = "default_cache_ttl")]
+    pub ttl_secs: u64,
+    /// Maximum number of cached responses
+    #[serde(default = "default_cache_max_entries")]
+    pub max_entries: usize,
+}
+
+fn default_cache_ttl() -> u64 {
+    60
+}
+
+fn default_cache_max_entries() -> usize {
+    1000
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: default_cache_ttl(),
+            max_entries: default_cache_max_entries(),
+        }
+    }
+}
+
+/// Request/response capture settings for a route
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CaptureConfig {
+    /// Fraction of requests captured, from 0.0 to 1.0 (default: 1.0)
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+    /// Directory capture files are written to (created if missing)
+    pub dir: PathBuf,
+    /// Bytes kept of each request and response body; longer bodies are
+    /// truncated (default: 64 KiB)
+    #[serde(default = "default_capture_max_bytes")]
+    pub max_bytes: usize,
+}
+
+fn default_capture_max_bytes() -> usize {
+    64 * 1024
+}
+
+/// Response comparison settings for a route
+///
+/// Sampled requests are replayed to `target` once the route's upstream has
+/// answered; the client always gets the route's response.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CompareConfig {
+    /// Base URL requests are replayed to, with the path and query sent to
+    /// the route's upstream
+    pub target: String,
+    /// Percentage of requests replayed, from 0 to 100 (default: 100)
+    #[serde(default = "default_compare_percent")]
+    pub percent: f64,
+    /// Compare response bodies as well as status codes (default: false)
+    #[serde(default)]
+    pub compare_body: bool,
+    /// Replay requests with unsafe methods (POST, PUT, DELETE, ...) too,
+    /// repeating their side effects on the comparison upstream (default:
+    /// false, only GET, HEAD, OPTIONS and TRACE are replayed)
+    #[serde(default)]
+    pub replay_unsafe_methods: bool,
+}
+
+fn default_compare_percent() -> f64 {
+    100.0
+}
+
+/// Idempotency key settings for a route
+///
+/// A key sent by the client is forwarded as is, unless the route's
+/// `forward_headers` leaves the header out: the client's key is then dropped
+/// and, with `generate`, replaced by a new one.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct IdempotencyKeyConfig {
+    /// Header carrying the key (default: "Idempotency-Key")
+    #[serde(default = "default_idempotency_key_header")]
+    pub header: String,
+    /// Generate a UUIDv4 key for requests without one (default: true)
+    #[serde(default = "default_enabled")]
+    pub generate: bool,
+}
+
+fn default_idempotency_key_header() -> String {
+    "Idempotency-Key".to_string()
+}
+
+/// Circuit breaker settings for a route
+///
+/// The breaker tracks the outcome of the most recent `window_size` upstream
+/// calls and opens when too many of them failed or were slow.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CircuitBreakerConfig {
+    /// Number of recent calls tracked
+    #[serde(default = "default_window_size")]
+    pub window_size: usize,
+    /// Minimum number of calls in the window before the circuit can open
+    #[serde(default = "default_minimum_calls")]
+    pub minimum_calls: usize,
+    /// Fraction of failed calls (0.0-1.0) that opens the circuit
+    #[serde(default = "default_failure_rate")]
+    pub failure_rate: f64,
+    /// Calls slower than this many milliseconds count as slow (default: disabled)
+    #[serde(default)]
+    pub slow_call_threshold_ms: Option<u64>,
+    /// Fraction of slow calls (0.0-1.0) that opens the circuit
+    #[serde(default = "default_slow_call_rate")]
+    pub slow_call_rate: f64,
+    /// Seconds the circuit stays open before a trial call is let through
+    #[serde(default = "default_open_duration")]
+    pub open_duration_secs: u64,
+}
+
+fn default_window_size() -> usize {
+    20
+}
+
+fn default_minimum_calls() -> usize {
+    10
+}
+
+fn default_failure_rate() -> f64 {
+    0.5
+}
+
+fn default_slow_call_rate() -> f64 {
+    0.5
+}
+
+fn default_open_duration() -> u64 {
+    30
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            window_size: default_window_size(),
+            minimum_calls: default_minimum_calls(),
+            failure_rate: default_failure_rate(),
+            slow_call_threshold_ms: None,
+            slow_call_rate: default_slow_call_rate(),
+            open_duration_secs: default_open_duration(),
+        }
+    }
+}
+
+/// TLS settings for connections to upstreams
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// Trust the operating system's root certificates
+    #[serde(default = "default_enabled")]
+    pub native_roots: bool,
+    /// PEM file with additional trusted root certificates
+    #[serde(default)]
+    pub ca_bundle: Option<PathBuf>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            native_roots: true,
+            ca_bundle: None,
+        }
+    }
+}
+
+/// Upstream connection pool settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct UpstreamPoolConfig {
+    /// Maximum idle connections kept per upstream host (default: unlimited)
+    #[serde(default)]
+    pub max_idle_per_host: Option<usize>,
+    /// Idle connection timeout in seconds (default: 90)
+    #[serde(default)]
+    pub idle_timeout: Option<u64>,
+}
+
+/// HTTP methods accepted in route `methods` lists
+pub const KNOWN_METHODS: &[&str] = &[
+    "GET",
+    "HEAD",
+    "POST",
+    "PUT",
+    "DELETE",
+    "CONNECT",
+    "OPTIONS",
+    "TRACE",
+    "PATCH",
+    // WebDAV
+    "PROPFIND",
+    "PROPPATCH",
+    "MKCOL",
+    "COPY",
+    "MOVE",
+    "LOCK",
+    "UNLOCK",
+];
+
+/// Normalize a route method list
+///
+/// Methods are trimmed and uppercased; a `"*"` entry means all methods and
+/// yields an empty list.
+pub fn normalize_methods(methods: &[String]) -> Vec<String> {
+    if methods.iter().any(|m| m.trim() == "*") {
+        return vec![];
+    }
+    methods
+        .iter()
+        .map(|m| m.trim().to_ascii_uppercase())
+        .collect()
+}
+
+/// Tokens accepted in route header value templates
+pub const HEADER_TEMPLATE_TOKENS: &[&str] =
+    &["path", "method", "host", "route_name", "now_rfc3339"];
+
+/// Expand a route header value, replacing each `{token}` with `value(token)`
+///
+/// `{{` and `}}` stand for literal braces. Fails on tokens not in
+/// [`HEADER_TEMPLATE_TOKENS`] and on unbalanced braces.
+pub fn expand_header_template(
+    template: &str,
+    mut value: impl FnMut(&str) -> String,
+) -> Result<String, String> {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        expanded.push_str(&rest[..i]);
+        let brace = &rest[i..i + 1];
+        rest = &rest[i + 1..];
+        if let Some(after) = rest.strip_prefix(brace) {
+            expanded.push_str(brace);
+            rest = after;
+            continue;
+        }
+        if brace == "}" {
+            return Err(format!("unmatched '}}' in '{}'", template));
+        }
+        let end = rest
+            .find('}')
+            .ok_or_else(|| format!("unclosed '{{' in '{}'", template))?;
+        let token = &rest[..end];
+        if !HEADER_TEMPLATE_TOKENS.contains(&token) {
+            return Err(format!(
+                "unknown template '{{{}}}' in '{}'",
+                token, template
+            ));
+        }
+        expanded.push_str(&value(token));
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Get the path of a profile's config file: `config.toml` + `prod` → `config.prod.toml`
+pub fn profile_path(path: &Path, profile: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("config");
+    let file_name = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}.{}.{}", stem, profile, ext),
+        None => format!("{}.{}", stem, profile),
+    };
+    path.with_file_name(file_name)
+}
+
+/// Merge an overlay TOML value into a base value
+///
+/// Tables are merged recursively, `routes` and `servers` arrays are merged by
+/// entry identity, and any other value is replaced by the overlay.
+fn merge_toml(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) if key == "routes" || key == "servers" => {
+                        merge_named_array(existing, value)
+                    }
+                    Some(existing) => merge_toml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Copy `[defaults.route]` options into each route that doesn't set them
+///
+/// Options are inherited whole: a route setting `headers` replaces the
+/// default headers rather than merging with them.
+fn apply_route_defaults(value: &mut toml::Value) -> anyhow::Result<()> {
+    let Some(defaults) = value
+        .get("defaults")
+        .and_then(|d| d.get("route"))
+        .and_then(toml::Value::as_table)
+        .cloned()
+    else {
+        return Ok(());
+    };
+    for key in ["path", "name"] {
+        if defaults.contains_key(key) {
+            anyhow::bail!("defaults.route can't set '{}'", key);
+        }
+    }
+
+    let routes = value
+        .get_mut("routes")
+        .and_then(toml::Value::as_array_mut)
+        .into_iter()
+        .flatten();
+    for route in routes.filter_map(toml::Value::as_table_mut) {
+        for (key, default) in &defaults {
+            route.entry(key.clone()).or_insert_with(|| default.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Merge arrays of tables, matching entries by `name` (or `path`)
+///
+/// Matching entries are merged, new entries are appended. Arrays that are not
+/// made of tables (like a server's route references) are replaced.
+fn merge_named_array(base: &mut toml::Value, overlay: toml::Value) {
+    fn identity(value: &toml::Value) -> Option<&str> {
+        value
+            .get("name")
+            .or_else(|| value.get("path"))
+            .and_then(|v| v.as_str())
+    }
+
+    let mergeable = |value: &toml::Value| {
+        value
+            .as_array()
+            .is_some_and(|items| items.iter().all(|item| item.is_table()))
+    };
+    if !mergeable(base) || !mergeable(&overlay) {
+        *base = overlay;
+        return;
+    }
+
+    if let (Some(base_items), toml::Value::Array(overlay_items)) = (base.as_array_mut(), overlay) {
+        for item in overlay_items {
+            let existing = identity(&item)
+                .and_then(|id| base_items.iter().position(|b| identity(b) == Some(id)));
+            match existing {
+                Some(index) => merge_toml(&mut base_items[index], item),
+                None => base_items.push(item),
+            }
+        }
+    }
 }
 
 /// Server configuration
@@ -99,6 +718,10 @@ pub struct ServerConfig {
     /// Host to bind to
     #[serde(default = "default_host")]
     pub host: String,
+    /// Hosts to bind to instead of `host`, one listener each sharing the
+    /// server's routes and state (e.g. a VIP and `127.0.0.1`)
+    #[serde(default)]
+    pub hosts: Vec<String>,
     /// Port to bind to
     #[serde(default = "default_port")]
     pub port: u16,
@@ -108,6 +731,53 @@ pub struct ServerConfig {
     /// Routes associated with this server (optional, if not set uses global routes)
     #[serde(default)]
     pub routes: Vec<String>,
+    /// Servers (by name) that must be listening before this server starts
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Maximum number of open client connections; further connections wait
+    /// until one closes (default: unlimited)
+    #[serde(default)]
+    pub max_connections: Option<usize>,
+    /// Resolve every route target's host at startup and refuse to start if
+    /// one doesn't resolve (default: resolved lazily on first request)
+    #[serde(default)]
+    pub resolve_upstreams_on_start: bool,
+    /// Listen on sockets passed by systemd (`LISTEN_FDS`) instead of
+    /// binding, falling back to binding when none are left (Linux only)
+    #[serde(default)]
+    pub socket_activation: bool,
+}
+
+impl ServerConfig {
+    /// Get the display name of the server (name, or its first bind address
+    /// if unnamed)
+    pub fn display_name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| self.bind_addrs()[0].clone())
+    }
+
+    /// Get the `host:port` addresses the server listens on: one per entry
+    /// of `hosts`, or `host` alone
+    ///
+    /// IPv6 hosts are bracketed, e.g. `[::1]:8080`.
+    pub fn bind_addrs(&self) -> Vec<String> {
+        let hosts = if self.hosts.is_empty() {
+            std::slice::from_ref(&self.host)
+        } else {
+            self.hosts.as_slice()
+        };
+        hosts
+            .iter()
+            .map(|host| {
+                if host.contains(':') && !host.starts_with('[') {
+                    format!("[{}]:{}", host, self.port)
+                } else {
+                    format!("{}:{}", host, self.port)
+                }
+            })
+            .collect()
+    }
 }
 
 fn default_host() -> String {
@@ -127,9 +797,14 @@ impl Default for ServerConfig {
         Self {
             name: None,
             host: default_host(),
+            hosts: vec![],
             port: default_port(),
             timeout: default_timeout(),
             routes: vec![],
+            depends_on: vec![],
+            max_connections: None,
+            resolve_upstreams_on_start: false,
+            socket_activation: false,
         }
     }
 }
@@ -143,17 +818,38 @@ pub struct MetricsConfig {
     /// Path to expose metrics
     #[serde(default = "default_metrics_path")]
     pub path: String,
+    /// Histogram buckets in seconds for latency and queue wait metrics
+    #[serde(default = "default_latency_buckets")]
+    pub latency_buckets: Vec<f64>,
+    /// Allowed values for the route `group` metrics label
+    #[serde(default)]
+    pub route_groups: Vec<String>,
 }
 
+/// Metrics group of routes without a `group`
+pub const DEFAULT_ROUTE_GROUP: &str = "default";
+
+/// Maximum number of route groups, keeping the `group` label bounded
+pub const MAX_ROUTE_GROUPS: usize = 32;
+
 fn default_metrics_path() -> String {
     "/metrics".to_string()
 }
 
+/// Default histogram buckets in seconds
+pub fn default_latency_buckets() -> Vec<f64> {
+    vec![
+        0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+    ]
+}
+
 impl Default for MetricsConfig {
     fn default() -> Self {
         Self {
             enabled: true,
             path: default_metrics_path(),
+            latency_buckets: default_latency_buckets(),
+            route_groups: vec![],
         }
     }
 }
@@ -167,111 +863,896 @@ pub struct HealthConfig {
     /// Path for health check endpoint
     #[serde(default = "default_health_path")]
     pub path: String,
+    /// Path for readiness endpoint
+    #[serde(default = "default_ready_path")]
+    pub ready_path: String,
+    /// Probes of critical route upstreams
+    #[serde(default)]
+    pub upstream: UpstreamProbeConfig,
+}
+
+/// Configuration of the upstream probes behind readiness
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamProbeConfig {
+    /// Seconds between probe rounds, jittered by up to 10% (unset = probe
+    /// only after (re)loading the configuration)
+    #[serde(default)]
+    pub interval_secs: Option<u64>,
+    /// Maximum number of upstreams probed at once
+    #[serde(default = "default_probe_concurrency")]
+    pub concurrency: usize,
+}
+
+fn default_probe_concurrency() -> usize {
+    16
+}
+
+impl Default for UpstreamProbeConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: None,
+            concurrency: default_probe_concurrency(),
+        }
+    }
 }
 
 fn default_health_path() -> String {
     "/health".to_string()
 }
 
+fn default_ready_path() -> String {
+    "/ready".to_string()
+}
+
 impl Default for HealthConfig {
     fn default() -> Self {
         Self {
             enabled: true,
             path: default_health_path(),
+            ready_path: default_ready_path(),
+            upstream: UpstreamProbeConfig::default(),
         }
     }
 }
 
-/// Master access token guard configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MasterAccessTokenConfig {
-    /// Whether the master access token guard is enabled
+/// Admin API configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AdminConfig {
+    /// Whether the admin endpoints (`/admin/drain`, `/admin/undrain`) are served
     #[serde(default)]
     pub enabled: bool,
-    /// Header name to check for the access token
-    #[serde(default = "default_master_token_header_name")]
-    pub header_name: String,
-    /// List of valid tokens (any one of these tokens will be accepted)
-    #[serde(default)]
-    pub tokens: Vec<String>,
 }
 
-fn default_master_token_header_name() -> String {
-    "Authorization".to_string()
+/// Error-rate alerting configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertsConfig {
+    /// URL the alert notifications are POSTed to (unset = alerting disabled)
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    /// Fraction of a route's requests failing with a 5xx status, over the
+    /// window, at which an alert fires
+    #[serde(default = "default_alert_error_rate_threshold")]
+    pub error_rate_threshold: f64,
+    /// Error rate below which a firing alert recovers
+    /// (default: half of `error_rate_threshold`)
+    #[serde(default)]
+    pub recovery_threshold: Option<f64>,
+    /// Length of the rolling window in seconds
+    #[serde(default = "default_alert_window_secs")]
+    pub window_secs: u64,
+    /// Requests a route needs within the window before it can fire
+    #[serde(default = "default_alert_min_requests")]
+    pub min_requests: u64,
 }
 
-impl Default for MasterAccessTokenConfig {
+impl Default for AlertsConfig {
     fn default() -> Self {
         Self {
-            enabled: false,
-            header_name: default_master_token_header_name(),
-            tokens: vec![],
+            webhook_url: None,
+            error_rate_threshold: default_alert_error_rate_threshold(),
+            recovery_threshold: None,
+            window_secs: default_alert_window_secs(),
+            min_requests: default_alert_min_requests(),
         }
     }
 }
 
-impl MasterAccessTokenConfig {
-    /// Validate an incoming token against the configured tokens
-    /// Returns true if access should be allowed, false otherwise
-    pub fn validate_token(&self, token: &str) -> bool {
-        // If guard is not enabled, allow all access
-        if !self.enabled {
-            return true;
-        }
-        // Defense in depth: if enabled but no tokens configured, deny access
-        // (This case should be caught by config validation, but handle it safely)
-        if self.tokens.is_empty() {
-            return false;
-        }
-        // Check if the provided token matches any configured token
-        self.tokens.iter().any(|t| t == token)
+impl AlertsConfig {
+    /// Get the error rate below which a firing alert recovers
+    pub fn recovery_threshold(&self) -> f64 {
+        self.recovery_threshold
+            .unwrap_or(self.error_rate_threshold / 2.0)
     }
 }
 
-/// Main gateway configuration
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
-pub struct GatewayConfig {
-    /// Single server configuration (for backward compatibility)
-    #[serde(default)]
-    pub server: ServerConfig,
-    /// Multiple servers configuration
-    #[serde(default)]
-    pub servers: Vec<ServerConfig>,
-    /// Metrics configuration
-    #[serde(default)]
-    pub metrics: MetricsConfig,
-    /// Health check configuration
+fn default_alert_error_rate_threshold() -> f64 {
+    0.5
+}
+
+fn default_alert_window_secs() -> u64 {
+    60
+}
+
+fn default_alert_min_requests() -> u64 {
+    10
+}
+
+/// Config file watcher configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchConfig {
+    /// Quiet period in milliseconds used to coalesce bursts of file change events
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+}
+
+fn default_debounce_ms() -> u64 {
+    300
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            debounce_ms: default_debounce_ms(),
+        }
+    }
+}
+
+/// Request/response forwarding configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardingConfig {
+    /// Forward verified client certificate details to upstreams as
+    /// `X-Client-Cert-Subject` / `X-Client-Cert-Serial` headers
+    #[serde(default)]
+    pub client_cert_headers: bool,
+    /// Response headers removed from every proxied response (e.g. `Server`)
+    #[serde(default)]
+    pub strip_response_headers: Vec<String>,
+    /// Handling of `Expect: 100-continue` requests (default: continue)
+    #[serde(default)]
+    pub expect_continue: ExpectContinue,
+    /// Send the path prefix removed by `strip_prefix` (and `routing.base_path`)
+    /// to upstreams so they can build public URLs
+    #[serde(default)]
+    pub forwarded_prefix: bool,
+    /// Header carrying the removed prefix
+    #[serde(default = "default_forwarded_prefix_header")]
+    pub forwarded_prefix_header: String,
+    /// `Server` header of every response (unset = left as the upstream sent it)
+    #[serde(default)]
+    pub server_header: Option<ServerHeader>,
+    /// Stream responses whose `Content-Length` exceeds this many bytes
+    /// instead of buffering them, unless the route caches or captures
+    /// responses (unset = buffer every response)
+    #[serde(default)]
+    pub buffer_threshold_bytes: Option<u64>,
+}
+
+/// `Server` header value sent when `server_header = true`
+pub const DEFAULT_SERVER_HEADER: &str = "open-gateway";
+
+/// `Server` header put on responses
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ServerHeader {
+    /// `true` sends `open-gateway`, `false` removes the header
+    Enabled(bool),
+    /// Value to send
+    Value(String),
+}
+
+impl ServerHeader {
+    /// Get the value to send, or `None` to remove the header
+    pub fn value(&self) -> Option<&str> {
+        match self {
+            Self::Enabled(true) => Some(DEFAULT_SERVER_HEADER),
+            Self::Enabled(false) => None,
+            Self::Value(value) => Some(value),
+        }
+    }
+}
+
+fn default_forwarded_prefix_header() -> String {
+    "X-Forwarded-Prefix".to_string()
+}
+
+impl Default for ForwardingConfig {
+    fn default() -> Self {
+        Self {
+            client_cert_headers: false,
+            strip_response_headers: Vec::new(),
+            expect_continue: ExpectContinue::default(),
+            forwarded_prefix: false,
+            forwarded_prefix_header: default_forwarded_prefix_header(),
+            server_header: None,
+            buffer_threshold_bytes: None,
+        }
+    }
+}
+
+/// How requests carrying `Expect: 100-continue` are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpectContinue {
+    /// Answer `100 Continue` once the matched route will accept the body;
+    /// requests it would reject get their final status without an upload
+    #[default]
+    Continue,
+    /// Answer `417 Expectation Failed` without reading the body
+    Reject,
+}
+
+/// Remote route source configuration
+///
+/// Routes fetched from a control-plane endpoint are merged with the routes
+/// from the config file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutesSourceConfig {
+    /// URL returning a JSON array of routes
+    pub url: String,
+    /// Seconds between fetches after startup (0 = fetch only at startup)
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+    /// Value sent in the `Authorization` header
+    #[serde(default)]
+    pub auth_header: Option<String>,
+}
+
+fn default_poll_interval() -> u64 {
+    30
+}
+
+/// Debug header configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DebugHeadersConfig {
+    /// Add `X-Gateway-Duration-Ms` to responses and forward
+    /// `X-Gateway-Received-At` to upstreams
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+/// Default values for configuration entries
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DefaultsConfig {
+    /// Route options (any `[[routes]]` key except `path` and `name`) used by
+    /// routes that don't set them; applied when the file is loaded
+    #[serde(default)]
+    pub route: toml::Table,
+}
+
+/// Access log configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessLogConfig {
+    /// Fraction of successful requests logged, from 0.0 to 1.0; responses
+    /// with status 400 and above are always logged (default: 1.0)
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+}
+
+impl Default for AccessLogConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: default_sample_rate(),
+        }
+    }
+}
+
+fn default_sample_rate() -> f64 {
+    1.0
+}
+
+/// Size limit configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LimitsConfig {
+    /// Largest upstream response body in bytes; larger buffered responses
+    /// get `502`, and streamed ones are cut off (default: unlimited)
+    #[serde(default)]
+    pub max_response_bytes: Option<usize>,
+    /// Longest upstream request path and query in bytes; longer requests
+    /// get `414` without reaching the upstream (default: unlimited)
+    #[serde(default)]
+    pub max_uri_bytes: Option<usize>,
+}
+
+/// Upstream client configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamClientConfig {
+    /// Seconds between re-resolutions of upstream hostnames (default:
+    /// disabled)
+    #[serde(default)]
+    pub dns_refresh_interval: Option<u64>,
+    /// Close idle upstream connections when a hostname resolves to other
+    /// addresses (default: true)
+    #[serde(default = "default_enabled")]
+    pub recycle_on_dns_change: bool,
+    /// Seconds after which upstream connections are replaced: connections
+    /// opened earlier aren't reused for new requests (default: unlimited)
+    #[serde(default)]
+    pub connection_max_lifetime: Option<u64>,
+    /// Seconds an upstream connection may stay idle in the pool before it
+    /// is closed; upstream pools' own `idle_timeout` takes precedence
+    /// (default: 90)
+    #[serde(default)]
+    pub connection_max_idle: Option<u64>,
+}
+
+impl Default for UpstreamClientConfig {
+    fn default() -> Self {
+        Self {
+            dns_refresh_interval: None,
+            recycle_on_dns_change: true,
+            connection_max_lifetime: None,
+            connection_max_idle: None,
+        }
+    }
+}
+
+/// Runtime state overlay configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuntimeStateConfig {
+    /// JSON sidecar file persisting operator toggles across restarts
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+/// Custom error page served for a status code
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorPageConfig {
+    /// File served to clients that prefer `text/html`
+    pub html_file: PathBuf,
+    /// Content type of the file (default: "text/html; charset=utf-8")
+    #[serde(default = "default_error_page_content_type")]
+    pub content_type: String,
+}
+
+fn default_error_page_content_type() -> String {
+    "text/html; charset=utf-8".to_string()
+}
+
+/// Request routing configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutingConfig {
+    /// Forward the request path to upstreams exactly as received, keeping
+    /// percent-encoded bytes such as `%2F` intact. Routes are still matched
+    /// against the decoded path. Disabling it forwards the decoded path, so
+    /// encoded slashes and dot segments reach upstreams as path separators
+    /// (default: true)
+    #[serde(default = "default_enabled")]
+    pub preserve_raw_path: bool,
+    /// Maximum number of enabled routes (default: unlimited)
+    #[serde(default)]
+    pub max_routes: Option<usize>,
+    /// Handling of repeated slashes in request paths (default: off)
+    #[serde(default)]
+    pub collapse_slashes: CollapseSlashes,
+    /// Prefix the whole gateway is mounted under (e.g. `/gateway`); stripped
+    /// before routes are matched and prepended to health/metrics paths
+    #[serde(default)]
+    pub base_path: String,
+    /// What to do when both `[server]` and `[[servers]]` are configured
+    /// (default: error)
+    #[serde(default)]
+    pub server_merge: ServerMerge,
+    /// Let HEAD requests match routes that only list GET (default: true)
+    #[serde(default = "default_head_implies_get")]
+    pub head_implies_get: bool,
+    /// Method used upstream for HEAD requests matched through a GET route
+    /// (default: head)
+    #[serde(default)]
+    pub head_upstream_method: HeadUpstreamMethod,
+    /// Match request paths against route patterns ignoring ASCII case; the
+    /// path is still forwarded as received (default: false)
+    #[serde(default)]
+    pub case_insensitive_paths: bool,
+    /// Which route serves a request matched by several routes (default: first)
+    #[serde(default)]
+    pub match_mode: MatchMode,
+    /// Treat `;` as a query parameter separator alongside `&` when routes
+    /// match or remove query parameters (legacy `a=1;b=2` queries)
+    #[serde(default)]
+    pub query_semicolon_separator: bool,
+    /// Let requests force their API key pool's strategy with
+    /// `?api_key_strategy=<name>`, for testing; keep off in production
+    #[serde(default)]
+    pub api_key_strategy_param: bool,
+    /// Redirect requests for alias hosts to a canonical host (optional)
+    #[serde(default)]
+    pub canonical_host: Option<CanonicalHostConfig>,
+}
+
+/// Redirect of alias hosts to the canonical host
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CanonicalHostConfig {
+    /// Host requests are redirected to, e.g. `example.com`
+    pub host: String,
+    /// Hosts (or IP addresses) redirected to `host`, matched ignoring case
+    /// and port
+    pub aliases: Vec<String>,
+    /// Scheme of the redirect location (default: https)
+    #[serde(default = "default_canonical_scheme")]
+    pub scheme: String,
+    /// Redirect status, 301 or 308 (default: 308)
+    #[serde(default = "default_canonical_status")]
+    pub status: u16,
+}
+
+fn default_canonical_scheme() -> String {
+    "https".to_string()
+}
+
+fn default_canonical_status() -> u16 {
+    308
+}
+
+impl CanonicalHostConfig {
+    /// Check whether a `Host` header value names one of the aliases
+    pub fn is_alias(&self, host: &str) -> bool {
+        let host = match host.rsplit_once(':') {
+            // Keep bracketed IPv6 addresses whole
+            Some((name, port)) if !port.contains(']') => name,
+            _ => host,
+        };
+        self.aliases
+            .iter()
+            .any(|alias| alias.eq_ignore_ascii_case(host))
+    }
+}
+
+impl Default for RoutingConfig {
+    fn default() -> Self {
+        Self {
+            preserve_raw_path: true,
+            max_routes: None,
+            collapse_slashes: CollapseSlashes::default(),
+            base_path: String::new(),
+            server_merge: ServerMerge::default(),
+            head_implies_get: default_head_implies_get(),
+            head_upstream_method: HeadUpstreamMethod::default(),
+            case_insensitive_paths: false,
+            match_mode: MatchMode::default(),
+            query_semicolon_separator: false,
+            api_key_strategy_param: false,
+            canonical_host: None,
+        }
+    }
+}
+
+fn default_head_implies_get() -> bool {
+    true
+}
+
+impl RoutingConfig {
+    /// Get a gateway endpoint path (e.g. `/health`) under the base path
+    pub fn endpoint_path(&self, path: &str) -> String {
+        format!("{}{}", self.base_path, path)
+    }
+}
+
+/// How repeated slashes in request paths are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CollapseSlashes {
+    /// Paths are matched and forwarded as received
+    #[default]
+    Off,
+    /// Repeated slashes are replaced by one before matching and forwarding
+    Collapse,
+    /// Clients are redirected (308) to the path with single slashes
+    Redirect,
+}
+
+/// How a request matched by several routes is resolved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    /// The first matching route in configured order is used
+    #[default]
+    First,
+    /// The route with the highest `priority` is used, then the one with the
+    /// longest literal prefix; ties go to the first in configured order
+    MostSpecific,
+    /// Routes that can match the same request are rejected at load
+    Error,
+}
+
+/// Method sent upstream for a HEAD request served by a GET route
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HeadUpstreamMethod {
+    /// The request is forwarded as HEAD
+    #[default]
+    Head,
+    /// The request is forwarded as GET and the response body is dropped,
+    /// for upstreams that don't implement HEAD
+    Get,
+}
+
+/// How a `[server]` section is combined with a `[[servers]]` list
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerMerge {
+    /// Configuring both sections is a validation error
+    #[default]
+    Error,
+    /// `server` is started alongside the `servers` list
+    Merge,
+}
+
+/// Master access token guard configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MasterAccessTokenConfig {
+    /// Whether the master access token guard is enabled
+    #[serde(default)]
+    pub enabled: bool,
+    /// Header name to check for the access token
+    #[serde(default = "default_master_token_header_name")]
+    pub header_name: String,
+    /// List of valid tokens (any one of these tokens will be accepted)
+    #[serde(default)]
+    pub tokens: Vec<MasterToken>,
+}
+
+/// Label recorded for master tokens configured without one
+pub const UNLABELED_TOKEN: &str = "unlabeled";
+
+/// A master access token, optionally labeled for auditing
+///
+/// Accepts either a plain string or `{ token = "...", label = "team-a" }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MasterToken {
+    /// Token without a label
+    Plain(String),
+    /// Token with a label identifying its holder in logs and metrics
+    Labeled {
+        /// Token value
+        token: String,
+        /// Audit label (e.g. team name)
+        label: String,
+    },
+}
+
+impl MasterToken {
+    /// Get the token value
+    pub fn token(&self) -> &str {
+        match self {
+            Self::Plain(token) | Self::Labeled { token, .. } => token,
+        }
+    }
+
+    /// Get the audit label ([`UNLABELED_TOKEN`] for plain tokens)
+    pub fn label(&self) -> &str {
+        match self {
+            Self::Plain(_) => UNLABELED_TOKEN,
+            Self::Labeled { label, .. } => label,
+        }
+    }
+}
+
+impl From<&str> for MasterToken {
+    fn from(token: &str) -> Self {
+        Self::Plain(token.to_string())
+    }
+}
+
+impl From<String> for MasterToken {
+    fn from(token: String) -> Self {
+        Self::Plain(token)
+    }
+}
+
+impl PartialEq<&str> for MasterToken {
+    fn eq(&self, other: &&str) -> bool {
+        self.token() == *other
+    }
+}
+
+fn default_master_token_header_name() -> String {
+    "Authorization".to_string()
+}
+
+impl Default for MasterAccessTokenConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            header_name: default_master_token_header_name(),
+            tokens: vec![],
+        }
+    }
+}
+
+impl MasterAccessTokenConfig {
+    /// Validate an incoming token against the configured tokens
+    /// Returns true if access should be allowed, false otherwise
+    pub fn validate_token(&self, token: &str) -> bool {
+        // If guard is not enabled, allow all access
+        if !self.enabled {
+            return true;
+        }
+        // Defense in depth: if enabled but no tokens configured, deny access
+        // (This case should be caught by config validation, but handle it safely)
+        if self.tokens.is_empty() {
+            return false;
+        }
+        // Check if the provided token matches any configured token
+        self.matching_token(token).is_some()
+    }
+
+    /// Find the configured token equal to `token`
+    ///
+    /// Unlike [`MasterAccessTokenConfig::validate_token`] this ignores
+    /// `enabled`, so callers can look up the label of an accepted token.
+    pub fn matching_token(&self, token: &str) -> Option<&MasterToken> {
+        self.tokens.iter().find(|t| t.token() == token)
+    }
+}
+
+/// Main gateway configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GatewayConfig {
+    /// Single server configuration (for backward compatibility)
+    #[serde(default)]
+    pub server: ServerConfig,
+    /// Multiple servers configuration
+    #[serde(default)]
+    pub servers: Vec<ServerConfig>,
+    /// Whether a `[server]` section was present in the parsed file
+    #[serde(skip)]
+    pub server_section: bool,
+    /// Metrics configuration
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    /// Health check configuration
     #[serde(default)]
     pub health: HealthConfig,
+    /// Admin API configuration
+    #[serde(default)]
+    pub admin: AdminConfig,
     /// Master access token guard configuration
     #[serde(default)]
     pub master_access_token: MasterAccessTokenConfig,
+    /// Config file watcher configuration
+    #[serde(default)]
+    pub watch: WatchConfig,
+    /// Request/response forwarding configuration
+    #[serde(default)]
+    pub forwarding: ForwardingConfig,
+    /// Request routing configuration
+    #[serde(default)]
+    pub routing: RoutingConfig,
+    /// Debug header configuration
+    #[serde(default)]
+    pub debug_headers: DebugHeadersConfig,
+    /// Remote route source (default: none)
+    #[serde(default)]
+    pub routes_source: Option<RoutesSourceConfig>,
+    /// Runtime state overlay persistence (default: in memory only)
+    #[serde(default)]
+    pub runtime_state: RuntimeStateConfig,
+    /// Error pages keyed by status code (e.g. `[errors.503]`)
+    #[serde(default)]
+    pub errors: HashMap<String, ErrorPageConfig>,
     /// Route configurations
     #[serde(default)]
     pub routes: Vec<RouteConfig>,
     /// API key pools
     #[serde(default)]
     pub api_key_pools: HashMap<String, ApiKeyPool>,
+    /// Isolated upstream connection pools referenced by routes
+    #[serde(default)]
+    pub upstream_pools: HashMap<String, UpstreamPoolConfig>,
+    /// Upstream TLS settings
+    #[serde(default)]
+    pub tls: TlsConfig,
+    /// Error-rate alerting
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+    /// Size limits
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    /// Upstream client settings
+    #[serde(default)]
+    pub client: UpstreamClientConfig,
+    /// Defaults inherited by file routes
+    #[serde(default)]
+    pub defaults: DefaultsConfig,
+    /// Per-request access log settings
+    #[serde(default)]
+    pub access_log: AccessLogConfig,
+}
+
+/// A configuration smell reported by [`GatewayConfig::lint`]
+///
+/// Unlike validation errors, warnings don't stop the gateway from starting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigWarning {
+    /// Stable warning code, e.g. `W001`
+    pub code: &'static str,
+    /// What was found and how to address it
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "warning[{}]: {}", self.code, self.message)
+    }
 }
 
 impl GatewayConfig {
     /// Load configuration from a TOML file
     pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
         let contents = fs::read_to_string(path)?;
-        let config: GatewayConfig = toml::from_str(&contents)?;
-        config.validate()?;
-        Ok(config)
+        Self::parse(&contents)
+    }
+
+    /// Load configuration from a base TOML file with an optional profile layered on top
+    ///
+    /// The profile file (e.g. `config.prod.toml` for profile `prod`) must exist
+    /// next to the base file. Its scalars override the base, tables are merged
+    /// recursively and `routes`/`servers` entries are merged by name (or path).
+    pub fn from_file_with_profile<P: AsRef<Path>>(
+        path: P,
+        profile: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let Some(profile) = profile else {
+            return Self::from_file(path);
+        };
+
+        let profile_path = profile_path(path, profile);
+        if !profile_path.exists() {
+            anyhow::bail!(
+                "Config file for profile '{}' not found: {}",
+                profile,
+                profile_path.display()
+            );
+        }
+
+        let mut base: toml::Value = toml::from_str(&fs::read_to_string(path)?)?;
+        let overlay: toml::Value = toml::from_str(&fs::read_to_string(&profile_path)?)?;
+        merge_toml(&mut base, overlay);
+        Self::from_value(base)
     }
 
     /// Load configuration from a TOML string
     pub fn parse(s: &str) -> anyhow::Result<Self> {
-        let config: GatewayConfig = toml::from_str(s)?;
+        Self::from_value(toml::from_str(s)?)
+    }
+
+    /// Build the configuration from a parsed TOML document
+    fn from_value(mut value: toml::Value) -> anyhow::Result<Self> {
+        apply_route_defaults(&mut value)?;
+        let server_section = value.get("server").is_some();
+        let mut config: GatewayConfig = value.try_into()?;
+        config.server_section = server_section;
+        config.normalize();
         config.validate()?;
         Ok(config)
     }
 
+    /// Normalize configuration values after parsing
+    pub fn normalize(&mut self) {
+        let base_path = self.routing.base_path.trim_end_matches('/');
+        self.routing.base_path = base_path.to_string();
+        for route in &mut self.routes {
+            route.methods = normalize_methods(&route.methods);
+        }
+    }
+
     /// Validate the configuration
     pub fn validate(&self) -> anyhow::Result<()> {
+        if self.server_section
+            && !self.servers.is_empty()
+            && self.routing.server_merge == ServerMerge::Error
+        {
+            anyhow::bail!(
+                "Both [server] and [[servers]] are configured; remove one, or set \
+                 routing.server_merge = \"merge\" to start [server] alongside the list"
+            );
+        }
+
+        if !self.routing.base_path.is_empty() && !self.routing.base_path.starts_with('/') {
+            anyhow::bail!(
+                "routing.base_path '{}' must start with '/'",
+                self.routing.base_path
+            );
+        }
+
+        if let Some(canonical) = &self.routing.canonical_host {
+            if canonical.host.is_empty() {
+                anyhow::bail!("routing.canonical_host.host must not be empty");
+            }
+            if canonical.is_alias(&canonical.host) {
+                anyhow::bail!(
+                    "routing.canonical_host.aliases must not include the canonical host '{}'",
+                    canonical.host
+                );
+            }
+            if !matches!(canonical.scheme.as_str(), "http" | "https") {
+                anyhow::bail!(
+                    "routing.canonical_host.scheme must be http or https, got '{}'",
+                    canonical.scheme
+                );
+            }
+            if !matches!(canonical.status, 301 | 308) {
+                anyhow::bail!(
+                    "routing.canonical_host.status must be 301 or 308, got {}",
+                    canonical.status
+                );
+            }
+        }
+
+        if self.client.dns_refresh_interval == Some(0) {
+            anyhow::bail!("client.dns_refresh_interval must be at least 1");
+        }
+        if self.client.connection_max_lifetime == Some(0) {
+            anyhow::bail!("client.connection_max_lifetime must be at least 1");
+        }
+        if self.client.connection_max_idle == Some(0) {
+            anyhow::bail!("client.connection_max_idle must be at least 1");
+        }
+
+        if let Some(max_routes) = self.routing.max_routes {
+            let enabled = self.routes.iter().filter(|r| r.enabled).count();
+            if enabled > max_routes {
+                anyhow::bail!(
+                    "{} routes configured, more than routing.max_routes ({})",
+                    enabled,
+                    max_routes
+                );
+            }
+        }
+
+        if !(0.0..=1.0).contains(&self.access_log.sample_rate) {
+            anyhow::bail!(
+                "access_log.sample_rate must be between 0.0 and 1.0, got {}",
+                self.access_log.sample_rate
+            );
+        }
+
+        if self.routing.match_mode == MatchMode::Error {
+            self.validate_no_overlaps()?;
+        }
+
+        if self.metrics.route_groups.len() > MAX_ROUTE_GROUPS {
+            anyhow::bail!(
+                "metrics.route_groups has {} entries, at most {} are allowed",
+                self.metrics.route_groups.len(),
+                MAX_ROUTE_GROUPS
+            );
+        }
+        for route in &self.routes {
+            if let Some(group) = &route.group {
+                if group != DEFAULT_ROUTE_GROUP && !self.metrics.route_groups.contains(group) {
+                    anyhow::bail!(
+                        "Route '{}' uses group '{}', which is not listed in metrics.route_groups",
+                        route.path,
+                        group
+                    );
+                }
+            }
+        }
+
+        for (status, page) in &self.errors {
+            let valid = status
+                .parse::<u16>()
+                .is_ok_and(|code| (400..=599).contains(&code));
+            if !valid {
+                anyhow::bail!(
+                    "errors.{}: key must be an HTTP error status code (400-599)",
+                    status
+                );
+            }
+            if page.html_file.as_os_str().is_empty() {
+                anyhow::bail!("errors.{}: html_file must not be empty", status);
+            }
+        }
+
+        if let Some(source) = &self.routes_source {
+            if source.url.trim().is_empty() {
+                anyhow::bail!("routes_source.url must not be empty");
+            }
+        }
+
         // Check that all routes reference valid API key pools
         for route in &self.routes {
             if let Some(pool_name) = &route.api_key_pool {
@@ -283,10 +1764,54 @@ impl GatewayConfig {
                     );
                 }
             }
+            if let Some(name) = &route.client_key_header {
+                if route.api_key_pool.is_none() {
+                    anyhow::bail!(
+                        "Route '{}' sets client_key_header without an api_key_pool",
+                        route.path
+                    );
+                }
+                if name.parse::<axum::http::HeaderName>().is_err() {
+                    anyhow::bail!(
+                        "Route '{}' has an invalid client_key_header '{}'",
+                        route.path,
+                        name
+                    );
+                }
+            }
+        }
+
+        // Check that route methods are known HTTP methods
+        for route in &self.routes {
+            for method in &route.methods {
+                if !KNOWN_METHODS.contains(&method.to_ascii_uppercase().as_str()) {
+                    anyhow::bail!("Route '{}' has invalid method '{}'", route.path, method);
+                }
+            }
         }
 
         // Check that all API key pools have at least one enabled key
+        // and use a known selection strategy
         for (name, pool) in &self.api_key_pools {
+            if !crate::api_key::StrategyRegistry::global().contains(pool.strategy.as_str()) {
+                anyhow::bail!(
+                    "API key pool '{}' uses unknown strategy '{}'",
+                    name,
+                    pool.strategy.as_str()
+                );
+            }
+            if let Some(oauth2) = &pool.oauth2 {
+                if oauth2.token_url.is_empty() {
+                    anyhow::bail!("API key pool '{}' has an empty oauth2.token_url", name);
+                }
+                if pool.query_param_name.is_some() {
+                    anyhow::bail!(
+                        "API key pool '{}' can't inject OAuth2 tokens as a query parameter",
+                        name
+                    );
+                }
+                continue;
+            }
             let enabled_keys: Vec<_> = pool.keys.iter().filter(|k| k.enabled).collect();
             if enabled_keys.is_empty() {
                 anyhow::bail!("API key pool '{}' has no enabled keys", name);
@@ -312,14 +1837,377 @@ impl GatewayConfig {
             }
         }
 
+        // Check that no two listeners share an address (port 0 binds an
+        // ephemeral port per listener)
+        let servers = self.get_servers();
+        let mut bound = std::collections::HashSet::new();
+        for server in &servers {
+            if server.hosts.iter().any(|h| h.trim().is_empty()) {
+                anyhow::bail!(
+                    "Server '{}' has an empty entry in hosts",
+                    server.display_name()
+                );
+            }
+            for addr in server.bind_addrs() {
+                if server.port != 0 && !bound.insert(addr.clone()) {
+                    anyhow::bail!(
+                        "Server '{}' binds {}, which another listener already binds",
+                        server.display_name(),
+                        addr
+                    );
+                }
+            }
+        }
+
+        // Check that server dependencies exist and are acyclic
+        for server in &servers {
+            if server.max_connections == Some(0) {
+                anyhow::bail!(
+                    "Server '{}' max_connections must be at least 1",
+                    server.display_name()
+                );
+            }
+            for dependency in &server.depends_on {
+                if !servers.iter().any(|s| s.display_name() == *dependency) {
+                    anyhow::bail!(
+                        "Server '{}' depends on unknown server '{}'",
+                        server.display_name(),
+                        dependency
+                    );
+                }
+            }
+        }
+        self.servers_in_startup_order()?;
+
+        // Check that histogram buckets are usable
+        let buckets = &self.metrics.latency_buckets;
+        if buckets.is_empty() || buckets.windows(2).any(|w| w[0] >= w[1]) {
+            anyhow::bail!("Metrics latency_buckets must be non-empty and strictly increasing");
+        }
+
+        if self.health.upstream.concurrency == 0 {
+            anyhow::bail!("health.upstream.concurrency must be at least 1");
+        }
+        if self.health.upstream.interval_secs == Some(0) {
+            anyhow::bail!("health.upstream.interval_secs must be at least 1");
+        }
+
+        // Check that routes have a usable concurrency limit
+        for route in &self.routes {
+            if route.max_concurrency == Some(0) {
+                anyhow::bail!("Route '{}' has max_concurrency set to 0", route.path);
+            }
+            if route.timeout_ms == Some(0) {
+                anyhow::bail!("Route '{}' has timeout_ms set to 0", route.path);
+            }
+            if route.aggregate.is_empty() && route.mock.is_none() && route.target.is_empty() {
+                anyhow::bail!("Route '{}' has no target", route.path);
+            }
+            if let Some(mock) = &route.mock {
+                if axum::http::StatusCode::from_u16(mock.status).is_err() {
+                    anyhow::bail!(
+                        "Route '{}' mock status {} is invalid",
+                        route.path,
+                        mock.status
+                    );
+                }
+                if mock.body.is_some() && mock.body_file.is_some() {
+                    anyhow::bail!("Route '{}' mock sets both body and body_file", route.path);
+                }
+                if let Some(path) = mock.body_file.as_ref().filter(|p| !p.is_file()) {
+                    anyhow::bail!(
+                        "Route '{}' mock body_file {} does not exist",
+                        route.path,
+                        path.display()
+                    );
+                }
+                for name in mock.headers.keys() {
+                    if axum::http::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                        anyhow::bail!(
+                            "Route '{}' mock header name '{}' is invalid",
+                            route.path,
+                            name
+                        );
+                    }
+                }
+            }
+            if let Some(path) = route
+                .empty_path
+                .as_ref()
+                .filter(|p| !p.is_empty() && !p.starts_with('/'))
+            {
+                anyhow::bail!(
+                    "Route '{}' empty_path '{}' must be empty or start with '/'",
+                    route.path,
+                    path
+                );
+            }
+            if let Some(sni) = &route.tls_sni {
+                if !matches!(
+                    rustls_pki_types::ServerName::try_from(sni.as_str()),
+                    Ok(rustls_pki_types::ServerName::DnsName(_))
+                ) {
+                    anyhow::bail!("Route '{}' tls_sni '{}' is not a DNS name", route.path, sni);
+                }
+                if !route.target.starts_with("https://") {
+                    anyhow::bail!(
+                        "Route '{}' sets tls_sni without an https target",
+                        route.path
+                    );
+                }
+                if route.title_case_headers {
+                    anyhow::bail!(
+                        "Route '{}' can't combine tls_sni with title_case_headers",
+                        route.path
+                    );
+                }
+            }
+            if let Some(path) = &route.body_schema {
+                crate::proxy::load_body_schema(path)
+                    .map_err(|e| anyhow::anyhow!("Route '{}': {}", route.path, e))?;
+            }
+            for (name, value) in &route.headers {
+                expand_header_template(value, |_| String::new()).map_err(|e| {
+                    anyhow::anyhow!("Route '{}' header '{}': {}", route.path, name, e)
+                })?;
+            }
+            let mapped_headers = route
+                .header_to_query
+                .keys()
+                .chain(route.query_to_header.values())
+                .chain(&route.forward_headers);
+            for name in mapped_headers {
+                if axum::http::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                    anyhow::bail!("Route '{}' maps invalid header name '{}'", route.path, name);
+                }
+            }
+            let mut aggregate_names = std::collections::HashSet::new();
+            for sub in &route.aggregate {
+                if sub.name.is_empty() || !aggregate_names.insert(sub.name.as_str()) {
+                    anyhow::bail!(
+                        "Route '{}' has an empty or duplicate aggregate name '{}'",
+                        route.path,
+                        sub.name
+                    );
+                }
+            }
+            if let LatencySampling::Fraction(rate) = route.latency_sampling {
+                if !(0.0..=1.0).contains(&rate) {
+                    anyhow::bail!(
+                        "Route '{}' latency_sampling must be between 0.0 and 1.0, got {}",
+                        route.path,
+                        rate
+                    );
+                }
+            }
+            if let Some(capture) = &route.capture {
+                if !(0.0..=1.0).contains(&capture.sample_rate) {
+                    anyhow::bail!(
+                        "Route '{}' capture sample_rate must be between 0.0 and 1.0, got {}",
+                        route.path,
+                        capture.sample_rate
+                    );
+                }
+                if capture.dir.as_os_str().is_empty() {
+                    anyhow::bail!("Route '{}' capture dir must not be empty", route.path);
+                }
+            }
+            if let Some(idempotency_key) = &route.idempotency_key {
+                let header = &idempotency_key.header;
+                if header.parse::<axum::http::HeaderName>().is_err() {
+                    anyhow::bail!(
+                        "Route '{}' has an invalid idempotency_key header '{}'",
+                        route.path,
+                        header
+                    );
+                }
+            }
+            if let Some(compare) = &route.compare {
+                if !(0.0..=100.0).contains(&compare.percent) {
+                    anyhow::bail!(
+                        "Route '{}' compare percent must be between 0 and 100, got {}",
+                        route.path,
+                        compare.percent
+                    );
+                }
+                let target = compare.target.parse::<axum::http::Uri>();
+                if !target.is_ok_and(|uri| uri.scheme().is_some() && uri.host().is_some()) {
+                    anyhow::bail!(
+                        "Route '{}' compare target '{}' is not an absolute URL",
+                        route.path,
+                        compare.target
+                    );
+                }
+            }
+            if let Some(cache) = &route.cache {
+                if cache.ttl_secs == 0 || cache.max_entries == 0 {
+                    anyhow::bail!(
+                        "Route '{}' cache ttl_secs and max_entries must be greater than 0",
+                        route.path
+                    );
+                }
+            }
+            if let Some(breaker) = &route.circuit_breaker {
+                if breaker.window_size == 0 || breaker.minimum_calls == 0 {
+                    anyhow::bail!(
+                        "Route '{}' circuit breaker window_size and minimum_calls must be greater than 0",
+                        route.path
+                    );
+                }
+                for (name, rate) in [
+                    ("failure_rate", breaker.failure_rate),
+                    ("slow_call_rate", breaker.slow_call_rate),
+                ] {
+                    if !(rate > 0.0 && rate <= 1.0) {
+                        anyhow::bail!(
+                            "Route '{}' circuit breaker {} must be in (0.0, 1.0], got {}",
+                            route.path,
+                            name,
+                            rate
+                        );
+                    }
+                }
+            }
+        }
+
         // Validate master access token configuration
         if self.master_access_token.enabled && self.master_access_token.tokens.is_empty() {
             anyhow::bail!("Master access token guard is enabled but no tokens are configured");
         }
+        if self.forwarding.forwarded_prefix
+            && self
+                .forwarding
+                .forwarded_prefix_header
+                .parse::<axum::http::HeaderName>()
+                .is_err()
+        {
+            anyhow::bail!(
+                "forwarding.forwarded_prefix_header '{}' is not a valid header name",
+                self.forwarding.forwarded_prefix_header
+            );
+        }
+        if let Some(value) = self
+            .forwarding
+            .server_header
+            .as_ref()
+            .and_then(|h| h.value())
+        {
+            if value.parse::<axum::http::HeaderValue>().is_err() {
+                anyhow::bail!(
+                    "forwarding.server_header '{}' is not a valid header value",
+                    value
+                );
+            }
+        }
+        if self.admin.enabled && !self.master_access_token.enabled {
+            anyhow::bail!("Admin API is enabled but the master access token guard is not");
+        }
+        if let Some(url) = &self.alerts.webhook_url {
+            if reqwest::Url::parse(url).is_err() {
+                anyhow::bail!("alerts.webhook_url '{}' is not a valid URL", url);
+            }
+            let threshold = self.alerts.error_rate_threshold;
+            if !(threshold > 0.0 && threshold <= 1.0) {
+                anyhow::bail!(
+                    "alerts.error_rate_threshold must be in (0.0, 1.0], got {}",
+                    threshold
+                );
+            }
+            let recovery = self.alerts.recovery_threshold();
+            if !(recovery >= 0.0 && recovery <= threshold) {
+                anyhow::bail!(
+                    "alerts.recovery_threshold must be in [0.0, error_rate_threshold], got {}",
+                    recovery
+                );
+            }
+            if self.alerts.window_secs == 0 {
+                anyhow::bail!("alerts.window_secs must be greater than 0");
+            }
+        }
+        for token in &self.master_access_token.tokens {
+            if token.label().trim().is_empty() {
+                anyhow::bail!("master_access_token: token labels must not be empty");
+            }
+        }
 
         Ok(())
     }
 
+    /// Collect configuration smells that validation accepts
+    ///
+    /// - `W001`: an enabled route matches every method and forwards the full
+    ///   path (no `methods`, `strip_prefix = false`)
+    /// - `W002`: a pool with the weighted strategy has a single key
+    /// - `W003`: the master access token guard has a single token, so it
+    ///   can't be rotated without downtime
+    /// - `W004`: a server references a disabled route
+    /// - `W005`: a `/` route exists without a `/*` route, so paths other
+    ///   than the root are no longer caught by it
+    pub fn lint(&self) -> Vec<ConfigWarning> {
+        let mut warnings = Vec::new();
+        for route in self.routes.iter().filter(|r| r.enabled) {
+            if route.methods.is_empty() && !route.strip_prefix {
+                warnings.push(ConfigWarning {
+                    code: "W001",
+                    message: format!(
+                        "Route '{}' matches every method and forwards the full path; set `methods` or `strip_prefix`",
+                        route.path
+                    ),
+                });
+            }
+        }
+
+        let mut pools: Vec<_> = self.api_key_pools.iter().collect();
+        pools.sort_by_key(|(name, _)| name.as_str());
+        for (name, pool) in pools {
+            if pool.strategy == ApiKeyStrategy::Weight && pool.keys.len() == 1 {
+                warnings.push(ConfigWarning {
+                    code: "W002",
+                    message: format!(
+                        "API key pool '{}' uses the weight strategy with a single key",
+                        name
+                    ),
+                });
+            }
+        }
+
+        let guard = &self.master_access_token;
+        if guard.enabled && guard.tokens.len() == 1 {
+            warnings.push(ConfigWarning {
+                code: "W003",
+                message: "Master access token guard has a single token; add a second one to rotate it without downtime".to_string(),
+            });
+        }
+
+        for server in self.get_servers() {
+            for route_ref in &server.routes {
+                let disabled = self.routes.iter().any(|r| {
+                    !r.enabled && (r.name.as_deref() == Some(route_ref) || r.path == *route_ref)
+                });
+                if disabled {
+                    warnings.push(ConfigWarning {
+                        code: "W004",
+                        message: format!(
+                            "Server '{}' references disabled route '{}'",
+                            server.display_name(),
+                            route_ref
+                        ),
+                    });
+                }
+            }
+        }
+
+        let enabled = |path: &str| self.routes.iter().any(|r| r.enabled && r.path == path);
+        if enabled("/") && !enabled("/*") {
+            warnings.push(ConfigWarning {
+                code: "W005",
+                message: "Route '/' matches only the root path; use '/*' to match every path"
+                    .to_string(),
+            });
+        }
+        warnings
+    }
+
     /// Get server address
     pub fn server_addr(&self) -> String {
         format!("{}:{}", self.server.host, self.server.port)
@@ -331,12 +2219,65 @@ impl GatewayConfig {
     }
 
     /// Get all configured servers (returns either `servers` list or a single-item list with `server`)
+    ///
+    /// With `routing.server_merge = "merge"` and both sections present,
+    /// `server` comes first, followed by the `servers` list.
     pub fn get_servers(&self) -> Vec<&ServerConfig> {
-        if !self.servers.is_empty() {
-            self.servers.iter().collect()
-        } else {
-            vec![&self.server]
+        if self.servers.is_empty() {
+            return vec![&self.server];
+        }
+        let mut servers = Vec::with_capacity(self.servers.len() + 1);
+        if self.server_section && self.routing.server_merge == ServerMerge::Merge {
+            servers.push(&self.server);
         }
+        servers.extend(&self.servers);
+        servers
+    }
+
+    /// Get all configured servers ordered so that every server comes after
+    /// the servers listed in its `depends_on`
+    ///
+    /// Servers without dependencies keep their configured order. Fails if the
+    /// dependencies form a cycle.
+    pub fn servers_in_startup_order(&self) -> anyhow::Result<Vec<&ServerConfig>> {
+        let servers = self.get_servers();
+        let mut started = vec![false; servers.len()];
+        let mut ordered = Vec::with_capacity(servers.len());
+
+        while ordered.len() < servers.len() {
+            let mut progressed = false;
+            for (i, server) in servers.iter().enumerate() {
+                if started[i] {
+                    continue;
+                }
+                let dependencies_started = server.depends_on.iter().all(|dependency| {
+                    servers
+                        .iter()
+                        .zip(&started)
+                        .any(|(s, &done)| done && s.display_name() == *dependency)
+                });
+                if dependencies_started {
+                    started[i] = true;
+                    ordered.push(*server);
+                    progressed = true;
+                }
+            }
+
+            if !progressed {
+                let remaining: Vec<String> = servers
+                    .iter()
+                    .zip(&started)
+                    .filter(|(_, &done)| !done)
+                    .map(|(s, _)| s.display_name())
+                    .collect();
+                anyhow::bail!(
+                    "Server dependency cycle detected between: {}",
+                    remaining.join(", ")
+                );
+            }
+        }
+
+        Ok(ordered)
     }
 
     /// Get routes for a specific server
@@ -360,6 +2301,88 @@ impl GatewayConfig {
         }
     }
 
+    /// Whether `next` can be applied to servers running this configuration
+    /// by swapping their route tables in place
+    ///
+    /// True when the routes, API key pools or upstream pools changed and
+    /// nothing else did.
+    /// Configurations with a routes source always restart, since their
+    /// poller merges remote routes into the configuration it started with.
+    pub fn reloads_in_place(&self, next: &GatewayConfig) -> bool {
+        if self.routes_source.is_some() || next.routes_source.is_some() {
+            return false;
+        }
+        let (Ok(current), Ok(next)) = (serde_json::to_value(self), serde_json::to_value(next))
+        else {
+            return false;
+        };
+        if current == next {
+            return false;
+        }
+        let without_routes = |mut value: serde_json::Value| {
+            if let Some(sections) = value.as_object_mut() {
+                sections.remove("routes");
+                sections.remove("api_key_pools");
+                sections.remove("upstream_pools");
+            }
+            value
+        };
+        without_routes(current) == without_routes(next)
+    }
+
+    /// Fail if two enabled routes served together can match the same request
+    fn validate_no_overlaps(&self) -> anyhow::Result<()> {
+        let fold = |pattern: &str| {
+            if self.routing.case_insensitive_paths {
+                pattern.to_ascii_lowercase()
+            } else {
+                pattern.to_string()
+            }
+        };
+        let methods_overlap = |a: &RouteConfig, b: &RouteConfig| {
+            a.methods.is_empty()
+                || b.methods.is_empty()
+                || a.methods.iter().any(|m| b.methods.contains(m))
+        };
+        // Routes requiring different values of a parameter never both match
+        let queries_overlap = |a: &RouteConfig, b: &RouteConfig| {
+            !a.match_query.iter().any(|(param, value)| {
+                b.match_query
+                    .get(param)
+                    .is_some_and(|other| value != "*" && other != "*" && value != other)
+            })
+        };
+        let label = |r: &RouteConfig| match &r.name {
+            Some(name) => format!("'{}' ({})", name, r.path),
+            None => format!("'{}'", r.path),
+        };
+
+        let mut conflicts = Vec::new();
+        for server in self.get_servers() {
+            let routes = self.routes_for_server(server);
+            for (i, a) in routes.iter().enumerate() {
+                for b in &routes[i + 1..] {
+                    let pair = format!("{} and {}", label(a), label(b));
+                    if crate::route_index::patterns_overlap(&fold(&a.path), &fold(&b.path))
+                        && methods_overlap(a, b)
+                        && queries_overlap(a, b)
+                        && !conflicts.contains(&pair)
+                    {
+                        conflicts.push(pair);
+                    }
+                }
+            }
+        }
+
+        if !conflicts.is_empty() {
+            anyhow::bail!(
+                "Overlapping routes with routing.match_mode = \"error\": {}",
+                conflicts.join(", ")
+            );
+        }
+        Ok(())
+    }
+
     /// Get server address for a specific server
     pub fn server_addr_for(server: &ServerConfig) -> String {
         format!("{}:{}", server.host, server.port)
@@ -490,71 +2513,283 @@ enabled = true
         assert_eq!(api_routes.len(), 1);
         assert_eq!(api_routes[0].path, "/api/v1/*");
 
-        let admin_routes = config.routes_for_server(&config.servers[1]);
-        assert_eq!(admin_routes.len(), 1);
-        assert_eq!(admin_routes[0].path, "/admin/*");
+        let admin_routes = config.routes_for_server(&config.servers[1]);
+        assert_eq!(admin_routes.len(), 1);
+        assert_eq!(admin_routes[0].path, "/admin/*");
+    }
+
+    #[test]
+    fn test_server_with_all_routes() {
+        let toml = r#"
+[[servers]]
+name = "main-server"
+host = "0.0.0.0"
+port = 8080
+# No routes specified - should use all enabled routes
+
+[[routes]]
+path = "/api/v1/*"
+target = "http://localhost:3001"
+
+[[routes]]
+path = "/api/v2/*"
+target = "http://localhost:3002"
+"#;
+
+        let config = GatewayConfig::parse(toml).unwrap();
+        let routes = config.routes_for_server(&config.servers[0]);
+        assert_eq!(routes.len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_server_route_reference() {
+        let toml = r#"
+[[servers]]
+name = "main-server"
+host = "0.0.0.0"
+port = 8080
+routes = ["nonexistent-route"]
+
+[[routes]]
+name = "api-v1"
+path = "/api/v1/*"
+target = "http://localhost:3001"
+"#;
+
+        let result = GatewayConfig::parse(toml);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_backward_compatibility_single_server() {
+        let toml = r#"
+[server]
+host = "127.0.0.1"
+port = 3000
+
+[[routes]]
+path = "/api/*"
+target = "http://localhost:8081"
+"#;
+
+        let config = GatewayConfig::parse(toml).unwrap();
+        // Should fall back to single server config
+        let servers = config.get_servers();
+        assert_eq!(servers.len(), 1);
+        assert_eq!(servers[0].host, "127.0.0.1");
+        assert_eq!(servers[0].port, 3000);
+    }
+
+    #[test]
+    fn test_watch_config() {
+        let config = GatewayConfig::default();
+        assert_eq!(config.watch.debounce_ms, 300);
+
+        let config = GatewayConfig::parse("[watch]\ndebounce_ms = 50\n").unwrap();
+        assert_eq!(config.watch.debounce_ms, 50);
+    }
+
+    #[test]
+    fn test_server_startup_order() {
+        let toml = r#"
+[[servers]]
+name = "admin-server"
+port = 9090
+depends_on = ["api-server"]
+
+[[servers]]
+name = "api-server"
+port = 8080
+
+[[servers]]
+name = "metrics-server"
+port = 9100
+"#;
+
+        let config = GatewayConfig::parse(toml).unwrap();
+        let order: Vec<String> = config
+            .servers_in_startup_order()
+            .unwrap()
+            .iter()
+            .map(|s| s.display_name())
+            .collect();
+        assert_eq!(order, vec!["api-server", "metrics-server", "admin-server"]);
+    }
+
+    #[test]
+    fn test_server_dependency_cycle() {
+        let toml = r#"
+[[servers]]
+name = "a"
+port = 8080
+depends_on = ["b"]
+
+[[servers]]
+name = "b"
+port = 8081
+depends_on = ["a"]
+"#;
+
+        let result = GatewayConfig::parse(toml);
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Server dependency cycle detected between: a, b"));
     }
 
     #[test]
-    fn test_server_with_all_routes() {
+    fn test_server_unknown_dependency() {
         let toml = r#"
 [[servers]]
-name = "main-server"
-host = "0.0.0.0"
+name = "a"
 port = 8080
-# No routes specified - should use all enabled routes
+depends_on = ["missing"]
+"#;
+
+        let result = GatewayConfig::parse(toml);
+        assert!(result.is_err());
+    }
 
+    #[test]
+    fn test_method_normalization() {
+        let toml = r#"
 [[routes]]
-path = "/api/v1/*"
-target = "http://localhost:3001"
+path = "/api/*"
+target = "http://localhost:8081"
+methods = ["get", "Post", " delete "]
 
 [[routes]]
-path = "/api/v2/*"
-target = "http://localhost:3002"
+path = "/any/*"
+target = "http://localhost:8081"
+methods = ["GET", "*"]
 "#;
 
         let config = GatewayConfig::parse(toml).unwrap();
-        let routes = config.routes_for_server(&config.servers[0]);
-        assert_eq!(routes.len(), 2);
+        assert_eq!(config.routes[0].methods, vec!["GET", "POST", "DELETE"]);
+        assert!(config.routes[1].methods.is_empty());
     }
 
     #[test]
-    fn test_invalid_server_route_reference() {
+    fn test_invalid_method_rejected() {
         let toml = r#"
-[[servers]]
-name = "main-server"
-host = "0.0.0.0"
-port = 8080
-routes = ["nonexistent-route"]
-
 [[routes]]
-name = "api-v1"
-path = "/api/v1/*"
-target = "http://localhost:3001"
+path = "/api/*"
+target = "http://localhost:8081"
+methods = ["GE T"]
 "#;
 
         let result = GatewayConfig::parse(toml);
         assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("invalid method 'GE T'"));
+    }
+
+    const BOTH_SERVER_SECTIONS: &str = r#"
+[server]
+name = "legacy"
+port = 8080
+
+[[servers]]
+name = "public"
+port = 8081
+
+[[servers]]
+name = "internal"
+port = 8082
+"#;
+
+    #[test]
+    fn test_server_and_servers_rejected_by_default() {
+        let err = GatewayConfig::parse(BOTH_SERVER_SECTIONS).unwrap_err();
+        assert!(err.to_string().contains("Both [server] and [[servers]]"));
     }
 
     #[test]
-    fn test_backward_compatibility_single_server() {
-        let toml = r#"
+    fn test_server_merged_with_servers() {
+        let config = GatewayConfig::parse(&format!(
+            "[routing]\nserver_merge = \"merge\"\n{}",
+            BOTH_SERVER_SECTIONS
+        ))
+        .unwrap();
+        let ports: Vec<u16> = config.get_servers().iter().map(|s| s.port).collect();
+        assert_eq!(ports, vec![8080, 8081, 8082]);
+
+        // Without a [server] section only the list is used
+        let config =
+            GatewayConfig::parse("[routing]\nserver_merge = \"merge\"\n[[servers]]\nport = 8081\n")
+                .unwrap();
+        assert_eq!(config.get_servers().len(), 1);
+    }
+
+    #[test]
+    fn test_profile_overrides_base() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_path = dir.path().join("config.toml");
+        fs::write(
+            &base_path,
+            r#"
 [server]
 host = "127.0.0.1"
-port = 3000
+port = 8080
 
 [[routes]]
+name = "api"
 path = "/api/*"
-target = "http://localhost:8081"
-"#;
+target = "http://localhost:3001"
 
-        let config = GatewayConfig::parse(toml).unwrap();
-        // Should fall back to single server config
-        let servers = config.get_servers();
-        assert_eq!(servers.len(), 1);
-        assert_eq!(servers[0].host, "127.0.0.1");
-        assert_eq!(servers[0].port, 3000);
+[[routes]]
+name = "admin"
+path = "/admin/*"
+target = "http://localhost:4000"
+"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("config.prod.toml"),
+            r#"
+[server]
+port = 80
+
+[[routes]]
+name = "api"
+path = "/api/*"
+target = "https://api.prod.example.com"
+
+[[routes]]
+name = "status"
+path = "/status"
+target = "http://localhost:5000"
+"#,
+        )
+        .unwrap();
+
+        let config = GatewayConfig::from_file_with_profile(&base_path, Some("prod")).unwrap();
+        assert_eq!(config.server.port, 80);
+        assert_eq!(config.server.host, "127.0.0.1");
+        assert_eq!(config.routes.len(), 3);
+        assert_eq!(config.routes[0].target, "https://api.prod.example.com");
+        assert_eq!(config.routes[1].target, "http://localhost:4000");
+        assert_eq!(config.routes[2].path, "/status");
+
+        // Without a profile only the base is loaded
+        let config = GatewayConfig::from_file_with_profile(&base_path, None).unwrap();
+        assert_eq!(config.server.port, 8080);
+        assert_eq!(config.routes.len(), 2);
+    }
+
+    #[test]
+    fn test_missing_profile_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_path = dir.path().join("config.toml");
+        fs::write(&base_path, "").unwrap();
+
+        let result = GatewayConfig::from_file_with_profile(&base_path, Some("staging"));
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("Config file for profile 'staging' not found"));
+        assert!(err.contains("config.staging.toml"));
     }
 
     #[test]
@@ -591,7 +2826,7 @@ target = "http://localhost:8081"
         let config = MasterAccessTokenConfig {
             enabled: true,
             header_name: "Authorization".to_string(),
-            tokens: vec!["valid-token".to_string(), "another-valid-token".to_string()],
+            tokens: vec!["valid-token".into(), "another-valid-token".into()],
         };
 
         assert!(config.validate_token("valid-token"));
@@ -604,7 +2839,7 @@ target = "http://localhost:8081"
         let config = MasterAccessTokenConfig {
             enabled: false,
             header_name: "Authorization".to_string(),
-            tokens: vec!["valid-token".to_string()],
+            tokens: vec!["valid-token".into()],
         };
 
         // When disabled, any token should be valid
@@ -612,6 +2847,31 @@ target = "http://localhost:8081"
         assert!(config.validate_token(""));
     }
 
+    #[test]
+    fn test_master_access_token_labels() {
+        let toml = r#"
+[master_access_token]
+enabled = true
+tokens = ["plain-token", { token = "team-token", label = "team-a" }]
+"#;
+
+        let config = GatewayConfig::parse(toml).unwrap();
+        let guard = &config.master_access_token;
+        assert!(guard.validate_token("plain-token"));
+        assert!(guard.validate_token("team-token"));
+        assert!(!guard.validate_token("team-a"));
+
+        assert_eq!(
+            guard.matching_token("plain-token").unwrap().label(),
+            UNLABELED_TOKEN
+        );
+        assert_eq!(
+            guard.matching_token("team-token").unwrap().label(),
+            "team-a"
+        );
+        assert!(guard.matching_token("other").is_none());
+    }
+
     #[test]
     fn test_master_access_token_enabled_no_tokens_error() {
         let toml = r#"
@@ -632,6 +2892,25 @@ target = "http://localhost:8081"
             .contains("Master access token guard is enabled but no tokens are configured"));
     }
 
+    #[test]
+    fn test_admin_requires_master_access_token() {
+        let err = GatewayConfig::parse("[admin]\nenabled = true\n").unwrap_err();
+        assert!(err.to_string().contains("Admin API is enabled"));
+
+        let config = GatewayConfig::parse(
+            r#"
+[admin]
+enabled = true
+
+[master_access_token]
+enabled = true
+tokens = ["secret"]
+"#,
+        )
+        .unwrap();
+        assert!(config.admin.enabled);
+    }
+
     #[test]
     fn test_master_access_token_defense_in_depth() {
         // Test that validate_token returns false when enabled but tokens are empty
@@ -647,4 +2926,562 @@ target = "http://localhost:8081"
         assert!(!config.validate_token("any-token"));
         assert!(!config.validate_token(""));
     }
+
+    #[test]
+    fn test_unknown_strategy_rejected() {
+        let toml = r#"
+[api_key_pools.default]
+strategy = "does_not_exist"
+keys = [{ key = "key1" }]
+"#;
+
+        let result = GatewayConfig::parse(toml);
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("unknown strategy 'does_not_exist'"));
+    }
+
+    #[test]
+    fn test_circuit_breaker_parse() {
+        let toml = r#"
+[[routes]]
+path = "/api/*"
+target = "http://localhost:8081"
+circuit_breaker = { slow_call_threshold_ms = 800, slow_call_rate = 0.3 }
+"#;
+
+        let config = GatewayConfig::parse(toml).unwrap();
+        let breaker = config.routes[0].circuit_breaker.as_ref().unwrap();
+        assert_eq!(breaker.slow_call_threshold_ms, Some(800));
+        assert_eq!(breaker.slow_call_rate, 0.3);
+        assert_eq!(breaker.window_size, 20);
+
+        let invalid = toml.replace("0.3", "1.5");
+        let result = GatewayConfig::parse(&invalid);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("slow_call_rate must be in (0.0, 1.0]"));
+    }
+
+    #[test]
+    fn test_base_path() {
+        let config = GatewayConfig::parse(
+            r#"
+[routing]
+base_path = "/gateway/"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.routing.base_path, "/gateway");
+        assert_eq!(
+            config.routing.endpoint_path(&config.health.path),
+            "/gateway/health"
+        );
+
+        let result = GatewayConfig::parse("[routing]\nbase_path = \"gateway\"");
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("must start with '/'"));
+    }
+
+    #[test]
+    fn test_route_groups() {
+        let toml = r#"
+[metrics]
+route_groups = ["payments"]
+
+[[routes]]
+path = "/pay/*"
+target = "http://localhost:8081"
+group = "payments"
+
+[[routes]]
+path = "/api/*"
+target = "http://localhost:8082"
+"#;
+        let config = GatewayConfig::parse(toml).unwrap();
+        assert_eq!(config.routes[0].group.as_deref(), Some("payments"));
+        assert_eq!(config.routes[1].group, None);
+
+        let result =
+            GatewayConfig::parse(&toml.replace("group = \"payments\"", "group = \"other\""));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("group 'other', which is not listed in metrics.route_groups"));
+    }
+
+    #[test]
+    fn test_max_routes() {
+        let toml = r#"
+[routing]
+max_routes = 1
+
+[[routes]]
+path = "/a/*"
+target = "http://localhost:8081"
+
+[[routes]]
+path = "/b/*"
+target = "http://localhost:8082"
+enabled = false
+"#;
+        assert!(GatewayConfig::parse(toml).is_ok());
+
+        let toml = toml.replace("enabled = false", "");
+        let result = GatewayConfig::parse(&toml);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("more than routing.max_routes (1)"));
+    }
+
+    #[test]
+    fn test_mock_route_needs_no_target() {
+        let config = GatewayConfig::parse(
+            r#"
+[[routes]]
+path = "/mock/*"
+mock = { status = 202, body = "ok", headers = { "Content-Type" = "text/plain" } }
+"#,
+        )
+        .unwrap();
+        let mock = config.routes[0].mock.as_ref().unwrap();
+        assert_eq!(mock.status, 202);
+        assert_eq!(mock.body.as_deref(), Some("ok"));
+
+        for (mock, message) in [
+            ("{ status = 1000 }", "mock status 1000 is invalid"),
+            (
+                "{ body = \"a\", body_file = \"a.json\" }",
+                "both body and body_file",
+            ),
+            (
+                "{ body_file = \"/nonexistent/mock.json\" }",
+                "does not exist",
+            ),
+        ] {
+            let err =
+                GatewayConfig::parse(&format!("[[routes]]\npath = \"/mock/*\"\nmock = {}", mock))
+                    .unwrap_err();
+            assert!(err.to_string().contains(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_body_schema_is_checked_at_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let schema = dir.path().join("user.json");
+        let route = |schema: &std::path::Path| {
+            format!(
+                "[[routes]]\npath = \"/users/*\"\ntarget = \"http://localhost:8081\"\nbody_schema = {:?}",
+                schema
+            )
+        };
+
+        std::fs::write(&schema, r#"{ "type": "object", "required": ["name"] }"#).unwrap();
+        let config = GatewayConfig::parse(&route(&schema)).unwrap();
+        assert_eq!(
+            config.routes[0].body_schema.as_deref(),
+            Some(schema.as_path())
+        );
+        assert_eq!(config.routes[0].body_schema_max_bytes, 1024 * 1024);
+
+        for invalid in [r#"{ "type": "text" }"#, r#"{ "$ref": "other.json" }"#] {
+            std::fs::write(&schema, invalid).unwrap();
+            let err = GatewayConfig::parse(&route(&schema)).unwrap_err();
+            assert!(err.to_string().contains("is invalid"), "{}", err);
+        }
+
+        let err = GatewayConfig::parse(&route(&dir.path().join("missing.json"))).unwrap_err();
+        assert!(err.to_string().contains("Failed to read schema"), "{}", err);
+    }
+
+    #[test]
+    fn test_match_mode_error_rejects_overlapping_routes() {
+        let routes = r#"
+[[routes]]
+name = "api"
+path = "/api/*"
+target = "http://localhost:3000"
+
+[[routes]]
+path = "/api/v1/*"
+target = "http://localhost:3001"
+
+[[routes]]
+path = "/users/*"
+methods = ["GET"]
+target = "http://localhost:3002"
+
+[[routes]]
+path = "/users/*"
+methods = ["POST"]
+target = "http://localhost:3003"
+"#;
+        let config = GatewayConfig::parse(routes).unwrap();
+        assert_eq!(config.routing.match_mode, MatchMode::First);
+
+        let err = GatewayConfig::parse(&format!("[routing]\nmatch_mode = \"error\"\n{}", routes))
+            .unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.contains("'api' (/api/*) and '/api/v1/*'"),
+            "{}",
+            message
+        );
+        assert!(!message.contains("/users/*"), "{}", message);
+
+        let config = GatewayConfig::parse(
+            r#"
+[routing]
+match_mode = "most_specific"
+
+[[routes]]
+path = "/api/*"
+target = "http://localhost:3000"
+priority = 5
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.routing.match_mode, MatchMode::MostSpecific);
+        assert_eq!(config.routes[0].priority, 5);
+    }
+
+    #[test]
+    fn test_match_query_overlaps() {
+        let config = |deep_values: (&str, &str)| {
+            GatewayConfig::parse(&format!(
+                r#"
+[routing]
+match_mode = "error"
+
+[[routes]]
+path = "/search"
+target = "http://deep"
+match_query = {{ deep = "{}" }}
+
+[[routes]]
+path = "/search"
+target = "http://shallow"
+match_query = {{ deep = "{}" }}
+"#,
+                deep_values.0, deep_values.1
+            ))
+        };
+        // Different required values can't both match
+        assert!(config(("true", "false")).is_ok());
+        assert!(config(("true", "true")).is_err());
+        assert!(config(("true", "*")).is_err());
+    }
+
+    #[test]
+    fn test_idempotency_key_config() {
+        let config = |idempotency_key: &str| {
+            GatewayConfig::parse(&format!(
+                "[[routes]]\npath = \"/api/*\"\ntarget = \"http://localhost:3000\"\nidempotency_key = {}\n",
+                idempotency_key
+            ))
+        };
+        let idempotency_key = config("{}").unwrap().routes[0]
+            .idempotency_key
+            .clone()
+            .unwrap();
+        assert_eq!(idempotency_key.header, "Idempotency-Key");
+        assert!(idempotency_key.generate);
+
+        let err = config("{ header = \"bad header\" }").unwrap_err();
+        assert!(
+            err.to_string().contains("invalid idempotency_key header"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_compare_validation() {
+        let config = |compare: &str| {
+            GatewayConfig::parse(&format!(
+                "[[routes]]\npath = \"/api/*\"\ntarget = \"http://localhost:3000\"\ncompare = {}\n",
+                compare
+            ))
+        };
+        let compare = config("{ target = \"http://localhost:3001\" }")
+            .unwrap()
+            .routes[0]
+            .compare
+            .clone()
+            .unwrap();
+        assert_eq!(compare.percent, 100.0);
+        assert!(!compare.compare_body);
+        assert!(!compare.replay_unsafe_methods);
+
+        let err = config("{ target = \"http://localhost:3001\", percent = 150 }").unwrap_err();
+        assert!(err.to_string().contains("percent"), "{}", err);
+        let err = config("{ target = \"localhost:3001\" }").unwrap_err();
+        assert!(err.to_string().contains("absolute URL"), "{}", err);
+    }
+
+    #[test]
+    fn test_latency_sampling() {
+        let config = |value: &str| {
+            GatewayConfig::parse(&format!(
+                "[[routes]]\npath = \"/api/*\"\ntarget = \"http://localhost:3000\"\nlatency_sampling = {}\n",
+                value
+            ))
+        };
+        let sampling = |value: &str| config(value).unwrap().routes[0].latency_sampling;
+        assert_eq!(
+            sampling("\"errors_only\""),
+            LatencySampling::Mode(LatencySamplingMode::ErrorsOnly)
+        );
+        assert_eq!(sampling("0.25"), LatencySampling::Fraction(0.25));
+        assert!(config("1.5").is_err());
+        assert!(config("\"some\"").is_err());
+    }
+
+    #[test]
+    fn test_lint() {
+        let codes = |toml: &str| -> Vec<&'static str> {
+            GatewayConfig::parse(toml)
+                .unwrap()
+                .lint()
+                .into_iter()
+                .map(|w| w.code)
+                .collect()
+        };
+
+        let clean = r#"
+[master_access_token]
+enabled = true
+tokens = ["token-a", "token-b"]
+
+[[routes]]
+name = "users"
+path = "/users/*"
+target = "http://localhost:3000"
+strip_prefix = true
+
+[api_key_pools.weighted]
+strategy = "weight"
+keys = [{ key = "key-1" }, { key = "key-2" }]
+"#;
+        assert!(codes(clean).is_empty());
+
+        assert_eq!(
+            codes("[[routes]]\npath = \"/api/*\"\ntarget = \"http://localhost:3000\"\n"),
+            ["W001"]
+        );
+        assert_eq!(
+            codes(
+                "[api_key_pools.weighted]\nstrategy = \"weight\"\nkeys = [{ key = \"key-1\" }]\n"
+            ),
+            ["W002"]
+        );
+        assert_eq!(
+            codes("[master_access_token]\nenabled = true\ntokens = [\"token-a\"]\n"),
+            ["W003"]
+        );
+        assert_eq!(
+            codes(
+                r#"
+[[servers]]
+name = "public"
+port = 8080
+routes = ["users"]
+
+[[routes]]
+name = "users"
+path = "/users/*"
+target = "http://localhost:3000"
+enabled = false
+"#
+            ),
+            ["W004"]
+        );
+        let root =
+            "[[routes]]\npath = \"/\"\ntarget = \"http://localhost:3000\"\nstrip_prefix = true\n";
+        assert_eq!(codes(root), ["W005"]);
+        assert!(codes(&format!(
+            "{}\n[[routes]]\npath = \"/*\"\ntarget = \"http://localhost:3001\"\nstrip_prefix = true\n",
+            root
+        ))
+        .is_empty());
+    }
+
+    #[test]
+    fn test_reloads_in_place() {
+        let config = |port: u16, extra: &str| {
+            GatewayConfig::parse(&format!(
+                "{}\n[[routes]]\npath = \"/api/*\"\ntarget = \"http://localhost:3000\"\napi_key_pool = \"default\"\n\n[api_key_pools.default]\nkeys = [{{ key = \"key-1\" }}]\n\n[server]\nport = {}\n",
+                extra, port
+            ))
+            .unwrap()
+        };
+        let current = config(8080, "");
+
+        // Only routes, key pools or upstream pools changed
+        let mut next = current.clone();
+        next.routes[0].target = "http://localhost:4000".to_string();
+        assert!(current.reloads_in_place(&next));
+        let mut next = current.clone();
+        next.api_key_pools.get_mut("default").unwrap().keys[0].key = "key-2".to_string();
+        assert!(current.reloads_in_place(&next));
+        let mut next = current.clone();
+        next.upstream_pools
+            .insert("slow".to_string(), UpstreamPoolConfig::default());
+        assert!(current.reloads_in_place(&next));
+
+        // Nothing changed, another section changed, or routes come from a source
+        assert!(!current.reloads_in_place(&current.clone()));
+        let mut next = config(9090, "");
+        next.routes[0].target = "http://localhost:4000".to_string();
+        assert!(!current.reloads_in_place(&next));
+        let sourced = config(
+            8080,
+            "[routes_source]\nurl = \"http://localhost:9000/routes\"\n",
+        );
+        let mut next = sourced.clone();
+        next.routes[0].target = "http://localhost:4000".to_string();
+        assert!(!sourced.reloads_in_place(&next));
+    }
+
+    #[test]
+    fn test_client_key_header_validation() {
+        let route = "[[routes]]\npath = \"/api/*\"\ntarget = \"http://localhost:3000\"\n";
+        let pool = "[api_key_pools.default]\nkeys = [{ key = \"key-1\" }]\n";
+        let config = |extra: &str, pools: &str| {
+            GatewayConfig::parse(&format!("{}{}\n{}", route, extra, pools))
+        };
+        assert!(config(
+            "api_key_pool = \"default\"\nclient_key_header = \"X-Api-Key\"",
+            pool
+        )
+        .is_ok());
+        let err = config("client_key_header = \"X-Api-Key\"", "").unwrap_err();
+        assert!(
+            err.to_string().contains("without an api_key_pool"),
+            "{}",
+            err
+        );
+        let err = config(
+            "api_key_pool = \"default\"\nclient_key_header = \"bad header\"",
+            pool,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("invalid client_key_header"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_tls_sni_validation() {
+        let config = |target: &str, sni: &str| {
+            GatewayConfig::parse(&format!(
+                "[[routes]]\npath = \"/api/*\"\ntarget = \"{}\"\ntls_sni = \"{}\"\n",
+                target, sni
+            ))
+        };
+        let parsed = config("https://10.0.0.5:8443", "api.internal").unwrap();
+        assert_eq!(parsed.routes[0].tls_sni.as_deref(), Some("api.internal"));
+
+        let err = config("http://10.0.0.5:8080", "api.internal").unwrap_err();
+        assert!(err.to_string().contains("https target"), "{}", err);
+        let err = config("https://10.0.0.5:8443", "10.0.0.5").unwrap_err();
+        assert!(err.to_string().contains("not a DNS name"), "{}", err);
+    }
+
+    #[test]
+    fn test_expand_header_template() {
+        let expand = |template: &str| {
+            expand_header_template(template, |token| format!("<{}>", token.to_uppercase()))
+        };
+        for token in HEADER_TEMPLATE_TOKENS {
+            assert_eq!(
+                expand(&format!("{{{}}}", token)).unwrap(),
+                format!("<{}>", token.to_uppercase())
+            );
+        }
+        assert_eq!(expand("plain value").unwrap(), "plain value");
+        assert_eq!(expand("{method} {path}!").unwrap(), "<METHOD> <PATH>!");
+        assert_eq!(expand("{{path}} {{}}").unwrap(), "{path} {}");
+
+        assert!(expand("{query}").unwrap_err().contains("unknown template"));
+        assert!(expand("{path").unwrap_err().contains("unclosed"));
+        assert!(expand("path}").unwrap_err().contains("unmatched"));
+
+        let invalid = r#"
+[[routes]]
+path = "/api/*"
+target = "http://localhost:3000"
+headers = { "X-Path" = "{url}" }
+"#;
+        let err = GatewayConfig::parse(invalid).unwrap_err().to_string();
+        assert!(err.contains("header 'X-Path'"), "{}", err);
+    }
+
+    #[test]
+    fn test_server_header_setting() {
+        let parse = |value: &str| {
+            GatewayConfig::parse(&format!("[forwarding]\nserver_header = {}", value))
+                .map(|config| config.forwarding.server_header)
+        };
+        assert_eq!(
+            parse("\"edge/2\"").unwrap().unwrap().value(),
+            Some("edge/2")
+        );
+        assert_eq!(
+            parse("true").unwrap().unwrap().value(),
+            Some("open-gateway")
+        );
+        assert_eq!(parse("false").unwrap().unwrap().value(), None);
+        assert!(GatewayConfig::parse("")
+            .unwrap()
+            .forwarding
+            .server_header
+            .is_none());
+        assert!(parse("\"bad\\nvalue\"").is_err());
+    }
+
+    #[test]
+    fn test_route_defaults() {
+        let config = GatewayConfig::parse(
+            r#"
+[defaults.route]
+methods = ["get", "POST", "PUT", "DELETE"]
+strip_prefix = true
+timeout_ms = 5000
+
+[[routes]]
+path = "/users/*"
+target = "http://localhost:3000"
+
+[[routes]]
+path = "/reports/*"
+target = "http://localhost:3001"
+methods = ["GET"]
+strip_prefix = false
+"#,
+        )
+        .unwrap();
+
+        let inherited = &config.routes[0];
+        assert_eq!(inherited.methods, vec!["GET", "POST", "PUT", "DELETE"]);
+        assert!(inherited.strip_prefix);
+        assert_eq!(inherited.timeout_ms, Some(5000));
+
+        let overridden = &config.routes[1];
+        assert_eq!(overridden.methods, vec!["GET"]);
+        assert!(!overridden.strip_prefix);
+        assert_eq!(overridden.timeout_ms, Some(5000));
+
+        let err = GatewayConfig::parse("[defaults.route]\npath = \"/*\"").unwrap_err();
+        assert!(err.to_string().contains("can't set 'path'"));
+    }
 }
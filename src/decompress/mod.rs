@@ -0,0 +1,144 @@
+//! Request body decompression module
+//!
+//! This module decodes `Content-Encoding` request bodies for routes with
+//! `decompress_request` enabled:
+//! - `gzip` (and `x-gzip`), `deflate` (zlib) and `br` are supported,
+//!   including stacked codings such as `gzip, br`
+//! - Decoding stops as soon as the output exceeds the route's limit, so a
+//!   small compressed body can't expand without bound
+//! - Bodies with a coding the gateway can't decode are forwarded unchanged
+
+use bytes::Bytes;
+use std::io::Read;
+
+/// Default limit on a decompressed request body (10 MiB)
+pub const DEFAULT_MAX_DECOMPRESSED_BYTES: usize = 10 * 1024 * 1024;
+
+/// Error decoding a request body
+#[derive(Debug, thiserror::Error)]
+pub enum DecompressError {
+    /// The decoded body exceeds the limit
+    #[error("Decompressed body exceeds {0} bytes")]
+    TooLarge(usize),
+    /// The body isn't valid for its content coding
+    #[error("Invalid {coding} body: {source}")]
+    Invalid {
+        /// Content coding being decoded
+        coding: String,
+        /// Decoder error
+        source: std::io::Error,
+    },
+}
+
+/// Check if every coding in a `Content-Encoding` value can be decoded
+pub fn is_supported(content_encoding: &str) -> bool {
+    codings(content_encoding).all(|coding| decoder(coding, &[][..]).is_some())
+}
+
+/// Decode `body` according to its `Content-Encoding` value
+///
+/// Returns `Ok(None)` when the value names a coding that isn't supported,
+/// in which case the body should be forwarded as is.
+pub fn decode(
+    content_encoding: &str,
+    body: Bytes,
+    limit: usize,
+) -> Result<Option<Bytes>, DecompressError> {
+    if !is_supported(content_encoding) {
+        return Ok(None);
+    }
+
+    // Codings are listed in the order they were applied
+    let codings: Vec<&str> = codings(content_encoding).collect();
+    let mut body = body;
+    for coding in codings.into_iter().rev() {
+        let Some(reader) = decoder(coding, &body[..]) else {
+            return Ok(None);
+        };
+        let mut decoded = Vec::new();
+        reader
+            .take(limit as u64 + 1)
+            .read_to_end(&mut decoded)
+            .map_err(|source| DecompressError::Invalid {
+                coding: coding.to_string(),
+                source,
+            })?;
+        if decoded.len() > limit {
+            return Err(DecompressError::TooLarge(limit));
+        }
+        body = Bytes::from(decoded);
+    }
+    Ok(Some(body))
+}
+
+/// Split a `Content-Encoding` value into its codings
+fn codings(content_encoding: &str) -> impl Iterator<Item = &str> {
+    content_encoding
+        .split(',')
+        .map(str::trim)
+        .filter(|coding| !coding.is_empty() && !coding.eq_ignore_ascii_case("identity"))
+}
+
+/// Get a decoding reader for a content coding
+fn decoder<'a>(coding: &str, body: &'a [u8]) -> Option<Box<dyn Read + 'a>> {
+    match coding.to_ascii_lowercase().as_str() {
+        "gzip" | "x-gzip" => Some(Box::new(flate2::read::MultiGzDecoder::new(body))),
+        "deflate" => Some(Box::new(flate2::read::ZlibDecoder::new(body))),
+        "br" => Some(Box::new(brotli_decompressor::Decompressor::new(body, 4096))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Bytes {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        Bytes::from(encoder.finish().unwrap())
+    }
+
+    #[test]
+    fn test_decode_supported_codings() {
+        let body = gzip(b"hello");
+        assert_eq!(
+            decode("gzip", body.clone(), 1024).unwrap().unwrap(),
+            "hello"
+        );
+        assert_eq!(decode("X-Gzip", body, 1024).unwrap().unwrap(), "hello");
+
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&gzip(b"stacked")).unwrap();
+        let body = Bytes::from(encoder.finish().unwrap());
+        assert_eq!(
+            decode("gzip, deflate", body, 1024).unwrap().unwrap(),
+            "stacked"
+        );
+    }
+
+    #[test]
+    fn test_unsupported_coding_is_left_alone() {
+        assert!(decode("zstd", Bytes::from_static(b"raw"), 1024)
+            .unwrap()
+            .is_none());
+        assert!(decode("gzip, zstd", gzip(b"raw"), 1024).unwrap().is_none());
+        assert!(is_supported("identity"));
+    }
+
+    #[test]
+    fn test_limit_and_invalid_body() {
+        let bomb = gzip(&vec![0u8; 1024 * 1024]);
+        assert!(bomb.len() < 2048);
+        assert!(matches!(
+            decode("gzip", bomb, 64 * 1024),
+            Err(DecompressError::TooLarge(_))
+        ));
+        assert!(matches!(
+            decode("gzip", Bytes::from_static(b"not gzip"), 1024),
+            Err(DecompressError::Invalid { .. })
+        ));
+    }
+}
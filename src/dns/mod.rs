@@ -0,0 +1,236 @@
+//! Upstream DNS refresh module
+//!
+//! This module re-resolves upstream hostnames for gateways with
+//! `[client] dns_refresh_interval`, so upstreams behind DNS records with
+//! short TTLs are followed when their addresses change:
+//! - The hosts of every route target (and aggregate sub-request target) are
+//!   resolved on each refresh; IP literals and mock routes are skipped
+//! - When a host resolves to a different set of addresses, the proxy's idle
+//!   connections are closed (with `recycle_on_dns_change`), so later
+//!   requests connect to the new addresses
+//! - Failed lookups are logged and keep the last known addresses
+
+use crate::proxy::ProxyService;
+use futures::future::BoxFuture;
+use std::collections::{BTreeSet, HashMap};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// Resolves upstream hostnames
+pub trait Resolve: Send + Sync {
+    /// Look up the addresses of `host`
+    fn resolve<'a>(
+        &'a self,
+        host: &'a str,
+        port: u16,
+    ) -> BoxFuture<'a, std::io::Result<Vec<IpAddr>>>;
+}
+
+/// Resolver asking the operating system, like upstream connections do
+pub struct SystemResolver;
+
+impl Resolve for SystemResolver {
+    fn resolve<'a>(
+        &'a self,
+        host: &'a str,
+        port: u16,
+    ) -> BoxFuture<'a, std::io::Result<Vec<IpAddr>>> {
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((host, port)).await?;
+            Ok(addrs.map(|addr| addr.ip()).collect())
+        })
+    }
+}
+
+/// Tracks the addresses of a proxy's upstream hosts
+pub struct DnsRefresher {
+    resolver: Arc<dyn Resolve>,
+    /// Close idle connections when addresses change
+    recycle: bool,
+    /// Last known addresses, keyed by host and port
+    known: HashMap<(String, u16), BTreeSet<IpAddr>>,
+}
+
+impl DnsRefresher {
+    /// Create a refresher resolving hosts with `resolver`
+    pub fn new(resolver: Arc<dyn Resolve>, recycle: bool) -> Self {
+        Self {
+            resolver,
+            recycle,
+            known: HashMap::new(),
+        }
+    }
+
+    /// Resolve every upstream host of `proxy`, recycling its connections
+    /// when a host's addresses changed
+    ///
+    /// The first resolution of a host only records its addresses. Returns
+    /// whether connections were recycled.
+    pub async fn refresh(&mut self, proxy: &ProxyService) -> bool {
+        let mut changed = false;
+        for (host, port) in upstream_hosts(proxy) {
+            let addrs: BTreeSet<IpAddr> = match self.resolver.resolve(&host, port).await {
+                Ok(addrs) if !addrs.is_empty() => addrs.into_iter().collect(),
+                Ok(_) => {
+                    warn!("Upstream host '{}' resolved to no addresses", host);
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Failed to resolve upstream host '{}': {}", host, e);
+                    continue;
+                }
+            };
+            if let Some(previous) = self.known.insert((host.clone(), port), addrs.clone()) {
+                if previous != addrs {
+                    info!("Upstream host '{}' now resolves to {:?}", host, addrs);
+                    changed = true;
+                }
+            }
+        }
+
+        if changed && self.recycle {
+            proxy.recycle_connections();
+            return true;
+        }
+        false
+    }
+}
+
+/// Hosts and ports of a proxy's upstream targets, without IP literals
+fn upstream_hosts(proxy: &ProxyService) -> BTreeSet<(String, u16)> {
+    let routes = proxy.get_routes();
+    routes
+        .iter()
+        .filter(|route| route.mock.is_none())
+        .flat_map(|route| {
+            std::iter::once(route.target.as_str())
+                .filter(|target| !target.is_empty())
+                .chain(route.aggregate.iter().map(|sub| sub.target.as_str()))
+        })
+        .filter_map(|target| {
+            let uri: axum::http::Uri = target.parse().ok()?;
+            let host = uri.host()?;
+            let bare = host.trim_start_matches('[').trim_end_matches(']');
+            if bare.parse::<IpAddr>().is_ok() {
+                return None;
+            }
+            let port = uri.port_u16().unwrap_or(match uri.scheme_str() {
+                Some("https") => 443,
+                _ => 80,
+            });
+            Some((host.to_string(), port))
+        })
+        .collect()
+}
+
+/// Refresh the addresses of `proxy`'s upstream hosts every `interval`
+/// until `shutdown_rx` turns `true`
+pub async fn run_dns_refresh(
+    proxy: Arc<ProxyService>,
+    mut refresher: DnsRefresher,
+    interval: Duration,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                refresher.refresh(&proxy).await;
+            }
+            changed = shutdown_rx.changed() => {
+                if changed.is_err() || *shutdown_rx.borrow() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GatewayConfig;
+    use crate::metrics::GatewayMetrics;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    /// Resolver answering with whatever addresses the test sets
+    struct FakeResolver(Mutex<Vec<IpAddr>>);
+
+    impl Resolve for FakeResolver {
+        fn resolve<'a>(
+            &'a self,
+            _host: &'a str,
+            _port: u16,
+        ) -> BoxFuture<'a, std::io::Result<Vec<IpAddr>>> {
+            let addrs = self.0.lock().unwrap().clone();
+            Box::pin(async move { Ok(addrs) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connections_recycled_when_addresses_change() {
+        // Upstream counting the connections it accepts
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let service = hyper::service::service_fn(|_req| async {
+                        Ok::<_, std::convert::Infallible>(hyper::Response::new(
+                            http_body_util::Full::new(bytes::Bytes::from_static(b"ok")),
+                        ))
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(hyper_util::rt::TokioIo::new(socket), service)
+                        .await;
+                });
+            }
+        });
+
+        let config = GatewayConfig::parse(&format!(
+            "[[routes]]\npath = \"/api/*\"\ntarget = \"http://localhost:{}\"",
+            addr.port()
+        ))
+        .unwrap();
+        let proxy = ProxyService::new(
+            ProxyService::routes_from_config(&config.routes, &HashMap::new()).unwrap(),
+            Arc::new(GatewayMetrics::new()),
+        )
+        .unwrap();
+        let send = || async {
+            let req = Request::builder()
+                .uri("/api/users")
+                .body(Body::empty())
+                .unwrap();
+            let response = proxy.forward(req).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        };
+
+        let resolver = Arc::new(FakeResolver(Mutex::new(vec!["10.0.0.1".parse().unwrap()])));
+        let mut refresher = DnsRefresher::new(resolver.clone(), true);
+        assert!(!refresher.refresh(&proxy).await);
+        send().await;
+        send().await;
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+
+        // Unchanged addresses keep the pooled connection
+        assert!(!refresher.refresh(&proxy).await);
+        send().await;
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+
+        // New addresses close it, so the next request connects again
+        *resolver.0.lock().unwrap() = vec!["10.0.0.2".parse().unwrap()];
+        assert!(refresher.refresh(&proxy).await);
+        send().await;
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    }
+}
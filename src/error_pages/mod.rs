@@ -0,0 +1,210 @@
+//! Error page module
+//!
+//! This module renders proxy errors for the client that made the request:
+//! - `[errors]` entries map a status code to an HTML file, loaded once when
+//!   the configuration is (re)loaded
+//! - Clients whose `Accept` header prefers `text/html` get the file
+//! - Clients asking for JSON get a JSON error object
+//! - Everyone else gets the plain text error message
+
+use crate::config::ErrorPageConfig;
+use crate::proxy::ProxyError;
+use axum::body::Body;
+use axum::http::{header, HeaderValue, Response, StatusCode};
+use axum::response::IntoResponse;
+use bytes::Bytes;
+use std::collections::HashMap;
+
+/// A loaded error page
+#[derive(Debug, Clone)]
+struct ErrorPage {
+    content_type: HeaderValue,
+    body: Bytes,
+}
+
+/// Error pages keyed by status code
+#[derive(Debug, Clone, Default)]
+pub struct ErrorPages {
+    pages: HashMap<StatusCode, ErrorPage>,
+}
+
+impl ErrorPages {
+    /// Read the configured error page files
+    pub fn load(config: &HashMap<String, ErrorPageConfig>) -> anyhow::Result<Self> {
+        let mut pages = HashMap::new();
+        for (status, page) in config {
+            let status = StatusCode::from_bytes(status.as_bytes())
+                .map_err(|_| anyhow::anyhow!("Invalid error page status code '{}'", status))?;
+            let body = std::fs::read(&page.html_file).map_err(|e| {
+                anyhow::anyhow!(
+                    "Failed to read error page {}: {}",
+                    page.html_file.display(),
+                    e
+                )
+            })?;
+            let content_type = HeaderValue::from_str(&page.content_type).map_err(|_| {
+                anyhow::anyhow!("Invalid error page content type '{}'", page.content_type)
+            })?;
+            pages.insert(
+                status,
+                ErrorPage {
+                    content_type,
+                    body: Bytes::from(body),
+                },
+            );
+        }
+        Ok(Self { pages })
+    }
+
+    /// Number of loaded pages
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Check if no pages are loaded
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Build the error response for a client sending `accept`
+    pub fn render(&self, err: &ProxyError, accept: Option<&str>) -> Response<Body> {
+        let status = err.status_code();
+        let accept = accept.unwrap_or("");
+        let html = media_quality(accept, "text/html");
+        let json = media_quality(accept, "application/json");
+
+        if html > 0.0 && html >= json {
+            if let Some(page) = self.pages.get(&status) {
+                return (
+                    status,
+                    [(header::CONTENT_TYPE, page.content_type.clone())],
+                    page.body.clone(),
+                )
+                    .into_response();
+            }
+        }
+        if json > 0.0 {
+            let body = serde_json::json!({
+                "status": status.as_u16(),
+                "error": err.reason_label(),
+                "message": err.to_string(),
+            });
+            return (status, axum::Json(body)).into_response();
+        }
+        (status, err.to_string()).into_response()
+    }
+}
+
+/// Get the quality an `Accept` header explicitly gives a media type
+///
+/// Only exact (`text/html`) and subtype wildcard (`text/*`) ranges count, so
+/// clients sending just `*/*` keep the plain text response. Returns 0.0 when
+/// the type isn't listed.
+fn media_quality(accept: &str, media_type: &str) -> f32 {
+    let main_type = media_type.split('/').next().unwrap_or("");
+    let mut best: Option<(u8, f32)> = None;
+    for range in accept.split(',') {
+        let mut parts = range.split(';');
+        let range_type = parts.next().unwrap_or("").trim();
+        let specificity = if range_type.eq_ignore_ascii_case(media_type) {
+            2
+        } else if range_type
+            .strip_suffix("/*")
+            .is_some_and(|t| t.eq_ignore_ascii_case(main_type))
+        {
+            1
+        } else {
+            continue;
+        };
+        let quality = parts
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if best.is_none_or(|(s, _)| specificity > s) {
+            best = Some((specificity, quality));
+        }
+    }
+    best.map(|(_, q)| q).unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::TimeoutPhase;
+    use std::io::Write;
+
+    const PAGE: &str = "<html><body>Back soon</body></html>";
+
+    fn load_pages() -> (ErrorPages, tempfile::NamedTempFile) {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(PAGE.as_bytes()).unwrap();
+        let config = HashMap::from([(
+            "504".to_string(),
+            ErrorPageConfig {
+                html_file: file.path().to_path_buf(),
+                content_type: "text/html; charset=utf-8".to_string(),
+            },
+        )]);
+        (ErrorPages::load(&config).unwrap(), file)
+    }
+
+    async fn body_string(response: Response<Body>) -> String {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    fn content_type(response: &Response<Body>) -> &str {
+        response.headers()[header::CONTENT_TYPE].to_str().unwrap()
+    }
+
+    #[test]
+    fn test_media_quality() {
+        let browser = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+        assert_eq!(media_quality(browser, "text/html"), 1.0);
+        assert_eq!(media_quality(browser, "application/json"), 0.0);
+        assert_eq!(media_quality("text/*;q=0.5", "text/html"), 0.5);
+        assert_eq!(media_quality("text/*, text/html;q=0", "text/html"), 0.0);
+        assert_eq!(media_quality("*/*", "text/html"), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_html_client_gets_error_page() {
+        let (pages, _file) = load_pages();
+        let err = ProxyError::Timeout(TimeoutPhase::Upstream);
+        let response = pages.render(&err, Some("text/html,*/*;q=0.8"));
+
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(content_type(&response), "text/html; charset=utf-8");
+        assert_eq!(body_string(response).await, PAGE);
+    }
+
+    #[tokio::test]
+    async fn test_json_client_gets_json_error() {
+        let (pages, _file) = load_pages();
+        let err = ProxyError::Timeout(TimeoutPhase::Upstream);
+        let response = pages.render(&err, Some("application/json"));
+
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(content_type(&response), "application/json");
+        let body: serde_json::Value = serde_json::from_str(&body_string(response).await).unwrap();
+        assert_eq!(body["status"], 504);
+        assert_eq!(body["error"], "timeout_upstream");
+        assert_eq!(body["message"], err.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_fallback_to_text() {
+        let (pages, _file) = load_pages();
+
+        // No page for 404, so an HTML client gets the text message
+        let response = pages.render(&ProxyError::NoRoute, Some("text/html"));
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(content_type(&response).starts_with("text/plain"));
+
+        let err = ProxyError::Timeout(TimeoutPhase::Upstream);
+        let response = pages.render(&err, None);
+        assert_eq!(body_string(response).await, err.to_string());
+    }
+}
@@ -3,11 +3,23 @@
 //! This module provides health check functionality for the gateway service:
 //! - Basic liveness check
 //! - Readiness check with upstream service health
+//! - Self-checks run after (re)loading the configuration, and optionally on
+//!   an interval, probing a bounded number of upstreams at once
+//! - Draining, which takes the service out of rotation while it keeps serving
 
+use crate::config::{ApiKeyStrategy, GatewayConfig};
+use axum::http::StatusCode;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::future::Future;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use std::time::Instant;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+/// Timeout for connecting to a critical upstream during self-checks
+const UPSTREAM_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// Health status
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -36,6 +48,51 @@ pub struct HealthResponse {
     pub uptime_seconds: u64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checks: Vec<ReadinessCheck>,
+}
+
+impl HealthResponse {
+    /// Get the HTTP status code to answer the probe with
+    pub fn status_code(&self) -> StatusCode {
+        if self.status == HealthStatus::Healthy {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    }
+}
+
+/// Result of a readiness self-check
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReadinessCheck {
+    /// Check name (e.g. `upstream:users`)
+    pub name: String,
+    /// Whether the check passed
+    pub passed: bool,
+    /// Details about the result
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl ReadinessCheck {
+    /// Create a passed check
+    pub fn pass(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            passed: true,
+            message: Some(message.into()),
+        }
+    }
+
+    /// Create a failed check
+    pub fn fail(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            passed: false,
+            message: Some(message.into()),
+        }
+    }
 }
 
 /// Health checker service
@@ -43,6 +100,8 @@ pub struct HealthResponse {
 pub struct HealthChecker {
     start_time: Instant,
     ready: Arc<AtomicBool>,
+    draining: Arc<AtomicBool>,
+    checks: Arc<RwLock<Vec<ReadinessCheck>>>,
     version: String,
 }
 
@@ -52,6 +111,8 @@ impl HealthChecker {
         Self {
             start_time: Instant::now(),
             ready: Arc::new(AtomicBool::new(true)),
+            draining: Arc::new(AtomicBool::new(false)),
+            checks: Arc::new(RwLock::new(Vec::new())),
             version: env!("CARGO_PKG_VERSION").to_string(),
         }
     }
@@ -63,12 +124,34 @@ impl HealthChecker {
             version: self.version.clone(),
             uptime_seconds: self.start_time.elapsed().as_secs(),
             message: None,
+            checks: Vec::new(),
         }
     }
 
     /// Get readiness status
+    ///
+    /// The service is ready when it has been marked ready, isn't draining and
+    /// all self-checks passed. The check results are included in the response.
     pub fn readiness(&self) -> HealthResponse {
-        let is_ready = self.ready.load(Ordering::Relaxed);
+        let marked_ready = self.ready.load(Ordering::Relaxed);
+        let draining = self.is_draining();
+        let checks = self.checks.read().unwrap().clone();
+        let failed: Vec<&str> = checks
+            .iter()
+            .filter(|c| !c.passed)
+            .map(|c| c.name.as_str())
+            .collect();
+        let is_ready = marked_ready && !draining && failed.is_empty();
+
+        let message = if !marked_ready {
+            Some("Service is not ready".to_string())
+        } else if draining {
+            Some("Service is draining".to_string())
+        } else if !failed.is_empty() {
+            Some(format!("Failed checks: {}", failed.join(", ")))
+        } else {
+            None
+        };
 
         HealthResponse {
             status: if is_ready {
@@ -78,11 +161,8 @@ impl HealthChecker {
             },
             version: self.version.clone(),
             uptime_seconds: self.start_time.elapsed().as_secs(),
-            message: if is_ready {
-                None
-            } else {
-                Some("Service is not ready".to_string())
-            },
+            message,
+            checks,
         }
     }
 
@@ -91,9 +171,29 @@ impl HealthChecker {
         self.ready.store(ready, Ordering::Relaxed);
     }
 
+    /// Start or stop draining
+    ///
+    /// Kept apart from [`set_ready`](Self::set_ready) so that self-checks
+    /// finishing while draining don't put the service back in rotation.
+    pub fn set_draining(&self, draining: bool) {
+        self.draining.store(draining, Ordering::Relaxed);
+    }
+
+    /// Check if the service is draining
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    /// Replace the self-check results
+    pub fn set_checks(&self, checks: Vec<ReadinessCheck>) {
+        *self.checks.write().unwrap() = checks;
+    }
+
     /// Check if the service is ready
     pub fn is_ready(&self) -> bool {
         self.ready.load(Ordering::Relaxed)
+            && !self.is_draining()
+            && self.checks.read().unwrap().iter().all(|c| c.passed)
     }
 
     /// Get uptime in seconds
@@ -103,21 +203,7 @@ impl HealthChecker {
 
     /// Get formatted uptime string
     pub fn uptime_formatted(&self) -> String {
-        let total_seconds = self.uptime_seconds();
-        let days = total_seconds / 86400;
-        let hours = (total_seconds % 86400) / 3600;
-        let minutes = (total_seconds % 3600) / 60;
-        let seconds = total_seconds % 60;
-
-        if days > 0 {
-            format!("{}d {}h {}m {}s", days, hours, minutes, seconds)
-        } else if hours > 0 {
-            format!("{}h {}m {}s", hours, minutes, seconds)
-        } else if minutes > 0 {
-            format!("{}m {}s", minutes, seconds)
-        } else {
-            format!("{}s", seconds)
-        }
+        format_uptime(self.uptime_seconds())
     }
 }
 
@@ -127,6 +213,143 @@ impl Default for HealthChecker {
     }
 }
 
+/// Format an uptime in seconds, e.g. `1h 2m 3s`
+pub fn format_uptime(total_seconds: u64) -> String {
+    let days = total_seconds / 86400;
+    let hours = (total_seconds % 86400) / 3600;
+    let minutes = (total_seconds % 3600) / 60;
+    let seconds = total_seconds % 60;
+
+    if days > 0 {
+        format!("{}d {}h {}m {}s", days, hours, minutes, seconds)
+    } else if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Run readiness self-checks against a loaded configuration
+///
+/// Checks that every API key pool used by an enabled route has a usable key
+/// and that the upstreams of routes marked `critical` accept connections.
+pub async fn run_self_checks(config: &GatewayConfig) -> Vec<ReadinessCheck> {
+    let routes: Vec<_> = config.routes.iter().filter(|r| r.enabled).collect();
+    let mut checks = Vec::new();
+
+    let pools: BTreeSet<&String> = routes
+        .iter()
+        .filter_map(|r| r.api_key_pool.as_ref())
+        .collect();
+    for name in pools {
+        let check_name = format!("api_key_pool:{}", name);
+        let usable = config.api_key_pools.get(name).map_or(0, |pool| {
+            pool.keys
+                .iter()
+                .filter(|k| k.enabled && !k.key.trim().is_empty())
+                .filter(|k| pool.strategy != ApiKeyStrategy::Weight || k.weight > 0)
+                .count()
+        });
+        checks.push(if usable > 0 {
+            ReadinessCheck::pass(check_name, format!("{} usable key(s)", usable))
+        } else {
+            ReadinessCheck::fail(check_name, "no usable keys")
+        });
+    }
+
+    let upstreams = routes
+        .iter()
+        .filter(|r| r.critical && r.mock.is_none())
+        .flat_map(|r| {
+            let label = r.name.clone().unwrap_or_else(|| r.path.clone());
+            let targets: Vec<String> = if r.aggregate.is_empty() {
+                vec![r.target.clone()]
+            } else {
+                r.aggregate.iter().map(|a| a.target.clone()).collect()
+            };
+            targets.into_iter().map(move |t| (label.clone(), t))
+        })
+        .collect();
+    checks.extend(probe_upstreams(upstreams, config.health.upstream.concurrency).await);
+
+    checks
+}
+
+/// Probe `(label, target)` upstreams, at most `concurrency` at once
+///
+/// The checks are returned in the order of `upstreams`.
+pub async fn probe_upstreams(
+    upstreams: Vec<(String, String)>,
+    concurrency: usize,
+) -> Vec<ReadinessCheck> {
+    run_bounded(upstreams, concurrency, |(label, target)| async move {
+        let check_name = format!("upstream:{}", label);
+        match check_upstream(&target).await {
+            Ok(()) => ReadinessCheck::pass(check_name, format!("{} reachable", target)),
+            Err(e) => ReadinessCheck::fail(check_name, format!("{} unreachable: {}", target, e)),
+        }
+    })
+    .await
+}
+
+/// Run `task` on every item with at most `concurrency` tasks in flight,
+/// returning the outputs in the order of `items`
+async fn run_bounded<T, F, Fut>(items: Vec<T>, concurrency: usize, task: F) -> Vec<Fut::Output>
+where
+    F: Fn(T) -> Fut,
+    Fut: Future + Send + 'static,
+    Fut::Output: Send + 'static,
+{
+    let mut pending = items.into_iter().map(task).enumerate();
+    let mut running = JoinSet::new();
+    let mut outputs = Vec::new();
+    for (index, future) in pending.by_ref().take(concurrency.max(1)) {
+        running.spawn(async move { (index, future.await) });
+    }
+    while let Some(joined) = running.join_next().await {
+        if let Ok(output) = joined {
+            outputs.push(output);
+        }
+        if let Some((index, future)) = pending.next() {
+            running.spawn(async move { (index, future.await) });
+        }
+    }
+    outputs.sort_by_key(|(index, _)| *index);
+    outputs.into_iter().map(|(_, output)| output).collect()
+}
+
+/// Get the delay before the next probe round: `interval` give or take 10%,
+/// so gateway instances started together don't probe in lockstep
+pub fn jittered_interval(interval: Duration) -> Duration {
+    interval.mul_f64(rand::thread_rng().gen_range(0.9..=1.1))
+}
+
+/// Check that a TCP connection to an upstream URL can be established
+async fn check_upstream(target: &str) -> Result<(), String> {
+    let uri: hyper::Uri = target.parse().map_err(|e| format!("invalid URL: {}", e))?;
+    let host = uri.host().ok_or("missing host")?;
+    let port = uri
+        .port_u16()
+        .unwrap_or(if uri.scheme_str() == Some("https") {
+            443
+        } else {
+            80
+        });
+
+    match tokio::time::timeout(
+        UPSTREAM_CHECK_TIMEOUT,
+        tokio::net::TcpStream::connect((host, port)),
+    )
+    .await
+    {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("connection timed out".to_string()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -159,6 +382,115 @@ mod tests {
         assert_eq!(health.status, HealthStatus::Healthy);
     }
 
+    #[tokio::test]
+    async fn test_reload_to_degraded_config_fails_readiness() {
+        let upstream = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let healthy = format!(
+            r#"
+[[routes]]
+path = "/api/*"
+target = "http://{}"
+critical = true
+api_key_pool = "default"
+
+[api_key_pools.default]
+strategy = "weight"
+keys = [{{ key = "key1", weight = 1 }}]
+"#,
+            upstream.local_addr().unwrap()
+        );
+
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed_addr = closed.local_addr().unwrap();
+        drop(closed);
+        let degraded = healthy
+            .replace(
+                &upstream.local_addr().unwrap().to_string(),
+                &closed_addr.to_string(),
+            )
+            .replace("weight = 1", "weight = 0");
+
+        let checker = HealthChecker::new();
+        let config = GatewayConfig::parse(&healthy).unwrap();
+        checker.set_checks(run_self_checks(&config).await);
+        let health = checker.readiness();
+        assert_eq!(health.status, HealthStatus::Healthy);
+        assert_eq!(health.checks.len(), 2);
+
+        // Reload to a config that is valid but cannot serve traffic
+        let config = GatewayConfig::parse(&degraded).unwrap();
+        checker.set_checks(run_self_checks(&config).await);
+        let health = checker.readiness();
+        assert_eq!(health.status, HealthStatus::Unhealthy);
+        assert!(!checker.is_ready());
+        assert_eq!(
+            health.message.as_deref(),
+            Some("Failed checks: api_key_pool:default, upstream:/api/*")
+        );
+        let upstream_check = health
+            .checks
+            .iter()
+            .find(|c| c.name == "upstream:/api/*")
+            .unwrap();
+        assert!(!upstream_check.passed);
+        assert!(upstream_check
+            .message
+            .as_ref()
+            .unwrap()
+            .contains("unreachable"));
+    }
+
+    #[tokio::test]
+    async fn test_probe_concurrency_is_bounded() {
+        let in_flight = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let max_in_flight = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let started = Instant::now();
+        let outputs = run_bounded((0..50).collect(), 8, |i: usize| {
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            async move {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                i
+            }
+        })
+        .await;
+
+        assert_eq!(outputs, (0..50).collect::<Vec<_>>());
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 8);
+        // 7 rounds of 20ms, far from 50 serial probes
+        assert!(started.elapsed() < Duration::from_millis(800));
+    }
+
+    #[tokio::test]
+    async fn test_probe_many_upstreams() {
+        let mut listeners = Vec::new();
+        let mut upstreams = Vec::new();
+        for i in 0..40 {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            upstreams.push((
+                format!("route{}", i),
+                format!("http://{}", listener.local_addr().unwrap()),
+            ));
+            listeners.push(listener);
+        }
+
+        let checks = probe_upstreams(upstreams, 4).await;
+        assert_eq!(checks.len(), 40);
+        assert!(checks.iter().all(|c| c.passed));
+        assert_eq!(checks[39].name, "upstream:route39");
+    }
+
+    #[test]
+    fn test_jittered_interval() {
+        for _ in 0..100 {
+            let delay = jittered_interval(Duration::from_secs(10));
+            assert!(delay >= Duration::from_secs(9) && delay <= Duration::from_secs(11));
+        }
+    }
+
     #[test]
     fn test_uptime_formatted() {
         let checker = HealthChecker::new();
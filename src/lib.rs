@@ -1,22 +1,57 @@
 //! Open Gateway - A simple and fast API gateway service
 //!
 //! This is a gateway service that provides:
-//! - Request routing to microservices
+//! - Request routing to microservices, indexed by path prefix
 //! - API key pool management with multiple selection strategies
 //! - Prometheus metrics
 //! - Health checks
 //! - TUI monitoring
 //! - Master access token guard for gateway protection
+//! - Config file watching for hot reload
+//! - Per-route circuit breaking
+//! - Per-route response caching with request coalescing
+//! - Log level configuration from CLI flags
+//! - Routes loaded from a remote control plane
+//! - HTML error pages for browser clients
+//! - Runtime route/key toggles that survive reloads
+//! - Admin API for draining before deploys
+//! - Configurable trusted roots for HTTPS upstreams
+//! - Request body decompression for upstreams without content coding support
+//! - Webhook alerts on route error rates
+//! - OAuth2 access tokens injected in place of static API keys
+//! - JSON Schema validation of request bodies
+//! - Sampled request/response capture to disk for debugging
+//! - Periodic re-resolution of upstream hostnames
+//! - Listening sockets inherited through systemd socket activation
+//! - A library entry point to run the gateway from an embedding application
 
+pub mod admin;
+pub mod alerts;
 pub mod api_key;
+pub mod cache;
+pub mod capture;
+pub mod circuit_breaker;
 pub mod config;
+pub mod decompress;
+pub mod dns;
+pub mod error_pages;
 pub mod health;
+pub mod logging;
 pub mod metrics;
+pub mod oauth2;
 pub mod proxy;
+pub mod route_index;
+pub mod routes_source;
+pub mod runtime_state;
+pub mod server;
+pub mod socket_activation;
+pub mod tls;
 pub mod tui;
+pub mod watcher;
 
 pub use config::GatewayConfig;
 pub use config::MasterAccessTokenConfig;
+pub use server::{build_router, run_gateway, run_gateway_with_reload, AppState};
 
 /// Application result type
 pub type Result<T> = anyhow::Result<T>;
@@ -0,0 +1,200 @@
+//! Logging setup module
+//!
+//! This module maps the CLI verbosity flags to a tracing filter:
+//! - `-q/--quiet` logs warnings and errors only
+//! - By default INFO and above is logged
+//! - `-v` enables DEBUG, `-vv` (or more) enables TRACE
+//! - `RUST_LOG`, when set, takes precedence over the flags
+//!
+//! Logs, access log lines included, are written to the process's standard
+//! streams; `flush` is called on shutdown so the last lines aren't lost.
+
+use std::io::Write;
+use std::time::Duration;
+use tracing::Level;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::{EnvFilter, FmtSubscriber};
+
+/// Get the log level for the CLI verbosity flags
+pub fn level_for(quiet: bool, verbose: u8) -> Level {
+    if quiet {
+        return Level::WARN;
+    }
+    match verbose {
+        0 => Level::INFO,
+        1 => Level::DEBUG,
+        _ => Level::TRACE,
+    }
+}
+
+/// Build the log filter
+///
+/// A valid `rust_log` directive string wins over the CLI flags.
+pub fn env_filter(quiet: bool, verbose: u8, rust_log: Option<&str>) -> EnvFilter {
+    rust_log
+        .filter(|directives| !directives.trim().is_empty())
+        .and_then(|directives| EnvFilter::try_new(directives).ok())
+        .unwrap_or_else(|| EnvFilter::new(level_for(quiet, verbose).as_str()))
+}
+
+/// Build a subscriber writing formatted logs to `writer`
+pub fn build_subscriber<W>(
+    quiet: bool,
+    verbose: u8,
+    rust_log: Option<&str>,
+    writer: W,
+) -> impl tracing::Subscriber + Send + Sync + 'static
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    FmtSubscriber::builder()
+        .with_env_filter(env_filter(quiet, verbose, rust_log))
+        .with_writer(writer)
+        .finish()
+}
+
+/// Install the global subscriber for the CLI verbosity flags
+pub fn init<W>(quiet: bool, verbose: u8, writer: W) -> anyhow::Result<()>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let rust_log = std::env::var("RUST_LOG").ok();
+    let subscriber = build_subscriber(quiet, verbose, rust_log.as_deref(), writer);
+    tracing::subscriber::set_global_default(subscriber)?;
+    Ok(())
+}
+
+/// Longest wait for the log streams to flush on shutdown
+pub const FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Flush stdout and stderr, waiting at most `timeout`
+///
+/// Returns whether the flush completed in time.
+pub fn flush(timeout: Duration) -> bool {
+    flush_writers(
+        vec![Box::new(std::io::stdout()), Box::new(std::io::stderr())],
+        timeout,
+    )
+}
+
+/// Flush `writers` in order, waiting at most `timeout`
+///
+/// The flush runs on its own thread so a stalled reader (e.g. a full pipe)
+/// can't hold up the exit. Returns whether it completed in time.
+pub fn flush_writers(writers: Vec<Box<dyn Write + Send>>, timeout: Duration) -> bool {
+    let (done_tx, done_rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for mut writer in writers {
+            let _ = writer.flush();
+        }
+        let _ = done_tx.send(());
+    });
+    done_rx.recv_timeout(timeout).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Writer collecting log output in memory
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Emit one line per level and return the captured output
+    fn capture(quiet: bool, verbose: u8, rust_log: Option<&str>) -> String {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = build_subscriber(quiet, verbose, rust_log, move || writer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("debug line");
+            tracing::info!("info line");
+            tracing::warn!("warn line");
+        });
+        let output = buffer.0.lock().unwrap().clone();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_level_for_flags() {
+        assert_eq!(level_for(false, 0), Level::INFO);
+        assert_eq!(level_for(false, 1), Level::DEBUG);
+        assert_eq!(level_for(false, 2), Level::TRACE);
+        assert_eq!(level_for(false, 5), Level::TRACE);
+        assert_eq!(level_for(true, 0), Level::WARN);
+    }
+
+    #[test]
+    fn test_debug_lines_only_with_verbose() {
+        let output = capture(false, 0, None);
+        assert!(output.contains("info line"));
+        assert!(!output.contains("debug line"));
+
+        let output = capture(false, 1, None);
+        assert!(output.contains("debug line"));
+    }
+
+    /// Writer buffering log output in front of a file, like stdout does
+    #[derive(Clone)]
+    struct BufferedFile(Arc<Mutex<std::io::BufWriter<std::fs::File>>>);
+
+    impl Write for BufferedFile {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.0.lock().unwrap().flush()
+        }
+    }
+
+    #[test]
+    fn test_flush_writes_buffered_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gateway.log");
+        let file = std::fs::File::create(&path).unwrap();
+        let buffered = BufferedFile(Arc::new(Mutex::new(std::io::BufWriter::new(file))));
+        let writer = buffered.clone();
+        let subscriber = build_subscriber(false, 0, None, move || writer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("first request");
+            tracing::info!("last request");
+        });
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+
+        assert!(flush_writers(vec![Box::new(buffered)], FLUSH_TIMEOUT));
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("first request"), "{}", contents);
+        assert!(contents.contains("last request"), "{}", contents);
+
+        print!("partial line without a newline");
+        assert!(flush(FLUSH_TIMEOUT));
+    }
+
+    #[test]
+    fn test_quiet_hides_info() {
+        let output = capture(true, 0, None);
+        assert!(!output.contains("info line"));
+        assert!(output.contains("warn line"));
+    }
+
+    #[test]
+    fn test_rust_log_takes_precedence() {
+        let output = capture(true, 0, Some("debug"));
+        assert!(output.contains("debug line"));
+
+        // An empty value falls back to the flags
+        let output = capture(false, 0, Some(""));
+        assert!(!output.contains("debug line"));
+    }
+}
@@ -8,40 +8,37 @@
 //! - Master access token guard for gateway protection
 //! - Hot reload support when config file changes
 
-use axum::{
-    body::Body,
-    extract::State,
-    http::{Request, StatusCode},
-    middleware::{self, Next},
-    response::{IntoResponse, Response},
-    routing::get,
-    Json, Router,
-};
 use clap::{Parser, Subcommand};
-use notify::{Event, RecursiveMode, Watcher};
 use open_gateway::{
+    admin,
     api_key::{create_selector, SharedApiKeySelector},
     config::GatewayConfig,
     health::HealthChecker,
+    logging,
     metrics::GatewayMetrics,
     proxy::ProxyService,
-    tui::MonitorApp,
-    MasterAccessTokenConfig,
+    run_gateway_with_reload,
+    runtime_state::RuntimeStateStore,
+    tui::{LiveGateway, MonitorApp, Tab, DEFAULT_REFRESH_MS},
+    watcher::watch_config_file,
 };
 use std::collections::HashMap;
-use std::net::SocketAddr;
-use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::watch;
-use tower_http::trace::TraceLayer;
-use tracing::{error, info, warn, Level};
-use tracing_subscriber::FmtSubscriber;
+use tracing::{error, info, warn};
 
 /// Open Gateway - A simple and fast API gateway service
 #[derive(Parser)]
 #[command(name = "open-gateway")]
 #[command(version, about = "A simple and fast API gateway service", long_about = None)]
 struct Cli {
+    /// Only log warnings and errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Increase log verbosity (-v = debug, -vv = trace); RUST_LOG takes precedence
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
     #[command(subcommand)]
     command: Commands,
 }
@@ -56,18 +53,48 @@ enum Commands {
         /// Watch config file for changes and hot reload
         #[arg(short, long, default_value = "false")]
         watch: bool,
+        /// Config profile layered over the base file (e.g. `prod` loads `config.prod.toml`)
+        #[arg(short, long)]
+        profile: Option<String>,
     },
     /// Start the TUI monitor
     Monitor {
         /// Configuration file path
         #[arg(short, long, default_value = "config.toml")]
         config: String,
+        /// Config profile layered over the base file
+        #[arg(short, long)]
+        profile: Option<String>,
+        /// Read a running gateway (e.g. `http://localhost:8080`) instead of the config file
+        #[arg(short, long)]
+        url: Option<String>,
+        /// Master access token sent to the running gateway
+        #[arg(long, requires = "url")]
+        token: Option<String>,
+        /// Header carrying the master access token
+        #[arg(long, default_value = "Authorization", requires = "url")]
+        token_header: String,
+        /// Interval between screen redraws, in milliseconds
+        #[arg(long, default_value_t = DEFAULT_REFRESH_MS, value_parser = clap::value_parser!(u64).range(10..))]
+        refresh_ms: u64,
+        /// Tab shown on launch: overview, routes, pools, config or help
+        #[arg(long, default_value = "overview")]
+        tab: Tab,
     },
     /// Validate the configuration file
     Validate {
         /// Configuration file path
         #[arg(short, long, default_value = "config.toml")]
         config: String,
+        /// Config profile layered over the base file
+        #[arg(short, long)]
+        profile: Option<String>,
+        /// Print the effective configuration as TOML, with secrets masked
+        #[arg(long)]
+        print_config: bool,
+        /// Exit with a non-zero status when configuration warnings are found
+        #[arg(long)]
+        fail_on_warning: bool,
     },
     /// Generate a sample configuration file
     Init {
@@ -77,58 +104,49 @@ enum Commands {
     },
 }
 
-/// Application state shared across handlers
-#[derive(Clone)]
-struct AppState {
-    proxy: Arc<ProxyService>,
-    metrics: Arc<GatewayMetrics>,
-    health: Arc<HealthChecker>,
-    master_access_token: MasterAccessTokenConfig,
-    #[allow(dead_code)]
-    config: GatewayConfig,
-}
-
-/// Master access token guard middleware
-///
-/// When enabled, this middleware validates that incoming requests include a valid
-/// access token in the configured header. This applies to ALL endpoints including
-/// health checks and metrics endpoints for maximum security.
-///
-/// If you need to exclude health/metrics from authentication, consider running
-/// a separate server instance without the guard for internal monitoring.
-async fn master_access_token_guard(
-    State(state): State<AppState>,
-    req: Request<Body>,
-    next: Next,
-) -> Response {
-    // If guard is not enabled, pass through
-    if !state.master_access_token.enabled {
-        return next.run(req).await;
-    }
-
-    // Get the token from the configured header
-    let token = req
-        .headers()
-        .get(&state.master_access_token.header_name)
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("");
-
-    // Validate the token
-    if state.master_access_token.validate_token(token) {
-        next.run(req).await
-    } else {
-        (StatusCode::UNAUTHORIZED, "Invalid or missing access token").into_response()
-    }
-}
-
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Start { config, watch } => start_server(&config, watch).await?,
-        Commands::Monitor { config } => start_monitor(&config).await?,
-        Commands::Validate { config } => validate_config(&config)?,
+        Commands::Start {
+            config,
+            watch,
+            profile,
+        } => {
+            logging::init(cli.quiet, cli.verbose, std::io::stdout)?;
+            let result = start_server(&config, profile.as_deref(), watch).await;
+            // Write out the last access log lines before exiting
+            logging::flush(logging::FLUSH_TIMEOUT);
+            result?
+        }
+        Commands::Monitor {
+            config,
+            profile,
+            url,
+            token,
+            token_header,
+            refresh_ms,
+            tab,
+        } => {
+            // The TUI owns stdout; logs go to stderr so they can be redirected
+            logging::init(cli.quiet, cli.verbose, std::io::stderr)?;
+            let refresh = Duration::from_millis(refresh_ms);
+            match url {
+                Some(url) => start_live_monitor(&url, &token_header, token, refresh, tab).await?,
+                None => start_monitor(&config, profile.as_deref(), refresh, tab).await?,
+            }
+        }
+        Commands::Validate {
+            config,
+            profile,
+            print_config,
+            fail_on_warning,
+        } => {
+            if !validate_config(&config, profile.as_deref(), print_config, fail_on_warning)? {
+                std::process::exit(1);
+            }
+        }
         Commands::Init { output } => generate_sample_config(&output)?,
     }
 
@@ -136,33 +154,53 @@ async fn main() -> anyhow::Result<()> {
 }
 
 /// Start the gateway server with optional hot reload
-async fn start_server(config_path: &str, watch_config: bool) -> anyhow::Result<()> {
-    // Setup logging
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::INFO)
-        .finish();
-    tracing::subscriber::set_global_default(subscriber)?;
-
-    // Create a channel for shutdown signaling
+async fn start_server(
+    config_path: &str,
+    profile: Option<&str>,
+    watch_config: bool,
+) -> anyhow::Result<()> {
+    // Create a channel for config change signaling
     let (shutdown_tx, _) = watch::channel(false);
 
     // Start config file watcher if enabled
     let config_path_owned = config_path.to_string();
+    let profile_owned = profile.map(str::to_string);
     let shutdown_tx_clone = shutdown_tx.clone();
 
     if watch_config {
         info!("Hot reload enabled - watching {} for changes", config_path);
+        let debounce_ms = GatewayConfig::from_file_with_profile(config_path, profile)
+            .map(|c| c.watch)
+            .unwrap_or_default()
+            .debounce_ms;
         let config_path_for_watcher = config_path_owned.clone();
+        let profile_for_watcher = profile_owned.clone();
         tokio::spawn(async move {
-            watch_config_file(&config_path_for_watcher, shutdown_tx_clone).await;
+            watch_config_file(
+                &config_path_for_watcher,
+                profile_for_watcher.as_deref(),
+                Duration::from_millis(debounce_ms),
+                shutdown_tx_clone,
+            )
+            .await;
         });
     }
 
+    // Operator toggles outlive each reload
+    let runtime_state = Arc::new(RuntimeStateStore::new());
+
     // Run server loop (restarts on config change when watch is enabled)
     loop {
         let mut shutdown_rx = shutdown_tx.subscribe();
 
-        match run_servers(&config_path_owned, shutdown_rx.clone()).await {
+        match run_servers(
+            &config_path_owned,
+            profile_owned.as_deref(),
+            &runtime_state,
+            &shutdown_tx,
+        )
+        .await
+        {
             Ok(()) => {
                 if watch_config {
                     // Check if we got a shutdown signal (config changed)
@@ -199,217 +237,113 @@ async fn start_server(config_path: &str, watch_config: bool) -> anyhow::Result<(
     Ok(())
 }
 
-/// Watch config file for changes and trigger reload
-async fn watch_config_file(config_path: &str, shutdown_tx: watch::Sender<bool>) {
-    let path = Path::new(config_path);
-    let parent_dir = path.parent().unwrap_or(Path::new("."));
-    let config_file_name = path
-        .file_name()
-        .and_then(|f| f.to_str())
-        .unwrap_or("")
-        .to_string();
-
-    let (tx, mut rx) = tokio::sync::mpsc::channel::<Result<Event, notify::Error>>(10);
-
-    let mut watcher = match notify::recommended_watcher(move |res| {
-        // Use try_send to avoid blocking the file system event thread
-        // If the channel is full, we drop the event (the next event will still trigger reload)
-        let _ = tx.try_send(res);
-    }) {
-        Ok(w) => w,
-        Err(e) => {
-            error!("Failed to create file watcher: {}", e);
-            return;
-        }
-    };
+/// Run all servers from configuration
+///
+/// Returns once the servers stop; a config change signalled on
+/// `changed_tx` stops them unless it only touches routes or API key pools,
+/// which are swapped in place and the signal cleared.
+async fn run_servers(
+    config_path: &str,
+    profile: Option<&str>,
+    runtime_state: &RuntimeStateStore,
+    changed_tx: &watch::Sender<bool>,
+) -> anyhow::Result<()> {
+    let mut changed_rx = changed_tx.subscribe();
+    let mut current = load_config(config_path, profile, runtime_state)?;
 
-    if let Err(e) = watcher.watch(parent_dir, RecursiveMode::NonRecursive) {
-        error!("Failed to watch config directory: {}", e);
-        return;
-    }
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let (reload_tx, reload_rx) = watch::channel(current.clone());
+    let gateway = run_gateway_with_reload(current.clone(), shutdown_rx, reload_rx);
+    tokio::pin!(gateway);
 
-    info!("Watching {} for changes", config_path);
-
-    while let Some(result) = rx.recv().await {
-        match result {
-            Ok(event) => {
-                // Check if the event is for our config file
-                let is_config_file = event.paths.iter().any(|p| {
-                    p.file_name()
-                        .and_then(|n| n.to_str())
-                        .map(|n| n == config_file_name)
-                        .unwrap_or(false)
-                });
-
-                if is_config_file {
-                    match event.kind {
-                        notify::EventKind::Modify(_) | notify::EventKind::Create(_) => {
-                            // Validate new config before triggering reload
-                            match GatewayConfig::from_file(config_path) {
-                                Ok(_) => {
-                                    info!("Config file changed, triggering reload...");
-                                    let _ = shutdown_tx.send(true);
-                                }
-                                Err(e) => {
-                                    warn!("Config file changed but invalid: {}", e);
-                                    warn!("Keeping current configuration");
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
+    loop {
+        tokio::select! {
+            result = &mut gateway => return result,
+            changed = changed_rx.changed() => {
+                if changed.is_err() {
+                    return gateway.await;
+                }
+                if !*changed_rx.borrow_and_update() {
+                    continue;
                 }
             }
-            Err(e) => {
-                error!("File watch error: {}", e);
+        }
+
+        match load_config(config_path, profile, runtime_state) {
+            Ok(next) if current.reloads_in_place(&next) => {
+                info!("Config changed only in routes or pools, reloading in place...");
+                current = next.clone();
+                reload_tx.send_replace(next);
+                changed_tx.send_replace(false);
+            }
+            _ => {
+                let _ = shutdown_tx.send(true);
+                return gateway.await;
             }
         }
     }
 }
 
-/// Run all servers from configuration
-async fn run_servers(
+/// Load the configuration and re-apply runtime toggles over it
+fn load_config(
     config_path: &str,
-    mut shutdown_rx: watch::Receiver<bool>,
-) -> anyhow::Result<()> {
-    // Load configuration
-    let config = GatewayConfig::from_file(config_path)?;
-    info!("Loaded configuration from {}", config_path);
-
-    // Create API key selectors
-    let api_key_selectors: HashMap<String, SharedApiKeySelector> = config
-        .api_key_pools
-        .iter()
-        .map(|(name, pool)| (name.clone(), create_selector(pool)))
-        .collect();
-
-    // Create shared metrics
-    let metrics = Arc::new(GatewayMetrics::new());
-
-    // Create shared health checker
-    let health = Arc::new(HealthChecker::new());
-
-    // Get all servers to start
-    let servers = config.get_servers();
-    info!("Starting {} server(s)", servers.len());
-    info!("Routes configured: {}", config.routes.len());
-    info!("API key pools configured: {}", config.api_key_pools.len());
-    if config.master_access_token.enabled {
-        info!(
-            "Master access token guard enabled (header: {})",
-            config.master_access_token.header_name
-        );
+    profile: Option<&str>,
+    runtime_state: &RuntimeStateStore,
+) -> anyhow::Result<GatewayConfig> {
+    let mut config = GatewayConfig::from_file_with_profile(config_path, profile)?;
+    match profile {
+        Some(profile) => info!(
+            "Loaded configuration from {} (profile: {})",
+            config_path, profile
+        ),
+        None => info!("Loaded configuration from {}", config_path),
     }
 
-    // Spawn a task for each server
-    let mut handles = Vec::new();
-
-    for server in servers {
-        // Get routes for this server
-        let server_routes: Vec<_> = config
-            .routes_for_server(server)
-            .into_iter()
-            .cloned()
-            .collect();
-
-        let proxy_routes = ProxyService::routes_from_config(&server_routes, &api_key_selectors);
-        let proxy = Arc::new(ProxyService::new(proxy_routes, metrics.clone()));
-
-        // Create app state for this server
-        let state = AppState {
-            proxy,
-            metrics: metrics.clone(),
-            health: health.clone(),
-            master_access_token: config.master_access_token.clone(),
-            config: config.clone(),
-        };
-
-        // Build router with master access token guard middleware
-        let app = Router::new()
-            .route(&config.health.path, get(health_handler))
-            .route(&config.metrics.path, get(metrics_handler))
-            .fallback(proxy_handler)
-            .layer(middleware::from_fn_with_state(
-                state.clone(),
-                master_access_token_guard,
-            ))
-            .layer(TraceLayer::new_for_http())
-            .with_state(state);
-
-        // Get server address
-        let addr: SocketAddr = GatewayConfig::server_addr_for(server).parse()?;
-        let server_name = server
-            .name
-            .clone()
-            .unwrap_or_else(|| format!("{}:{}", server.host, server.port));
-
-        info!(
-            "Starting server '{}' on {} with {} route(s)",
-            server_name,
-            addr,
-            server_routes.len()
-        );
+    // Re-apply runtime toggles over the fresh configuration
+    runtime_state.attach(config.runtime_state.path.as_deref())?;
+    runtime_state.apply(&mut config);
+    Ok(config)
+}
 
-        if config.health.enabled {
-            info!("  Health endpoint at {}", config.health.path);
-        }
-        if config.metrics.enabled {
-            info!("  Metrics endpoint at {}", config.metrics.path);
-        }
+/// Start the TUI monitor against a running gateway
+async fn start_live_monitor(
+    url: &str,
+    token_header: &str,
+    token: Option<String>,
+    refresh: Duration,
+    tab: Tab,
+) -> anyhow::Result<()> {
+    let gateway = LiveGateway::new(url, token_header, token);
+    let view = gateway
+        .fetch()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to read gateway at {}: {}", url, e))?;
 
-        // Spawn the server task with graceful shutdown support
-        let server_shutdown_rx = shutdown_rx.clone();
-        let handle = tokio::spawn(async move {
-            let listener = tokio::net::TcpListener::bind(addr).await?;
-            axum::serve(listener, app.into_make_service())
-                .with_graceful_shutdown(async move {
-                    let mut rx = server_shutdown_rx;
-                    loop {
-                        if rx.changed().await.is_err() {
-                            break;
-                        }
-                        if *rx.borrow() {
-                            break;
-                        }
-                    }
-                })
-                .await?;
-            Ok::<(), anyhow::Error>(())
-        });
-        handles.push(handle);
-    }
+    // Metrics and health come from the live gateway; these are placeholders
+    let metrics = Arc::new(GatewayMetrics::new());
+    let health = Arc::new(HealthChecker::new());
 
-    // Wait for shutdown signal or server error
-    tokio::select! {
-        _ = async {
-            loop {
-                if shutdown_rx.changed().await.is_err() {
-                    break;
-                }
-                if *shutdown_rx.borrow() {
-                    break;
-                }
-            }
-        } => {
-            info!("Shutdown signal received, stopping servers...");
-        }
-        result = async {
-            for handle in handles {
-                handle.await??;
-            }
-            Ok::<(), anyhow::Error>(())
-        } => {
-            return result;
-        }
-    }
+    let mut app = MonitorApp::new(view.config.clone(), metrics, health, Vec::new())
+        .with_live(gateway, view)
+        .with_refresh(refresh)
+        .with_tab(tab);
+    app.run().await?;
 
     Ok(())
 }
 
 /// Start the TUI monitor
-async fn start_monitor(config_path: &str) -> anyhow::Result<()> {
-    // Load configuration
-    let config = GatewayConfig::from_file(config_path)?;
+async fn start_monitor(
+    config_path: &str,
+    profile: Option<&str>,
+    refresh: Duration,
+    tab: Tab,
+) -> anyhow::Result<()> {
+    // Load configuration; an invalid file is shown as an error until it's fixed
+    let (config, config_error) = match GatewayConfig::from_file_with_profile(config_path, profile) {
+        Ok(config) => (config, None),
+        Err(e) => (GatewayConfig::default(), Some(format!("{:#}", e))),
+    };
 
     // Create API key selectors
     let api_key_selectors: HashMap<String, SharedApiKeySelector> = config
@@ -425,76 +359,123 @@ async fn start_monitor(config_path: &str) -> anyhow::Result<()> {
     let health = Arc::new(HealthChecker::new());
 
     // Create proxy routes for display
-    let proxy_routes = ProxyService::routes_from_config(&config.routes, &api_key_selectors);
+    let proxy_routes = ProxyService::routes_from_config(&config.routes, &api_key_selectors)?;
+
+    // Reload the display whenever the file changes to a valid configuration
+    let (changed_tx, _) = watch::channel(false);
+    let watcher = {
+        let config_path = config_path.to_string();
+        let profile = profile.map(str::to_string);
+        let debounce = Duration::from_millis(config.watch.debounce_ms);
+        let changed_tx = changed_tx.clone();
+        tokio::spawn(async move {
+            watch_config_file(&config_path, profile.as_deref(), debounce, changed_tx).await;
+        })
+    };
 
     // Run TUI
-    let mut app = MonitorApp::new(config, metrics, health, proxy_routes);
-    app.run().await?;
+    let mut app = MonitorApp::new(config, metrics, health, proxy_routes)
+        .with_refresh(refresh)
+        .with_tab(tab)
+        .with_config_file(config_path, profile, changed_tx);
+    if let Some(error) = config_error {
+        app = app.with_config_error(error);
+    }
+    let result = app.run().await;
+    watcher.abort();
+    result?;
 
     Ok(())
 }
 
-/// Validate configuration file
-fn validate_config(config_path: &str) -> anyhow::Result<()> {
-    match GatewayConfig::from_file(config_path) {
-        Ok(config) => {
-            println!("✓ Configuration is valid!");
-            println!();
-
-            // Display servers
-            let servers = config.get_servers();
-            println!("Servers: {}", servers.len());
-            for server in &servers {
-                let name = server
-                    .name
-                    .clone()
-                    .unwrap_or_else(|| format!("{}:{}", server.host, server.port));
-                let route_count = config.routes_for_server(server).len();
-                println!(
-                    "  {} ({}:{}) - {} route(s)",
-                    name, server.host, server.port, route_count
-                );
-            }
-            println!();
-
-            println!("Routes: {}", config.routes.len());
-            for route in &config.routes {
-                let status = if route.enabled { "✓" } else { "✗" };
-                let name = route
-                    .name
-                    .clone()
-                    .map(|n| format!("[{}] ", n))
-                    .unwrap_or_default();
-                println!("  {} {}{} → {}", status, name, route.path, route.target);
-            }
-            println!();
+/// Validate configuration file, optionally printing the effective configuration
+///
+/// Warnings are printed to stderr. Returns whether validation passed: the
+/// configuration is invalid, or has warnings with `fail_on_warning`, if not.
+fn validate_config(
+    config_path: &str,
+    profile: Option<&str>,
+    print_config: bool,
+    fail_on_warning: bool,
+) -> anyhow::Result<bool> {
+    let config = match GatewayConfig::from_file_with_profile(config_path, profile) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("✗ Configuration is invalid:");
+            eprintln!("  {}", e);
+            return Ok(false);
+        }
+    };
 
-            println!("API Key Pools: {}", config.api_key_pools.len());
-            for (name, pool) in &config.api_key_pools {
-                println!("  {} ({:?}, {} keys)", name, pool.strategy, pool.keys.len());
-            }
-            println!();
+    let warnings = config.lint();
+    for warning in &warnings {
+        eprintln!("{}", warning);
+    }
+    let passed = warnings.is_empty() || !fail_on_warning;
 
+    if print_config {
+        print!("{}", toml::to_string_pretty(&admin::redact(&config))?);
+    } else {
+        println!("✓ Configuration is valid!");
+        println!();
+
+        // Display servers
+        let servers = config.get_servers();
+        println!("Servers: {}", servers.len());
+        for server in &servers {
+            let name = server
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("{}:{}", server.host, server.port));
+            let route_count = config.routes_for_server(server).len();
             println!(
-                "Master Access Token Guard: {}",
-                if config.master_access_token.enabled {
-                    format!(
-                        "enabled (header: {}, {} token(s))",
-                        config.master_access_token.header_name,
-                        config.master_access_token.tokens.len()
-                    )
-                } else {
-                    "disabled".to_string()
-                }
+                "  {} ({}) - {} route(s)",
+                name,
+                server.bind_addrs().join(", "),
+                route_count
             );
-            Ok(())
         }
-        Err(e) => {
-            eprintln!("✗ Configuration is invalid:");
-            eprintln!("  {}", e);
-            std::process::exit(1);
+        println!();
+
+        println!("Routes: {}", config.routes.len());
+        for route in &config.routes {
+            let status = if route.enabled { "✓" } else { "✗" };
+            let name = route
+                .name
+                .clone()
+                .map(|n| format!("[{}] ", n))
+                .unwrap_or_default();
+            println!("  {} {}{} → {}", status, name, route.path, route.target);
+        }
+        println!();
+
+        println!("API Key Pools: {}", config.api_key_pools.len());
+        for (name, pool) in &config.api_key_pools {
+            println!("  {} ({:?}, {} keys)", name, pool.strategy, pool.keys.len());
         }
+        println!();
+
+        println!(
+            "Master Access Token Guard: {}",
+            if config.master_access_token.enabled {
+                format!(
+                    "enabled (header: {}, {} token(s))",
+                    config.master_access_token.header_name,
+                    config.master_access_token.tokens.len()
+                )
+            } else {
+                "disabled".to_string()
+            }
+        );
+    }
+
+    if !passed {
+        eprintln!(
+            "✗ {} warning(s) found with --fail-on-warning",
+            warnings.len()
+        );
     }
+    Ok(passed)
 }
 
 /// Generate sample configuration file
@@ -538,6 +519,7 @@ path = "/metrics"
 [health]
 enabled = true
 path = "/health"
+ready_path = "/ready"
 
 # Master Access Token Guard Configuration
 # When enabled, all requests must include a valid token in the specified header
@@ -550,6 +532,7 @@ header_name = "Authorization"  # Header name to check for the token
 tokens = [
     # "Bearer your-secret-token-1",
     # "Bearer your-secret-token-2",
+    # { token = "Bearer team-a-token", label = "team-a" },  # Label recorded in logs and metrics
 ]
 
 # Route configurations
@@ -615,29 +598,37 @@ keys = [
     Ok(())
 }
 
-/// Health check handler
-async fn health_handler(State(state): State<AppState>) -> impl IntoResponse {
-    let health = state.health.liveness();
-    (
-        if matches!(health.status, open_gateway::health::HealthStatus::Healthy) {
-            StatusCode::OK
-        } else {
-            StatusCode::SERVICE_UNAVAILABLE
-        },
-        Json(health),
-    )
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_fail_on_warning() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, contents: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+        let validate = |path: &str, fail_on_warning: bool| {
+            validate_config(path, None, false, fail_on_warning).unwrap()
+        };
 
-/// Metrics handler
-async fn metrics_handler(State(state): State<AppState>) -> impl IntoResponse {
-    let output = state.metrics.prometheus_output();
-    (StatusCode::OK, output)
-}
+        // A route forwarding every method with its full path (W001)
+        let smelly = write(
+            "smelly.toml",
+            "[[routes]]\npath = \"/api/*\"\ntarget = \"http://localhost:3000\"\n",
+        );
+        assert!(validate(&smelly, false));
+        assert!(!validate(&smelly, true));
+
+        let clean = write(
+            "clean.toml",
+            "[[routes]]\npath = \"/api/*\"\ntarget = \"http://localhost:3000\"\nmethods = [\"GET\"]\n",
+        );
+        assert!(validate(&clean, true));
 
-/// Proxy handler - forwards requests to target services
-async fn proxy_handler(State(state): State<AppState>, req: Request<Body>) -> impl IntoResponse {
-    match state.proxy.forward(req).await {
-        Ok(response) => response.into_response(),
-        Err((status, message)) => (status, message).into_response(),
+        let invalid = write("invalid.toml", "[[routes]]\npath = \"/api/*\"\n");
+        assert!(!validate(&invalid, false));
     }
 }
@@ -5,16 +5,41 @@
 //! - Request latency histogram
 //! - Active connections gauge
 //! - API key usage counter
+//! - Concurrency limit queue wait histogram
+//! - Master token authentication counter by token label
+//! - Requests cancelled by client disconnects
+//! - Routed requests by route and route group
+//! - Request and response body bytes by route
+//! - Upstream responses rejected for exceeding the size limit
+//! - Open client connections by server
+//!
+//! Metrics are exposed in the Prometheus text format, or in the OpenMetrics
+//! format for scrapers that ask for it.
+//!
+//! The proxy records its metrics through the [`MetricsSink`] trait, which
+//! [`GatewayMetrics`] implements; embedders can forward them elsewhere (e.g.
+//! StatsD) with their own sink, or drop them with [`NoopMetrics`].
 
+use crate::config::LatencySampling;
+use prometheus::proto::{Metric, MetricFamily, MetricType};
 use prometheus::{
     CounterVec, Encoder, GaugeVec, HistogramOpts, HistogramVec, Opts, Registry, TextEncoder,
 };
 use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt::Write as _;
 use std::hash::{Hash, Hasher};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
+/// Content type of the Prometheus text format
+pub const PROMETHEUS_CONTENT_TYPE: &str = prometheus::TEXT_FORMAT;
+
+/// Content type of the OpenMetrics text format
+pub const OPENMETRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
 /// Gateway metrics collector
 #[derive(Clone)]
 pub struct GatewayMetrics {
@@ -23,6 +48,19 @@ pub struct GatewayMetrics {
     request_latency: HistogramVec,
     active_connections: GaugeVec,
     api_key_usage_counter: CounterVec,
+    queue_wait: HistogramVec,
+    auth_requests: CounterVec,
+    client_cancelled: CounterVec,
+    route_requests: CounterVec,
+    request_bytes: CounterVec,
+    response_bytes: CounterVec,
+    responses_too_large: CounterVec,
+    uris_too_long: CounterVec,
+    shadow_comparisons: CounterVec,
+    shadow_mismatches: CounterVec,
+    methods_rejected: CounterVec,
+    invalid_bodies: CounterVec,
+    server_connections: GaugeVec,
     // Simple counters for TUI display
     total_requests: Arc<AtomicU64>,
     total_errors: Arc<AtomicU64>,
@@ -31,6 +69,11 @@ pub struct GatewayMetrics {
 impl GatewayMetrics {
     /// Create a new metrics instance
     pub fn new() -> Self {
+        Self::with_buckets(crate::config::default_latency_buckets())
+    }
+
+    /// Create a new metrics instance with custom histogram buckets (in seconds)
+    pub fn with_buckets(buckets: Vec<f64>) -> Self {
         let registry = Registry::new();
 
         let request_counter = CounterVec::new(
@@ -44,13 +87,21 @@ impl GatewayMetrics {
                 "gateway_request_latency_seconds",
                 "Request latency in seconds",
             )
-            .buckets(vec![
-                0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
-            ]),
+            .buckets(buckets.clone()),
             &["method", "path"],
         )
         .expect("Failed to create latency histogram");
 
+        let queue_wait = HistogramVec::new(
+            HistogramOpts::new(
+                "gateway_queue_wait_seconds",
+                "Time spent waiting for a route concurrency permit in seconds",
+            )
+            .buckets(buckets),
+            &["route"],
+        )
+        .expect("Failed to create queue wait histogram");
+
         let active_connections = GaugeVec::new(
             Opts::new("gateway_active_connections", "Number of active connections"),
             &["route"],
@@ -58,11 +109,122 @@ impl GatewayMetrics {
         .expect("Failed to create active connections gauge");
 
         let api_key_usage_counter = CounterVec::new(
-            Opts::new("gateway_api_key_usage_total", "Total number of requests per API key"),
+            Opts::new(
+                "gateway_api_key_usage_total",
+                "Total number of requests per API key",
+            ),
             &["api_key", "route"],
         )
         .expect("Failed to create API key usage counter");
 
+        let auth_requests = CounterVec::new(
+            Opts::new(
+                "gateway_auth_requests_total",
+                "Total number of requests accepted per master token label",
+            ),
+            &["label"],
+        )
+        .expect("Failed to create auth request counter");
+
+        let client_cancelled = CounterVec::new(
+            Opts::new(
+                "gateway_client_cancelled_total",
+                "Total number of requests abandoned by the client before completion",
+            ),
+            &["route"],
+        )
+        .expect("Failed to create client cancelled counter");
+
+        let route_requests = CounterVec::new(
+            Opts::new(
+                "gateway_route_requests_total",
+                "Total number of requests matched to a route",
+            ),
+            &["route", "group", "status"],
+        )
+        .expect("Failed to create route request counter");
+
+        let request_bytes = CounterVec::new(
+            Opts::new(
+                "gateway_request_bytes_total",
+                "Total bytes of request bodies forwarded to upstreams",
+            ),
+            &["route"],
+        )
+        .expect("Failed to create request bytes counter");
+
+        let response_bytes = CounterVec::new(
+            Opts::new(
+                "gateway_response_bytes_total",
+                "Total bytes of response bodies returned to clients",
+            ),
+            &["route"],
+        )
+        .expect("Failed to create response bytes counter");
+
+        let responses_too_large = CounterVec::new(
+            Opts::new(
+                "gateway_responses_too_large_total",
+                "Total number of upstream responses exceeding the size limit",
+            ),
+            &["route"],
+        )
+        .expect("Failed to create oversized response counter");
+
+        let uris_too_long = CounterVec::new(
+            Opts::new(
+                "gateway_uris_too_long_total",
+                "Total number of requests whose upstream URI exceeds the length limit",
+            ),
+            &["route"],
+        )
+        .expect("Failed to create URI too long counter");
+
+        let shadow_comparisons = CounterVec::new(
+            Opts::new(
+                "gateway_shadow_comparisons_total",
+                "Total number of requests replayed to a route's comparison upstream",
+            ),
+            &["route"],
+        )
+        .expect("Failed to create shadow comparison counter");
+
+        let shadow_mismatches = CounterVec::new(
+            Opts::new(
+                "gateway_shadow_mismatch_total",
+                "Total number of comparison responses differing from the route's",
+            ),
+            &["route"],
+        )
+        .expect("Failed to create shadow mismatch counter");
+
+        let methods_rejected = CounterVec::new(
+            Opts::new(
+                "gateway_method_rejected_total",
+                "Total number of requests whose path matches a route but whose method doesn't",
+            ),
+            &["method", "route"],
+        )
+        .expect("Failed to create rejected method counter");
+
+        let invalid_bodies = CounterVec::new(
+            Opts::new(
+                "gateway_invalid_bodies_total",
+                "Total number of request bodies rejected by a route's body schema",
+            ),
+            &["route"],
+        )
+        .expect("Failed to create invalid body counter");
+
+        let server_connections = GaugeVec::new(
+            Opts::new(
+                "gateway_server_connections",
+                "Number of open client connections per server",
+            ),
+            &["server"],
+        )
+        .expect("Failed to create server connections gauge");
+
         registry
             .register(Box::new(request_counter.clone()))
             .expect("Failed to register request counter");
@@ -75,6 +237,45 @@ impl GatewayMetrics {
         registry
             .register(Box::new(api_key_usage_counter.clone()))
             .expect("Failed to register API key usage counter");
+        registry
+            .register(Box::new(queue_wait.clone()))
+            .expect("Failed to register queue wait histogram");
+        registry
+            .register(Box::new(auth_requests.clone()))
+            .expect("Failed to register auth request counter");
+        registry
+            .register(Box::new(client_cancelled.clone()))
+            .expect("Failed to register client cancelled counter");
+        registry
+            .register(Box::new(route_requests.clone()))
+            .expect("Failed to register route request counter");
+        registry
+            .register(Box::new(request_bytes.clone()))
+            .expect("Failed to register request bytes counter");
+        registry
+            .register(Box::new(response_bytes.clone()))
+            .expect("Failed to register response bytes counter");
+        registry
+            .register(Box::new(responses_too_large.clone()))
+            .expect("Failed to register oversized response counter");
+        registry
+            .register(Box::new(uris_too_long.clone()))
+            .expect("Failed to register URI too long counter");
+        registry
+            .register(Box::new(shadow_comparisons.clone()))
+            .expect("Failed to register shadow comparison counter");
+        registry
+            .register(Box::new(shadow_mismatches.clone()))
+            .expect("Failed to register shadow mismatch counter");
+        registry
+            .register(Box::new(methods_rejected.clone()))
+            .expect("Failed to register rejected method counter");
+        registry
+            .register(Box::new(invalid_bodies.clone()))
+            .expect("Failed to register invalid body counter");
+        registry
+            .register(Box::new(server_connections.clone()))
+            .expect("Failed to register server connections gauge");
 
         Self {
             registry,
@@ -82,6 +283,19 @@ impl GatewayMetrics {
             request_latency,
             active_connections,
             api_key_usage_counter,
+            queue_wait,
+            auth_requests,
+            client_cancelled,
+            route_requests,
+            request_bytes,
+            response_bytes,
+            responses_too_large,
+            uris_too_long,
+            shadow_comparisons,
+            shadow_mismatches,
+            methods_rejected,
+            invalid_bodies,
+            server_connections,
             total_requests: Arc::new(AtomicU64::new(0)),
             total_errors: Arc::new(AtomicU64::new(0)),
         }
@@ -89,6 +303,19 @@ impl GatewayMetrics {
 
     /// Record a request with its status and latency
     pub fn record_request(&self, method: &str, path: &str, status: u16, latency: Duration) {
+        self.record_request_sampled(method, path, status, latency, &LatencySampling::default());
+    }
+
+    /// Record a request, observing its latency only if `sampling` selects
+    /// the response
+    pub fn record_request_sampled(
+        &self,
+        method: &str,
+        path: &str,
+        status: u16,
+        latency: Duration,
+        sampling: &LatencySampling,
+    ) {
         let status_str = status.to_string();
 
         // Normalize path for metrics (to avoid high cardinality)
@@ -98,9 +325,11 @@ impl GatewayMetrics {
             .with_label_values(&[method, &normalized_path, &status_str])
             .inc();
 
-        self.request_latency
-            .with_label_values(&[method, &normalized_path])
-            .observe(latency.as_secs_f64());
+        if sampling.observes(status) {
+            self.request_latency
+                .with_label_values(&[method, &normalized_path])
+                .observe(latency.as_secs_f64());
+        }
 
         // Update simple counters
         self.total_requests.fetch_add(1, Ordering::Relaxed);
@@ -119,6 +348,28 @@ impl GatewayMetrics {
         self.active_connections.with_label_values(&[route]).dec();
     }
 
+    /// Increment open client connections for a server
+    pub fn inc_server_connections(&self, server: &str) {
+        self.server_connections.with_label_values(&[server]).inc();
+    }
+
+    /// Decrement open client connections for a server
+    pub fn dec_server_connections(&self, server: &str) {
+        self.server_connections.with_label_values(&[server]).dec();
+    }
+
+    /// Get the number of open client connections for a server
+    pub fn server_connections(&self, server: &str) -> u64 {
+        self.server_connections.with_label_values(&[server]).get() as u64
+    }
+
+    /// Record how long a request waited for a route concurrency permit
+    pub fn record_queue_wait(&self, route: &str, wait: Duration) {
+        self.queue_wait
+            .with_label_values(&[route])
+            .observe(wait.as_secs_f64());
+    }
+
     /// Record API key usage for a route
     /// Uses a hash of the API key to protect credentials while maintaining observability
     pub fn record_api_key_usage(&self, api_key: &str, route: &str) {
@@ -129,6 +380,221 @@ impl GatewayMetrics {
             .inc();
     }
 
+    /// Get the total number of recorded uses of an API key across all routes
+    pub fn api_key_usage(&self, api_key: &str) -> u64 {
+        let api_key_hash = Self::hash_api_key(api_key);
+        self.registry
+            .gather()
+            .iter()
+            .filter(|family| family.name() == "gateway_api_key_usage_total")
+            .flat_map(|family| family.get_metric())
+            .filter(|metric| {
+                metric
+                    .get_label()
+                    .iter()
+                    .any(|l| l.name() == "api_key" && l.value() == api_key_hash)
+            })
+            .map(|metric| metric.get_counter().value() as u64)
+            .sum()
+    }
+
+    /// Record a request accepted with the master token carrying `label`
+    pub fn record_auth_request(&self, label: &str) {
+        self.auth_requests.with_label_values(&[label]).inc();
+    }
+
+    /// Get the number of requests accepted with tokens carrying `label`
+    pub fn auth_requests(&self, label: &str) -> u64 {
+        self.registry
+            .gather()
+            .iter()
+            .filter(|family| family.name() == "gateway_auth_requests_total")
+            .flat_map(|family| family.get_metric())
+            .filter(|metric| {
+                metric
+                    .get_label()
+                    .iter()
+                    .any(|l| l.name() == "label" && l.value() == label)
+            })
+            .map(|metric| metric.get_counter().value() as u64)
+            .sum()
+    }
+
+    /// Record the outcome of a request matched to a route
+    pub fn record_route_request(&self, route: &str, group: &str, status: u16) {
+        self.route_requests
+            .with_label_values(&[route, group, &status.to_string()])
+            .inc();
+    }
+
+    /// Get request and 5xx error counts per route label, across groups
+    pub fn route_totals(&self) -> HashMap<String, RouteTotals> {
+        let mut totals: HashMap<String, RouteTotals> = HashMap::new();
+        for metric in self
+            .registry
+            .gather()
+            .iter()
+            .filter(|family| family.name() == "gateway_route_requests_total")
+            .flat_map(|family| family.get_metric())
+        {
+            let label = |name: &str| {
+                metric
+                    .get_label()
+                    .iter()
+                    .find(|l| l.name() == name)
+                    .map(|l| l.value().to_string())
+                    .unwrap_or_default()
+            };
+            let count = metric.get_counter().value() as u64;
+            let entry = totals.entry(label("route")).or_default();
+            entry.requests += count;
+            if label("status").starts_with('5') {
+                entry.errors += count;
+            }
+        }
+        totals
+    }
+
+    /// Record a request abandoned by the client before it completed
+    pub fn record_client_cancelled(&self, route: &str) {
+        self.client_cancelled.with_label_values(&[route]).inc();
+    }
+
+    /// Get the number of requests to `route` abandoned by the client
+    pub fn client_cancelled(&self, route: &str) -> u64 {
+        self.registry
+            .gather()
+            .iter()
+            .filter(|family| family.name() == "gateway_client_cancelled_total")
+            .flat_map(|family| family.get_metric())
+            .filter(|metric| {
+                metric
+                    .get_label()
+                    .iter()
+                    .any(|l| l.name() == "route" && l.value() == route)
+            })
+            .map(|metric| metric.get_counter().value() as u64)
+            .sum()
+    }
+
+    /// Record an upstream response for `route` exceeding the size limit
+    pub fn record_response_too_large(&self, route: &str) {
+        self.responses_too_large.with_label_values(&[route]).inc();
+    }
+
+    /// Get the number of oversized upstream responses for `route`
+    pub fn responses_too_large(&self, route: &str) -> u64 {
+        self.route_counter("gateway_responses_too_large_total", route)
+    }
+
+    /// Record a request to `route` whose upstream URI exceeds the length limit
+    pub fn record_uri_too_long(&self, route: &str) {
+        self.uris_too_long.with_label_values(&[route]).inc();
+    }
+
+    /// Get the number of requests to `route` rejected for their URI length
+    pub fn uris_too_long(&self, route: &str) -> u64 {
+        self.route_counter("gateway_uris_too_long_total", route)
+    }
+
+    /// Record a request to `route` replayed to its comparison upstream
+    pub fn record_shadow_comparison(&self, route: &str) {
+        self.shadow_comparisons.with_label_values(&[route]).inc();
+    }
+
+    /// Get the number of completed comparisons for `route`
+    pub fn shadow_comparisons(&self, route: &str) -> u64 {
+        self.route_counter("gateway_shadow_comparisons_total", route)
+    }
+
+    /// Record a comparison response for `route` differing from the route's
+    pub fn record_shadow_mismatch(&self, route: &str) {
+        self.shadow_mismatches.with_label_values(&[route]).inc();
+    }
+
+    /// Get the number of comparison mismatches for `route`
+    pub fn shadow_mismatches(&self, route: &str) -> u64 {
+        self.route_counter("gateway_shadow_mismatch_total", route)
+    }
+
+    /// Record a request to `route`'s path refused because of its `method`
+    pub fn record_method_rejected(&self, method: &str, route: &str) {
+        self.methods_rejected
+            .with_label_values(&[method, route])
+            .inc();
+    }
+
+    /// Get the number of `method` requests refused by `route`
+    pub fn methods_rejected(&self, method: &str, route: &str) -> u64 {
+        self.registry
+            .gather()
+            .iter()
+            .filter(|family| family.name() == "gateway_method_rejected_total")
+            .flat_map(|family| family.get_metric())
+            .filter(|metric| {
+                let labels = metric.get_label();
+                labels
+                    .iter()
+                    .any(|l| l.name() == "method" && l.value() == method)
+                    && labels
+                        .iter()
+                        .any(|l| l.name() == "route" && l.value() == route)
+            })
+            .map(|metric| metric.get_counter().value() as u64)
+            .sum()
+    }
+
+    /// Record a request body for `route` rejected by its body schema
+    pub fn record_invalid_body(&self, route: &str) {
+        self.invalid_bodies.with_label_values(&[route]).inc();
+    }
+
+    /// Get the number of request bodies rejected by the body schema of `route`
+    pub fn invalid_bodies(&self, route: &str) -> u64 {
+        self.route_counter("gateway_invalid_bodies_total", route)
+    }
+
+    /// Record the size of a request body received for `route`
+    pub fn record_request_bytes(&self, route: &str, bytes: u64) {
+        self.request_bytes
+            .with_label_values(&[route])
+            .inc_by(bytes as f64);
+    }
+
+    /// Record the size of a response body returned for `route`
+    pub fn record_response_bytes(&self, route: &str, bytes: u64) {
+        self.response_bytes
+            .with_label_values(&[route])
+            .inc_by(bytes as f64);
+    }
+
+    /// Get the request body bytes received for `route`
+    pub fn request_bytes(&self, route: &str) -> u64 {
+        self.route_counter("gateway_request_bytes_total", route)
+    }
+
+    /// Get the response body bytes returned for `route`
+    pub fn response_bytes(&self, route: &str) -> u64 {
+        self.route_counter("gateway_response_bytes_total", route)
+    }
+
+    /// Get the value of a counter family for a `route` label
+    fn route_counter(&self, family_name: &str, route: &str) -> u64 {
+        self.registry
+            .gather()
+            .iter()
+            .filter(|family| family.name() == family_name)
+            .flat_map(|family| family.get_metric())
+            .filter(|metric| {
+                metric
+                    .get_label()
+                    .iter()
+                    .any(|l| l.name() == "route" && l.value() == route)
+            })
+            .map(|metric| metric.get_counter().value() as u64)
+            .sum()
+    }
+
     /// Hash an API key to protect credentials in metrics
     /// Returns a string representation of the hash for use in metrics
     ///
@@ -143,12 +609,22 @@ impl GatewayMetrics {
     }
 
     /// Get the Prometheus metrics output
-    pub fn prometheus_output(&self) -> String {
-        let encoder = TextEncoder::new();
-        let metric_families = self.registry.gather();
-        let mut buffer = Vec::new();
-        encoder.encode(&metric_families, &mut buffer).unwrap();
-        String::from_utf8(buffer).unwrap()
+    pub fn prometheus_output(&self) -> anyhow::Result<String> {
+        encode_prometheus(&self.registry.gather())
+    }
+
+    /// Export metrics in the OpenMetrics text format
+    ///
+    /// Counter families drop their `_total` suffix (kept on the samples),
+    /// families named `*_seconds` or `*_bytes` carry a unit, and the output
+    /// ends with `# EOF`.
+    pub fn openmetrics_output(&self) -> String {
+        let mut output = String::new();
+        for family in self.registry.gather() {
+            write_openmetrics_family(&mut output, &family);
+        }
+        output.push_str("# EOF\n");
+        output
     }
 
     /// Get total request count
@@ -206,6 +682,152 @@ impl Default for GatewayMetrics {
     }
 }
 
+/// Destination of the metrics recorded while forwarding requests
+///
+/// Only the request and route counts are required; the other events are
+/// ignored unless a sink overrides them.
+pub trait MetricsSink: Send + Sync {
+    /// Record a request, observing its latency only if `sampling` selects
+    /// the response
+    fn record_request_sampled(
+        &self,
+        method: &str,
+        path: &str,
+        status: u16,
+        latency: Duration,
+        sampling: &LatencySampling,
+    );
+
+    /// Record a request with its status and latency
+    fn record_request(&self, method: &str, path: &str, status: u16, latency: Duration) {
+        self.record_request_sampled(method, path, status, latency, &LatencySampling::default());
+    }
+
+    /// Record a request routed to `route`, with its response status
+    fn record_route_request(&self, route: &str, group: &str, status: u16);
+
+    /// Record a use of `api_key` for `route`
+    fn record_api_key_usage(&self, _api_key: &str, _route: &str) {}
+
+    /// Record how long a request waited for a route concurrency permit
+    fn record_queue_wait(&self, _route: &str, _wait: Duration) {}
+
+    /// Record a request to `route` abandoned by the client
+    fn record_client_cancelled(&self, _route: &str) {}
+
+    /// Record the size of a request body received for `route`
+    fn record_request_bytes(&self, _route: &str, _bytes: u64) {}
+
+    /// Record the size of a response body returned for `route`
+    fn record_response_bytes(&self, _route: &str, _bytes: u64) {}
+
+    /// Record an upstream response for `route` exceeding the size limit
+    fn record_response_too_large(&self, _route: &str) {}
+
+    /// Record a request to `route` whose upstream URI exceeds the length limit
+    fn record_uri_too_long(&self, _route: &str) {}
+
+    /// Record a request body for `route` rejected by its body schema
+    fn record_invalid_body(&self, _route: &str) {}
+
+    /// Record a request to `route`'s path refused because of its `method`
+    fn record_method_rejected(&self, _method: &str, _route: &str) {}
+
+    /// Record a request to `route` replayed to its comparison upstream
+    fn record_shadow_comparison(&self, _route: &str) {}
+
+    /// Record a comparison response for `route` differing from the route's
+    fn record_shadow_mismatch(&self, _route: &str) {}
+}
+
+impl MetricsSink for GatewayMetrics {
+    fn record_request_sampled(
+        &self,
+        method: &str,
+        path: &str,
+        status: u16,
+        latency: Duration,
+        sampling: &LatencySampling,
+    ) {
+        GatewayMetrics::record_request_sampled(self, method, path, status, latency, sampling);
+    }
+
+    fn record_route_request(&self, route: &str, group: &str, status: u16) {
+        GatewayMetrics::record_route_request(self, route, group, status);
+    }
+
+    fn record_api_key_usage(&self, api_key: &str, route: &str) {
+        GatewayMetrics::record_api_key_usage(self, api_key, route);
+    }
+
+    fn record_queue_wait(&self, route: &str, wait: Duration) {
+        GatewayMetrics::record_queue_wait(self, route, wait);
+    }
+
+    fn record_client_cancelled(&self, route: &str) {
+        GatewayMetrics::record_client_cancelled(self, route);
+    }
+
+    fn record_request_bytes(&self, route: &str, bytes: u64) {
+        GatewayMetrics::record_request_bytes(self, route, bytes);
+    }
+
+    fn record_response_bytes(&self, route: &str, bytes: u64) {
+        GatewayMetrics::record_response_bytes(self, route, bytes);
+    }
+
+    fn record_response_too_large(&self, route: &str) {
+        GatewayMetrics::record_response_too_large(self, route);
+    }
+
+    fn record_uri_too_long(&self, route: &str) {
+        GatewayMetrics::record_uri_too_long(self, route);
+    }
+
+    fn record_invalid_body(&self, route: &str) {
+        GatewayMetrics::record_invalid_body(self, route);
+    }
+
+    fn record_method_rejected(&self, method: &str, route: &str) {
+        GatewayMetrics::record_method_rejected(self, method, route);
+    }
+
+    fn record_shadow_comparison(&self, route: &str) {
+        GatewayMetrics::record_shadow_comparison(self, route);
+    }
+
+    fn record_shadow_mismatch(&self, route: &str) {
+        GatewayMetrics::record_shadow_mismatch(self, route);
+    }
+}
+
+/// Sink discarding every metric
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopMetrics;
+
+impl MetricsSink for NoopMetrics {
+    fn record_request_sampled(
+        &self,
+        _method: &str,
+        _path: &str,
+        _status: u16,
+        _latency: Duration,
+        _sampling: &LatencySampling,
+    ) {
+    }
+
+    fn record_route_request(&self, _route: &str, _group: &str, _status: u16) {}
+}
+
+/// Request counts of a route
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RouteTotals {
+    /// Requests matched to the route
+    pub requests: u64,
+    /// Requests that failed with a 5xx status
+    pub errors: u64,
+}
+
 /// A snapshot of metrics for display
 #[derive(Debug, Clone)]
 pub struct MetricsSnapshot {
@@ -214,6 +836,159 @@ pub struct MetricsSnapshot {
     pub error_rate: f64,
 }
 
+/// Encode metric families in the Prometheus text format
+fn encode_prometheus(metric_families: &[MetricFamily]) -> anyhow::Result<String> {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(metric_families, &mut buffer)
+        .map_err(|e| anyhow::anyhow!("Failed to encode metrics: {}", e))?;
+    String::from_utf8(buffer).map_err(|e| anyhow::anyhow!("Metrics output is not UTF-8: {}", e))
+}
+
+/// Write one metric family in the OpenMetrics text format
+fn write_openmetrics_family(output: &mut String, family: &MetricFamily) {
+    let metric_type = family.get_field_type();
+    let name = match metric_type {
+        MetricType::COUNTER => family
+            .name()
+            .strip_suffix("_total")
+            .unwrap_or(family.name()),
+        _ => family.name(),
+    };
+    let type_name = match metric_type {
+        MetricType::COUNTER => "counter",
+        MetricType::GAUGE => "gauge",
+        MetricType::HISTOGRAM => "histogram",
+        MetricType::SUMMARY => "summary",
+        MetricType::UNTYPED => "unknown",
+    };
+
+    let _ = writeln!(output, "# TYPE {} {}", name, type_name);
+    if let Some(unit) = ["seconds", "bytes"]
+        .into_iter()
+        .find(|unit| name.ends_with(&format!("_{}", unit)))
+    {
+        let _ = writeln!(output, "# UNIT {} {}", name, unit);
+    }
+    if !family.help().is_empty() {
+        let _ = writeln!(
+            output,
+            "# HELP {} {}",
+            name,
+            escape_openmetrics(family.help())
+        );
+    }
+
+    for metric in family.get_metric() {
+        match metric_type {
+            MetricType::COUNTER => {
+                let value = metric.get_counter().value();
+                write_openmetrics_sample(output, name, "_total", metric, None, value);
+            }
+            MetricType::GAUGE => {
+                let value = metric.get_gauge().value();
+                write_openmetrics_sample(output, name, "", metric, None, value);
+            }
+            MetricType::HISTOGRAM => {
+                let histogram = metric.get_histogram();
+                for bucket in histogram.get_bucket() {
+                    if bucket.upper_bound().is_infinite() {
+                        continue;
+                    }
+                    let le = format_openmetrics_value(bucket.upper_bound());
+                    let count = bucket.cumulative_count() as f64;
+                    write_openmetrics_sample(
+                        output,
+                        name,
+                        "_bucket",
+                        metric,
+                        Some(("le", &le)),
+                        count,
+                    );
+                }
+                let count = histogram.sample_count() as f64;
+                write_openmetrics_sample(
+                    output,
+                    name,
+                    "_bucket",
+                    metric,
+                    Some(("le", "+Inf")),
+                    count,
+                );
+                write_openmetrics_sample(output, name, "_count", metric, None, count);
+                let sum = histogram.sample_sum();
+                write_openmetrics_sample(output, name, "_sum", metric, None, sum);
+            }
+            MetricType::SUMMARY => {
+                let summary = metric.get_summary();
+                for quantile in summary.get_quantile() {
+                    let q = format_openmetrics_value(quantile.quantile());
+                    let value = quantile.value();
+                    write_openmetrics_sample(
+                        output,
+                        name,
+                        "",
+                        metric,
+                        Some(("quantile", &q)),
+                        value,
+                    );
+                }
+                let count = summary.sample_count() as f64;
+                write_openmetrics_sample(output, name, "_count", metric, None, count);
+                let sum = summary.sample_sum();
+                write_openmetrics_sample(output, name, "_sum", metric, None, sum);
+            }
+            MetricType::UNTYPED => {
+                let value = metric.untyped.value();
+                write_openmetrics_sample(output, name, "", metric, None, value);
+            }
+        }
+    }
+}
+
+/// Write one sample line, with the metric's labels plus an optional extra label
+fn write_openmetrics_sample(
+    output: &mut String,
+    name: &str,
+    suffix: &str,
+    metric: &Metric,
+    extra_label: Option<(&str, &str)>,
+    value: f64,
+) {
+    let labels: Vec<String> = metric
+        .get_label()
+        .iter()
+        .map(|l| (l.name(), l.value()))
+        .chain(extra_label)
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_openmetrics(value)))
+        .collect();
+    output.push_str(name);
+    output.push_str(suffix);
+    if !labels.is_empty() {
+        let _ = write!(output, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(output, " {}", format_openmetrics_value(value));
+}
+
+/// Format a sample value, spelling infinities and NaN the OpenMetrics way
+fn format_openmetrics_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Escape a label value or help text
+fn escape_openmetrics(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -238,6 +1013,41 @@ mod tests {
         assert_eq!(metrics.total_errors(), 1);
     }
 
+    #[test]
+    fn test_latency_sampling() {
+        let metrics = GatewayMetrics::new();
+        let observed = |path: &str| {
+            metrics
+                .request_latency
+                .with_label_values(&["GET", path])
+                .get_sample_count()
+        };
+        let errors_only = LatencySampling::Mode(crate::config::LatencySamplingMode::ErrorsOnly);
+        for status in [200, 304, 404, 502] {
+            metrics.record_request_sampled(
+                "GET",
+                "/errors",
+                status,
+                Duration::from_millis(5),
+                &errors_only,
+            );
+        }
+        assert_eq!(observed("/errors"), 2);
+
+        // A zero fraction still observes errors; requests are all counted
+        for status in [200, 200, 500] {
+            metrics.record_request_sampled(
+                "GET",
+                "/fraction",
+                status,
+                Duration::from_millis(5),
+                &LatencySampling::Fraction(0.0),
+            );
+        }
+        assert_eq!(observed("/fraction"), 1);
+        assert_eq!(metrics.total_requests(), 7);
+    }
+
     #[test]
     fn test_error_rate() {
         let metrics = GatewayMetrics::new();
@@ -268,16 +1078,67 @@ mod tests {
         assert_eq!(GatewayMetrics::normalize_path("/api/users"), "/api/users");
     }
 
+    #[test]
+    fn test_auth_requests_by_label() {
+        let metrics = GatewayMetrics::new();
+        metrics.record_auth_request("team-a");
+        metrics.record_auth_request("team-a");
+        metrics.record_auth_request(crate::config::UNLABELED_TOKEN);
+
+        assert_eq!(metrics.auth_requests("team-a"), 2);
+        assert_eq!(metrics.auth_requests("unlabeled"), 1);
+        assert_eq!(metrics.auth_requests("team-b"), 0);
+        assert!(metrics
+            .prometheus_output()
+            .unwrap()
+            .contains("gateway_auth_requests_total{label=\"team-a\"} 2"));
+    }
+
     #[test]
     fn test_prometheus_output() {
         let metrics = GatewayMetrics::new();
         metrics.record_request("GET", "/api/test", 200, Duration::from_millis(10));
 
-        let output = metrics.prometheus_output();
+        let output = metrics.prometheus_output().unwrap();
         assert!(output.contains("gateway_requests_total"));
         assert!(output.contains("gateway_request_latency_seconds"));
     }
 
+    #[test]
+    fn test_encoding_error_is_returned() {
+        // A family without any metric can't be encoded
+        let mut family = MetricFamily::default();
+        family.set_name("gateway_empty".to_string());
+        family.set_field_type(MetricType::COUNTER);
+        let err = encode_prometheus(&[family]).unwrap_err();
+        assert!(
+            err.to_string().starts_with("Failed to encode metrics"),
+            "{}",
+            err
+        );
+
+        assert!(encode_prometheus(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_openmetrics_output() {
+        let metrics = GatewayMetrics::new();
+        metrics.record_request("GET", "/api/test", 200, Duration::from_millis(10));
+        metrics.record_request_bytes("users", 42);
+
+        let output = metrics.openmetrics_output();
+        assert!(output.ends_with("# EOF\n"));
+        assert!(output.contains("# TYPE gateway_requests counter\n"));
+        assert!(output.contains(
+            "gateway_requests_total{method=\"GET\",path=\"/api/test\",status=\"200\"} 1\n"
+        ));
+        assert!(output.contains("# TYPE gateway_request_latency_seconds histogram\n"));
+        assert!(output.contains("# UNIT gateway_request_latency_seconds seconds\n"));
+        assert!(output.contains("le=\"+Inf\"} 1\n"));
+        assert!(output.contains("# UNIT gateway_request_bytes bytes\n"));
+        assert!(output.contains("gateway_request_bytes_total{route=\"users\"} 42\n"));
+    }
+
     #[test]
     fn test_api_key_usage_counter() {
         let metrics = GatewayMetrics::new();
@@ -288,7 +1149,7 @@ mod tests {
         metrics.record_api_key_usage("key2", "/api/v1");
         metrics.record_api_key_usage("key1", "/api/v2");
 
-        let output = metrics.prometheus_output();
+        let output = metrics.prometheus_output().unwrap();
         assert!(output.contains("gateway_api_key_usage_total"));
         // Check that hashed keys are recorded (not raw keys)
         assert!(!output.contains("api_key=\"key1\""));
@@ -297,6 +1158,28 @@ mod tests {
         assert!(output.contains("api_key=\"key_"));
     }
 
+    #[test]
+    fn test_custom_buckets() {
+        let metrics = GatewayMetrics::with_buckets(vec![0.1, 1.0]);
+        metrics.record_queue_wait("api", Duration::from_millis(50));
+
+        let output = metrics.prometheus_output().unwrap();
+        assert!(output.contains("gateway_queue_wait_seconds_bucket{route=\"api\",le=\"0.1\"} 1"));
+        assert!(!output.contains("le=\"0.005\""));
+    }
+
+    #[test]
+    fn test_api_key_usage_lookup() {
+        let metrics = GatewayMetrics::new();
+        metrics.record_api_key_usage("key1", "/api/v1");
+        metrics.record_api_key_usage("key1", "/api/v2");
+        metrics.record_api_key_usage("key2", "/api/v1");
+
+        assert_eq!(metrics.api_key_usage("key1"), 2);
+        assert_eq!(metrics.api_key_usage("key2"), 1);
+        assert_eq!(metrics.api_key_usage("unused"), 0);
+    }
+
     #[test]
     fn test_hash_api_key() {
         // Test that the same key produces the same hash
@@ -311,4 +1194,21 @@ mod tests {
         // Test that hash format is correct
         assert!(hash1.starts_with("key_"));
     }
+
+    #[test]
+    fn test_body_bytes() {
+        let metrics = GatewayMetrics::new();
+        assert_eq!(metrics.request_bytes("users"), 0);
+
+        metrics.record_request_bytes("users", 100);
+        metrics.record_request_bytes("users", 28);
+        metrics.record_response_bytes("users", 2048);
+        assert_eq!(metrics.request_bytes("users"), 128);
+        assert_eq!(metrics.response_bytes("users"), 2048);
+        assert_eq!(metrics.response_bytes("orders"), 0);
+        assert!(metrics
+            .prometheus_output()
+            .unwrap()
+            .contains("gateway_response_bytes_total{route=\"users\"} 2048"));
+    }
 }
@@ -0,0 +1,296 @@
+//! OAuth2 token module
+//!
+//! This module supplies access tokens for API key pools with an `oauth2`
+//! section, using the client credentials grant:
+//! - A token is fetched on first use and cached
+//! - It is refreshed shortly before it expires (a tenth of its lifetime
+//!   early, at most a minute), with one fetch shared by concurrent requests
+//! - If a refresh fails while the cached token is still valid, the cached
+//!   token keeps being used; otherwise the error is returned
+//! - After a failed fetch the endpoint isn't asked again for a backoff
+//!   window (one second, doubling up to 30s); requests in the meantime get
+//!   the cached token or the last error without waiting
+
+use crate::config::OAuth2Config;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+use tracing::warn;
+
+/// Lifetime assumed for tokens returned without `expires_in`
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(3600);
+
+/// Longest time a token is refreshed before it expires
+const MAX_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// Deadline for a token request
+const TOKEN_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wait before retrying after the first failed fetch
+const MIN_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Longest wait between retries after consecutive failed fetches
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Successful token endpoint response
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+/// A cached access token
+struct CachedToken {
+    value: String,
+    refresh_at: Instant,
+    expires_at: Instant,
+}
+
+/// The last failed fetch, and when the endpoint may be asked again
+struct FailedFetch {
+    error: String,
+    backoff: Duration,
+    retry_at: Instant,
+}
+
+/// Cached token and fetch failures of a token source
+#[derive(Default)]
+struct TokenState {
+    token: Option<CachedToken>,
+    failed: Option<FailedFetch>,
+}
+
+/// Fetches and caches access tokens for one set of client credentials
+pub struct TokenSource {
+    config: OAuth2Config,
+    client: reqwest::Client,
+    state: Mutex<TokenState>,
+}
+
+impl std::fmt::Debug for TokenSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenSource")
+            .field("token_url", &self.config.token_url)
+            .field("client_id", &self.config.client_id)
+            .finish()
+    }
+}
+
+impl TokenSource {
+    /// Create a token source for the given client credentials
+    pub fn new(config: OAuth2Config) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            state: Mutex::new(TokenState::default()),
+        }
+    }
+
+    /// Get an access token, fetching a new one when the cached one is due
+    pub async fn token(&self) -> anyhow::Result<String> {
+        let mut state = self.state.lock().await;
+        let now = Instant::now();
+        if let Some(token) = state.token.as_ref().filter(|t| now < t.refresh_at) {
+            return Ok(token.value.clone());
+        }
+        let valid = |state: &TokenState| {
+            state
+                .token
+                .as_ref()
+                .filter(|t| now < t.expires_at)
+                .map(|t| t.value.clone())
+        };
+
+        // Don't ask the endpoint again until the backoff window is over
+        if let Some(failed) = state.failed.as_ref().filter(|f| now < f.retry_at) {
+            return valid(&state).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{} (retrying in {}ms)",
+                    failed.error,
+                    (failed.retry_at - now).as_millis()
+                )
+            });
+        }
+
+        match self.fetch().await {
+            Ok(token) => {
+                let value = token.value.clone();
+                *state = TokenState {
+                    token: Some(token),
+                    failed: None,
+                };
+                Ok(value)
+            }
+            Err(e) => {
+                let backoff = state.failed.as_ref().map_or(MIN_RETRY_BACKOFF, |f| {
+                    (f.backoff * 2).min(MAX_RETRY_BACKOFF)
+                });
+                state.failed = Some(FailedFetch {
+                    error: e.to_string(),
+                    backoff,
+                    retry_at: Instant::now() + backoff,
+                });
+                match valid(&state) {
+                    Some(token) => {
+                        warn!(
+                            "Failed to refresh OAuth2 token from {}, using the cached token: {}",
+                            self.config.token_url, e
+                        );
+                        Ok(token)
+                    }
+                    None => Err(e),
+                }
+            }
+        }
+    }
+
+    /// Request a new token from the token endpoint
+    async fn fetch(&self) -> anyhow::Result<CachedToken> {
+        let mut form = vec![
+            ("grant_type", "client_credentials"),
+            ("client_id", self.config.client_id.as_str()),
+            ("client_secret", self.config.client_secret.as_str()),
+        ];
+        if let Some(scope) = &self.config.scope {
+            form.push(("scope", scope.as_str()));
+        }
+
+        let response: TokenResponse = self
+            .client
+            .post(&self.config.token_url)
+            .form(&form)
+            .timeout(TOKEN_REQUEST_TIMEOUT)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| {
+                anyhow::anyhow!("Token request to {} failed: {}", self.config.token_url, e)
+            })?
+            .json()
+            .await
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Invalid token response from {}: {}",
+                    self.config.token_url,
+                    e
+                )
+            })?;
+
+        let fetched_at = Instant::now();
+        let lifetime = response
+            .expires_in
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TOKEN_LIFETIME);
+        Ok(CachedToken {
+            value: response.access_token,
+            refresh_at: fetched_at + lifetime - (lifetime / 10).min(MAX_REFRESH_MARGIN),
+            expires_at: fetched_at + lifetime,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, routing::post, Form, Json, Router};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Spawn a token endpoint issuing `token-<n>` tokens valid for
+    /// `expires_in` seconds, failing with 500 when `expires_in` is 0
+    async fn spawn_token_endpoint(expires_in: u64) -> (String, Arc<AtomicUsize>) {
+        async fn issue(
+            State((calls, expires_in)): State<(Arc<AtomicUsize>, u64)>,
+            Form(form): Form<HashMap<String, String>>,
+        ) -> Result<Json<serde_json::Value>, axum::http::StatusCode> {
+            assert_eq!(form["grant_type"], "client_credentials");
+            assert_eq!(form["client_id"], "gateway");
+            assert_eq!(form["client_secret"], "s3cret");
+            let n = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            if expires_in == 0 {
+                return Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+            }
+            Ok(Json(serde_json::json!({
+                "access_token": format!("token-{}", n),
+                "token_type": "Bearer",
+                "expires_in": expires_in,
+                "scope": form.get("scope"),
+            })))
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = Router::new()
+            .route("/token", post(issue))
+            .with_state((calls.clone(), expires_in));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (format!("http://{}/token", addr), calls)
+    }
+
+    fn credentials(token_url: String) -> OAuth2Config {
+        OAuth2Config {
+            token_url,
+            client_id: "gateway".to_string(),
+            client_secret: "s3cret".to_string(),
+            scope: Some("read write".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_token_cached_until_refresh() {
+        let (url, calls) = spawn_token_endpoint(1).await;
+        let source = TokenSource::new(credentials(url));
+
+        assert_eq!(source.token().await.unwrap(), "token-1");
+        assert_eq!(source.token().await.unwrap(), "token-1");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Refreshed once the token is about to expire
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        assert_eq!(source.token().await.unwrap(), "token-2");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_fetch_is_an_error() {
+        let (url, calls) = spawn_token_endpoint(0).await;
+        let source = TokenSource::new(credentials(url));
+
+        let err = source.token().await.unwrap_err();
+        assert!(err.to_string().contains("Token request"), "{}", err);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_fetch_backs_off() {
+        let (url, calls) = spawn_token_endpoint(0).await;
+        let source = TokenSource::new(credentials(url));
+        assert!(source.token().await.is_err());
+
+        // Within the window requests fail at once, without a fetch
+        let started = Instant::now();
+        let err = source.token().await.unwrap_err();
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert!(err.to_string().contains("Token request"), "{}", err);
+        assert!(err.to_string().contains("retrying in"), "{}", err);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Once it's over the endpoint is asked again, and the window doubles
+        tokio::time::sleep(MIN_RETRY_BACKOFF).await;
+        assert!(source.token().await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        tokio::time::sleep(MIN_RETRY_BACKOFF).await;
+        assert!(source.token().await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        let state = source.state.lock().await;
+        assert_eq!(
+            state.failed.as_ref().unwrap().backoff,
+            MIN_RETRY_BACKOFF * 2
+        );
+    }
+}
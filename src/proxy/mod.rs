@@ -1,33 +1,551 @@
 //! Proxy module for forwarding requests to target services
 //!
 //! This module handles request forwarding, including:
-//! - Path manipulation (strip prefix)
+//! - Path manipulation (strip prefix, percent-encoded paths)
 //! - Header injection (API keys, custom headers)
-//! - Request/Response transformation
+//! - Request/Response transformation (response header stripping)
 //! - Support for both HTTP and HTTPS targets
+//! - Client certificate details forwarding
+//! - Circuit breaking for failing or slow upstreams
+//! - Response caching with request coalescing
+//! - Buffering small responses and streaming large ones
+//! - Aggregate routes merging several upstream JSON responses
+//! - Sampled replay to a comparison upstream, counting diverging responses
 
 use crate::api_key::SharedApiKeySelector;
-use crate::config::RouteConfig;
-use crate::metrics::GatewayMetrics;
+use crate::cache::{cache_key, wait_for_leader, CachedResponse, Flight, ResponseCache};
+use crate::capture::{Capture, CapturedRequest};
+use crate::circuit_breaker::{BreakerCall, CircuitBreaker};
+use crate::config::{
+    expand_header_template, normalize_methods, AccessLogConfig, AggregateTarget, CollapseSlashes,
+    CompareConfig, DebugHeadersConfig, ExpectContinue, ForwardingConfig, HeadUpstreamMethod,
+    LatencySampling, LimitsConfig, MatchMode, MockConfig, RouteConfig, RoutingConfig, TlsConfig,
+    UpstreamClientConfig, UpstreamPoolConfig, DEFAULT_ROUTE_GROUP,
+};
+use crate::decompress::{self, DecompressError};
+use crate::metrics::MetricsSink;
+use crate::route_index::RouteIndex;
+use arc_swap::ArcSwap;
 use axum::body::Body;
-use axum::http::{Request, Response, StatusCode};
+use axum::http::{
+    header, HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode, Uri,
+};
+use axum::response::IntoResponse;
 use http_body_util::BodyExt;
 use hyper_util::client::legacy::Client;
 use hyper_util::rt::TokioExecutor;
+use jsonschema::Validator;
+use rustls_pki_types::ServerName;
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
-use std::time::Instant;
-use tracing::warn;
+use std::time::{Duration, Instant};
+use tokio::sync::{watch, Semaphore};
+use tracing::{debug, info, warn, Instrument};
+
+/// HTTP client used to reach upstream targets
+type HttpClient = Client<
+    hyper_rustls::HttpsConnector<hyper_util::client::legacy::connect::HttpConnector>,
+    http_body_util::combinators::BoxBody<bytes::Bytes, hyper::Error>,
+>;
+
+/// Clients used to reach upstreams, replaced together to drop their idle
+/// connections
+struct UpstreamClients {
+    /// Shared client for routes without an isolated upstream pool
+    default: Arc<HttpClient>,
+    /// Isolated clients keyed by upstream pool name
+    pools: HashMap<String, Arc<HttpClient>>,
+    /// HTTP/1.1 clients sending title-cased headers, keyed by upstream pool
+    /// and built on first use
+    title_case: std::sync::Mutex<HashMap<Option<String>, Arc<HttpClient>>>,
+    /// Clients presenting a TLS server name override, keyed by upstream pool
+    /// and server name and built on first use
+    sni: std::sync::Mutex<SniClients>,
+}
+
+/// Clients presenting a TLS server name override
+type SniClients = HashMap<(Option<String>, ServerName<'static>), Arc<HttpClient>>;
 
 /// Proxy service for forwarding requests
 #[derive(Clone)]
 pub struct ProxyService {
-    client: Client<
-        hyper_rustls::HttpsConnector<hyper_util::client::legacy::connect::HttpConnector>,
-        http_body_util::combinators::BoxBody<bytes::Bytes, hyper::Error>,
-    >,
-    routes: Vec<ProxyRoute>,
-    metrics: Arc<GatewayMetrics>,
+    /// Upstream clients, replaced when idle connections are recycled
+    clients: Arc<ArcSwap<UpstreamClients>>,
+    /// Settings of the configured upstream pools, keyed by name
+    pool_settings: Arc<std::sync::Mutex<HashMap<String, UpstreamPoolConfig>>>,
+    /// Connection settings shared by all upstream clients
+    client_settings: UpstreamClientConfig,
+    /// TLS settings shared by all upstream clients
+    tls: Arc<rustls::ClientConfig>,
+    /// Route table, replaced atomically when routes change at runtime
+    routes: Arc<ArcSwap<RouteTable>>,
+    metrics: Arc<dyn MetricsSink>,
+    forwarding: ForwardingConfig,
+    routing: RoutingConfig,
+    debug_headers: DebugHeadersConfig,
+    limits: LimitsConfig,
+    access_log: AccessLogConfig,
+    /// Permits for comparison replays in flight
+    comparisons: Arc<Semaphore>,
+}
+
+/// Response header carrying the gateway's processing time in milliseconds
+pub const GATEWAY_DURATION_HEADER: &str = "x-gateway-duration-ms";
+
+/// Request header carrying the time the gateway received the request
+pub const GATEWAY_RECEIVED_AT_HEADER: &str = "x-gateway-received-at";
+
+/// Request header with the client's deadline: an RFC 3339 time, or a number
+/// of milliseconds from now
+pub const REQUEST_DEADLINE_HEADER: &str = "x-request-deadline";
+
+/// Request header telling the upstream when the gateway stops waiting for it
+pub const UPSTREAM_DEADLINE_HEADER: &str = "x-upstream-deadline";
+
+/// Query parameter forcing the key selection strategy of one request, with
+/// `routing.api_key_strategy_param`
+pub const API_KEY_STRATEGY_PARAM: &str = "api_key_strategy";
+
+/// Deadline of a request with `X-Request-Deadline`, narrowed to the route
+/// timeout once the request is forwarded
+#[derive(Debug, Clone, Copy)]
+struct RequestDeadline(Instant);
+
+/// Parse an `X-Request-Deadline` value into the time left before it passes
+///
+/// Deadlines already past give a zero duration; invalid values give `None`.
+fn parse_deadline(value: &str, now: chrono::DateTime<chrono::Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(ms) = value.parse::<u64>() {
+        return Some(Duration::from_millis(ms));
+    }
+    let deadline = chrono::DateTime::parse_from_rfc3339(value).ok()?;
+    Some(
+        (deadline.with_timezone(&chrono::Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Characters re-encoded when forwarding a decoded path
+const PATH_ENCODE_SET: &percent_encoding::AsciiSet = &percent_encoding::CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Percent-decode a request path for route matching
+pub fn decode_path(raw_path: &str) -> String {
+    percent_encoding::percent_decode_str(raw_path)
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+/// Replace runs of slashes in a path with a single slash
+pub fn collapse_slashes(path: &str) -> String {
+    let mut collapsed = String::with_capacity(path.len());
+    for c in path.chars() {
+        if c != '/' || !collapsed.ends_with('/') {
+            collapsed.push(c);
+        }
+    }
+    collapsed
+}
+
+/// Strip the gateway base path from a request path
+///
+/// Returns `None` if the path is outside the base path. A request for the
+/// base path itself maps to `/`.
+pub fn strip_base_path<'a>(path: &'a str, base_path: &str) -> Option<&'a str> {
+    match path.strip_prefix(base_path)? {
+        "" => Some("/"),
+        rest if rest.starts_with('/') => Some(rest),
+        _ => None,
+    }
+}
+
+/// Replace the path of a request URI, keeping its query unless it is empty
+fn set_request_path(req: &mut Request<Body>, path: &str) -> Result<(), ProxyError> {
+    let path_and_query = match req.uri().query().filter(|query| !query.is_empty()) {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+    let mut parts = req.uri().clone().into_parts();
+    parts.path_and_query = Some(
+        path_and_query
+            .parse()
+            .map_err(|e| ProxyError::InvalidRequest(axum::http::Error::from(e)))?,
+    );
+    *req.uri_mut() = Uri::from_parts(parts).map_err(|e| ProxyError::InvalidRequest(e.into()))?;
+    Ok(())
+}
+
+/// Re-encode a decoded path so it forms a valid request URI path
+///
+/// Slashes are left as-is, so an encoded `%2F` in the original request
+/// becomes a path separator.
+pub fn encode_path(path: &str) -> String {
+    percent_encoding::utf8_percent_encode(path, PATH_ENCODE_SET).to_string()
+}
+
+/// Collect header/query mappings in parameter order, so the query string
+/// built from them doesn't depend on map iteration order
+fn sorted_mappings<T>(mappings: impl Iterator<Item = T>, key: impl Fn(&T) -> String) -> Vec<T> {
+    let mut mappings: Vec<T> = mappings.collect();
+    mappings.sort_by_key(key);
+    mappings
+}
+
+/// Split a query string into its parameters, each with the separator
+/// preceding it (none for the first)
+///
+/// Parameters are returned exactly as received, empty ones included, so
+/// joining them back with their separators restores the query. With
+/// `semicolons`, `;` separates parameters as well as `&`.
+fn split_query(query: &str, semicolons: bool) -> Vec<(Option<char>, &str)> {
+    let mut params = Vec::new();
+    let mut separator = None;
+    let mut start = 0;
+    for (i, c) in query.char_indices() {
+        if c == '&' || (semicolons && c == ';') {
+            params.push((separator, &query[start..i]));
+            separator = Some(c);
+            start = i + 1;
+        }
+    }
+    params.push((separator, &query[start..]));
+    params
+}
+
+/// Apply a route's header/query mappings to a request query string
+///
+/// Parameters listed in `query_to_header` are removed from the query and
+/// returned as headers; headers listed in `header_to_query` are appended as
+/// parameters (replacing any parameter of the same name). Mappings whose
+/// source is missing are skipped. Other parameters, repeated ones included,
+/// keep their order, encoding and separators.
+fn remap_query(
+    route: &ProxyRoute,
+    query: Option<&str>,
+    headers: &HeaderMap,
+    semicolons: bool,
+) -> (Option<String>, Vec<(HeaderName, HeaderValue)>) {
+    if route.header_to_query.is_empty() && route.query_to_header.is_empty() {
+        return (query.map(str::to_string), Vec::new());
+    }
+
+    let decode = |s: &str| {
+        percent_encoding::percent_decode_str(&s.replace('+', " "))
+            .decode_utf8_lossy()
+            .into_owned()
+    };
+    let encode = |s: &str| {
+        percent_encoding::utf8_percent_encode(s, percent_encoding::NON_ALPHANUMERIC).to_string()
+    };
+
+    let mut pairs: Vec<(String, Option<char>, &str)> = query
+        .map(|query| split_query(query, semicolons))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(separator, pair)| {
+            (
+                decode(pair.split('=').next().unwrap_or("")),
+                separator,
+                pair,
+            )
+        })
+        .collect();
+
+    let mut surfaced = Vec::new();
+    for (param, name) in &route.query_to_header {
+        let value = pairs
+            .iter()
+            .find(|(key, _, _)| key == param)
+            .map(|(_, _, pair)| decode(pair.split_once('=').map_or("", |(_, v)| v)));
+        if let Some(value) = value.and_then(|v| HeaderValue::from_str(&v).ok()) {
+            surfaced.push((name.clone(), value));
+        }
+        pairs.retain(|(key, _, _)| key != param);
+    }
+
+    let mut appended = Vec::new();
+    for (name, param) in &route.header_to_query {
+        if let Some(value) = headers.get(name).and_then(|v| v.to_str().ok()) {
+            pairs.retain(|(key, _, _)| key != param);
+            appended.push(format!("{}={}", encode(param), encode(value)));
+        }
+    }
+
+    // Appended parameters use `&`
+    let remaining = pairs
+        .iter()
+        .map(|(_, separator, pair)| (*separator, *pair))
+        .chain(appended.iter().map(|pair| (Some('&'), pair.as_str())));
+    (join_query(remaining), surfaced)
+}
+
+/// Join query parameters split by [`split_query`] back together
+///
+/// The first parameter loses its separator. Returns `None` for an empty
+/// query.
+fn join_query<'a>(params: impl Iterator<Item = (Option<char>, &'a str)>) -> Option<String> {
+    let mut rebuilt = String::new();
+    for (i, (separator, pair)) in params.enumerate() {
+        if i > 0 {
+            rebuilt.push(separator.unwrap_or('&'));
+        }
+        rebuilt.push_str(pair);
+    }
+    (!rebuilt.is_empty()).then_some(rebuilt)
+}
+
+/// Remove every `name` parameter from a query string, returning the rest of
+/// the query and the decoded value of the first one
+fn take_query_param(
+    query: Option<&str>,
+    name: &str,
+    semicolons: bool,
+) -> (Option<String>, Option<String>) {
+    let Some(query) = query else {
+        return (None, None);
+    };
+    let mut value = None;
+    let params = split_query(query, semicolons);
+    let remaining = params.into_iter().filter(|(_, pair)| {
+        let (key, raw) = pair.split_once('=').unwrap_or((pair, ""));
+        if key != name {
+            return true;
+        }
+        if value.is_none() {
+            value = Some(
+                percent_encoding::percent_decode_str(raw)
+                    .decode_utf8_lossy()
+                    .into_owned(),
+            );
+        }
+        false
+    });
+    let rest = join_query(remaining);
+    (rest, value)
+}
+
+/// Strip `prefix` from a path, ignoring ASCII case
+///
+/// Prefixes are only stripped from paths that matched the route, so this is
+/// an exact strip unless paths are matched case-insensitively.
+fn strip_prefix_ignore_case<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    path.get(..prefix.len())
+        .filter(|head| head.eq_ignore_ascii_case(prefix))
+        .map(|_| &path[prefix.len()..])
+}
+
+/// Strip a decoded `prefix` from a raw (percent-encoded) path, ignoring
+/// ASCII case like [`strip_prefix_ignore_case`]
+///
+/// Returns the raw remainder after the prefix, or `None` if the decoded path
+/// does not start with `prefix`.
+fn strip_decoded_prefix<'a>(raw_path: &'a str, prefix: &str) -> Option<&'a str> {
+    let raw = raw_path.as_bytes();
+    let mut pos = 0;
+    for &expected in prefix.as_bytes() {
+        let (byte, len) = match raw.get(pos..pos + 3) {
+            Some([b'%', hi, lo]) if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit() => {
+                let hex = std::str::from_utf8(&raw[pos + 1..pos + 3]).ok()?;
+                (u8::from_str_radix(hex, 16).ok()?, 3)
+            }
+            _ => (*raw.get(pos)?, 1),
+        };
+        if !byte.eq_ignore_ascii_case(&expected) {
+            return None;
+        }
+        pos += len;
+    }
+    raw_path.get(pos..)
+}
+
+/// Header carrying the verified client certificate subject
+pub const CLIENT_CERT_SUBJECT_HEADER: &str = "x-client-cert-subject";
+
+/// Header carrying the verified client certificate serial number
+pub const CLIENT_CERT_SERIAL_HEADER: &str = "x-client-cert-serial";
+
+/// Verified client certificate details
+///
+/// Inserted into the request extensions by the TLS acceptor after a client
+/// certificate has been verified, and forwarded to upstreams when
+/// `forwarding.client_cert_headers` is enabled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientCertInfo {
+    /// Certificate subject distinguished name
+    pub subject: String,
+    /// Certificate serial number (hex)
+    pub serial: String,
+}
+
+/// Errors returned when forwarding a request
+#[derive(Debug, thiserror::Error)]
+pub enum ProxyError {
+    /// No route matches the request path
+    #[error("No matching route found")]
+    NoRoute,
+    /// A route matches the path but not the request method
+    #[error("Method not allowed")]
+    MethodNotAllowed,
+    /// The route doesn't accept the request body's content type
+    #[error("Unsupported media type")]
+    UnsupportedMediaType,
+    /// The route deadline expired
+    #[error("Request timed out while {}", .0.description())]
+    Timeout(TimeoutPhase),
+    /// The client's `X-Request-Deadline` had passed on arrival
+    #[error("Request deadline exceeded")]
+    DeadlineExceeded,
+    /// The client request body could not be read
+    #[error("Failed to read request body: {0}")]
+    RequestBody(axum::Error),
+    /// The client request body exceeds the configured limit
+    #[error("Request body too large")]
+    BodyTooLarge,
+    /// The client request body doesn't match its content coding
+    #[error("Failed to decode request body: {0}")]
+    RequestDecoding(DecompressError),
+    /// The upstream request could not be built
+    #[error("Failed to build request: {0}")]
+    InvalidRequest(axum::http::Error),
+    /// The client expects `100 Continue` and the gateway is set to refuse
+    #[error("Expectation failed")]
+    ExpectationFailed,
+    /// The route's circuit breaker is open
+    #[error("Circuit breaker is open")]
+    CircuitOpen,
+    /// The route's concurrency limiter is unavailable
+    #[error("Route concurrency limit unavailable")]
+    ConcurrencyLimit,
+    /// The upstream request failed
+    #[error("Failed to forward request: {0}")]
+    Upstream(hyper_util::client::legacy::Error),
+    /// The upstream response body could not be read
+    #[error("Failed to read response body: {0}")]
+    UpstreamBody(Box<dyn std::error::Error + Send + Sync>),
+    /// The JSON request body doesn't match the route's body schema
+    #[error("Request body does not match the schema: {}", .0.join("; "))]
+    InvalidBody(Vec<String>),
+    /// The upstream request path and query exceed `limits.max_uri_bytes`
+    #[error("Request URI exceeds {0} bytes")]
+    UriTooLong(usize),
+    /// The upstream response body exceeds `limits.max_response_bytes`
+    #[error("Upstream response exceeds {0} bytes")]
+    ResponseTooLarge(usize),
+    /// No OAuth2 access token could be obtained for the route's pool
+    #[error("Failed to get an upstream access token: {0}")]
+    TokenUnavailable(anyhow::Error),
+}
+
+impl ProxyError {
+    /// HTTP status returned to the client
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NoRoute => StatusCode::NOT_FOUND,
+            Self::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            Self::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::Timeout(_) | Self::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+            Self::RequestBody(_) | Self::InvalidRequest(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::BodyTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::UriTooLong(_) => StatusCode::URI_TOO_LONG,
+            Self::RequestDecoding(_) | Self::InvalidBody(_) => StatusCode::BAD_REQUEST,
+            Self::ExpectationFailed => StatusCode::EXPECTATION_FAILED,
+            Self::CircuitOpen | Self::ConcurrencyLimit | Self::TokenUnavailable(_) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            Self::Upstream(_) | Self::UpstreamBody(_) | Self::ResponseTooLarge(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+        }
+    }
+
+    /// Short label for metrics and logs
+    pub fn reason_label(&self) -> &'static str {
+        match self {
+            Self::NoRoute => "no_route",
+            Self::MethodNotAllowed => "method_not_allowed",
+            Self::UnsupportedMediaType => "unsupported_media_type",
+            Self::Timeout(TimeoutPhase::RequestBody) => "timeout_request_body",
+            Self::Timeout(TimeoutPhase::Upstream) => "timeout_upstream",
+            Self::Timeout(TimeoutPhase::ResponseBody) => "timeout_response_body",
+            Self::DeadlineExceeded => "deadline_exceeded",
+            Self::RequestBody(_) => "request_body",
+            Self::BodyTooLarge => "body_too_large",
+            Self::UriTooLong(_) => "uri_too_long",
+            Self::RequestDecoding(_) => "request_decoding",
+            Self::InvalidBody(_) => "invalid_body",
+            Self::InvalidRequest(_) => "invalid_request",
+            Self::ExpectationFailed => "expectation_failed",
+            Self::CircuitOpen => "circuit_open",
+            Self::ConcurrencyLimit => "concurrency_limit",
+            Self::Upstream(_) => "upstream",
+            Self::UpstreamBody(_) => "upstream_body",
+            Self::ResponseTooLarge(_) => "response_too_large",
+            Self::TokenUnavailable(_) => "token_unavailable",
+        }
+    }
+}
+
+/// Phase of a request when its route deadline expired
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutPhase {
+    /// Reading the client request body
+    RequestBody,
+    /// Waiting for the upstream response headers
+    Upstream,
+    /// Reading the upstream response body
+    ResponseBody,
+}
+
+impl TimeoutPhase {
+    fn description(&self) -> &'static str {
+        match self {
+            Self::RequestBody => "reading the request body",
+            Self::Upstream => "waiting for the upstream",
+            Self::ResponseBody => "reading the response body",
+        }
+    }
+}
+
+/// Tracks the current phase of a request for timeout reporting
+struct PhaseTracker(std::sync::atomic::AtomicU8);
+
+impl PhaseTracker {
+    fn new() -> Self {
+        Self(std::sync::atomic::AtomicU8::new(
+            TimeoutPhase::RequestBody as u8,
+        ))
+    }
+
+    fn set(&self, phase: TimeoutPhase) {
+        self.0
+            .store(phase as u8, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    fn get(&self) -> TimeoutPhase {
+        match self.0.load(std::sync::atomic::Ordering::Relaxed) {
+            0 => TimeoutPhase::RequestBody,
+            1 => TimeoutPhase::Upstream,
+            _ => TimeoutPhase::ResponseBody,
+        }
+    }
+}
+
+impl IntoResponse for ProxyError {
+    fn into_response(self) -> axum::response::Response {
+        (self.status_code(), self.to_string()).into_response()
+    }
 }
 
 /// A compiled proxy route with its selector
@@ -41,59 +559,268 @@ pub struct ProxyRoute {
     pub target: String,
     /// Whether to strip the prefix
     pub strip_prefix: bool,
-    /// HTTP methods to match (empty = all)
+    /// Path forwarded when stripping leaves an empty path (None = `/`)
+    pub empty_path: Option<String>,
+    /// HTTP methods to match, uppercased (empty = all)
     pub methods: Vec<String>,
     /// API key selector
     pub api_key_selector: Option<SharedApiKeySelector>,
+    /// Name of the API key pool the selector was built from (for logs)
+    pub api_key_pool: Option<String>,
+    /// Header in which clients may send their own key, forwarded instead
+    /// of a pool key
+    pub client_key_header: Option<HeaderName>,
     /// Additional headers
     pub headers: HashMap<String, String>,
+    /// Request headers moved into the query string, sorted by parameter name
+    pub header_to_query: Vec<(HeaderName, String)>,
+    /// Query parameters moved into request headers, sorted by parameter name
+    pub query_to_header: Vec<(String, HeaderName)>,
+    /// Client request headers forwarded (empty = all)
+    pub forward_headers: Vec<HeaderName>,
+    /// Query parameters required to match, with their value (None = any
+    /// value), sorted by parameter name
+    pub match_query: Vec<(String, Option<String>)>,
     /// Route description
     pub description: Option<String>,
+    /// Isolated upstream connection pool name
+    pub upstream_pool: Option<String>,
+    /// Concurrency limit for upstream requests (None = unlimited)
+    pub concurrency_limit: Option<Arc<Semaphore>>,
+    /// Deadline for the whole request (None = no deadline)
+    pub timeout: Option<Duration>,
+    /// Stream responses, failing them after this long without data
+    pub stream_idle_timeout: Option<Duration>,
+    /// Longest upstream path and query in bytes (None = `limits.max_uri_bytes`)
+    pub max_uri_bytes: Option<usize>,
+    /// Circuit breaker guarding the upstream (None = disabled)
+    pub circuit_breaker: Option<Arc<CircuitBreaker>>,
+    /// Response cache (None = disabled)
+    pub cache: Option<Arc<ResponseCache>>,
+    /// Response headers removed from this route's responses
+    pub strip_response_headers: Vec<String>,
+    /// Aggregate sub-requests (empty = regular route)
+    pub aggregate: Vec<AggregateTarget>,
+    /// Send requests over HTTP/1.1 with title-cased header names
+    pub title_case_headers: bool,
+    /// Server name presented to the upstream in TLS handshakes (None = the
+    /// target's host)
+    pub tls_sni: Option<ServerName<'static>>,
+    /// Accepted request media types, lowercase (empty = accept all)
+    pub accept_content_types: Vec<String>,
+    /// Decode compressed request bodies up to this many bytes (None = forward as is)
+    pub decompress_request: Option<usize>,
+    /// Schema JSON request bodies must match (None = not validated)
+    pub body_schema: Option<Arc<Validator>>,
+    /// Limit on a body validated against `body_schema`
+    pub body_schema_max_bytes: usize,
+    /// Sampled request/response capture (None = disabled)
+    pub capture: Option<Arc<Capture>>,
+    /// Sampled replay to a comparison upstream (None = disabled)
+    pub compare: Option<CompareConfig>,
+    /// Header given a generated idempotency key when the client sends none
+    pub idempotency_key: Option<HeaderName>,
+    /// Fixed response served instead of forwarding (None = proxied route)
+    pub mock: Option<Arc<MockResponse>>,
+    /// Precedence over overlapping routes in `most_specific` match mode
+    pub priority: i32,
+    /// Idle upstream connections kept open ahead of requests
+    pub prewarm_connections: usize,
+    /// Responses whose latency is observed in the histogram
+    pub latency_sampling: LatencySampling,
+    /// Metrics group
+    pub group: String,
+}
+
+/// Fixed response served by a mock route
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    /// Response status
+    pub status: StatusCode,
+    /// Response headers
+    pub headers: header::HeaderMap,
+    /// Response body
+    pub body: bytes::Bytes,
+}
+
+impl MockResponse {
+    /// Build the response from a route's mock settings, reading `body_file`
+    pub fn from_config(config: &MockConfig) -> Self {
+        let body = match &config.body_file {
+            Some(path) => match std::fs::read(path) {
+                Ok(contents) => bytes::Bytes::from(contents),
+                Err(e) => {
+                    warn!("Failed to read mock body_file {}: {}", path.display(), e);
+                    bytes::Bytes::new()
+                }
+            },
+            None => bytes::Bytes::from(config.body.clone().unwrap_or_default()),
+        };
+        let mut headers = header::HeaderMap::new();
+        for (key, value) in &config.headers {
+            if let (Ok(name), Ok(value)) = (
+                key.parse::<header::HeaderName>(),
+                value.parse::<header::HeaderValue>(),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+        Self {
+            status: StatusCode::from_u16(config.status).unwrap_or(StatusCode::OK),
+            headers,
+            body,
+        }
+    }
+
+    fn to_response(&self) -> Response<Body> {
+        let mut response = Response::new(Body::from(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        response
+            .headers_mut()
+            .insert(header::CONTENT_LENGTH, self.body.len().into());
+        response
+    }
 }
 
 impl ProxyRoute {
-    /// Check if this route matches the given path and method
-    pub fn matches(&self, path: &str, method: &str) -> bool {
-        // Check method
-        if !self.methods.is_empty() && !self.methods.iter().any(|m| m.eq_ignore_ascii_case(method))
-        {
-            return false;
+    /// Get the label identifying this route in metrics (name, or path pattern)
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.path_pattern)
+    }
+
+    /// Check if responses must be read whole, whatever their size, because
+    /// they are cached, captured or have their bodies compared
+    pub fn buffers_responses(&self) -> bool {
+        self.cache.is_some()
+            || self.capture.is_some()
+            || self
+                .compare
+                .as_ref()
+                .is_some_and(|compare| compare.compare_body)
+    }
+
+    /// Check if this route matches the given path, method and query string
+    ///
+    /// Query parameters are separated by `&` only; the route table also
+    /// splits on `;` with `routing.query_semicolon_separator`.
+    pub fn matches(&self, path: &str, method: &str, query: Option<&str>) -> bool {
+        self.method_matches(method) && self.path_matches(path) && self.query_matches(query, false)
+    }
+
+    /// Check if the query string carries the parameters in `match_query`
+    ///
+    /// With `semicolons`, `;` separates parameters as well as `&`.
+    fn query_matches(&self, query: Option<&str>, semicolons: bool) -> bool {
+        if self.match_query.is_empty() {
+            return true;
         }
+        let decode = |s: &str| {
+            percent_encoding::percent_decode_str(&s.replace('+', " "))
+                .decode_utf8_lossy()
+                .into_owned()
+        };
+        let params: Vec<(String, String)> = split_query(query.unwrap_or(""), semicolons)
+            .into_iter()
+            .map(|(_, pair)| pair)
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode(name), decode(value))
+            })
+            .collect();
+        self.match_query.iter().all(|(param, required)| {
+            params.iter().any(|(name, value)| {
+                name == param
+                    && match required {
+                        Some(required) => value == required,
+                        None => true,
+                    }
+            })
+        })
+    }
+
+    /// Check if the route accepts the method (methods are uppercase at load)
+    fn method_matches(&self, method: &str) -> bool {
+        self.methods.is_empty() || self.methods.iter().any(|m| m == method)
+    }
 
-        // Check path pattern
-        self.path_matches(path)
+    /// Rank the route among overlapping routes: priority, then the length
+    /// of the pattern's literal prefix
+    fn specificity(&self) -> (i32, usize) {
+        (
+            self.priority,
+            crate::route_index::pattern_prefix(&self.path_pattern).len(),
+        )
     }
 
     /// Check if path matches the pattern
     fn path_matches(&self, path: &str) -> bool {
-        let pattern = &self.path_pattern;
+        pattern_matches(&self.path_pattern, path)
+    }
 
-        // Handle wildcard patterns
-        if pattern.ends_with("/*") {
-            let prefix = &pattern[..pattern.len() - 2];
-            return path == prefix || path.starts_with(&format!("{}/", prefix));
+    /// Check whether the route accepts the body of a request
+    ///
+    /// Only POST, PUT and PATCH requests are checked; their `Content-Type`
+    /// media type must match an accepted type exactly or a `type/*` entry.
+    pub fn accepts_content_type(&self, method: &str, content_type: Option<&str>) -> bool {
+        if self.accept_content_types.is_empty() || !matches!(method, "POST" | "PUT" | "PATCH") {
+            return true;
         }
+        let Some(content_type) = content_type else {
+            return false;
+        };
+        let media_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase();
+        let main_type = media_type.split('/').next().unwrap_or("");
+        self.accept_content_types.iter().any(|accepted| {
+            *accepted == media_type || accepted.strip_suffix("/*") == Some(main_type)
+        })
+    }
 
-        // Handle exact match with optional trailing slash
-        if pattern.ends_with('/') {
-            let base = &pattern[..pattern.len() - 1];
-            return path == base || path == pattern || path.starts_with(pattern);
+    /// Get the path prefix removed from forwarded paths by `strip_prefix`
+    pub fn stripped_prefix(&self) -> Option<&str> {
+        if !self.strip_prefix {
+            return None;
         }
-
-        // Exact match
-        path == pattern || path.starts_with(&format!("{}/", pattern))
+        let pattern = &self.path_pattern;
+        pattern
+            .strip_suffix("/*")
+            .or_else(|| pattern.strip_suffix('/'))
+            .filter(|prefix| !prefix.is_empty())
     }
 
     /// Get the target URL for a request path
     pub fn get_target_url(&self, path: &str, query: Option<&str>) -> String {
         let target_path = if self.strip_prefix {
-            self.strip_path_prefix(path)
+            self.strip_path_prefix(path, strip_prefix_ignore_case)
         } else {
             path.to_string()
         };
+        self.join_target(target_path, query)
+    }
+
+    /// Get the target URL for a raw (percent-encoded) request path
+    ///
+    /// The path is forwarded verbatim; only the matched prefix is stripped,
+    /// comparing it against the decoded form of the path.
+    pub fn get_raw_target_url(&self, raw_path: &str, query: Option<&str>) -> String {
+        let target_path = if self.strip_prefix {
+            self.strip_path_prefix(raw_path, strip_decoded_prefix)
+        } else {
+            raw_path.to_string()
+        };
+        self.join_target(target_path, query)
+    }
 
+    /// Join a forwarded path and query onto the route target
+    fn join_target(&self, target_path: String, query: Option<&str>) -> String {
         let base = self.target.trim_end_matches('/');
-        let path_part = if target_path.starts_with('/') {
+        let path_part = if target_path.is_empty() || target_path.starts_with('/') {
             target_path
         } else {
             format!("/{}", target_path)
@@ -106,22 +833,26 @@ impl ProxyRoute {
     }
 
     /// Strip the matched prefix from the path
-    fn strip_path_prefix(&self, path: &str) -> String {
+    fn strip_path_prefix<'a>(
+        &self,
+        path: &'a str,
+        strip: impl Fn(&'a str, &str) -> Option<&'a str>,
+    ) -> String {
         let pattern = &self.path_pattern;
 
         if pattern.ends_with("/*") {
             let prefix = &pattern[..pattern.len() - 2];
-            if let Some(remainder) = path.strip_prefix(prefix) {
+            if let Some(remainder) = strip(path, prefix) {
                 if remainder.is_empty() || remainder == "/" {
-                    return "/".to_string();
+                    return self.stripped_empty_path();
                 }
                 return remainder.to_string();
             }
         } else if pattern.ends_with('/') {
             let prefix = &pattern[..pattern.len() - 1];
-            if let Some(remainder) = path.strip_prefix(prefix) {
+            if let Some(remainder) = strip(path, prefix) {
                 if remainder.is_empty() {
-                    return "/".to_string();
+                    return self.stripped_empty_path();
                 }
                 return remainder.to_string();
             }
@@ -129,376 +860,6332 @@ impl ProxyRoute {
 
         path.to_string()
     }
+
+    /// Path forwarded when stripping the prefix leaves nothing
+    fn stripped_empty_path(&self) -> String {
+        self.empty_path.clone().unwrap_or_else(|| "/".to_string())
+    }
 }
 
-impl ProxyService {
-    /// Create a new proxy service with support for both HTTP and HTTPS targets
-    pub fn new(routes: Vec<ProxyRoute>, metrics: Arc<GatewayMetrics>) -> Self {
-        // Create HTTPS connector with native roots
-        let https = hyper_rustls::HttpsConnectorBuilder::new()
-            .with_native_roots()
-            .expect("Failed to load native root certificates")
-            .https_or_http()
-            .enable_http1()
-            .enable_http2()
-            .build();
+/// Check if a path matches a route path pattern
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    // The root pattern matches only the root; `/*` matches every path
+    if pattern == "/" {
+        return path == "/";
+    }
+
+    // Handle wildcard patterns
+    if let Some(prefix) = pattern.strip_suffix("/*") {
+        return path == prefix || path.starts_with(&format!("{}/", prefix));
+    }
+
+    // Handle exact match with optional trailing slash
+    if let Some(base) = pattern.strip_suffix('/') {
+        return path == base || path == pattern || path.starts_with(pattern);
+    }
+
+    // Exact match
+    path == pattern || path.starts_with(&format!("{}/", pattern))
+}
+
+/// Status recorded for requests the client abandoned (nginx's 499)
+pub const CLIENT_CLOSED_REQUEST: u16 = 499;
+
+/// Most comparison replays in flight per proxy; requests sampled beyond it
+/// aren't replayed
+pub const MAX_COMPARISONS_IN_FLIGHT: usize = 64;
+
+/// Client headers describing the request body, forwarded even when a route's
+/// `forward_headers` doesn't list them
+const BODY_HEADERS: [HeaderName; 3] = [
+    header::CONTENT_TYPE,
+    header::CONTENT_LENGTH,
+    header::CONTENT_ENCODING,
+];
+
+/// Most schema errors reported for one request body
+const MAX_SCHEMA_ERRORS: usize = 10;
+
+/// Timeout of comparison replays on routes without `timeout_ms`
+pub const DEFAULT_COMPARE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Interval between rounds re-opening prewarmed connections, shorter than
+/// the default 90s idle timeout of upstream connections
+pub const PREWARM_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Records a request as cancelled by the client if dropped before completion
+///
+/// Hyper drops the handler future when the client disconnects, which drops
+/// the in-flight upstream call along with it; this guard is what's left to
+/// account for the request.
+struct CancelGuard<'a> {
+    metrics: &'a dyn MetricsSink,
+    route: &'a ProxyRoute,
+    method: &'a str,
+    path: &'a str,
+    start: Instant,
+    completed: bool,
+}
 
-        let client = Client::builder(TokioExecutor::new()).build(https);
+impl Drop for CancelGuard<'_> {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.metrics.record_request(
+                self.method,
+                self.path,
+                CLIENT_CLOSED_REQUEST,
+                self.start.elapsed(),
+            );
+            self.metrics.record_route_request(
+                self.route.label(),
+                &self.route.group,
+                CLIENT_CLOSED_REQUEST,
+            );
+            self.metrics.record_client_cancelled(self.route.label());
+        }
+    }
+}
+
+/// Routes with the index used to look them up
+pub struct RouteTable {
+    routes: Arc<Vec<ProxyRoute>>,
+    index: RouteIndex,
+    /// Lowercase path patterns, when paths are matched case-insensitively
+    folded_patterns: Option<Vec<String>>,
+    /// How a path matched by several routes is resolved
+    match_mode: MatchMode,
+    /// Whether `;` separates query parameters as well as `&`
+    query_semicolons: bool,
+}
+
+impl RouteTable {
+    /// Build the lookup index for `routes`
+    pub fn new(routes: Vec<ProxyRoute>) -> Self {
+        Self::with_config(routes, &RoutingConfig::default())
+    }
 
+    /// Build the lookup index for `routes` with the routing settings for
+    /// path case, overlapping routes and query separators
+    pub fn with_config(routes: Vec<ProxyRoute>, routing: &RoutingConfig) -> Self {
+        let folded_patterns: Option<Vec<String>> = routing.case_insensitive_paths.then(|| {
+            routes
+                .iter()
+                .map(|r| r.path_pattern.to_ascii_lowercase())
+                .collect()
+        });
+        let index = match &folded_patterns {
+            Some(patterns) => RouteIndex::new(patterns.iter().map(String::as_str)),
+            None => RouteIndex::new(routes.iter().map(|r| r.path_pattern.as_str())),
+        };
         Self {
-            client,
-            routes,
-            metrics,
+            routes: Arc::new(routes),
+            index,
+            folded_patterns,
+            match_mode: routing.match_mode,
+            query_semicolons: routing.query_semicolon_separator,
         }
     }
 
-    /// Create proxy routes from configuration
-    pub fn routes_from_config(
-        routes: &[RouteConfig],
-        api_key_selectors: &HashMap<String, SharedApiKeySelector>,
-    ) -> Vec<ProxyRoute> {
-        routes
-            .iter()
-            .filter(|r| r.enabled)
-            .map(|route| {
-                let api_key_selector = route
-                    .api_key_pool
-                    .as_ref()
-                    .and_then(|name| api_key_selectors.get(name).cloned());
+    /// Get the routes in configured order
+    pub fn routes(&self) -> &Arc<Vec<ProxyRoute>> {
+        &self.routes
+    }
 
-                ProxyRoute {
-                    name: route.name.clone(),
-                    path_pattern: route.path.clone(),
-                    target: route.target.clone(),
-                    strip_prefix: route.strip_prefix,
-                    methods: route.methods.clone(),
-                    api_key_selector,
-                    headers: route.headers.clone(),
-                    description: route.description.clone(),
-                }
+    /// Find the route matching the path, method and query string
+    ///
+    /// With several matches, the match mode picks the route: the first in
+    /// configured order, or the most specific one.
+    pub fn find(&self, path: &str, method: &str, query: Option<&str>) -> Option<&ProxyRoute> {
+        let path = self.fold(path);
+        let mut matches = self
+            .candidates(&path)
+            .filter(|&(i, r)| {
+                r.method_matches(method)
+                    && self.pattern_matches(i, &path)
+                    && r.query_matches(query, self.query_semicolons)
             })
-            .collect()
+            .map(|(_, r)| r);
+        match self.match_mode {
+            // `min_by_key` keeps the first of equally specific routes
+            MatchMode::MostSpecific => matches.min_by_key(|r| std::cmp::Reverse(r.specificity())),
+            MatchMode::First | MatchMode::Error => matches.next(),
+        }
     }
 
-    /// Forward a request to the appropriate target
-    pub async fn forward(
-        &self,
-        req: Request<Body>,
-    ) -> Result<Response<Body>, (StatusCode, String)> {
-        let start = Instant::now();
-        let method = req.method().to_string();
-        let path = req.uri().path().to_string();
+    /// Check if any route matches the path and query string, regardless of
+    /// method
+    pub fn path_matches(&self, path: &str, query: Option<&str>) -> bool {
+        self.find_any_method(path, query).is_some()
+    }
 
-        // Find matching route
-        let route = self
-            .routes
-            .iter()
-            .find(|r| r.matches(&path, &method))
-            .ok_or_else(|| {
-                self.metrics
-                    .record_request(&method, &path, 404, start.elapsed());
-                (StatusCode::NOT_FOUND, "No matching route found".to_string())
-            })?;
-
-        // Get the query string
-        let query = req.uri().query();
+    /// Find the first route matching the path and query string, regardless
+    /// of method
+    pub fn find_any_method(&self, path: &str, query: Option<&str>) -> Option<&ProxyRoute> {
+        let path = self.fold(path);
+        self.candidates(&path)
+            .find(|&(i, r)| {
+                self.pattern_matches(i, &path) && r.query_matches(query, self.query_semicolons)
+            })
+            .map(|(_, r)| r)
+    }
 
-        // Get the API key selector from route config
-        let api_key_selector = route.api_key_selector.as_ref();
+    /// Lowercase the path when matching case-insensitively
+    fn fold<'a>(&self, path: &'a str) -> std::borrow::Cow<'a, str> {
+        match self.folded_patterns {
+            Some(_) => std::borrow::Cow::Owned(path.to_ascii_lowercase()),
+            None => std::borrow::Cow::Borrowed(path),
+        }
+    }
 
-        // Get the API key if a selector is configured
-        let api_key = api_key_selector.and_then(|s| s.get_key().map(|k| k.to_string()));
+    fn pattern_matches(&self, i: usize, path: &str) -> bool {
+        match &self.folded_patterns {
+            Some(patterns) => pattern_matches(&patterns[i], path),
+            None => self.routes[i].path_matches(path),
+        }
+    }
 
-        // Build target URL, optionally inject API key as query parameter
-        let target_url = {
-            let base_url = route.get_target_url(&path, query);
+    fn candidates<'a>(&'a self, path: &str) -> impl Iterator<Item = (usize, &'a ProxyRoute)> {
+        self.index
+            .candidates(path)
+            .iter()
+            .map(|&i| (i, &self.routes[i]))
+    }
+}
 
-            // If API key should be injected as query parameter, append it
-            if let (Some(selector), Some(ref key)) = (api_key_selector, &api_key) {
-                if let Some(ref query_param_name) = selector.query_param_name {
-                    // URL-encode the API key value for safe inclusion in query string
-                    let encoded_key = percent_encoding::utf8_percent_encode(
-                        key,
-                        percent_encoding::NON_ALPHANUMERIC,
-                    )
-                    .to_string();
-                    if base_url.contains('?') {
-                        format!("{}&{}={}", base_url, query_param_name, encoded_key)
-                    } else {
-                        format!("{}?{}={}", base_url, query_param_name, encoded_key)
-                    }
-                } else {
-                    base_url
-                }
-            } else {
-                base_url
-            }
-        };
+impl ProxyService {
+    /// Create a new proxy service with support for both HTTP and HTTPS targets
+    ///
+    /// HTTPS upstreams are verified against the system's root certificates;
+    /// fails if they can't be loaded. Use [`ProxyService::with_tls`] for
+    /// other TLS settings.
+    pub fn new(routes: Vec<ProxyRoute>, metrics: Arc<dyn MetricsSink>) -> anyhow::Result<Self> {
+        let tls = crate::tls::client_config(&TlsConfig::default())?;
+        Ok(Self::with_tls(routes, metrics, tls))
+    }
 
-        // Build new request
-        let (parts, body) = req.into_parts();
+    /// Create a new proxy service using the given TLS client configuration
+    ///
+    /// Routes referencing an upstream pool get an isolated client with default
+    /// pool settings; use [`ProxyService::with_upstream_pools`] to configure them.
+    pub fn with_tls(
+        routes: Vec<ProxyRoute>,
+        metrics: Arc<dyn MetricsSink>,
+        tls: Arc<rustls::ClientConfig>,
+    ) -> Self {
+        let pool_settings = HashMap::new();
+        let client_settings = UpstreamClientConfig::default();
+        let clients = Self::build_clients(&pool_settings, &routes, &client_settings, &tls);
 
-        let mut builder = Request::builder().method(parts.method).uri(&target_url);
+        Self {
+            clients: Arc::new(ArcSwap::from_pointee(clients)),
+            pool_settings: Arc::new(std::sync::Mutex::new(pool_settings)),
+            client_settings,
+            tls,
+            routes: Arc::new(ArcSwap::from_pointee(RouteTable::new(routes))),
+            metrics,
+            forwarding: ForwardingConfig::default(),
+            routing: RoutingConfig::default(),
+            debug_headers: DebugHeadersConfig::default(),
+            limits: LimitsConfig::default(),
+            access_log: AccessLogConfig::default(),
+            comparisons: Arc::new(Semaphore::new(MAX_COMPARISONS_IN_FLIGHT)),
+        }
+    }
+
+    /// Apply upstream pool settings to the isolated clients
+    pub fn with_upstream_pools(self, pools: &HashMap<String, UpstreamPoolConfig>) -> Self {
+        self.set_upstream_pools(pools);
+        self
+    }
+
+    /// Replace the upstream pool settings
+    ///
+    /// When they changed, every client is rebuilt with the new settings, as
+    /// by [`ProxyService::recycle_connections`]. Pools named by routes but
+    /// not configured use the default settings.
+    pub fn set_upstream_pools(&self, pools: &HashMap<String, UpstreamPoolConfig>) {
+        {
+            let mut settings = self.pool_settings.lock().unwrap();
+            if *settings == *pools {
+                return;
+            }
+            *settings = pools.clone();
+        }
+        self.recycle_connections();
+    }
+
+    /// Apply the `[client]` connection settings to every upstream client
+    ///
+    /// `connection_max_lifetime` is enforced by [`run_connection_recycle`].
+    pub fn with_client(mut self, client_settings: UpstreamClientConfig) -> Self {
+        self.client_settings = client_settings;
+        self.recycle_connections();
+        self
+    }
+
+    /// Replace every upstream client, closing their idle connections
+    ///
+    /// Requests in flight finish on their current connections; later
+    /// requests open new ones, resolving upstream hosts again.
+    pub fn recycle_connections(&self) {
+        let pool_settings = self.pool_settings.lock().unwrap().clone();
+        let clients = Self::build_clients(
+            &pool_settings,
+            &self.get_routes(),
+            &self.client_settings,
+            &self.tls,
+        );
+        self.clients.store(Arc::new(clients));
+    }
+
+    /// Build clients for the upstream pools named by `routes` that have none
+    ///
+    /// Clients already built keep their connections.
+    fn add_pool_clients(&self, routes: &[ProxyRoute]) {
+        let clients = self.clients.load();
+        let missing: Vec<&String> = routes
+            .iter()
+            .filter_map(|r| r.upstream_pool.as_ref())
+            .filter(|name| !clients.pools.contains_key(*name))
+            .collect();
+        if missing.is_empty() {
+            return;
+        }
+
+        let mut pools = clients.pools.clone();
+        for name in missing {
+            let settings = self.pool_settings.lock().unwrap().get(name).cloned();
+            let settings = settings.unwrap_or_default();
+            let client = Self::build_client(&settings, &self.client_settings, &self.tls);
+            pools.insert(name.clone(), Arc::new(client));
+        }
+        self.clients.store(Arc::new(UpstreamClients {
+            default: clients.default.clone(),
+            pools,
+            title_case: std::sync::Mutex::new(clients.title_case.lock().unwrap().clone()),
+            sni: std::sync::Mutex::new(clients.sni.lock().unwrap().clone()),
+        }));
+    }
+
+    /// Build the default client and a client for each configured pool or
+    /// pool named by `routes`; the others are built on first use
+    fn build_clients(
+        pool_settings: &HashMap<String, UpstreamPoolConfig>,
+        routes: &[ProxyRoute],
+        client_settings: &UpstreamClientConfig,
+        tls: &Arc<rustls::ClientConfig>,
+    ) -> UpstreamClients {
+        let default = UpstreamPoolConfig::default();
+        let mut pools = HashMap::new();
+        for (name, settings) in pool_settings {
+            let client = Self::build_client(settings, client_settings, tls);
+            pools.insert(name.clone(), Arc::new(client));
+        }
+        for name in routes.iter().filter_map(|r| r.upstream_pool.as_ref()) {
+            if !pools.contains_key(name) {
+                let client = Self::build_client(&default, client_settings, tls);
+                pools.insert(name.clone(), Arc::new(client));
+            }
+        }
+        UpstreamClients {
+            default: Arc::new(Self::build_client(&default, client_settings, tls)),
+            pools,
+            title_case: std::sync::Mutex::new(HashMap::new()),
+            sni: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Start building a client with a pool's idle connection limits
+    ///
+    /// Connections idle for longer than the pool's `idle_timeout`, or
+    /// `[client] connection_max_idle`, are closed.
+    fn client_builder(
+        settings: &UpstreamPoolConfig,
+        client_settings: &UpstreamClientConfig,
+    ) -> hyper_util::client::legacy::Builder {
+        let mut builder = Client::builder(TokioExecutor::new());
+        if let Some(idle_timeout) = settings
+            .idle_timeout
+            .or(client_settings.connection_max_idle)
+        {
+            builder.pool_idle_timeout(Duration::from_secs(idle_timeout));
+        }
+        if let Some(max_idle) = settings.max_idle_per_host {
+            builder.pool_max_idle_per_host(max_idle);
+        }
+        builder
+    }
+
+    /// Build an HTTP/HTTPS client with the given pool settings
+    fn build_client(
+        settings: &UpstreamPoolConfig,
+        client_settings: &UpstreamClientConfig,
+        tls: &Arc<rustls::ClientConfig>,
+    ) -> HttpClient {
+        let https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_tls_config(tls.as_ref().clone())
+            .https_or_http()
+            .enable_http1()
+            .enable_http2()
+            .build();
+
+        Self::client_builder(settings, client_settings).build(https)
+    }
+
+    /// Build the HTTP/1.1-only client for routes with `title_case_headers`
+    fn build_title_case_client(
+        settings: &UpstreamPoolConfig,
+        client_settings: &UpstreamClientConfig,
+        tls: &Arc<rustls::ClientConfig>,
+    ) -> HttpClient {
+        let https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_tls_config(tls.as_ref().clone())
+            .https_or_http()
+            .enable_http1()
+            .build();
+
+        Self::client_builder(settings, client_settings)
+            .http1_title_case_headers(true)
+            .build(https)
+    }
+
+    /// Build a client presenting `server_name` in TLS handshakes instead of
+    /// the target's host
+    fn build_sni_client(
+        settings: &UpstreamPoolConfig,
+        client_settings: &UpstreamClientConfig,
+        tls: &Arc<rustls::ClientConfig>,
+        server_name: ServerName<'static>,
+    ) -> HttpClient {
+        let https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_tls_config(tls.as_ref().clone())
+            .https_or_http()
+            .with_server_name_resolver(hyper_rustls::FixedServerNameResolver::new(server_name))
+            .enable_http1()
+            .enable_http2()
+            .build();
+
+        Self::client_builder(settings, client_settings).build(https)
+    }
+
+    /// Settings of an upstream pool; the default ones without a pool or for
+    /// pools that aren't configured
+    fn settings_for(&self, pool: Option<&String>) -> UpstreamPoolConfig {
+        pool.and_then(|name| self.pool_settings.lock().unwrap().get(name).cloned())
+            .unwrap_or_default()
+    }
+
+    /// Get the client used to reach a route's upstream
+    ///
+    /// Routes with `tls_sni` or `title_case_headers` get a client of their
+    /// own per upstream pool, so they keep the pool's isolation and limits.
+    fn client_for(&self, route: &ProxyRoute) -> Arc<HttpClient> {
+        let clients = self.clients.load();
+        let pool = route.upstream_pool.as_ref();
+        if let Some(server_name) = &route.tls_sni {
+            let key = (pool.cloned(), server_name.clone());
+            if let Some(client) = clients.sni.lock().unwrap().get(&key) {
+                return client.clone();
+            }
+            let client = Arc::new(Self::build_sni_client(
+                &self.settings_for(pool),
+                &self.client_settings,
+                &self.tls,
+                server_name.clone(),
+            ));
+            return clients
+                .sni
+                .lock()
+                .unwrap()
+                .entry(key)
+                .or_insert(client)
+                .clone();
+        }
+        if route.title_case_headers {
+            if let Some(client) = clients.title_case.lock().unwrap().get(&pool.cloned()) {
+                return client.clone();
+            }
+            let client = Arc::new(Self::build_title_case_client(
+                &self.settings_for(pool),
+                &self.client_settings,
+                &self.tls,
+            ));
+            return clients
+                .title_case
+                .lock()
+                .unwrap()
+                .entry(pool.cloned())
+                .or_insert(client)
+                .clone();
+        }
+        route
+            .upstream_pool
+            .as_ref()
+            .and_then(|name| clients.pools.get(name))
+            .unwrap_or(&clients.default)
+            .clone()
+    }
+
+    /// Set the forwarding configuration
+    pub fn with_forwarding(mut self, forwarding: ForwardingConfig) -> Self {
+        self.forwarding = forwarding;
+        self
+    }
+
+    /// Set the routing configuration
+    pub fn with_routing(mut self, routing: RoutingConfig) -> Self {
+        self.routing = routing;
+        self.set_routes(self.get_routes().as_ref().clone());
+        self
+    }
+
+    /// Set the debug header configuration
+    pub fn with_debug_headers(mut self, debug_headers: DebugHeadersConfig) -> Self {
+        self.debug_headers = debug_headers;
+        self
+    }
+
+    /// Set the size limits
+    pub fn with_limits(mut self, limits: LimitsConfig) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set the access log configuration
+    pub fn with_access_log(mut self, access_log: AccessLogConfig) -> Self {
+        self.access_log = access_log;
+        self
+    }
+
+    /// Create proxy routes from configuration
+    ///
+    /// Fails if a route's `body_schema` can't be loaded, so a route never
+    /// runs without the validation it asks for, or if its `tls_sni` isn't a
+    /// DNS name.
+    pub fn routes_from_config(
+        routes: &[RouteConfig],
+        api_key_selectors: &HashMap<String, SharedApiKeySelector>,
+    ) -> anyhow::Result<Vec<ProxyRoute>> {
+        routes
+            .iter()
+            .filter(|r| r.enabled)
+            .map(|route| {
+                let api_key_selector = route
+                    .api_key_pool
+                    .as_ref()
+                    .and_then(|name| api_key_selectors.get(name).cloned());
+                let capture = route.capture.clone().map(|config| {
+                    let key_headers: Vec<&str> = api_key_selector
+                        .as_ref()
+                        .map(|selector| selector.header_name.as_str())
+                        .into_iter()
+                        .chain(route.client_key_header.as_deref())
+                        .chain(route.headers.keys().map(String::as_str))
+                        .collect();
+                    let key_params: Vec<&str> = api_key_selector
+                        .as_ref()
+                        .and_then(|selector| selector.query_param_name.as_deref())
+                        .into_iter()
+                        .collect();
+                    Arc::new(Capture::new(config, &key_headers, &key_params))
+                });
+                let body_schema = route
+                    .body_schema
+                    .as_deref()
+                    .map(load_body_schema)
+                    .transpose()
+                    .map_err(|e| anyhow::anyhow!("Route '{}': {}", route.path, e))?
+                    .map(Arc::new);
+                let tls_sni = match &route.tls_sni {
+                    Some(name) => match ServerName::try_from(name.clone()) {
+                        Ok(server_name @ ServerName::DnsName(_)) => Some(server_name),
+                        _ => anyhow::bail!(
+                            "Route '{}' tls_sni '{}' is not a DNS name",
+                            route.path,
+                            name
+                        ),
+                    },
+                    None => None,
+                };
+
+                Ok(ProxyRoute {
+                    name: route.name.clone(),
+                    path_pattern: route.path.clone(),
+                    target: route.target.clone(),
+                    strip_prefix: route.strip_prefix,
+                    empty_path: route.empty_path.clone(),
+                    methods: normalize_methods(&route.methods),
+                    api_key_pool: api_key_selector.as_ref().and(route.api_key_pool.clone()),
+                    api_key_selector,
+                    client_key_header: route
+                        .client_key_header
+                        .as_deref()
+                        .and_then(|name| name.parse().ok()),
+                    headers: route.headers.clone(),
+                    header_to_query: sorted_mappings(
+                        route
+                            .header_to_query
+                            .iter()
+                            .filter_map(|(h, q)| Some((h.parse().ok()?, q.clone()))),
+                        |(_, param)| param.clone(),
+                    ),
+                    query_to_header: sorted_mappings(
+                        route
+                            .query_to_header
+                            .iter()
+                            .filter_map(|(q, h)| Some((q.clone(), h.parse().ok()?))),
+                        |(param, _)| param.clone(),
+                    ),
+                    forward_headers: route
+                        .forward_headers
+                        .iter()
+                        .filter_map(|name| name.parse().ok())
+                        .collect(),
+                    match_query: sorted_mappings(
+                        route.match_query.iter().map(|(param, value)| {
+                            (param.clone(), (value != "*").then(|| value.clone()))
+                        }),
+                        |(param, _)| param.clone(),
+                    ),
+                    description: route.description.clone(),
+                    upstream_pool: route.upstream_pool.clone(),
+                    concurrency_limit: route
+                        .max_concurrency
+                        .map(|limit| Arc::new(Semaphore::new(limit))),
+                    timeout: route.timeout_ms.map(Duration::from_millis),
+                    stream_idle_timeout: route.stream_idle_timeout_ms.map(Duration::from_millis),
+                    max_uri_bytes: route.max_uri_bytes,
+                    circuit_breaker: route
+                        .circuit_breaker
+                        .clone()
+                        .map(|config| Arc::new(CircuitBreaker::new(config))),
+                    cache: route
+                        .cache
+                        .as_ref()
+                        .map(|config| Arc::new(ResponseCache::new(config))),
+                    strip_response_headers: route.strip_response_headers.clone(),
+                    aggregate: route.aggregate.clone(),
+                    title_case_headers: route.title_case_headers,
+                    tls_sni,
+                    accept_content_types: route
+                        .accept_content_types
+                        .iter()
+                        .map(|t| t.trim().to_ascii_lowercase())
+                        .collect(),
+                    decompress_request: route
+                        .decompress_request
+                        .then_some(route.max_decompressed_bytes),
+                    body_schema,
+                    body_schema_max_bytes: route.body_schema_max_bytes,
+                    capture,
+                    compare: route.compare.clone(),
+                    idempotency_key: route
+                        .idempotency_key
+                        .as_ref()
+                        .filter(|config| config.generate)
+                        .and_then(|config| config.header.parse().ok()),
+                    mock: route
+                        .mock
+                        .as_ref()
+                        .map(|config| Arc::new(MockResponse::from_config(config))),
+                    priority: route.priority,
+                    prewarm_connections: route.prewarm_connections,
+                    latency_sampling: route.latency_sampling,
+                    group: route
+                        .group
+                        .clone()
+                        .unwrap_or_else(|| DEFAULT_ROUTE_GROUP.to_string()),
+                })
+            })
+            .collect()
+    }
+
+    /// Record a failed request and return its error
+    fn fail(&self, method: &str, path: &str, start: Instant, err: ProxyError) -> ProxyError {
+        self.metrics
+            .record_request(method, path, err.status_code().as_u16(), start.elapsed());
+        err
+    }
+
+    /// Forward a request to the appropriate target
+    ///
+    /// The request runs in a `proxy` span recording the matched route, the
+    /// upstream host, the API key pool (never the key), the status and the
+    /// latency in milliseconds, and is logged (subject to access log
+    /// sampling) once it completes.
+    pub async fn forward(&self, req: Request<Body>) -> Result<Response<Body>, ProxyError> {
+        let start = Instant::now();
+        let span = tracing::info_span!(
+            "proxy",
+            route = tracing::field::Empty,
+            upstream_host = tracing::field::Empty,
+            key_pool = tracing::field::Empty,
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        );
+        let result = self
+            .forward_request(req, start)
+            .instrument(span.clone())
+            .await;
+        let status = match &result {
+            Ok(response) => response.status(),
+            Err(err) => err.status_code(),
+        };
+        span.record("status", status.as_u16());
+        span.record("latency_ms", start.elapsed().as_millis() as u64);
+        if self.should_log(status) {
+            span.in_scope(|| info!("Request completed"));
+        }
+        result
+    }
+
+    /// Decide whether a completed request is written to the access log
+    ///
+    /// Error responses are always logged; others are sampled at
+    /// `access_log.sample_rate`.
+    fn should_log(&self, status: StatusCode) -> bool {
+        let rate = self.access_log.sample_rate;
+        status.as_u16() >= 400 || rate >= 1.0 || (rate > 0.0 && rand::random::<f64>() < rate)
+    }
+
+    /// Forward a request within its `proxy` span
+    async fn forward_request(
+        &self,
+        mut req: Request<Body>,
+        start: Instant,
+    ) -> Result<Response<Body>, ProxyError> {
+        let method = req.method().to_string();
+
+        // A lone trailing `?` is the same request as no query at all, for
+        // matching and forwarding alike
+        if req.uri().query() == Some("") {
+            let path = req.uri().path().to_string();
+            set_request_path(&mut req, &path)?;
+        }
+
+        // Normalize repeated slashes before anything looks at the path
+        if self.routing.collapse_slashes != CollapseSlashes::Off && req.uri().path().contains("//")
+        {
+            let path = collapse_slashes(req.uri().path());
+            if self.routing.collapse_slashes == CollapseSlashes::Redirect {
+                let path_and_query = match req.uri().query() {
+                    Some(query) => format!("{}?{}", path, query),
+                    None => path.clone(),
+                };
+                let response = Response::builder()
+                    .status(StatusCode::PERMANENT_REDIRECT)
+                    .header(header::LOCATION, path_and_query)
+                    .body(Body::empty())
+                    .map_err(ProxyError::InvalidRequest)?;
+                self.metrics.record_request(
+                    &method,
+                    &decode_path(&path),
+                    response.status().as_u16(),
+                    start.elapsed(),
+                );
+                return Ok(response);
+            }
+            set_request_path(&mut req, &path)?;
+        }
+
+        // Requests outside the base path don't reach any route
+        if !self.routing.base_path.is_empty() {
+            match strip_base_path(req.uri().path(), &self.routing.base_path) {
+                Some(stripped) => {
+                    let stripped = stripped.to_string();
+                    set_request_path(&mut req, &stripped)?;
+                }
+                None => {
+                    let path = decode_path(req.uri().path());
+                    return Err(self.fail(&method, &path, start, ProxyError::NoRoute));
+                }
+            }
+        }
+
+        // Routes are matched against the decoded path
+        let path = decode_path(req.uri().path());
+
+        // Find matching route; a path served only for other methods is a 405
+        // Use one snapshot of the route table for the whole request
+        let routes = self.routes.load_full();
+        let query = req.uri().query();
+        let head_via_get = method == "HEAD"
+            && self.routing.head_implies_get
+            && routes.find(&path, &method, query).is_none();
+        let route_method = if head_via_get { "GET" } else { method.as_str() };
+        let route = match routes.find(&path, route_method, query) {
+            Some(route) => route,
+            None => {
+                let err = match routes.find_any_method(&path, query) {
+                    Some(matched) => {
+                        warn!(
+                            "Method {} not allowed on route '{}' for {}",
+                            method,
+                            matched.label(),
+                            path
+                        );
+                        self.metrics
+                            .record_method_rejected(&method, matched.label());
+                        ProxyError::MethodNotAllowed
+                    }
+                    None => ProxyError::NoRoute,
+                };
+                return Err(self.fail(&method, &path, start, err));
+            }
+        };
+
+        let span = tracing::Span::current();
+        span.record("route", route.label());
+        if route.mock.is_none() && route.aggregate.is_empty() {
+            if let Some(host) = extract_host_from_url(&route.target) {
+                span.record("upstream_host", host.as_str());
+            }
+        }
+        if let Some(pool) = &route.api_key_pool {
+            span.record("key_pool", pool.as_str());
+        }
+
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok());
+        if !route.accepts_content_type(&method, content_type) {
+            let err = ProxyError::UnsupportedMediaType;
+            self.metrics.record_route_request(
+                route.label(),
+                &route.group,
+                err.status_code().as_u16(),
+            );
+            return Err(self.fail(&method, &path, start, err));
+        }
+
+        // Requests whose deadline has passed aren't worth forwarding
+        if let Some(value) = req.headers().get(REQUEST_DEADLINE_HEADER) {
+            let left = value
+                .to_str()
+                .ok()
+                .and_then(|v| parse_deadline(v, chrono::Utc::now()));
+            match left {
+                Some(left) if left.is_zero() => {
+                    let err = ProxyError::DeadlineExceeded;
+                    self.metrics.record_route_request(
+                        route.label(),
+                        &route.group,
+                        err.status_code().as_u16(),
+                    );
+                    return Err(self.fail(&method, &path, start, err));
+                }
+                Some(left) => {
+                    if let Some(deadline) = Instant::now().checked_add(left) {
+                        req.extensions_mut().insert(RequestDeadline(deadline));
+                    }
+                }
+                None => debug!("Ignoring invalid {} header", REQUEST_DEADLINE_HEADER),
+            }
+        }
+
+        if head_via_get && self.routing.head_upstream_method == HeadUpstreamMethod::Get {
+            *req.method_mut() = Method::GET;
+        }
+
+        let mut cancel_guard = CancelGuard {
+            metrics: self.metrics.as_ref(),
+            route,
+            method: &method,
+            path: &path,
+            start,
+            completed: false,
+        };
+
+        let result = match route.cache.as_ref().zip(cache_key(&req)) {
+            Some((cache, key)) => {
+                self.forward_cached(route, cache, &key, req, start, &method, &path)
+                    .await
+            }
+            None => {
+                self.forward_to_route(route, req, start, &method, &path)
+                    .await
+            }
+        };
+        cancel_guard.completed = true;
+        let status = match &result {
+            Ok(response) => response.status(),
+            Err(err) => err.status_code(),
+        };
+        self.metrics
+            .record_route_request(route.label(), &route.group, status.as_u16());
+        let mut response = result?;
+
+        // HEAD responses never carry a body, whatever the upstream sent
+        if method == "HEAD" {
+            *response.body_mut() = Body::empty();
+        } else if let Some(length) = hyper::body::Body::size_hint(response.body()).exact() {
+            // Buffered responses have a known size; streamed ones are
+            // counted as their chunks pass through
+            self.metrics.record_response_bytes(route.label(), length);
+        }
+
+        // Written once the upstream response has been fully read
+        if self.debug_headers.enabled {
+            let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+            if let Ok(value) = format!("{:.3}", duration_ms).parse() {
+                response
+                    .headers_mut()
+                    .insert(GATEWAY_DURATION_HEADER, value);
+            }
+        }
+
+        Ok(response)
+    }
+
+    /// Serve a cacheable request from the route cache
+    ///
+    /// On a miss, concurrent identical requests share a single upstream call.
+    /// Clients whose `If-None-Match` matches the response's `ETag` get a
+    /// `304 Not Modified`; on a miss the header is forwarded and a `304` from
+    /// the upstream is relayed without being shared or stored.
+    #[allow(clippy::too_many_arguments)]
+    async fn forward_cached(
+        &self,
+        route: &ProxyRoute,
+        cache: &Arc<ResponseCache>,
+        key: &str,
+        req: Request<Body>,
+        start: Instant,
+        method: &str,
+        path: &str,
+    ) -> Result<Response<Body>, ProxyError> {
+        let if_none_match = req.headers().get(header::IF_NONE_MATCH).cloned();
+        let request_headers = req.headers().clone();
+        if let Some(cached) = cache.get(key, &request_headers) {
+            let response = cached.to_response_for(if_none_match.as_ref());
+            self.metrics.record_request_sampled(
+                method,
+                path,
+                response.status().as_u16(),
+                start.elapsed(),
+                &route.latency_sampling,
+            );
+            return Ok(response);
+        }
+
+        match cache.begin(key) {
+            Flight::Follower(rx) => match wait_for_leader(rx, &request_headers).await {
+                Some(shared) => {
+                    let response = shared.to_response_for(if_none_match.as_ref());
+                    self.metrics.record_request_sampled(
+                        method,
+                        path,
+                        response.status().as_u16(),
+                        start.elapsed(),
+                        &route.latency_sampling,
+                    );
+                    Ok(response)
+                }
+                // The leading request failed, or its response can't be
+                // shared with this one; fetch on our own
+                None => self.forward_to_route(route, req, start, method, path).await,
+            },
+            Flight::Leader(guard) => {
+                let response = self
+                    .forward_to_route(route, req, start, method, path)
+                    .await?;
+                // Only meaningful to this client; waiting requests fetch on their own
+                if response.status() == StatusCode::NOT_MODIFIED {
+                    return Ok(response);
+                }
+                let (parts, body) = response.into_parts();
+                let body = axum::body::to_bytes(body, usize::MAX)
+                    .await
+                    .map_err(|e| ProxyError::UpstreamBody(e.into()))?;
+                let cached = CachedResponse {
+                    status: parts.status,
+                    headers: parts.headers,
+                    body,
+                };
+                let response = cached.to_response_for(if_none_match.as_ref());
+                guard.complete(&request_headers, cached);
+                Ok(response)
+            }
+        }
+    }
+
+    /// Fan out to an aggregate route's sub-requests and merge their bodies
+    ///
+    /// Each sub-response is placed under its name in a JSON object. Failed
+    /// sub-requests are reported as `{"error": ...}` entries; the status is
+    /// 207 when some failed and 502 when all of them did.
+    async fn forward_aggregate(
+        &self,
+        route: &ProxyRoute,
+        start: Instant,
+        method: &str,
+        path: &str,
+        request: &HeaderContext<'_>,
+    ) -> Result<Response<Body>, ProxyError> {
+        let client = self.client_for(route);
+        let client = client.as_ref();
+        let calls = route.aggregate.iter().map(|sub| async move {
+            let timeout = Duration::from_millis(sub.timeout_ms);
+            let result = tokio::time::timeout(timeout, fetch_json(client, route, sub, request))
+                .await
+                .unwrap_or_else(|_| Err(format!("timed out after {}ms", sub.timeout_ms)));
+            (sub.name.clone(), result)
+        });
+        let results = futures::future::join_all(calls).await;
+
+        let failed = results.iter().filter(|(_, r)| r.is_err()).count();
+        let status = if failed == 0 {
+            StatusCode::OK
+        } else if failed == results.len() {
+            StatusCode::BAD_GATEWAY
+        } else {
+            StatusCode::MULTI_STATUS
+        };
+        let merged: serde_json::Map<String, serde_json::Value> = results
+            .into_iter()
+            .map(|(name, result)| {
+                let value = result.unwrap_or_else(|e| serde_json::json!({ "error": e }));
+                (name, value)
+            })
+            .collect();
+
+        self.metrics.record_request_sampled(
+            method,
+            path,
+            status.as_u16(),
+            start.elapsed(),
+            &route.latency_sampling,
+        );
+
+        let body = serde_json::Value::Object(merged).to_string();
+        Response::builder()
+            .status(status)
+            .header(axum::http::header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .map_err(ProxyError::InvalidRequest)
+    }
+
+    /// Forward a request to a matched route's upstream
+    ///
+    /// With a route timeout, a single deadline covers reading the request
+    /// body, the upstream call and reading the response body. A client
+    /// deadline sooner than the route timeout replaces it.
+    async fn forward_to_route(
+        &self,
+        route: &ProxyRoute,
+        mut req: Request<Body>,
+        start: Instant,
+        method: &str,
+        path: &str,
+    ) -> Result<Response<Body>, ProxyError> {
+        let route_deadline = route.timeout.and_then(|t| Instant::now().checked_add(t));
+        let client_deadline = req.extensions().get::<RequestDeadline>().map(|d| d.0);
+        let deadline = match (route_deadline, client_deadline) {
+            (Some(route_deadline), Some(client_deadline)) => {
+                Some(route_deadline.min(client_deadline))
+            }
+            (route_deadline, client_deadline) => route_deadline.or(client_deadline),
+        };
+        if let Some(deadline) = deadline.filter(|_| client_deadline.is_some()) {
+            req.extensions_mut().insert(RequestDeadline(deadline));
+        }
+
+        let phase = PhaseTracker::new();
+        let forward = self.forward_phases(route, req, start, method, path, &phase);
+        let Some(deadline) = deadline else {
+            return forward.await;
+        };
+
+        match tokio::time::timeout_at(deadline.into(), forward).await {
+            Ok(result) => result,
+            // A call the circuit breaker let through was settled as failed
+            // when the timeout dropped it
+            Err(_) => Err(self.fail(method, path, start, ProxyError::Timeout(phase.get()))),
+        }
+    }
+
+    /// Forward a request to a route's upstream, reporting progress to `phase`
+    async fn forward_phases(
+        &self,
+        route: &ProxyRoute,
+        req: Request<Body>,
+        start: Instant,
+        method: &str,
+        path: &str,
+        phase: &PhaseTracker,
+    ) -> Result<Response<Body>, ProxyError> {
+        if let Some(mock) = &route.mock {
+            let response = mock.to_response();
+            self.metrics.record_request_sampled(
+                method,
+                path,
+                response.status().as_u16(),
+                start.elapsed(),
+                &route.latency_sampling,
+            );
+            return Ok(response);
+        }
+
+        if !route.aggregate.is_empty() {
+            phase.set(TimeoutPhase::Upstream);
+            let request = HeaderContext::new(route, req.method(), req.uri(), req.headers());
+            return self
+                .forward_aggregate(route, start, method, path, &request)
+                .await;
+        }
+
+        // Get the query string, with the route's header/query mappings applied
+        let (query, mapped_headers) = remap_query(
+            route,
+            req.uri().query(),
+            req.headers(),
+            self.routing.query_semicolon_separator,
+        );
+
+        // A strategy forced for testing with `?api_key_strategy=` applies to
+        // this request only, and the parameter isn't forwarded
+        let (query, forced_strategy) = if self.routing.api_key_strategy_param {
+            take_query_param(
+                query.as_deref(),
+                API_KEY_STRATEGY_PARAM,
+                self.routing.query_semicolon_separator,
+            )
+        } else {
+            (query, None)
+        };
+        let query = query.as_deref();
+
+        // Get the API key selector from route config; clients presenting
+        // their own key send it as is, without a pool key
+        let client_key = route
+            .client_key_header
+            .as_ref()
+            .and_then(|name| req.headers().get(name))
+            .is_some_and(|value| !value.is_empty());
+        let api_key_selector = route.api_key_selector.as_ref().filter(|_| !client_key);
+
+        // Get the API key (or OAuth2 access token) if a selector is configured
+        let api_key = match api_key_selector {
+            Some(selector) => match selector.token_source() {
+                Some(source) => match source.token().await {
+                    Ok(token) => Some(format!("Bearer {}", token)),
+                    Err(e) => {
+                        warn!("No access token for route '{}': {}", route.label(), e);
+                        return Err(self.fail(
+                            method,
+                            path,
+                            start,
+                            ProxyError::TokenUnavailable(e),
+                        ));
+                    }
+                },
+                None => match &forced_strategy {
+                    Some(strategy) => selector.get_key_with(strategy),
+                    None => selector.get_key(),
+                }
+                .map(|k| k.to_string()),
+            },
+            None => None,
+        };
+        // Pool keys, as opposed to OAuth2 access tokens, which change on
+        // every refresh and mustn't become metric labels
+        let pool_key = api_key
+            .as_deref()
+            .filter(|_| api_key_selector.is_some_and(|s| s.token_source().is_none()));
+
+        let base_url = if self.routing.preserve_raw_path {
+            route.get_raw_target_url(req.uri().path(), query)
+        } else {
+            route.get_target_url(&encode_path(path), query)
+        };
+
+        // Build target URL, optionally inject API key as query parameter
+        let target_url = {
+            // If API key should be injected as query parameter, append it
+            if let (Some(selector), Some(ref key)) = (api_key_selector, &api_key) {
+                if let Some(ref query_param_name) = selector.query_param_name {
+                    // URL-encode the API key value for safe inclusion in query string
+                    let encoded_key = percent_encoding::utf8_percent_encode(
+                        key,
+                        percent_encoding::NON_ALPHANUMERIC,
+                    )
+                    .to_string();
+                    if base_url.contains('?') {
+                        format!("{}&{}={}", base_url, query_param_name, encoded_key)
+                    } else {
+                        format!("{}?{}={}", base_url, query_param_name, encoded_key)
+                    }
+                } else {
+                    base_url.clone()
+                }
+            } else {
+                base_url.clone()
+            }
+        };
+
+        // Refuse URIs the upstream would likely reject, before dialing it
+        if let Some(limit) = route.max_uri_bytes.or(self.limits.max_uri_bytes) {
+            let length = request_target(&target_url).len();
+            if length > limit {
+                warn!(
+                    "Upstream URI for route '{}' is {} bytes, over the {} byte limit",
+                    route.label(),
+                    length,
+                    limit
+                );
+                self.metrics.record_uri_too_long(route.label());
+                return Err(self.fail(method, path, start, ProxyError::UriTooLong(limit)));
+            }
+        }
+
+        // Build new request
+        let (parts, body) = req.into_parts();
+
+        // Hyper answers `Expect: 100-continue` once the body is first read, so
+        // refuse up front anything that would be rejected after the upload
+        let expects_continue = parts
+            .headers
+            .get(header::EXPECT)
+            .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"100-continue"));
+        if expects_continue {
+            if self.forwarding.expect_continue == ExpectContinue::Reject {
+                return Err(self.fail(method, path, start, ProxyError::ExpectationFailed));
+            }
+            if route
+                .circuit_breaker
+                .as_ref()
+                .is_some_and(|breaker| breaker.is_rejecting())
+            {
+                return Err(self.fail(method, path, start, ProxyError::CircuitOpen));
+            }
+        }
+
+        let mut builder = Request::builder()
+            .method(parts.method.clone())
+            .uri(&target_url);
 
         // Copy headers
         if let Some(headers) = builder.headers_mut() {
             for (key, value) in parts.headers.iter() {
-                // Skip hop-by-hop headers (including Host, which we'll set from target URL)
-                if !is_hop_by_hop_header(key.as_str()) {
+                // Skip hop-by-hop headers (including Host, which we'll set from
+                // target URL), the expectation, which the gateway has met, and
+                // headers outside the route's allow-list; the body's headers are
+                // always kept so the upstream can read it
+                let allowed = route.forward_headers.is_empty()
+                    || route.forward_headers.contains(key)
+                    || BODY_HEADERS.contains(key);
+                if allowed && !is_hop_by_hop_header(key.as_str()) && key != header::EXPECT {
                     headers.insert(key.clone(), value.clone());
                 }
             }
 
-            // Set Host header from target URL to ensure HTTPS targets work correctly
-            match extract_host_from_url(&target_url) {
-                Some(target_host) => match target_host.parse::<axum::http::header::HeaderValue>() {
-                    Ok(header_value) => {
-                        headers.insert(axum::http::header::HOST, header_value);
-                    }
-                    Err(e) => {
-                        warn!(
-                            "Failed to parse target host '{}' as header value: {}",
-                            target_host, e
-                        );
-                    }
-                },
-                None => {
-                    warn!(
-                        "Failed to extract host from target URL '{}', Host header may be incorrect",
-                        target_url
-                    );
-                }
+            // Headers moved into the query string aren't forwarded as headers
+            for (name, _) in &route.header_to_query {
+                headers.remove(name);
+            }
+            for (name, value) in mapped_headers {
+                headers.insert(name, value);
+            }
+
+            // Set Host header from target URL to ensure HTTPS targets work correctly
+            match extract_host_from_url(&target_url) {
+                Some(target_host) => match target_host.parse::<axum::http::header::HeaderValue>() {
+                    Ok(header_value) => {
+                        headers.insert(axum::http::header::HOST, header_value);
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to parse target host '{}' as header value: {}",
+                            target_host, e
+                        );
+                    }
+                },
+                None => {
+                    warn!(
+                        "Failed to extract host from target URL '{}', Host header may be incorrect",
+                        target_url
+                    );
+                }
+            }
+
+            // Forward verified client certificate details, never trusting
+            // client-supplied versions of these headers
+            if self.forwarding.client_cert_headers {
+                headers.remove(CLIENT_CERT_SUBJECT_HEADER);
+                headers.remove(CLIENT_CERT_SERIAL_HEADER);
+                if let Some(cert) = parts.extensions.get::<ClientCertInfo>() {
+                    if let Ok(value) = cert.subject.parse::<axum::http::header::HeaderValue>() {
+                        headers.insert(CLIENT_CERT_SUBJECT_HEADER, value);
+                    }
+                    if let Ok(value) = cert.serial.parse::<axum::http::header::HeaderValue>() {
+                        headers.insert(CLIENT_CERT_SERIAL_HEADER, value);
+                    }
+                }
+            }
+
+            // Tell the upstream the public prefix it is mounted under
+            if self.forwarding.forwarded_prefix {
+                if let Ok(name) = self
+                    .forwarding
+                    .forwarded_prefix_header
+                    .parse::<axum::http::header::HeaderName>()
+                {
+                    headers.remove(&name);
+                    let prefix = format!(
+                        "{}{}",
+                        self.routing.base_path,
+                        route.stripped_prefix().unwrap_or("")
+                    );
+                    if !prefix.is_empty() {
+                        if let Ok(value) = prefix.parse::<axum::http::header::HeaderValue>() {
+                            headers.insert(name, value);
+                        }
+                    }
+                }
+            }
+
+            // Forward the time the gateway received the request
+            if self.debug_headers.enabled {
+                let received_at = chrono::Utc::now()
+                    - chrono::Duration::from_std(start.elapsed()).unwrap_or_default();
+                let value = received_at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+                if let Ok(value) = value.parse::<axum::http::header::HeaderValue>() {
+                    headers.insert(GATEWAY_RECEIVED_AT_HEADER, value);
+                }
+            }
+
+            // Tell the upstream how long the gateway waits, in place of the
+            // client's deadline
+            headers.remove(REQUEST_DEADLINE_HEADER);
+            if let Some(RequestDeadline(deadline)) = parts.extensions.get::<RequestDeadline>() {
+                let left = deadline.saturating_duration_since(Instant::now());
+                let at = chrono::Utc::now() + chrono::Duration::from_std(left).unwrap_or_default();
+                let value = at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+                if let Ok(value) = value.parse::<axum::http::header::HeaderValue>() {
+                    headers.insert(UPSTREAM_DEADLINE_HEADER, value);
+                }
+                let grpc = parts
+                    .headers
+                    .get(header::CONTENT_TYPE)
+                    .is_some_and(|v| v.as_bytes().starts_with(b"application/grpc"));
+                if grpc {
+                    // At most 8 digits are allowed
+                    let value = format!("{}m", left.as_millis().min(99_999_999));
+                    if let Ok(value) = value.parse::<axum::http::header::HeaderValue>() {
+                        headers.insert("grpc-timeout", value);
+                    }
+                }
+            }
+
+            // Add custom headers
+            let request = HeaderContext::new(route, &parts.method, &parts.uri, &parts.headers);
+            for (key, value) in &route.headers {
+                if let Ok(header_name) = key.parse::<axum::http::header::HeaderName>() {
+                    if let Ok(header_value) = request
+                        .expand(value)
+                        .parse::<axum::http::header::HeaderValue>()
+                    {
+                        headers.insert(header_name, header_value);
+                    }
+                }
+            }
+
+            // Give requests without an idempotency key a fresh one; a client's
+            // own key was copied above if the allow-list lets it through
+            if let Some(name) = &route.idempotency_key {
+                if !headers.contains_key(name) {
+                    if let Ok(value) = HeaderValue::from_str(&uuid::Uuid::new_v4().to_string()) {
+                        headers.insert(name.clone(), value);
+                    }
+                }
+            }
+
+            // Inject API key as header if configured (only when query_param_name is NOT set)
+            if let Some(selector) = api_key_selector {
+                // Only inject as header if query_param_name is not set
+                if selector.query_param_name.is_none() {
+                    if let Some(ref key) = api_key {
+                        if let Ok(header_name) = selector
+                            .header_name
+                            .parse::<axum::http::header::HeaderName>()
+                        {
+                            if let Ok(header_value) = key.parse::<axum::http::header::HeaderValue>()
+                            {
+                                headers.insert(header_name, header_value);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Convert body to the expected type
+        let mut body_bytes = match axum::body::to_bytes(body, usize::MAX).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                return Err(self.fail(method, path, start, ProxyError::RequestBody(e)));
+            }
+        };
+
+        self.metrics
+            .record_request_bytes(route.label(), body_bytes.len() as u64);
+
+        // Decode compressed bodies for upstreams that can't
+        let content_encoding = parts
+            .headers
+            .get(header::CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok());
+        if let Some((limit, encoding)) = route.decompress_request.zip(content_encoding) {
+            match decompress::decode(encoding, body_bytes.clone(), limit) {
+                Ok(Some(decoded)) => {
+                    if let Some(headers) = builder.headers_mut() {
+                        headers.remove(header::CONTENT_ENCODING);
+                        headers.insert(header::CONTENT_LENGTH, decoded.len().into());
+                    }
+                    body_bytes = decoded;
+                }
+                Ok(None) => {}
+                Err(DecompressError::TooLarge(_)) => {
+                    return Err(self.fail(method, path, start, ProxyError::BodyTooLarge));
+                }
+                Err(e) => {
+                    return Err(self.fail(method, path, start, ProxyError::RequestDecoding(e)));
+                }
+            }
+        }
+
+        // Reject JSON bodies that don't match the route's schema
+        if let Some(schema) = &route.body_schema {
+            let content_type = parts
+                .headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok());
+            let checked = check_body_schema(
+                schema,
+                route.body_schema_max_bytes,
+                content_type,
+                &body_bytes,
+            );
+            if let Err(err) = checked {
+                if matches!(err, ProxyError::InvalidBody(_)) {
+                    self.metrics.record_invalid_body(route.label());
+                }
+                return Err(self.fail(method, path, start, err));
+            }
+        }
+
+        // Record the request as sent upstream if this one is captured
+        let captured = route
+            .capture
+            .as_ref()
+            .filter(|capture| capture.sampled())
+            .map(|capture| {
+                let headers = builder.headers_ref().cloned().unwrap_or_default();
+                let request = CapturedRequest::new(
+                    method,
+                    &parts.uri.to_string(),
+                    headers,
+                    body_bytes.clone(),
+                );
+                (capture, request)
+            });
+
+        // Keep the request for the comparison upstream if this one is replayed;
+        // unsafe methods are only replayed when the route opts in, since the
+        // replay repeats their side effects
+        let replay = route
+            .compare
+            .as_ref()
+            .filter(|compare| compare.replay_unsafe_methods || parts.method.is_safe())
+            .filter(|compare| {
+                compare.percent >= 100.0
+                    || (compare.percent > 0.0 && rand::random::<f64>() * 100.0 < compare.percent)
+            })
+            .map(|compare| {
+                // Upstream credentials stay with the route's upstream
+                let mut headers = builder.headers_ref().cloned().unwrap_or_default();
+                for name in [
+                    header::HOST,
+                    header::AUTHORIZATION,
+                    header::PROXY_AUTHORIZATION,
+                    header::COOKIE,
+                ] {
+                    headers.remove(name);
+                }
+                let injected = route
+                    .headers
+                    .keys()
+                    .map(String::as_str)
+                    .chain(api_key_selector.map(|selector| selector.header_name.as_str()))
+                    .chain(route.client_key_header.as_ref().map(HeaderName::as_str));
+                for name in injected {
+                    headers.remove(name);
+                }
+                ComparedRequest {
+                    method: parts.method.clone(),
+                    // Without a query parameter carrying a pool key
+                    uri: format!(
+                        "{}{}",
+                        compare.target.trim_end_matches('/'),
+                        request_target(&base_url)
+                    ),
+                    headers,
+                    body: body_bytes.clone(),
+                }
+            });
+
+        let boxed_body = http_body_util::Full::new(body_bytes)
+            .map_err(|e| match e {})
+            .boxed();
+
+        let new_req = builder
+            .body(boxed_body)
+            .map_err(|e| self.fail(method, path, start, ProxyError::InvalidRequest(e)))?;
+
+        phase.set(TimeoutPhase::Upstream);
+
+        // Wait for a concurrency permit if the route is limited; the permit is
+        // held until the upstream response has been read
+        let queue_start = Instant::now();
+        let permit =
+            match &route.concurrency_limit {
+                Some(semaphore) => {
+                    Some(semaphore.clone().acquire_owned().await.map_err(|_| {
+                        self.fail(method, path, start, ProxyError::ConcurrencyLimit)
+                    })?)
+                }
+                None => None,
+            };
+        self.metrics
+            .record_queue_wait(route.label(), queue_start.elapsed());
+
+        // Reject without reaching the upstream while the circuit is open
+        let breaker_call = match &route.circuit_breaker {
+            Some(breaker) => match breaker.try_call() {
+                Some(call) => Some(call),
+                None => return Err(self.fail(method, path, start, ProxyError::CircuitOpen)),
+            },
+            None => None,
+        };
+
+        // Send request
+        let upstream_start = Instant::now();
+        let result = self.client_for(route).request(new_req).await;
+        let upstream_latency = upstream_start.elapsed();
+        // Failed calls are settled now; others once the body has been read
+        // or handed on, so a stalled body counts as a failure
+        let mut breaker_call = breaker_call;
+        if !matches!(&result, Ok(r) if !r.status().is_server_error()) {
+            settle(breaker_call.take(), false, upstream_latency);
+        }
+        let response =
+            result.map_err(|e| self.fail(method, path, start, ProxyError::Upstream(e)))?;
+
+        let status = response.status().as_u16();
+        self.metrics.record_request_sampled(
+            method,
+            path,
+            status,
+            start.elapsed(),
+            &route.latency_sampling,
+        );
+
+        // Record API key usage if a pool key was used
+        // This is recorded after successful proxy to ensure we only count
+        // requests that were successfully forwarded to the target
+        if let Some(key) = pool_key {
+            let route_name = route.name.as_deref().unwrap_or(path);
+            self.metrics.record_api_key_usage(key, route_name);
+        }
+
+        let (mut parts, body) = response.into_parts();
+        strip_response_headers(
+            &mut parts.headers,
+            self.forwarding
+                .strip_response_headers
+                .iter()
+                .chain(&route.strip_response_headers),
+        );
+
+        // Refuse bodies announced as too large before reading them
+        let max_bytes = self.limits.max_response_bytes;
+        let content_length = parts
+            .headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        if let Some(limit) = max_bytes.filter(|&limit| content_length > Some(limit as u64)) {
+            settle(breaker_call, true, upstream_latency);
+            return Err(self.response_too_large(route, limit));
+        }
+
+        // Streamed bodies are passed through as they arrive
+        let meter = |limit| ResponseMeter {
+            limit,
+            route: route.label().to_string(),
+            metrics: self.metrics.clone(),
+        };
+        if let Some(idle_timeout) = route.stream_idle_timeout {
+            settle(breaker_call, true, upstream_latency);
+            if let Some((capture, request)) = &captured {
+                self.write_capture(route, capture, request, &parts, None)
+                    .await;
+            }
+            if let Some(replay) = replay {
+                self.spawn_comparison(route, replay, parts.status, None);
+            }
+            let body = streamed_body(body, Some(idle_timeout), meter(max_bytes), permit);
+            return Ok(Response::from_parts(parts, body));
+        }
+
+        // Large bodies are streamed too, unless the route needs them whole
+        let large = matches!(
+            (content_length, self.forwarding.buffer_threshold_bytes),
+            (Some(length), Some(threshold)) if length > threshold
+        );
+        if large && !route.buffers_responses() {
+            settle(breaker_call, true, upstream_latency);
+            if let Some(replay) = replay {
+                self.spawn_comparison(route, replay, parts.status, None);
+            }
+            return Ok(Response::from_parts(
+                parts,
+                streamed_body(body, None, meter(None), permit),
+            ));
+        }
+
+        // Convert response body
+        phase.set(TimeoutPhase::ResponseBody);
+        let collected = match max_bytes {
+            Some(limit) => http_body_util::Limited::new(body, limit).collect().await,
+            None => http_body_util::BodyExt::collect(body)
+                .await
+                .map_err(Into::into),
+        };
+        let too_large = matches!(&collected, Err(e) if e.is::<http_body_util::LengthLimitError>());
+        settle(
+            breaker_call,
+            collected.is_ok() || too_large,
+            upstream_latency,
+        );
+        let body_bytes = match collected {
+            Ok(collected) => collected.to_bytes(),
+            Err(_) if too_large => {
+                return Err(self.response_too_large(route, max_bytes.unwrap_or_default()));
+            }
+            Err(e) => return Err(ProxyError::UpstreamBody(e)),
+        };
+        set_buffered_framing(&mut parts.headers, body_bytes.len(), false);
+        if let Some((capture, request)) = &captured {
+            self.write_capture(route, capture, request, &parts, Some(&body_bytes))
+                .await;
+        }
+        if let Some(replay) = replay {
+            self.spawn_comparison(route, replay, parts.status, Some(body_bytes.clone()));
+        }
+        let response = Response::from_parts(parts, Body::from(body_bytes));
+
+        Ok(response)
+    }
+
+    /// Replay a request to the route's comparison upstream in the background,
+    /// counting a mismatch when its status (or, with `compare_body`, its
+    /// body) differs from the response the client got
+    ///
+    /// `body` is `None` for streamed responses, which only have their status
+    /// compared. A failed replay counts as a mismatch. Replays beyond
+    /// [`MAX_COMPARISONS_IN_FLIGHT`] are dropped.
+    fn spawn_comparison(
+        &self,
+        route: &ProxyRoute,
+        replay: ComparedRequest,
+        status: StatusCode,
+        body: Option<bytes::Bytes>,
+    ) {
+        let Ok(permit) = self.comparisons.clone().try_acquire_owned() else {
+            debug!(
+                "Route '{}' comparison skipped: too many comparisons in flight",
+                route.label()
+            );
+            return;
+        };
+        let client = self.client_for(route);
+        let metrics = self.metrics.clone();
+        let route_label = route.label().to_string();
+        let compare_body = route
+            .compare
+            .as_ref()
+            .is_some_and(|compare| compare.compare_body);
+        let timeout = route.timeout.unwrap_or(DEFAULT_COMPARE_TIMEOUT);
+        tokio::spawn(async move {
+            let _permit = permit;
+            let uri = replay.uri.clone();
+            let compared = async {
+                let mut builder = Request::builder().method(replay.method).uri(&replay.uri);
+                if let Some(headers) = builder.headers_mut() {
+                    *headers = replay.headers;
+                }
+                let request = builder.body(
+                    http_body_util::Full::new(replay.body)
+                        .map_err(|e| match e {})
+                        .boxed(),
+                )?;
+                let response = client.request(request).await?;
+                let other_status = response.status();
+                let other_body = if body.is_some() && compare_body {
+                    Some(response.into_body().collect().await?.to_bytes())
+                } else {
+                    None
+                };
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>((other_status, other_body))
+            };
+            let result = tokio::time::timeout(timeout, compared)
+                .await
+                .unwrap_or_else(|_| Err("timed out".into()));
+
+            metrics.record_shadow_comparison(&route_label);
+            let mismatch = match result {
+                Ok((other_status, _)) if other_status != status => {
+                    warn!(
+                        "Route '{}' comparison status differs: {} from the upstream, {} from {}",
+                        route_label, status, other_status, uri
+                    );
+                    true
+                }
+                Ok((_, Some(other_body))) if Some(&other_body) != body.as_ref() => {
+                    warn!(
+                        "Route '{}' comparison body differs from {}",
+                        route_label, uri
+                    );
+                    true
+                }
+                Ok(_) => false,
+                Err(e) => {
+                    warn!(
+                        "Route '{}' comparison request to {} failed: {}",
+                        route_label, uri, e
+                    );
+                    true
+                }
+            };
+            if mismatch {
+                metrics.record_shadow_mismatch(&route_label);
+            }
+        });
+    }
+
+    /// Write a captured request and its response, logging failures
+    async fn write_capture(
+        &self,
+        route: &ProxyRoute,
+        capture: &Capture,
+        request: &CapturedRequest,
+        response: &axum::http::response::Parts,
+        body: Option<&[u8]>,
+    ) {
+        let written = capture
+            .write(
+                route.label(),
+                request,
+                response.status,
+                &response.headers,
+                body,
+            )
+            .await;
+        match written {
+            Ok(path) => debug!(
+                "Captured request to route '{}' in {}",
+                route.label(),
+                path.display()
+            ),
+            Err(e) => warn!(
+                "Failed to write capture for route '{}': {}",
+                route.label(),
+                e
+            ),
+        }
+    }
+
+    /// Open idle connections to the upstreams of routes with
+    /// `prewarm_connections`
+    ///
+    /// Each route's target gets that many concurrent `HEAD` requests through
+    /// the route's client, whose pool then keeps the connections for the
+    /// next requests. Returns the number of requests that got a response.
+    pub async fn prewarm(&self) -> usize {
+        let routes = self.get_routes();
+        let probes = routes
+            .iter()
+            .filter(|route| route.mock.is_none() && route.aggregate.is_empty())
+            .flat_map(|route| (0..route.prewarm_connections).map(move |_| route))
+            .map(|route| async move {
+                let body = http_body_util::Empty::new().map_err(|e| match e {}).boxed();
+                let req = Request::head(&route.target).body(body).ok()?;
+                let response = self.client_for(route).request(req).await;
+                match response {
+                    // Read the (empty) body so the connection returns to the pool
+                    Ok(response) => response.into_body().collect().await.ok().map(|_| ()),
+                    Err(e) => {
+                        debug!("Prewarming route '{}' failed: {}", route.label(), e);
+                        None
+                    }
+                }
+            });
+        futures::future::join_all(probes)
+            .await
+            .into_iter()
+            .flatten()
+            .count()
+    }
+
+    /// Count and log an upstream response over the size limit
+    fn response_too_large(&self, route: &ProxyRoute, limit: usize) -> ProxyError {
+        warn!(
+            "Response from route '{}' exceeds {} bytes",
+            route.label(),
+            limit
+        );
+        self.metrics.record_response_too_large(route.label());
+        ProxyError::ResponseTooLarge(limit)
+    }
+
+    /// Get a snapshot of the configured routes
+    pub fn get_routes(&self) -> Arc<Vec<ProxyRoute>> {
+        self.routes.load().routes().clone()
+    }
+
+    /// Replace the route table
+    ///
+    /// Requests already in flight keep using the table they started with.
+    /// Upstream pools first named by `routes` get their own client.
+    pub fn set_routes(&self, routes: Vec<ProxyRoute>) {
+        self.add_pool_clients(&routes);
+        self.routes
+            .store(Arc::new(RouteTable::with_config(routes, &self.routing)));
+    }
+}
+
+/// Prewarm upstream connections now and every [`PREWARM_INTERVAL`] until
+/// `shutdown_rx` turns `true`
+pub async fn run_prewarm(proxy: Arc<ProxyService>, mut shutdown_rx: watch::Receiver<bool>) {
+    let mut ticker = tokio::time::interval(PREWARM_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                let opened = proxy.prewarm().await;
+                debug!("Prewarmed {} upstream connection(s)", opened);
+            }
+            changed = shutdown_rx.changed() => {
+                if changed.is_err() || *shutdown_rx.borrow() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Replace `proxy`'s upstream clients every `lifetime` until `shutdown_rx`
+/// turns `true`, so no connection is reused for longer than `lifetime`
+///
+/// Connections opened between two replacements are closed once their
+/// requests finish, so every upstream reconnects periodically.
+pub async fn run_connection_recycle(
+    proxy: Arc<ProxyService>,
+    lifetime: Duration,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    let start = tokio::time::Instant::now() + lifetime;
+    let mut ticker = tokio::time::interval_at(start, lifetime);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                debug!("Recycling upstream connections older than {:?}", lifetime);
+                proxy.recycle_connections();
+            }
+            changed = shutdown_rx.changed() => {
+                if changed.is_err() || *shutdown_rx.borrow() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Record the outcome of a call the circuit breaker let through, if any
+fn settle(call: Option<BreakerCall<'_>>, success: bool, latency: Duration) {
+    if let Some(call) = call {
+        call.record(success, latency);
+    }
+}
+
+/// Byte count and size cap of a streamed upstream response
+struct ResponseMeter {
+    /// Most bytes the body may carry (unset = no limit)
+    limit: Option<usize>,
+    route: String,
+    metrics: Arc<dyn MetricsSink>,
+}
+
+/// Pass an upstream body through, failing it once no data arrives for
+/// `idle_timeout`, if set
+///
+/// Failing the body aborts the client response and drops the upstream
+/// connection. Chunks are counted in the route's response bytes as they
+/// pass through, and the body also fails once it exceeds the meter's limit.
+/// `permit` (the route's concurrency permit) is held until the body ends.
+fn streamed_body(
+    body: hyper::body::Incoming,
+    idle_timeout: Option<Duration>,
+    meter: ResponseMeter,
+    permit: Option<tokio::sync::OwnedSemaphorePermit>,
+) -> Body {
+    use futures::StreamExt;
+
+    let data = body.into_data_stream();
+    let state = Some((data, 0usize, meter, permit));
+    let stream = futures::stream::unfold(state, move |state| async move {
+        let (mut data, read, meter, permit) = state?;
+        let next = match idle_timeout {
+            Some(idle_timeout) => tokio::time::timeout(idle_timeout, data.next()).await,
+            None => Ok(data.next().await),
+        };
+        match next {
+            Ok(Some(Ok(chunk))) => {
+                let read = read + chunk.len();
+                if let Some(limit) = meter.limit.filter(|&limit| read > limit) {
+                    warn!(
+                        "Response from route '{}' exceeds {} bytes; closing the stream",
+                        meter.route, limit
+                    );
+                    meter.metrics.record_response_too_large(&meter.route);
+                    let err =
+                        std::io::Error::other(format!("upstream response exceeds {} bytes", limit));
+                    return Some((Err(err), None));
+                }
+                meter
+                    .metrics
+                    .record_response_bytes(&meter.route, chunk.len() as u64);
+                Some((Ok(chunk), Some((data, read, meter, permit))))
+            }
+            Ok(Some(Err(e))) => Some((Err(std::io::Error::other(e)), None)),
+            Ok(None) => None,
+            Err(_) => {
+                warn!(
+                    "No data from upstream for {:?}; closing the stream",
+                    idle_timeout.unwrap_or_default()
+                );
+                let err = std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    "upstream stream idle timeout",
+                );
+                Some((Err(err), None))
+            }
+        }
+    });
+    Body::from_stream(stream)
+}
+
+/// Request values substituted into route header templates
+struct HeaderContext<'a> {
+    path: &'a str,
+    method: &'a str,
+    host: &'a str,
+    route_name: &'a str,
+}
+
+impl<'a> HeaderContext<'a> {
+    fn new(
+        route: &'a ProxyRoute,
+        method: &'a Method,
+        uri: &'a Uri,
+        headers: &'a HeaderMap,
+    ) -> Self {
+        let host = headers
+            .get(header::HOST)
+            .and_then(|v| v.to_str().ok())
+            .or_else(|| uri.authority().map(|a| a.as_str()))
+            .unwrap_or("");
+        Self {
+            path: uri.path(),
+            method: method.as_str(),
+            host,
+            route_name: route.label(),
+        }
+    }
+
+    /// Expand a route header value; values without braces are used as-is
+    fn expand<'v>(&self, value: &'v str) -> std::borrow::Cow<'v, str> {
+        if !value.contains(['{', '}']) {
+            return std::borrow::Cow::Borrowed(value);
+        }
+        let expanded = expand_header_template(value, |token| match token {
+            "path" => self.path.to_string(),
+            "method" => self.method.to_string(),
+            "host" => self.host.to_string(),
+            "route_name" => self.route_name.to_string(),
+            "now_rfc3339" => chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            _ => String::new(),
+        });
+        // Templates are checked at load; a route built in code may still be invalid
+        std::borrow::Cow::Owned(expanded.unwrap_or_else(|_| value.to_string()))
+    }
+}
+
+/// Fetch one aggregate sub-request and parse its JSON body
+async fn fetch_json(
+    client: &HttpClient,
+    route: &ProxyRoute,
+    sub: &AggregateTarget,
+    request: &HeaderContext<'_>,
+) -> Result<serde_json::Value, String> {
+    let mut builder = Request::get(&sub.target);
+    for (key, value) in &route.headers {
+        builder = builder.header(key, request.expand(value).as_ref());
+    }
+    let body = http_body_util::Empty::new().map_err(|e| match e {}).boxed();
+    let req = builder.body(body).map_err(|e| e.to_string())?;
+
+    let response = client.request(req).await.map_err(|e| e.to_string())?;
+    let status = response.status();
+    let bytes = response
+        .into_body()
+        .collect()
+        .await
+        .map_err(|e| e.to_string())?
+        .to_bytes();
+    if !status.is_success() {
+        return Err(format!("upstream returned {}", status));
+    }
+    serde_json::from_slice(&bytes).map_err(|e| format!("invalid JSON response: {}", e))
+}
+
+/// Remove hop-by-hop and unwanted headers from an upstream response
+///
+/// Drops the standard hop-by-hop headers, any header named in the response's
+/// own `Connection` header, and the given `strip` names (case-insensitive).
+pub(crate) fn strip_response_headers<'a>(
+    headers: &mut axum::http::HeaderMap,
+    strip: impl Iterator<Item = &'a String>,
+) {
+    let mut remove: Vec<String> = headers
+        .get_all(axum::http::header::CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect();
+    remove.extend(strip.map(|name| name.to_lowercase()));
+    remove.extend(
+        headers
+            .keys()
+            .map(|name| name.as_str())
+            .filter(|name| *name != "host" && is_hop_by_hop_header(name))
+            .map(str::to_string),
+    );
+
+    for name in remove {
+        headers.remove(name.as_str());
+    }
+}
+
+/// Check a request body against a route's body schema
+///
+/// Empty bodies aren't checked. Others must be JSON (`application/json` or a
+/// `+json` type) and at most `max_bytes` long.
+fn check_body_schema(
+    schema: &Validator,
+    max_bytes: usize,
+    content_type: Option<&str>,
+    body: &[u8],
+) -> Result<(), ProxyError> {
+    if body.is_empty() {
+        return Ok(());
+    }
+    if body.len() > max_bytes {
+        return Err(ProxyError::BodyTooLarge);
+    }
+    let media_type = content_type
+        .and_then(|v| v.split(';').next())
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    if media_type != "application/json" && !media_type.ends_with("+json") {
+        return Err(ProxyError::UnsupportedMediaType);
+    }
+    let value: serde_json::Value = serde_json::from_slice(body)
+        .map_err(|e| ProxyError::InvalidBody(vec![format!("/: invalid JSON: {}", e)]))?;
+    let errors: Vec<String> = schema
+        .iter_errors(&value)
+        .take(MAX_SCHEMA_ERRORS)
+        .map(|e| {
+            let location = e.instance_path().as_str();
+            let location = if location.is_empty() { "/" } else { location };
+            format!("{}: {}", location, e)
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ProxyError::InvalidBody(errors))
+    }
+}
+
+/// Read and compile a route's `body_schema` file
+pub fn load_body_schema(path: &Path) -> anyhow::Result<Validator> {
+    let contents = std::fs::read(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read schema {}: {}", path.display(), e))?;
+    let schema: serde_json::Value = serde_json::from_slice(&contents)
+        .map_err(|e| anyhow::anyhow!("Schema {} is not valid JSON: {}", path.display(), e))?;
+    jsonschema::validator_for(&schema)
+        .map_err(|e| anyhow::anyhow!("Schema {} is invalid: {}", path.display(), e))
+}
+
+/// Check if a header is a hop-by-hop header that should not be forwarded.
+///
+/// Note: While RFC 7230 doesn't classify "host" as a hop-by-hop header,
+/// we include it here because the proxy must replace the Host header with
+/// the target server's host for HTTPS targets to work correctly.
+/// The Host header will be explicitly set from the target URL after filtering.
+fn is_hop_by_hop_header(name: &str) -> bool {
+    matches!(
+        name.to_lowercase().as_str(),
+        "connection"
+            | "keep-alive"
+            | "proxy-authenticate"
+            | "proxy-authorization"
+            | "te"
+            | "trailers"
+            | "transfer-encoding"
+            | "upgrade"
+            | "host"
+    )
+}
+
+/// Fix the framing headers of a response whose body has been buffered
+///
+/// The upstream may have used `Transfer-Encoding: chunked`; once the body is
+/// buffered (and possibly transformed) the response must carry a single
+/// `Content-Length` matching the new body instead. When `decoded` is true the
+/// body no longer has the upstream content coding, so `Content-Encoding` is
+/// removed as well.
+pub(crate) fn set_buffered_framing(
+    headers: &mut axum::http::HeaderMap,
+    body_len: usize,
+    decoded: bool,
+) {
+    headers.remove(axum::http::header::TRANSFER_ENCODING);
+    if decoded {
+        headers.remove(axum::http::header::CONTENT_ENCODING);
+    }
+    headers.insert(
+        axum::http::header::CONTENT_LENGTH,
+        axum::http::header::HeaderValue::from(body_len),
+    );
+}
+
+/// Extract host and optional port from a URL string
+fn extract_host_from_url(url: &str) -> Option<String> {
+    // Parse the URL to extract host
+    if let Ok(parsed) = url.parse::<axum::http::Uri>() {
+        if let Some(authority) = parsed.authority() {
+            return Some(authority.to_string());
+        }
+    }
+    None
+}
+
+/// A request kept for replay to a route's comparison upstream
+struct ComparedRequest {
+    method: Method,
+    /// Comparison target with the path and query sent upstream
+    uri: String,
+    headers: HeaderMap,
+    body: bytes::Bytes,
+}
+
+/// Path and query of a URL string, as sent in the upstream request line
+fn request_target(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.find(['/', '?']).map_or("", |start| &rest[start..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit_breaker::CircuitState;
+    use crate::metrics::GatewayMetrics;
+
+    fn create_test_route() -> ProxyRoute {
+        ProxyRoute {
+            name: None,
+            path_pattern: "/api/*".to_string(),
+            target: "http://localhost:8081".to_string(),
+            strip_prefix: true,
+            empty_path: None,
+            methods: vec![],
+            api_key_selector: None,
+            api_key_pool: None,
+            client_key_header: None,
+            headers: HashMap::new(),
+            header_to_query: Vec::new(),
+            query_to_header: Vec::new(),
+            forward_headers: Vec::new(),
+            match_query: Vec::new(),
+            description: Some("Test route".to_string()),
+            upstream_pool: None,
+            concurrency_limit: None,
+            timeout: None,
+            stream_idle_timeout: None,
+            max_uri_bytes: None,
+            circuit_breaker: None,
+            cache: None,
+            strip_response_headers: vec![],
+            aggregate: vec![],
+            title_case_headers: false,
+            tls_sni: None,
+            accept_content_types: vec![],
+            decompress_request: None,
+            body_schema: None,
+            body_schema_max_bytes: 1024 * 1024,
+            capture: None,
+            compare: None,
+            idempotency_key: None,
+            mock: None,
+            priority: 0,
+            prewarm_connections: 0,
+            latency_sampling: LatencySampling::default(),
+            group: DEFAULT_ROUTE_GROUP.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_route_matching() {
+        let route = create_test_route();
+
+        assert!(route.matches("/api/users", "GET", None));
+        assert!(route.matches("/api/users/1", "POST", None));
+        assert!(route.matches("/api", "GET", None));
+        assert!(!route.matches("/other/path", "GET", None));
+    }
+
+    #[test]
+    fn test_root_route_matching() {
+        let route = |pattern: &str, strip_prefix: bool| ProxyRoute {
+            name: Some(pattern.to_string()),
+            path_pattern: pattern.to_string(),
+            strip_prefix,
+            ..create_test_route()
+        };
+
+        // `/` matches only the root
+        let root = route("/", false);
+        assert!(root.matches("/", "GET", None));
+        assert!(!root.matches("/users", "GET", None));
+        assert!(!root.matches("/api/users", "GET", None));
+        assert_eq!(root.get_target_url("/", None), "http://localhost:8081/");
+
+        // `/*` matches every path, including the root
+        let catch_all = route("/*", false);
+        assert!(catch_all.matches("/", "GET", None));
+        assert!(catch_all.matches("/users", "GET", None));
+        assert!(catch_all.matches("/api/users", "GET", None));
+
+        // Stripping the prefix leaves the paths as they were
+        let root = route("/", true);
+        assert_eq!(root.stripped_prefix(), None);
+        assert_eq!(
+            root.get_target_url("/", Some("a=1")),
+            "http://localhost:8081/?a=1"
+        );
+        let catch_all = route("/*", true);
+        assert_eq!(
+            catch_all.get_target_url("/", None),
+            "http://localhost:8081/"
+        );
+        assert_eq!(
+            catch_all.get_target_url("/api/users", None),
+            "http://localhost:8081/api/users"
+        );
+
+        // The root route wins the root, the catch-all everything else
+        let table = RouteTable::new(vec![route("/", false), route("/*", false)]);
+        assert_eq!(table.find("/", "GET", None).unwrap().label(), "/");
+        assert_eq!(table.find("/users", "GET", None).unwrap().label(), "/*");
+        let most_specific = RoutingConfig {
+            match_mode: MatchMode::MostSpecific,
+            ..Default::default()
+        };
+        let table =
+            RouteTable::with_config(vec![route("/*", false), route("/", false)], &most_specific);
+        assert_eq!(table.find("/", "GET", None).unwrap().label(), "/");
+        assert_eq!(table.find("/users", "GET", None).unwrap().label(), "/*");
+    }
+
+    #[test]
+    fn test_method_filtering() {
+        let route = ProxyRoute {
+            methods: vec!["GET".to_string(), "POST".to_string()],
+            ..create_test_route()
+        };
+
+        assert!(route.matches("/api/users", "GET", None));
+        assert!(route.matches("/api/users", "POST", None));
+        assert!(!route.matches("/api/users", "DELETE", None));
+    }
+
+    #[test]
+    fn test_indexed_lookup_matches_linear_scan() {
+        let patterns = [
+            "/api/v2/*",
+            "/api/*",
+            "/api",
+            "/users/",
+            "/users/admin",
+            "/static/*",
+            "/*",
+            "/health",
+            "/",
+            "/api/v2/items",
+            "api/*",
+            "//double/*",
+        ];
+        let routes: Vec<ProxyRoute> = patterns
+            .iter()
+            .enumerate()
+            .map(|(i, pattern)| ProxyRoute {
+                name: Some(format!("route-{}", i)),
+                path_pattern: pattern.to_string(),
+                // Every third route only serves POST
+                methods: if i % 3 == 0 {
+                    vec!["POST".to_string()]
+                } else {
+                    vec![]
+                },
+                ..create_test_route()
+            })
+            .collect();
+        let paths = [
+            "/",
+            "",
+            "/api",
+            "/api/",
+            "/api/v2",
+            "/api/v2/items",
+            "/api/v2/items/1",
+            "/apiv2",
+            "/users",
+            "/users/",
+            "/users/admin",
+            "/users/admin/x",
+            "/static/app.js",
+            "/health",
+            "/healthz",
+            "/unknown/path",
+            "//double/x",
+            "api/x",
+        ];
+
+        // Also check without the catch-all routes, where misses are possible
+        for routes in [
+            routes.clone(),
+            routes
+                .iter()
+                .filter(|r| r.path_pattern != "/*" && r.path_pattern != "/")
+                .cloned()
+                .collect(),
+        ] {
+            let table = RouteTable::new(routes.clone());
+            for path in paths {
+                for method in ["GET", "POST"] {
+                    let linear = routes.iter().find(|r| r.matches(path, method, None));
+                    let indexed = table.find(path, method, None);
+                    assert_eq!(
+                        indexed.map(|r| r.label()),
+                        linear.map(|r| r.label()),
+                        "{} {}",
+                        method,
+                        path
+                    );
+                }
+                assert_eq!(
+                    table.path_matches(path, None),
+                    routes.iter().any(|r| r.path_matches(path)),
+                    "{}",
+                    path
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_target_url_with_strip_prefix() {
+        let route = create_test_route();
+
+        assert_eq!(
+            route.get_target_url("/api/users", None),
+            "http://localhost:8081/users"
+        );
+        assert_eq!(
+            route.get_target_url("/api/users/1", None),
+            "http://localhost:8081/users/1"
+        );
+        assert_eq!(
+            route.get_target_url("/api/users", Some("page=1")),
+            "http://localhost:8081/users?page=1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_empty_query_dropped() {
+        let route = create_test_route();
+        assert_eq!(
+            route.get_target_url("/api/users", Some("")),
+            "http://localhost:8081/users"
+        );
+        assert_eq!(
+            route.get_raw_target_url("/api/users", Some("")),
+            "http://localhost:8081/users"
+        );
+
+        // A request ending in a lone `?` is forwarded without it
+        let addr = spawn_echo_upstream().await;
+        for preserve_raw_path in [false, true] {
+            let proxy = ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new()))
+                .unwrap()
+                .with_routing(RoutingConfig {
+                    preserve_raw_path,
+                    ..Default::default()
+                });
+            let req = Request::builder()
+                .uri("/api/users?")
+                .body(Body::empty())
+                .unwrap();
+            let echoed = forward_json(&proxy, req).await;
+            assert_eq!(echoed["uri"], "/users");
+        }
+    }
+
+    #[test]
+    fn test_target_url_without_strip_prefix() {
+        let route = ProxyRoute {
+            strip_prefix: false,
+            ..create_test_route()
+        };
+
+        assert_eq!(
+            route.get_target_url("/api/users", None),
+            "http://localhost:8081/api/users"
+        );
+    }
+
+    #[test]
+    fn test_target_url_empty_path() {
+        let with = |empty_path: Option<&str>| ProxyRoute {
+            target: "http://localhost:8081/app/".to_string(),
+            empty_path: empty_path.map(str::to_string),
+            ..create_test_route()
+        };
+
+        for path in ["/api", "/api/"] {
+            assert_eq!(
+                with(None).get_target_url(path, None),
+                "http://localhost:8081/app/"
+            );
+            assert_eq!(
+                with(Some("/")).get_target_url(path, None),
+                "http://localhost:8081/app/"
+            );
+            assert_eq!(
+                with(Some("")).get_target_url(path, Some("q=1")),
+                "http://localhost:8081/app?q=1"
+            );
+            assert_eq!(
+                with(Some("/index")).get_raw_target_url(path, None),
+                "http://localhost:8081/app/index"
+            );
+        }
+
+        // Only an empty remainder is replaced
+        assert_eq!(
+            with(Some("/index")).get_target_url("/api/users", None),
+            "http://localhost:8081/app/users"
+        );
+    }
+
+    #[test]
+    fn test_raw_target_url_strips_decoded_prefix() {
+        let route = create_test_route();
+
+        assert_eq!(
+            route.get_raw_target_url("/api/a%2Fb", None),
+            "http://localhost:8081/a%2Fb"
+        );
+        assert_eq!(
+            route.get_raw_target_url("/%61pi/a%2Fb", Some("x=1")),
+            "http://localhost:8081/a%2Fb?x=1"
+        );
+    }
+
+    #[test]
+    fn test_decode_and_encode_path() {
+        assert_eq!(decode_path("/api/a%2Fb%20c"), "/api/a/b c");
+        assert_eq!(encode_path("/api/a/b c"), "/api/a/b%20c");
+        assert_eq!(encode_path("/api/100%"), "/api/100%25");
+    }
+
+    #[test]
+    fn test_proxy_error_status_and_label() {
+        let invalid_request = Request::builder().uri("not a uri").body(()).unwrap_err();
+        let cases = [
+            (ProxyError::NoRoute, StatusCode::NOT_FOUND, "no_route"),
+            (
+                ProxyError::MethodNotAllowed,
+                StatusCode::METHOD_NOT_ALLOWED,
+                "method_not_allowed",
+            ),
+            (
+                ProxyError::UnsupportedMediaType,
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_media_type",
+            ),
+            (
+                ProxyError::Timeout(TimeoutPhase::RequestBody),
+                StatusCode::GATEWAY_TIMEOUT,
+                "timeout_request_body",
+            ),
+            (
+                ProxyError::Timeout(TimeoutPhase::Upstream),
+                StatusCode::GATEWAY_TIMEOUT,
+                "timeout_upstream",
+            ),
+            (
+                ProxyError::Timeout(TimeoutPhase::ResponseBody),
+                StatusCode::GATEWAY_TIMEOUT,
+                "timeout_response_body",
+            ),
+            (
+                ProxyError::DeadlineExceeded,
+                StatusCode::GATEWAY_TIMEOUT,
+                "deadline_exceeded",
+            ),
+            (
+                ProxyError::RequestBody(axum::Error::new(std::io::Error::other("reset"))),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "request_body",
+            ),
+            (
+                ProxyError::BodyTooLarge,
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "body_too_large",
+            ),
+            (
+                ProxyError::RequestDecoding(DecompressError::Invalid {
+                    coding: "gzip".to_string(),
+                    source: std::io::Error::other("corrupt"),
+                }),
+                StatusCode::BAD_REQUEST,
+                "request_decoding",
+            ),
+            (
+                ProxyError::InvalidRequest(invalid_request),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "invalid_request",
+            ),
+            (
+                ProxyError::ExpectationFailed,
+                StatusCode::EXPECTATION_FAILED,
+                "expectation_failed",
+            ),
+            (
+                ProxyError::CircuitOpen,
+                StatusCode::SERVICE_UNAVAILABLE,
+                "circuit_open",
+            ),
+            (
+                ProxyError::ConcurrencyLimit,
+                StatusCode::SERVICE_UNAVAILABLE,
+                "concurrency_limit",
+            ),
+            (
+                ProxyError::UpstreamBody("truncated".into()),
+                StatusCode::BAD_GATEWAY,
+                "upstream_body",
+            ),
+            (
+                ProxyError::UriTooLong(8192),
+                StatusCode::URI_TOO_LONG,
+                "uri_too_long",
+            ),
+            (
+                ProxyError::ResponseTooLarge(1024),
+                StatusCode::BAD_GATEWAY,
+                "response_too_large",
+            ),
+            (
+                ProxyError::InvalidBody(vec!["/: expected object, got array".to_string()]),
+                StatusCode::BAD_REQUEST,
+                "invalid_body",
+            ),
+            (
+                ProxyError::TokenUnavailable(anyhow::anyhow!("token endpoint down")),
+                StatusCode::SERVICE_UNAVAILABLE,
+                "token_unavailable",
+            ),
+        ];
+
+        for (err, status, label) in cases {
+            assert_eq!(err.status_code(), status, "{:?}", err);
+            assert_eq!(err.reason_label(), label);
+            assert_eq!(err.into_response().status(), status);
+        }
+    }
+
+    #[test]
+    fn test_extract_host_from_url() {
+        // HTTP URL without port
+        assert_eq!(
+            extract_host_from_url("http://example.com/path"),
+            Some("example.com".to_string())
+        );
+
+        // HTTP URL with port
+        assert_eq!(
+            extract_host_from_url("http://localhost:8080/path"),
+            Some("localhost:8080".to_string())
+        );
+
+        // HTTPS URL without port
+        assert_eq!(
+            extract_host_from_url("https://api.example.com/v1/users"),
+            Some("api.example.com".to_string())
+        );
+
+        // HTTPS URL with port
+        assert_eq!(
+            extract_host_from_url("https://api.example.com:443/v1/users"),
+            Some("api.example.com:443".to_string())
+        );
+
+        // Relative path (no authority)
+        assert_eq!(extract_host_from_url("/just/a/path"), None);
+    }
+
+    /// Start an upstream that echoes the received request as JSON
+    async fn spawn_echo_upstream() -> std::net::SocketAddr {
+        async fn echo(req: Request<Body>) -> axum::Json<serde_json::Value> {
+            let headers: serde_json::Map<String, serde_json::Value> = req
+                .headers()
+                .iter()
+                .map(|(k, v)| {
+                    (
+                        k.as_str().to_string(),
+                        serde_json::Value::String(v.to_str().unwrap_or("").to_string()),
+                    )
+                })
+                .collect();
+            let method = req.method().to_string();
+            let uri = req.uri().to_string();
+            let body = axum::body::to_bytes(req.into_body(), usize::MAX)
+                .await
+                .unwrap_or_default();
+            axum::Json(serde_json::json!({
+                "method": method,
+                "uri": uri,
+                "headers": headers,
+                "body": String::from_utf8_lossy(&body),
+            }))
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = axum::Router::new().fallback(echo);
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        addr
+    }
+
+    /// Forward a request and parse the echoed upstream request
+    async fn forward_json(proxy: &ProxyService, req: Request<Body>) -> serde_json::Value {
+        let response = proxy.forward(req).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn route_to(addr: std::net::SocketAddr) -> ProxyRoute {
+        ProxyRoute {
+            target: format!("http://{}", addr),
+            ..create_test_route()
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_route_swap_under_concurrent_traffic() {
+        let addr = spawn_echo_upstream().await;
+        // Each generation tags its route and its API key; a request routed
+        // by one table but keyed by another would mix the two
+        let table = move |generation: u32| {
+            let config = crate::GatewayConfig::parse(&format!(
+                r#"
+[[routes]]
+path = "/api/*"
+target = "http://{addr}"
+api_key_pool = "default"
+headers = {{ X-Generation = "{generation}" }}
+
+[[routes]]
+path = "/api/gen{generation}/*"
+target = "http://{addr}"
+api_key_pool = "default"
+headers = {{ X-Generation = "{generation}" }}
+
+[api_key_pools.default]
+header_name = "X-Api-Key"
+keys = [{{ key = "key-{generation}" }}]
+"#
+            ))
+            .unwrap();
+            let selectors = config
+                .api_key_pools
+                .iter()
+                .map(|(name, pool)| (name.clone(), crate::api_key::create_selector(pool)))
+                .collect();
+            ProxyService::routes_from_config(&config.routes, &selectors).unwrap()
+        };
+        let proxy = Arc::new(ProxyService::new(table(1), Arc::new(GatewayMetrics::new())).unwrap());
+
+        let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let swapper = {
+            let proxy = proxy.clone();
+            let done = done.clone();
+            tokio::spawn(async move {
+                let mut generation = 1;
+                while !done.load(std::sync::atomic::Ordering::SeqCst) {
+                    generation = 3 - generation;
+                    proxy.set_routes(table(generation));
+                    tokio::task::yield_now().await;
+                }
+            })
+        };
+
+        let clients: Vec<_> = (0..8)
+            .map(|client| {
+                let proxy = proxy.clone();
+                tokio::spawn(async move {
+                    let mut seen = std::collections::HashSet::new();
+                    for i in 0..50 {
+                        let uri = format!("/api/gen{}/items/{}", 1 + (client + i) % 2, i);
+                        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+                        let json = forward_json(&proxy, req).await;
+                        let generation = json["headers"]["x-generation"].as_str().unwrap();
+                        let key = json["headers"]["x-api-key"].as_str().unwrap();
+                        assert_eq!(key, format!("key-{}", generation), "torn table");
+                        seen.insert(generation.to_string());
+                    }
+                    seen
+                })
+            })
+            .collect();
+
+        let mut seen = std::collections::HashSet::new();
+        for client in clients {
+            seen.extend(client.await.unwrap());
+        }
+        done.store(true, std::sync::atomic::Ordering::SeqCst);
+        swapper.await.unwrap();
+        // Both tables served traffic while being swapped
+        assert_eq!(seen.len(), 2, "{:?}", seen);
+    }
+
+    #[tokio::test]
+    async fn test_case_insensitive_paths() {
+        let addr = spawn_echo_upstream().await;
+        let get = |uri: &str| {
+            Request::builder()
+                .uri(uri.to_string())
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let proxy = ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_routing(RoutingConfig {
+                case_insensitive_paths: true,
+                ..Default::default()
+            });
+        // The prefix is stripped, the rest of the path keeps its case
+        let echoed = forward_json(&proxy, get("/API/Users?Page=1")).await;
+        assert_eq!(echoed["uri"], "/Users?Page=1");
+        let echoed = forward_json(&proxy, get("/api/users")).await;
+        assert_eq!(echoed["uri"], "/users");
+
+        let proxy =
+            ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new())).unwrap();
+        let err = proxy.forward(get("/API/Users")).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_route_table_case_insensitive() {
+        let routes = vec![ProxyRoute {
+            path_pattern: "/Users/*".to_string(),
+            methods: vec!["GET".to_string()],
+            ..create_test_route()
+        }];
+        let routing = RoutingConfig {
+            case_insensitive_paths: true,
+            ..Default::default()
+        };
+        let table = RouteTable::with_config(routes.clone(), &routing);
+        assert!(table.find("/users/1", "GET", None).is_some());
+        assert!(table.find("/USERS", "GET", None).is_some());
+        assert!(table.find("/users/1", "POST", None).is_none());
+        assert!(table.path_matches("/uSeRs/1", None));
+
+        let table = RouteTable::new(routes);
+        assert!(table.find("/users/1", "GET", None).is_none());
+        assert!(table.find("/Users/1", "GET", None).is_some());
+    }
+
+    #[test]
+    fn test_route_table_match_modes() {
+        let route = |pattern: &str, priority: i32| ProxyRoute {
+            name: Some(pattern.to_string()),
+            path_pattern: pattern.to_string(),
+            priority,
+            ..create_test_route()
+        };
+        let routes = vec![
+            route("/api/*", 0),
+            route("/api/v1/*", 0),
+            route("/api/v1/users", 0),
+            route("/*", 0),
+        ];
+        fn found(table: &RouteTable, path: &str) -> String {
+            table.find(path, "GET", None).unwrap().label().to_string()
+        }
+
+        let table = RouteTable::new(routes.clone());
+        assert_eq!(found(&table, "/api/v1/users/1"), "/api/*");
+
+        let most_specific = RoutingConfig {
+            match_mode: MatchMode::MostSpecific,
+            ..Default::default()
+        };
+        let table = RouteTable::with_config(routes.clone(), &most_specific);
+        assert_eq!(found(&table, "/api/v1/users/1"), "/api/v1/users");
+        assert_eq!(found(&table, "/api/v1/items"), "/api/v1/*");
+        assert_eq!(found(&table, "/api/v2"), "/api/*");
+        assert_eq!(found(&table, "/other"), "/*");
+
+        // An explicit priority outranks a longer prefix
+        let mut routes = routes;
+        routes[0].priority = 1;
+        let table = RouteTable::with_config(routes, &most_specific);
+        assert_eq!(found(&table, "/api/v1/users/1"), "/api/*");
+
+        // Error mode rejects overlaps at load, so the first match is used
+        let error = RoutingConfig {
+            match_mode: MatchMode::Error,
+            ..Default::default()
+        };
+        let table = RouteTable::with_config(vec![route("/users/*", 0)], &error);
+        assert_eq!(found(&table, "/users/1"), "/users/*");
+    }
+
+    #[tokio::test]
+    async fn test_encoded_slash_preserved_when_enabled() {
+        let addr = spawn_echo_upstream().await;
+        let proxy = ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_routing(RoutingConfig {
+                preserve_raw_path: true,
+                ..Default::default()
+            });
+
+        let req = Request::builder()
+            .uri("/api/files/a%2Fb?x=1")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(echoed["uri"], "/files/a%2Fb?x=1");
+    }
+
+    #[test]
+    fn test_collapse_slashes() {
+        assert_eq!(collapse_slashes("/api//v1///users"), "/api/v1/users");
+        assert_eq!(collapse_slashes("//"), "/");
+        assert_eq!(collapse_slashes("/api/v1/"), "/api/v1/");
+    }
+
+    #[tokio::test]
+    async fn test_repeated_slashes_collapsed() {
+        let addr = spawn_echo_upstream().await;
+        let proxy = ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_routing(RoutingConfig {
+                collapse_slashes: CollapseSlashes::Collapse,
+                ..Default::default()
+            });
+
+        let req = Request::builder()
+            .uri("//api//v1///users?x=1")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(echoed["uri"], "/v1/users?x=1");
+    }
+
+    #[tokio::test]
+    async fn test_repeated_slashes_redirected() {
+        let addr = spawn_echo_upstream().await;
+        let proxy = ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_routing(RoutingConfig {
+                collapse_slashes: CollapseSlashes::Redirect,
+                ..Default::default()
+            });
+
+        let req = Request::builder()
+            .uri("/api//v1///users?x=1")
+            .body(Body::empty())
+            .unwrap();
+        let response = proxy.forward(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()["location"], "/api/v1/users?x=1");
+
+        // Canonical paths are forwarded as usual
+        let req = Request::builder()
+            .uri("/api/v1/users")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(echoed["uri"], "/v1/users");
+    }
+
+    #[test]
+    fn test_strip_base_path() {
+        assert_eq!(
+            strip_base_path("/gateway/api/x", "/gateway"),
+            Some("/api/x")
+        );
+        assert_eq!(strip_base_path("/gateway", "/gateway"), Some("/"));
+        assert_eq!(strip_base_path("/gateway/", "/gateway"), Some("/"));
+        assert_eq!(strip_base_path("/gatewayx/api", "/gateway"), None);
+        assert_eq!(strip_base_path("/api/x", "/gateway"), None);
+    }
+
+    #[tokio::test]
+    async fn test_base_path_stripped_before_matching() {
+        let addr = spawn_echo_upstream().await;
+        let proxy = ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_routing(RoutingConfig {
+                base_path: "/gateway".to_string(),
+                ..Default::default()
+            });
+
+        let req = Request::builder()
+            .uri("/gateway/api/users/1?x=1")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(echoed["uri"], "/users/1?x=1");
+
+        // Paths outside the base path are not routed
+        let req = Request::builder()
+            .uri("/api/users/1")
+            .body(Body::empty())
+            .unwrap();
+        let err = proxy.forward(req).await.unwrap_err();
+        assert!(matches!(err, ProxyError::NoRoute));
+    }
+
+    #[tokio::test]
+    async fn test_repeated_slashes_kept_by_default() {
+        let addr = spawn_echo_upstream().await;
+        let proxy =
+            ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new())).unwrap();
+
+        let req = Request::builder()
+            .uri("/api//v1/users")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(echoed["uri"], "//v1/users");
+    }
+
+    #[tokio::test]
+    async fn test_encoded_slash_preserved_by_default() {
+        let addr = spawn_echo_upstream().await;
+        let proxy =
+            ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new())).unwrap();
+
+        let req = Request::builder()
+            .uri("/api/files/a%2Fb")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(echoed["uri"], "/files/a%2Fb");
+
+        // Decoding is opt-in
+        let proxy = proxy.with_routing(RoutingConfig {
+            preserve_raw_path: false,
+            ..Default::default()
+        });
+        let req = Request::builder()
+            .uri("/api/files/a%2Fb")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(echoed["uri"], "/files/a/b");
+    }
+
+    #[tokio::test]
+    async fn test_encoded_traversal_stays_below_stripped_prefix() {
+        let addr = spawn_echo_upstream().await;
+        let proxy =
+            ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new())).unwrap();
+
+        // Encoded dot segments reach the upstream encoded, as a single
+        // segment, instead of `/../../admin`
+        for uri in ["/api/..%2f..%2fadmin", "/api/%2e%2e%2f%2e%2e%2fadmin"] {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let echoed = forward_json(&proxy, req).await;
+            assert_eq!(echoed["uri"], uri.strip_prefix("/api").unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_encoded_path_matches_decoded_route() {
+        let addr = spawn_echo_upstream().await;
+        let proxy = ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_routing(RoutingConfig {
+                preserve_raw_path: true,
+                ..Default::default()
+            });
+
+        let req = Request::builder()
+            .uri("/%61pi/a%2Fb")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(echoed["uri"], "/a%2Fb");
+    }
+
+    #[tokio::test]
+    async fn test_route_errors() {
+        let route = ProxyRoute {
+            methods: vec!["GET".to_string()],
+            ..create_test_route()
+        };
+        let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new())).unwrap();
+
+        let req = Request::builder()
+            .uri("/other")
+            .body(Body::empty())
+            .unwrap();
+        let err = proxy.forward(req).await.unwrap_err();
+        assert!(matches!(err, ProxyError::NoRoute));
+
+        let req = Request::builder()
+            .method("DELETE")
+            .uri("/api/users")
+            .body(Body::empty())
+            .unwrap();
+        let err = proxy.forward(req).await.unwrap_err();
+        assert!(matches!(err, ProxyError::MethodNotAllowed));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_upstream_error() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let proxy =
+            ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new())).unwrap();
+        let req = Request::builder()
+            .uri("/api/users")
+            .body(Body::empty())
+            .unwrap();
+        let err = proxy.forward(req).await.unwrap_err();
+        assert!(matches!(err, ProxyError::Upstream(_)));
+        assert_eq!(err.status_code(), StatusCode::BAD_GATEWAY);
+        assert_eq!(err.reason_label(), "upstream");
+    }
+
+    #[tokio::test]
+    async fn test_debug_headers() {
+        let addr = spawn_echo_upstream().await;
+        let proxy = ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_debug_headers(DebugHeadersConfig { enabled: true });
+
+        let req = Request::builder()
+            .uri("/api/users")
+            .body(Body::empty())
+            .unwrap();
+        let response = proxy.forward(req).await.unwrap();
+        let duration: f64 = response.headers()[GATEWAY_DURATION_HEADER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(duration > 0.0);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let echoed: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let received_at = echoed["headers"][GATEWAY_RECEIVED_AT_HEADER]
+            .as_str()
+            .unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(received_at).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_debug_headers_disabled_by_default() {
+        let addr = spawn_echo_upstream().await;
+        let proxy =
+            ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new())).unwrap();
+
+        let req = Request::builder()
+            .uri("/api/users")
+            .body(Body::empty())
+            .unwrap();
+        let response = proxy.forward(req).await.unwrap();
+        assert!(response.headers().get(GATEWAY_DURATION_HEADER).is_none());
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let echoed: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(echoed["headers"].get(GATEWAY_RECEIVED_AT_HEADER).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_client_cert_headers_forwarded() {
+        let addr = spawn_echo_upstream().await;
+        let proxy = ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_forwarding(ForwardingConfig {
+                client_cert_headers: true,
+                ..Default::default()
+            });
+
+        let mut req = Request::builder()
+            .uri("/api/users")
+            .header(CLIENT_CERT_SUBJECT_HEADER, "CN=spoofed")
+            .header(CLIENT_CERT_SERIAL_HEADER, "deadbeef")
+            .body(Body::empty())
+            .unwrap();
+        req.extensions_mut().insert(ClientCertInfo {
+            subject: "CN=client.example.com,O=Example".to_string(),
+            serial: "01a2b3".to_string(),
+        });
+
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(
+            echoed["headers"][CLIENT_CERT_SUBJECT_HEADER],
+            "CN=client.example.com,O=Example"
+        );
+        assert_eq!(echoed["headers"][CLIENT_CERT_SERIAL_HEADER], "01a2b3");
+
+        // Without a verified certificate, spoofed headers are removed
+        let req = Request::builder()
+            .uri("/api/users")
+            .header(CLIENT_CERT_SUBJECT_HEADER, "CN=spoofed")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert!(echoed["headers"].get(CLIENT_CERT_SUBJECT_HEADER).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_prefix_header() {
+        let addr = spawn_echo_upstream().await;
+        let proxy = ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_forwarding(ForwardingConfig {
+                forwarded_prefix: true,
+                ..Default::default()
+            });
+
+        let req = Request::builder()
+            .uri("/api/users?page=2")
+            .header("x-forwarded-prefix", "/spoofed")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(echoed["uri"], "/users?page=2");
+        assert_eq!(echoed["headers"]["x-forwarded-prefix"], "/api");
+
+        // The header name is configurable and includes the base path
+        let proxy = ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_forwarding(ForwardingConfig {
+                forwarded_prefix: true,
+                forwarded_prefix_header: "X-Public-Prefix".to_string(),
+                ..Default::default()
+            })
+            .with_routing(RoutingConfig {
+                base_path: "/gw".to_string(),
+                ..Default::default()
+            });
+        let req = Request::builder()
+            .uri("/gw/api/users")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(echoed["uri"], "/users");
+        assert_eq!(echoed["headers"]["x-public-prefix"], "/gw/api");
+    }
+
+    /// Start an upstream that answers every connection with a raw HTTP response
+    async fn spawn_raw_upstream(response: &'static str) -> std::net::SocketAddr {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 8192];
+                    let mut read = 0;
+                    while !buf[..read].windows(4).any(|w| w == b"\r\n\r\n") {
+                        match socket.read(&mut buf[read..]).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => read += n,
+                        }
+                    }
+                    let _ = socket.write_all(response.as_bytes()).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+        addr
+    }
+
+    /// Spawn an upstream that never answers
+    ///
+    /// Sends on `received` once a request has arrived and on `closed` once
+    /// the gateway closed the upstream connection.
+    async fn spawn_hanging_upstream() -> (
+        std::net::SocketAddr,
+        tokio::sync::oneshot::Receiver<()>,
+        tokio::sync::oneshot::Receiver<()>,
+    ) {
+        use tokio::io::AsyncReadExt;
+
+        let (received_tx, received_rx) = tokio::sync::oneshot::channel();
+        let (closed_tx, closed_rx) = tokio::sync::oneshot::channel();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 8192];
+            let mut received_tx = Some(received_tx);
+            loop {
+                match socket.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {
+                        if let Some(tx) = received_tx.take() {
+                            let _ = tx.send(());
+                        }
+                    }
+                }
+            }
+            let _ = closed_tx.send(());
+        });
+        (addr, received_rx, closed_rx)
+    }
+
+    /// Serve `proxy` over HTTP like the gateway's fallback handler
+    async fn spawn_gateway(proxy: Arc<ProxyService>) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = axum::Router::new().fallback(move |req: Request<Body>| {
+            let proxy = proxy.clone();
+            async move { proxy.forward(req).await.into_response() }
+        });
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        addr
+    }
+
+    /// Read from `socket` until the received bytes contain `needle`
+    async fn read_until(socket: &mut tokio::net::TcpStream, needle: &str) -> String {
+        use tokio::io::AsyncReadExt;
+
+        let mut received = Vec::new();
+        let mut buf = [0u8; 4096];
+        while !String::from_utf8_lossy(&received).contains(needle) {
+            let n = tokio::time::timeout(Duration::from_secs(5), socket.read(&mut buf))
+                .await
+                .unwrap_or_else(|_| {
+                    panic!(
+                        "timed out waiting for {:?}, got {:?}",
+                        needle,
+                        String::from_utf8_lossy(&received)
+                    )
+                })
+                .unwrap();
+            assert!(n > 0, "connection closed before {:?}", needle);
+            received.extend_from_slice(&buf[..n]);
+        }
+        String::from_utf8_lossy(&received).into_owned()
+    }
+
+    #[tokio::test]
+    async fn test_expect_continue_upload() {
+        use tokio::io::AsyncWriteExt;
+
+        let upstream = spawn_echo_upstream().await;
+        let proxy = Arc::new(
+            ProxyService::new(vec![route_to(upstream)], Arc::new(GatewayMetrics::new())).unwrap(),
+        );
+        let gateway = spawn_gateway(proxy).await;
+
+        // The client holds the body back until it sees 100 Continue
+        let mut client = tokio::net::TcpStream::connect(gateway).await.unwrap();
+        client
+            .write_all(
+                b"POST /api/upload HTTP/1.1\r\nHost: gateway\r\nContent-Length: 5\r\nExpect: 100-continue\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        read_until(&mut client, "HTTP/1.1 100 Continue\r\n\r\n").await;
+
+        client.write_all(b"hello").await.unwrap();
+        let response = read_until(&mut client, "}").await;
+        assert!(response.contains("HTTP/1.1 200 OK"));
+        assert!(response.contains(r#""body":"hello""#));
+        // The expectation is met by the gateway and not forwarded
+        assert!(!response.contains("100-continue"));
+    }
+
+    #[tokio::test]
+    async fn test_expect_continue_rejected() {
+        use tokio::io::AsyncWriteExt;
+
+        let upstream = spawn_echo_upstream().await;
+        let proxy = ProxyService::new(vec![route_to(upstream)], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_forwarding(ForwardingConfig {
+                expect_continue: ExpectContinue::Reject,
+                ..Default::default()
+            });
+        let gateway = spawn_gateway(Arc::new(proxy)).await;
+
+        let mut client = tokio::net::TcpStream::connect(gateway).await.unwrap();
+        client
+            .write_all(
+                b"POST /api/upload HTTP/1.1\r\nHost: gateway\r\nContent-Length: 5\r\nExpect: 100-continue\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let response = read_until(&mut client, "\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 417"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn test_client_disconnect_aborts_upstream_call() {
+        use tokio::io::AsyncWriteExt;
+
+        let (upstream, received, closed) = spawn_hanging_upstream().await;
+        let metrics = Arc::new(GatewayMetrics::new());
+        let proxy = Arc::new(ProxyService::new(vec![route_to(upstream)], metrics.clone()).unwrap());
+
+        let gateway = spawn_gateway(proxy).await;
+
+        let mut client = tokio::net::TcpStream::connect(gateway).await.unwrap();
+        client
+            .write_all(b"GET /api/slow HTTP/1.1\r\nHost: gateway\r\n\r\n")
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), received)
+            .await
+            .expect("request did not reach the upstream")
+            .unwrap();
+
+        drop(client);
+        tokio::time::timeout(Duration::from_secs(5), closed)
+            .await
+            .expect("upstream call was not aborted")
+            .unwrap();
+
+        let route = route_to(upstream);
+        for _ in 0..50 {
+            if metrics.client_cancelled(route.label()) == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(metrics.client_cancelled(route.label()), 1);
+        assert_eq!(metrics.total_errors(), 1);
+    }
+
+    /// Spawn an upstream that hands the raw request head to the test
+    async fn spawn_capturing_upstream(
+    ) -> (std::net::SocketAddr, tokio::sync::mpsc::Receiver<String>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (tx, rx) = tokio::sync::mpsc::channel(4);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 8192];
+                    let mut read = 0;
+                    while !buf[..read].windows(4).any(|w| w == b"\r\n\r\n") {
+                        match socket.read(&mut buf[read..]).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => read += n,
+                        }
+                    }
+                    let _ = tx
+                        .send(String::from_utf8_lossy(&buf[..read]).into_owned())
+                        .await;
+                    let _ = socket
+                        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                        .await;
+                });
+            }
+        });
+        (addr, rx)
+    }
+
+    #[tokio::test]
+    async fn test_route_group_metrics_label() {
+        let addr = spawn_echo_upstream().await;
+        let metrics = Arc::new(GatewayMetrics::new());
+        let routes = vec![
+            ProxyRoute {
+                name: Some("pay".to_string()),
+                path_pattern: "/pay/*".to_string(),
+                group: "payments".to_string(),
+                ..route_to(addr)
+            },
+            ProxyRoute {
+                name: Some("api".to_string()),
+                ..route_to(addr)
+            },
+        ];
+        let proxy = ProxyService::new(routes, metrics.clone()).unwrap();
+
+        for uri in ["/pay/1", "/api/1"] {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            proxy.forward(req).await.unwrap();
+        }
+
+        let output = metrics.prometheus_output().unwrap();
+        assert!(output.contains(
+            r#"gateway_route_requests_total{group="payments",route="pay",status="200"} 1"#
+        ));
+        assert!(output.contains(
+            r#"gateway_route_requests_total{group="default",route="api",status="200"} 1"#
+        ));
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_body_bytes_metrics() {
+        let addr = spawn_raw_upstream("HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello").await;
+        let metrics = Arc::new(GatewayMetrics::new());
+        let proxy = ProxyService::new(
+            vec![ProxyRoute {
+                name: Some("items".to_string()),
+                ..route_to(addr)
+            }],
+            metrics.clone(),
+        )
+        .unwrap();
+
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/items")
+            .body(Body::from("0123456789"))
+            .unwrap();
+        let response = proxy.forward(req).await.unwrap();
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        assert_eq!(metrics.request_bytes("items"), 10);
+        assert_eq!(metrics.response_bytes("items"), 5);
+    }
+
+    #[tokio::test]
+    async fn test_chunked_response_bytes_counted() {
+        let addr = spawn_raw_upstream(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n",
+        )
+        .await;
+        let metrics = Arc::new(GatewayMetrics::new());
+        let route = |name: &str, stream_idle_timeout: Option<Duration>| ProxyRoute {
+            name: Some(name.to_string()),
+            path_pattern: format!("/{}/*", name),
+            stream_idle_timeout,
+            ..route_to(addr)
+        };
+        let proxy = ProxyService::new(
+            vec![
+                route("buffered", None),
+                route("streamed", Some(Duration::from_secs(5))),
+            ],
+            metrics.clone(),
+        )
+        .unwrap();
+
+        for name in ["buffered", "streamed"] {
+            let req = Request::builder()
+                .uri(format!("/{}/items", name))
+                .body(Body::empty())
+                .unwrap();
+            let response = proxy.forward(req).await.unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(&body[..], b"hello world");
+            assert_eq!(metrics.response_bytes(name), 11, "{}", name);
+        }
+    }
+
+    /// Layer collecting the fields recorded on `proxy` spans
+    #[derive(Clone, Default)]
+    struct ProxySpanFields(Arc<std::sync::Mutex<HashMap<String, String>>>);
+
+    impl tracing::field::Visit for ProxySpanFields {
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl<S> tracing_subscriber::Layer<S> for ProxySpanFields
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_record(
+            &self,
+            id: &tracing::span::Id,
+            values: &tracing::span::Record<'_>,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            if ctx.span(id).is_some_and(|span| span.name() == "proxy") {
+                values.record(&mut self.clone());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_proxy_span_records_request_fields() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let fields = ProxySpanFields::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(fields.clone()));
+
+        let addr = spawn_echo_upstream().await;
+        let pool = crate::config::ApiKeyPool {
+            keys: vec![crate::config::ApiKeyConfig {
+                key: "secret-key".to_string(),
+                weight: 1,
+                enabled: true,
+            }],
+            strategy: Default::default(),
+            header_name: "X-Api-Key".to_string(),
+            query_param_name: None,
+            oauth2: None,
+        };
+        let route = ProxyRoute {
+            name: Some("users".to_string()),
+            api_key_selector: Some(crate::api_key::create_selector(&pool)),
+            api_key_pool: Some("upstream-keys".to_string()),
+            ..route_to(addr)
+        };
+        let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new())).unwrap();
+        let req = Request::builder()
+            .uri("/api/users")
+            .body(Body::empty())
+            .unwrap();
+        proxy.forward(req).await.unwrap();
+
+        let fields = fields.0.lock().unwrap().clone();
+        assert_eq!(fields["route"], "users");
+        assert_eq!(fields["upstream_host"], addr.to_string());
+        assert_eq!(fields["key_pool"], "upstream-keys");
+        assert_eq!(fields["status"], "200");
+        assert!(fields["latency_ms"].parse::<u64>().is_ok());
+        assert!(!fields.values().any(|v| v.contains("secret-key")));
+    }
+
+    #[tokio::test]
+    async fn test_forward_headers_allow_list() {
+        let addr = spawn_echo_upstream().await;
+        let pool = crate::config::ApiKeyPool {
+            keys: vec![crate::config::ApiKeyConfig {
+                key: "pool-key".to_string(),
+                weight: 1,
+                enabled: true,
+            }],
+            strategy: Default::default(),
+            header_name: "X-Api-Key".to_string(),
+            query_param_name: None,
+            oauth2: None,
+        };
+        let route = ProxyRoute {
+            forward_headers: vec![HeaderName::from_static("x-trace-id")],
+            headers: HashMap::from([("X-Gateway".to_string(), "yes".to_string())]),
+            api_key_selector: Some(crate::api_key::create_selector(&pool)),
+            ..route_to(addr)
+        };
+        let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new())).unwrap();
+        let req = Request::builder()
+            .uri("/api/users")
+            .header("X-Trace-Id", "trace-1")
+            .header("X-Internal-Token", "do-not-leak")
+            .header("Cookie", "session=1")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        let headers = echoed["headers"].as_object().unwrap();
+
+        // Allowed client headers and the gateway's own reach the upstream
+        assert_eq!(headers["x-trace-id"], "trace-1");
+        assert_eq!(headers["x-gateway"], "yes");
+        assert_eq!(headers["x-api-key"], "pool-key");
+        assert_eq!(headers["host"], addr.to_string());
+        assert!(!headers.contains_key("x-internal-token"));
+        assert!(!headers.contains_key("cookie"));
+    }
+
+    #[tokio::test]
+    async fn test_forward_headers_keep_body_headers() {
+        let addr = spawn_echo_upstream().await;
+        let route = ProxyRoute {
+            forward_headers: vec![HeaderName::from_static("x-trace-id")],
+            ..route_to(addr)
+        };
+        let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new())).unwrap();
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/users")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::CONTENT_LENGTH, "13")
+            .header("X-Internal-Token", "do-not-leak")
+            .body(Body::from(r#"{"name":"a"}"#.to_string() + " "))
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        let headers = echoed["headers"].as_object().unwrap();
+
+        assert_eq!(echoed["method"], "POST");
+        assert_eq!(echoed["body"], "{\"name\":\"a\"} ");
+        assert_eq!(headers["content-type"], "application/json");
+        assert_eq!(headers["content-length"], "13");
+        assert!(!headers.contains_key("x-internal-token"));
+    }
+
+    #[tokio::test]
+    async fn test_api_key_strategy_param() {
+        let addr = spawn_echo_upstream().await;
+        let key = |name: &str, weight: u32| crate::config::ApiKeyConfig {
+            key: name.to_string(),
+            weight,
+            enabled: true,
+        };
+        // Round-robin alternates; weighted selection never picks "key-b"
+        let pool = crate::config::ApiKeyPool {
+            keys: vec![key("key-a", 1), key("key-b", 0)],
+            strategy: Default::default(),
+            header_name: "X-Api-Key".to_string(),
+            query_param_name: None,
+            oauth2: None,
+        };
+        let proxy = |api_key_strategy_param: bool| {
+            let route = ProxyRoute {
+                api_key_selector: Some(crate::api_key::create_selector(&pool)),
+                ..route_to(addr)
+            };
+            ProxyService::new(vec![route], Arc::new(GatewayMetrics::new()))
+                .unwrap()
+                .with_routing(RoutingConfig {
+                    api_key_strategy_param,
+                    ..Default::default()
+                })
+        };
+        let request = || {
+            Request::builder()
+                .uri("/api/users?api_key_strategy=weight&page=1")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let forced = proxy(true);
+        for _ in 0..4 {
+            let echoed = forward_json(&forced, request()).await;
+            assert_eq!(echoed["headers"]["x-api-key"], "key-a");
+            assert_eq!(echoed["uri"], "/users?page=1");
+        }
+
+        // Without the flag the parameter is just forwarded
+        let unforced = proxy(false);
+        let mut keys = Vec::new();
+        for _ in 0..2 {
+            let echoed = forward_json(&unforced, request()).await;
+            assert_eq!(echoed["uri"], "/users?api_key_strategy=weight&page=1");
+            keys.push(echoed["headers"]["x-api-key"].as_str().unwrap().to_string());
+        }
+        assert_eq!(keys, ["key-a", "key-b"]);
+
+        // A forced round-robin keeps cycling across requests
+        let mut keys = Vec::new();
+        for _ in 0..4 {
+            let req = Request::builder()
+                .uri("/api/users?api_key_strategy=round_robin")
+                .body(Body::empty())
+                .unwrap();
+            let echoed = forward_json(&forced, req).await;
+            keys.push(echoed["headers"]["x-api-key"].as_str().unwrap().to_string());
+        }
+        assert_eq!(keys, ["key-a", "key-b", "key-a", "key-b"]);
+    }
+
+    #[test]
+    fn test_take_query_param() {
+        assert_eq!(
+            take_query_param(
+                Some("a=1&api_key_strategy=random&b=2"),
+                "api_key_strategy",
+                false
+            ),
+            (Some("a=1&b=2".to_string()), Some("random".to_string()))
+        );
+        assert_eq!(
+            take_query_param(Some("api_key_strategy=weight"), "api_key_strategy", false),
+            (None, Some("weight".to_string()))
+        );
+        assert_eq!(
+            take_query_param(Some("a=1;b=2"), "api_key_strategy", true),
+            (Some("a=1;b=2".to_string()), None)
+        );
+        assert_eq!(
+            take_query_param(None, "api_key_strategy", false),
+            (None, None)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_key_header_skips_pool() {
+        let addr = spawn_echo_upstream().await;
+        let config = crate::GatewayConfig::parse(&format!(
+            r#"
+[[routes]]
+path = "/api/*"
+target = "http://{addr}"
+api_key_pool = "default"
+client_key_header = "X-Api-Key"
+
+[[routes]]
+path = "/bearer/*"
+target = "http://{addr}"
+api_key_pool = "default"
+client_key_header = "Authorization"
+
+[api_key_pools.default]
+header_name = "X-Api-Key"
+keys = [{{ key = "pool-key-1" }}]
+"#
+        ))
+        .unwrap();
+        let selectors = config
+            .api_key_pools
+            .iter()
+            .map(|(name, pool)| (name.clone(), crate::api_key::create_selector(pool)))
+            .collect();
+        let metrics = Arc::new(GatewayMetrics::new());
+        let proxy = ProxyService::new(
+            ProxyService::routes_from_config(&config.routes, &selectors).unwrap(),
+            metrics.clone(),
+        )
+        .unwrap();
+        let send = |uri: &str, header: Option<(&str, &str)>| {
+            let mut req = Request::builder().uri(uri.to_string());
+            if let Some((name, value)) = header {
+                req = req.header(name, value);
+            }
+            forward_json(&proxy, req.body(Body::empty()).unwrap())
+        };
+
+        // The client's own key is forwarded as is
+        let echoed = send("/api/users", Some(("X-Api-Key", "client-own-key"))).await;
+        assert_eq!(echoed["headers"]["x-api-key"], "client-own-key");
+        let echoed = send("/bearer/users", Some(("Authorization", "Bearer client"))).await;
+        assert_eq!(echoed["headers"]["authorization"], "Bearer client");
+        assert!(echoed["headers"].get("x-api-key").is_none());
+        assert_eq!(metrics.api_key_usage("pool-key-1"), 0);
+
+        // Without it, a pool key is injected
+        let echoed = send("/api/users", None).await;
+        assert_eq!(echoed["headers"]["x-api-key"], "pool-key-1");
+        let echoed = send("/bearer/users", None).await;
+        assert_eq!(echoed["headers"]["x-api-key"], "pool-key-1");
+    }
+
+    #[tokio::test]
+    async fn test_oauth2_pool_injects_bearer_token() {
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let token_calls = calls.clone();
+        let app = axum::Router::new().route(
+            "/token",
+            axum::routing::post(move || {
+                token_calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                async {
+                    axum::Json(serde_json::json!({"access_token": "at-123", "expires_in": 3600}))
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let token_addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let addr = spawn_echo_upstream().await;
+        let route = |token_url: String| {
+            let pool = crate::config::ApiKeyPool {
+                oauth2: Some(crate::config::OAuth2Config {
+                    token_url,
+                    client_id: "gateway".to_string(),
+                    client_secret: "s3cret".to_string(),
+                    scope: None,
+                }),
+                header_name: "Authorization".to_string(),
+                ..Default::default()
+            };
+            ProxyRoute {
+                api_key_selector: Some(crate::api_key::create_selector(&pool)),
+                api_key_pool: Some("oauth".to_string()),
+                ..route_to(addr)
+            }
+        };
+        let get = || {
+            Request::builder()
+                .uri("/api/users")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let metrics = Arc::new(GatewayMetrics::new());
+        let proxy = ProxyService::new(
+            vec![route(format!("http://{}/token", token_addr))],
+            metrics.clone(),
+        )
+        .unwrap();
+        for _ in 0..2 {
+            let echoed = forward_json(&proxy, get()).await;
+            assert_eq!(echoed["headers"]["authorization"], "Bearer at-123");
+        }
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        // Access tokens aren't counted as key usage
+        assert_eq!(metrics.api_key_usage("Bearer at-123"), 0);
+        assert!(!metrics
+            .prometheus_output()
+            .unwrap()
+            .contains("gateway_api_key_usage_total{"));
+
+        // Without a token the request isn't forwarded
+        let unreachable = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let proxy = ProxyService::new(
+            vec![route(format!("http://{}/token", unreachable))],
+            Arc::new(GatewayMetrics::new()),
+        )
+        .unwrap();
+        let err = proxy.forward(get()).await.unwrap_err();
+        assert!(matches!(err, ProxyError::TokenUnavailable(_)));
+        assert_eq!(err.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    /// Layer counting access log events
+    #[derive(Clone, Default)]
+    struct AccessLogEvents(Arc<std::sync::atomic::AtomicUsize>);
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for AccessLogEvents {
+        fn on_event(
+            &self,
+            event: &tracing::Event<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            struct Message(bool);
+            impl tracing::field::Visit for Message {
+                fn record_debug(
+                    &mut self,
+                    field: &tracing::field::Field,
+                    value: &dyn std::fmt::Debug,
+                ) {
+                    if field.name() == "message" {
+                        self.0 = format!("{:?}", value) == "Request completed";
+                    }
+                }
+            }
+            let mut message = Message(false);
+            event.record(&mut message);
+            if message.0 {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_access_log_sampling() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let events = AccessLogEvents::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(events.clone()));
+        let route = ProxyRoute {
+            mock: Some(Arc::new(MockResponse::from_config(&MockConfig {
+                status: 200,
+                headers: HashMap::new(),
+                body: Some("ok".to_string()),
+                body_file: None,
+            }))),
+            ..create_test_route()
+        };
+        let get = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let logged = || events.0.load(std::sync::atomic::Ordering::SeqCst);
+
+        // Rate 0: only errors are logged
+        let proxy = ProxyService::new(vec![route.clone()], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_access_log(AccessLogConfig { sample_rate: 0.0 });
+        for _ in 0..10 {
+            proxy.forward(get("/api/users")).await.unwrap();
+        }
+        assert_eq!(logged(), 0);
+        proxy.forward(get("/missing")).await.unwrap_err();
+        assert_eq!(logged(), 1);
+
+        // Rate 1: everything is logged
+        let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_access_log(AccessLogConfig { sample_rate: 1.0 });
+        for _ in 0..10 {
+            proxy.forward(get("/api/users")).await.unwrap();
+        }
+        proxy.forward(get("/missing")).await.unwrap_err();
+        assert_eq!(logged(), 12);
+    }
+
+    #[tokio::test]
+    async fn test_header_to_query_mapping() {
+        let addr = spawn_echo_upstream().await;
+        let proxy = ProxyService::new(
+            vec![ProxyRoute {
+                header_to_query: vec![(
+                    "x-api-version".parse().unwrap(),
+                    "api-version".to_string(),
+                )],
+                ..route_to(addr)
+            }],
+            Arc::new(GatewayMetrics::new()),
+        )
+        .unwrap();
+
+        let req = Request::builder()
+            .uri("/api/users?page=2&api-version=old")
+            .header("X-Api-Version", "2024-01 beta")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(
+            echoed["uri"],
+            "/users?page=2&api%2Dversion=2024%2D01%20beta"
+        );
+        assert!(echoed["headers"].get("x-api-version").is_none());
+
+        // Without the header the query is forwarded as is
+        let req = Request::builder()
+            .uri("/api/users?page=2")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(echoed["uri"], "/users?page=2");
+    }
+
+    #[tokio::test]
+    async fn test_query_to_header_mapping() {
+        let addr = spawn_echo_upstream().await;
+        let proxy = ProxyService::new(
+            vec![ProxyRoute {
+                query_to_header: vec![("tenant".to_string(), "x-tenant".parse().unwrap())],
+                ..route_to(addr)
+            }],
+            Arc::new(GatewayMetrics::new()),
+        )
+        .unwrap();
+
+        let req = Request::builder()
+            .uri("/api/users?tenant=acme%20corp&page=2")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(echoed["uri"], "/users?page=2");
+        assert_eq!(echoed["headers"]["x-tenant"], "acme corp");
+
+        let req = Request::builder()
+            .uri("/api/users")
+            .header("X-Tenant", "from-client")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(echoed["uri"], "/users");
+        assert_eq!(echoed["headers"]["x-tenant"], "from-client");
+    }
+
+    #[test]
+    fn test_remap_query_preserves_other_params() {
+        let route = ProxyRoute {
+            query_to_header: vec![("tenant".to_string(), "x-tenant".parse().unwrap())],
+            ..create_test_route()
+        };
+        let remap = |query: &str, semicolons: bool| {
+            remap_query(&route, Some(query), &HeaderMap::new(), semicolons).0
+        };
+
+        // Repeated and encoded params (including an encoded `&`) are kept
+        // byte for byte, in order, around the removed one
+        assert_eq!(
+            remap("tag=a&tenant=acme&tag=b&q=x%26y&tag=a", false).as_deref(),
+            Some("tag=a&tag=b&q=x%26y&tag=a")
+        );
+        assert_eq!(
+            remap("q=caf%C3%A9+au+lait&&flag&tenant=acme&sort=-date&", false).as_deref(),
+            Some("q=caf%C3%A9+au+lait&&flag&sort=-date&")
+        );
+        assert_eq!(remap("tenant=acme&tenant=other", false), None);
+        // Without the removed param the query round-trips unchanged
+        assert_eq!(
+            remap("b=2&a=1&b=1;c=%3B", false).as_deref(),
+            Some("b=2&a=1&b=1;c=%3B")
+        );
+
+        // Legacy `;` separators only split params when enabled
+        assert_eq!(
+            remap("a=1;tenant=acme;b=2", false).as_deref(),
+            Some("a=1;tenant=acme;b=2")
+        );
+        assert_eq!(
+            remap("a=1;tenant=acme;b=2&c=3", true).as_deref(),
+            Some("a=1;b=2&c=3")
+        );
+        assert_eq!(
+            remap("tenant=acme;a=1&b=2", true).as_deref(),
+            Some("a=1&b=2")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decompress_request_body() {
+        let addr = spawn_echo_upstream().await;
+        let proxy = ProxyService::new(
+            vec![ProxyRoute {
+                decompress_request: Some(1024),
+                ..route_to(addr)
+            }],
+            Arc::new(GatewayMetrics::new()),
+        )
+        .unwrap();
+
+        let body = gzip(br#"{"name":"gateway"}"#);
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/items")
+            .header(header::CONTENT_ENCODING, "gzip")
+            .header(header::CONTENT_LENGTH, body.len())
+            .body(Body::from(body))
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(echoed["body"], r#"{"name":"gateway"}"#);
+        assert_eq!(echoed["headers"]["content-length"], "18");
+        assert!(echoed["headers"].get("content-encoding").is_none());
+
+        // A body that expands past the limit is rejected before forwarding
+        let bomb = gzip(&vec![b'a'; 64 * 1024]);
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/items")
+            .header(header::CONTENT_ENCODING, "gzip")
+            .body(Body::from(bomb))
+            .unwrap();
+        let err = proxy.forward(req).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_decompress_request_unsupported_and_invalid_bodies() {
+        let addr = spawn_echo_upstream().await;
+        let proxy = ProxyService::new(
+            vec![ProxyRoute {
+                decompress_request: Some(1024),
+                ..route_to(addr)
+            }],
+            Arc::new(GatewayMetrics::new()),
+        )
+        .unwrap();
+
+        // Codings the gateway can't decode reach the upstream untouched
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/items")
+            .header(header::CONTENT_ENCODING, "zstd")
+            .body(Body::from("raw"))
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(echoed["body"], "raw");
+        assert_eq!(echoed["headers"]["content-encoding"], "zstd");
+
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/items")
+            .header(header::CONTENT_ENCODING, "gzip")
+            .body(Body::from("not gzip"))
+            .unwrap();
+        let err = proxy.forward(req).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_accept_content_types() {
+        let addr = spawn_echo_upstream().await;
+        let metrics = Arc::new(GatewayMetrics::new());
+        let route = ProxyRoute {
+            name: Some("upload".to_string()),
+            accept_content_types: vec!["application/json".to_string()],
+            ..route_to(addr)
+        };
+        let proxy = ProxyService::new(vec![route], metrics.clone()).unwrap();
+
+        let post = |content_type: &str| {
+            Request::builder()
+                .method("POST")
+                .uri("/api/upload")
+                .header(header::CONTENT_TYPE, content_type)
+                .body(Body::from("{}"))
+                .unwrap()
+        };
+
+        let echoed = forward_json(&proxy, post("application/json; charset=utf-8")).await;
+        assert_eq!(echoed["body"], "{}");
+
+        let err = proxy.forward(post("text/plain")).await.unwrap_err();
+        assert!(matches!(err, ProxyError::UnsupportedMediaType));
+        assert!(metrics.prometheus_output().unwrap().contains(
+            r#"gateway_route_requests_total{group="default",route="upload",status="415"} 1"#
+        ));
+
+        // Requests without a body aren't checked
+        let req = Request::builder()
+            .uri("/api/upload")
+            .body(Body::empty())
+            .unwrap();
+        assert!(proxy.forward(req).await.is_ok());
+    }
+
+    #[test]
+    fn test_accepts_content_type() {
+        let route = ProxyRoute {
+            accept_content_types: vec!["application/json".to_string(), "text/*".to_string()],
+            ..create_test_route()
+        };
+        assert!(route.accepts_content_type("POST", Some("Application/JSON")));
+        assert!(route.accepts_content_type("PUT", Some("text/csv; charset=utf-8")));
+        assert!(!route.accepts_content_type("PATCH", Some("application/xml")));
+        assert!(!route.accepts_content_type("POST", None));
+        assert!(route.accepts_content_type("GET", None));
+        assert!(create_test_route().accepts_content_type("POST", None));
+    }
+
+    #[tokio::test]
+    async fn test_title_case_headers() {
+        let (addr, mut requests) = spawn_capturing_upstream().await;
+        let route = ProxyRoute {
+            title_case_headers: true,
+            headers: HashMap::from([("X-Legacy-Token".to_string(), "secret".to_string())]),
+            ..route_to(addr)
+        };
+        let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new())).unwrap();
+
+        let req = Request::builder()
+            .uri("/api/items")
+            .header("x-custom-header", "value")
+            .body(Body::empty())
+            .unwrap();
+        proxy.forward(req).await.unwrap();
+
+        let head = requests.recv().await.unwrap();
+        assert!(head.starts_with("GET /items HTTP/1.1\r\n"), "{}", head);
+        assert!(head.contains("\r\nX-Custom-Header: value\r\n"), "{}", head);
+        assert!(head.contains("\r\nX-Legacy-Token: secret\r\n"), "{}", head);
+        assert!(head.contains("\r\nHost: "), "{}", head);
+
+        // Without the option hyper sends lowercase names
+        let (addr, mut requests) = spawn_capturing_upstream().await;
+        let proxy =
+            ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new())).unwrap();
+        let req = Request::builder()
+            .uri("/api/items")
+            .header("X-Custom-Header", "value")
+            .body(Body::empty())
+            .unwrap();
+        proxy.forward(req).await.unwrap();
+        let head = requests.recv().await.unwrap();
+        assert!(head.contains("\r\nx-custom-header: value\r\n"), "{}", head);
+    }
+
+    fn head_request() -> Request<Body> {
+        Request::builder()
+            .method("HEAD")
+            .uri("/api/items")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_head_matches_get_route() {
+        let get_only = |addr| ProxyRoute {
+            methods: vec!["GET".to_string()],
+            ..route_to(addr)
+        };
+
+        let (addr, mut requests) = spawn_capturing_upstream().await;
+        let proxy =
+            ProxyService::new(vec![get_only(addr)], Arc::new(GatewayMetrics::new())).unwrap();
+        let response = proxy.forward(head_request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let head = requests.recv().await.unwrap();
+        assert!(head.starts_with("HEAD /items HTTP/1.1\r\n"), "{}", head);
+
+        // Forwarded as GET, the upstream body is dropped but its length kept
+        let addr = spawn_raw_upstream("HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello").await;
+        let proxy = ProxyService::new(vec![get_only(addr)], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_routing(RoutingConfig {
+                head_upstream_method: HeadUpstreamMethod::Get,
+                ..Default::default()
+            });
+        let response = proxy.forward(head_request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "5");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+
+        // Disabled, HEAD is just another method the route doesn't accept
+        let proxy = ProxyService::new(vec![get_only(addr)], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_routing(RoutingConfig {
+                head_implies_get: false,
+                ..Default::default()
+            });
+        let err = proxy.forward(head_request()).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn test_chunked_response_gets_content_length() {
+        let addr = spawn_raw_upstream(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nContent-Type: text/plain\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n",
+        )
+        .await;
+        let proxy =
+            ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new())).unwrap();
+
+        let req = Request::builder()
+            .uri("/api/stream")
+            .body(Body::empty())
+            .unwrap();
+        let response = proxy.forward(req).await.unwrap();
+
+        let headers = response.headers();
+        assert!(headers.get(axum::http::header::TRANSFER_ENCODING).is_none());
+        assert_eq!(
+            headers
+                .get_all(axum::http::header::CONTENT_LENGTH)
+                .iter()
+                .collect::<Vec<_>>(),
+            vec!["11"]
+        );
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"hello world");
+    }
+
+    #[tokio::test]
+    async fn test_response_headers_stripped() {
+        let addr = spawn_raw_upstream(
+            "HTTP/1.1 200 OK\r\nServer: internal/1.0\r\nX-Powered-By: php\r\nX-Debug: 1\r\nConnection: X-Internal-Trace, close\r\nX-Internal-Trace: abc\r\nKeep-Alive: timeout=5\r\nX-Kept: yes\r\nContent-Length: 2\r\n\r\nok",
+        )
+        .await;
+        let route = ProxyRoute {
+            strip_response_headers: vec!["x-debug".to_string()],
+            ..route_to(addr)
+        };
+        let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_forwarding(ForwardingConfig {
+                strip_response_headers: vec!["Server".to_string(), "X-Powered-By".to_string()],
+                ..Default::default()
+            });
+
+        let req = Request::builder()
+            .uri("/api/users")
+            .body(Body::empty())
+            .unwrap();
+        let response = proxy.forward(req).await.unwrap();
+
+        let headers = response.headers();
+        for name in [
+            "server",
+            "x-powered-by",
+            "x-debug",
+            "connection",
+            "x-internal-trace",
+            "keep-alive",
+        ] {
+            assert!(headers.get(name).is_none(), "{} was not stripped", name);
+        }
+        assert_eq!(headers.get("x-kept").unwrap(), "yes");
+    }
+
+    #[tokio::test]
+    async fn test_body_schema_validation() {
+        let addr = spawn_echo_upstream().await;
+        let schema = jsonschema::validator_for(&serde_json::json!({
+            "type": "object",
+            "required": ["name"],
+            "properties": { "name": { "type": "string" } }
+        }))
+        .unwrap();
+        let route = ProxyRoute {
+            name: Some("users".to_string()),
+            body_schema: Some(Arc::new(schema)),
+            body_schema_max_bytes: 64,
+            ..route_to(addr)
+        };
+        let metrics = Arc::new(GatewayMetrics::new());
+        let proxy = ProxyService::new(vec![route], metrics.clone()).unwrap();
+        let post = |content_type: &str, body: &'static str| {
+            Request::builder()
+                .method("POST")
+                .uri("/api/users")
+                .header(header::CONTENT_TYPE, content_type)
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let echoed = forward_json(&proxy, post("application/json", r#"{"name":"ada"}"#)).await;
+        assert_eq!(echoed["body"], r#"{"name":"ada"}"#);
+
+        let err = proxy
+            .forward(post("application/json; charset=utf-8", r#"{"name":1}"#))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            err.to_string(),
+            "Request body does not match the schema: /name: 1 is not of type \"string\""
+        );
+        let err = proxy
+            .forward(post("application/json", "{not json"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ProxyError::InvalidBody(_)));
+        assert_eq!(metrics.invalid_bodies("users"), 2);
+
+        // Bodies that can't be validated are refused as well
+        let err = proxy.forward(post("text/plain", "ada")).await.unwrap_err();
+        assert!(matches!(err, ProxyError::UnsupportedMediaType));
+        let err = proxy
+            .forward(post(
+                "application/json",
+                r#"{"name":"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"}"#,
+            ))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ProxyError::BodyTooLarge));
+    }
+
+    /// Sink recording the name of every method called on it
+    #[derive(Default)]
+    struct RecordingSink(std::sync::Mutex<Vec<&'static str>>);
+
+    impl RecordingSink {
+        fn calls(&self) -> Vec<&'static str> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl MetricsSink for RecordingSink {
+        fn record_request_sampled(
+            &self,
+            _method: &str,
+            _path: &str,
+            _status: u16,
+            _latency: Duration,
+            _sampling: &LatencySampling,
+        ) {
+            self.0.lock().unwrap().push("record_request_sampled");
+        }
+
+        fn record_route_request(&self, _route: &str, _group: &str, _status: u16) {
+            self.0.lock().unwrap().push("record_route_request");
+        }
+
+        fn record_api_key_usage(&self, _api_key: &str, _route: &str) {
+            self.0.lock().unwrap().push("record_api_key_usage");
+        }
+
+        fn record_request_bytes(&self, _route: &str, _bytes: u64) {
+            self.0.lock().unwrap().push("record_request_bytes");
+        }
+
+        fn record_method_rejected(&self, _method: &str, _route: &str) {
+            self.0.lock().unwrap().push("record_method_rejected");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forward_records_to_metrics_sink() {
+        let addr = spawn_echo_upstream().await;
+        let pool = crate::config::ApiKeyPool {
+            keys: vec![crate::config::ApiKeyConfig {
+                key: "pool-key".to_string(),
+                weight: 1,
+                enabled: true,
+            }],
+            strategy: Default::default(),
+            header_name: "X-Api-Key".to_string(),
+            query_param_name: None,
+            oauth2: None,
+        };
+        let route = ProxyRoute {
+            methods: vec!["POST".to_string()],
+            api_key_selector: Some(crate::api_key::create_selector(&pool)),
+            ..route_to(addr)
+        };
+        let sink = Arc::new(RecordingSink::default());
+        let proxy = ProxyService::new(vec![route], sink.clone()).unwrap();
+
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/users")
+            .body(Body::from("{}"))
+            .unwrap();
+        proxy.forward(req).await.unwrap();
+        let calls = sink.calls();
+        for expected in [
+            "record_request_bytes",
+            "record_request_sampled",
+            "record_api_key_usage",
+            "record_route_request",
+        ] {
+            assert!(calls.contains(&expected), "{} not in {:?}", expected, calls);
+        }
+
+        // Refused requests reach the sink too
+        let req = Request::builder()
+            .method("GET")
+            .uri("/api/users")
+            .body(Body::empty())
+            .unwrap();
+        proxy.forward(req).await.unwrap_err();
+        assert_eq!(
+            &sink.calls()[calls.len()..],
+            ["record_method_rejected", "record_request_sampled"]
+        );
+
+        // A no-op sink is enough to run a proxy
+        let proxy =
+            ProxyService::new(vec![route_to(addr)], Arc::new(crate::metrics::NoopMetrics)).unwrap();
+        let req = Request::builder()
+            .uri("/api/users")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(proxy.forward(req).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_method_counted() {
+        let metrics = Arc::new(GatewayMetrics::new());
+        let route = ProxyRoute {
+            name: Some("files".to_string()),
+            methods: vec!["GET".to_string(), "PUT".to_string()],
+            ..create_test_route()
+        };
+        let proxy = ProxyService::new(vec![route], metrics.clone()).unwrap();
+
+        let propfind = Method::from_bytes(b"PROPFIND").unwrap();
+        let req = Request::builder()
+            .method(propfind)
+            .uri("/api/files/report.txt")
+            .body(Body::empty())
+            .unwrap();
+        let err = proxy.forward(req).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(metrics.methods_rejected("PROPFIND", "files"), 1);
+
+        // Paths no route serves are not a method problem
+        let req = Request::builder()
+            .method("DELETE")
+            .uri("/other")
+            .body(Body::empty())
+            .unwrap();
+        let err = proxy.forward(req).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+        assert_eq!(metrics.methods_rejected("DELETE", "files"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_idempotency_key_generated_when_missing() {
+        let addr = spawn_echo_upstream().await;
+        let proxy = ProxyService::new(
+            vec![ProxyRoute {
+                idempotency_key: Some(HeaderName::from_static("idempotency-key")),
+                ..route_to(addr)
+            }],
+            Arc::new(GatewayMetrics::new()),
+        )
+        .unwrap();
+
+        // A client's own key is forwarded untouched
+        let req = Request::builder()
+            .uri("/api/orders")
+            .header("Idempotency-Key", "order-42")
+            .body(Body::empty())
+            .unwrap();
+        let echoed = forward_json(&proxy, req).await;
+        assert_eq!(echoed["headers"]["idempotency-key"], "order-42");
+
+        // Otherwise each request gets a fresh key
+        let mut keys = Vec::new();
+        for _ in 0..2 {
+            let req = Request::builder()
+                .uri("/api/orders")
+                .body(Body::empty())
+                .unwrap();
+            let echoed = forward_json(&proxy, req).await;
+            let key = echoed["headers"]["idempotency-key"]
+                .as_str()
+                .unwrap()
+                .to_string();
+            keys.push(key);
+        }
+        assert_ne!(keys[0], keys[1]);
+    }
+
+    #[tokio::test]
+    async fn test_compare_counts_mismatches() {
+        const OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        const KO: &str = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nko";
+        const ERROR: &str = "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 2\r\n\r\nok";
+        let primary = spawn_raw_upstream(OK).await;
+
+        // (comparison upstream, compare_body, mismatch expected)
+        for (upstream, compare_body, mismatch) in [
+            (OK, true, false),
+            (KO, false, false),
+            (KO, true, true),
+            (ERROR, false, true),
+        ] {
+            let other = spawn_raw_upstream(upstream).await;
+            let metrics = Arc::new(GatewayMetrics::new());
+            let route = ProxyRoute {
+                compare: Some(CompareConfig {
+                    target: format!("http://{}", other),
+                    percent: 100.0,
+                    compare_body,
+                    replay_unsafe_methods: false,
+                }),
+                ..route_to(primary)
+            };
+            let proxy = ProxyService::new(vec![route], metrics.clone()).unwrap();
+
+            // The client gets the route's response either way
+            let req = Request::builder()
+                .uri("/api/users")
+                .body(Body::empty())
+                .unwrap();
+            let response = proxy.forward(req).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(&body[..], b"ok");
+
+            for _ in 0..100 {
+                if metrics.shadow_comparisons("/api/*") == 1 {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            assert_eq!(metrics.shadow_comparisons("/api/*"), 1);
+            assert_eq!(
+                metrics.shadow_mismatches("/api/*"),
+                u64::from(mismatch),
+                "{} (compare_body = {})",
+                upstream,
+                compare_body
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compare_replay_without_credentials() {
+        const OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let primary = spawn_raw_upstream(OK).await;
+
+        // Comparison upstream reporting the requests it gets
+        let (tx, mut replays) = tokio::sync::mpsc::unbounded_channel();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let other = listener.local_addr().unwrap();
+        let app = axum::Router::new().fallback(move |req: Request<Body>| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send((
+                    req.method().clone(),
+                    req.uri().clone(),
+                    req.headers().clone(),
+                ));
+                "ok"
+            }
+        });
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let pool = |query_param_name: Option<&str>| crate::config::ApiKeyPool {
+            keys: vec![crate::config::ApiKeyConfig {
+                key: "pool-key".to_string(),
+                weight: 1,
+                enabled: true,
+            }],
+            strategy: Default::default(),
+            header_name: "X-Api-Key".to_string(),
+            query_param_name: query_param_name.map(str::to_string),
+            oauth2: None,
+        };
+        let route = |query_param_name: Option<&str>, replay_unsafe_methods: bool| ProxyRoute {
+            compare: Some(CompareConfig {
+                target: format!("http://{}", other),
+                percent: 100.0,
+                compare_body: false,
+                replay_unsafe_methods,
+            }),
+            headers: HashMap::from([("X-Internal-Token".to_string(), "internal".to_string())]),
+            api_key_selector: Some(crate::api_key::create_selector(&pool(query_param_name))),
+            ..route_to(primary)
+        };
+        let request = |method: &str| {
+            Request::builder()
+                .method(method)
+                .uri("/api/users?page=2")
+                .header(header::AUTHORIZATION, "Bearer client-token")
+                .header(header::COOKIE, "session=secret")
+                .header("X-Trace", "visible")
+                .body(Body::empty())
+                .unwrap()
+        };
+        type Replay = (Method, Uri, HeaderMap);
+        async fn next_replay(replays: &mut tokio::sync::mpsc::UnboundedReceiver<Replay>) -> Replay {
+            tokio::time::timeout(Duration::from_secs(5), replays.recv())
+                .await
+                .unwrap()
+                .unwrap()
+        }
+
+        let proxy =
+            ProxyService::new(vec![route(None, false)], Arc::new(GatewayMetrics::new())).unwrap();
+        proxy.forward(request("GET")).await.unwrap();
+        let (method, uri, headers) = next_replay(&mut replays).await;
+        assert_eq!(method, Method::GET);
+        assert_eq!(uri, "/users?page=2");
+        assert_eq!(headers["x-trace"], "visible");
+        for name in ["authorization", "cookie", "x-api-key", "x-internal-token"] {
+            assert!(!headers.contains_key(name), "{} replayed", name);
+        }
+
+        // A key sent as a query parameter isn't replayed either
+        let proxy = ProxyService::new(
+            vec![route(Some("api_key"), false)],
+            Arc::new(GatewayMetrics::new()),
+        )
+        .unwrap();
+        proxy.forward(request("GET")).await.unwrap();
+        let (_, uri, _) = next_replay(&mut replays).await;
+        assert_eq!(uri, "/users?page=2");
+
+        // Unsafe methods are only replayed when the route opts in
+        let proxy =
+            ProxyService::new(vec![route(None, false)], Arc::new(GatewayMetrics::new())).unwrap();
+        proxy.forward(request("POST")).await.unwrap();
+        proxy.forward(request("HEAD")).await.unwrap();
+        let (method, _, _) = next_replay(&mut replays).await;
+        assert_eq!(method, Method::HEAD);
+
+        let proxy =
+            ProxyService::new(vec![route(None, true)], Arc::new(GatewayMetrics::new())).unwrap();
+        proxy.forward(request("POST")).await.unwrap();
+        let (method, _, _) = next_replay(&mut replays).await;
+        assert_eq!(method, Method::POST);
+    }
+
+    #[tokio::test]
+    async fn test_compare_replays_bounded() {
+        const OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let primary = spawn_raw_upstream(OK).await;
+        // Comparison upstream that never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let other = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let metrics = Arc::new(GatewayMetrics::new());
+        let route = ProxyRoute {
+            compare: Some(CompareConfig {
+                target: format!("http://{}", other),
+                percent: 100.0,
+                compare_body: false,
+                replay_unsafe_methods: false,
+            }),
+            timeout: Some(Duration::from_secs(1)),
+            ..route_to(primary)
+        };
+        let proxy = ProxyService::new(vec![route], metrics.clone()).unwrap();
+        for _ in 0..MAX_COMPARISONS_IN_FLIGHT + 10 {
+            let req = Request::builder()
+                .uri("/api/users")
+                .body(Body::empty())
+                .unwrap();
+            proxy.forward(req).await.unwrap();
+        }
+        assert_eq!(proxy.comparisons.available_permits(), 0);
+
+        // The replays in flight time out; the extra samples were dropped
+        for _ in 0..200 {
+            if proxy.comparisons.available_permits() == MAX_COMPARISONS_IN_FLIGHT {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(
+            proxy.comparisons.available_permits(),
+            MAX_COMPARISONS_IN_FLIGHT
+        );
+        assert_eq!(
+            metrics.shadow_comparisons("/api/*"),
+            MAX_COMPARISONS_IN_FLIGHT as u64
+        );
+        assert_eq!(
+            metrics.shadow_mismatches("/api/*"),
+            MAX_COMPARISONS_IN_FLIGHT as u64
+        );
+    }
+
+    #[tokio::test]
+    async fn test_capture_writes_masked_request_and_response() {
+        let addr = spawn_echo_upstream().await;
+        let dir = tempfile::tempdir().unwrap();
+        let capture_route = |sample_rate: f64| ProxyRoute {
+            name: Some("users".to_string()),
+            capture: Some(Arc::new(Capture::new(
+                crate::config::CaptureConfig {
+                    sample_rate,
+                    dir: dir.path().join("captures"),
+                    max_bytes: 8,
+                },
+                &["X-Upstream-Key"],
+                &[],
+            ))),
+            headers: HashMap::from([(
+                "X-Upstream-Key".to_string(),
+                "upstream-key-1234".to_string(),
+            )]),
+            ..route_to(addr)
+        };
+        let request = || {
+            Request::builder()
+                .method("POST")
+                .uri("/api/users?page=2")
+                .header(header::AUTHORIZATION, "Bearer client-secret-token")
+                .header(header::COOKIE, "session=cookie-secret-value")
+                .header("X-Trace", "visible")
+                .body(Body::from("0123456789abcdef"))
+                .unwrap()
+        };
+
+        // Not sampled: nothing written
+        let proxy =
+            ProxyService::new(vec![capture_route(0.0)], Arc::new(GatewayMetrics::new())).unwrap();
+        proxy.forward(request()).await.unwrap();
+        assert!(!dir.path().join("captures").exists());
+
+        let proxy =
+            ProxyService::new(vec![capture_route(1.0)], Arc::new(GatewayMetrics::new())).unwrap();
+        let response = proxy.forward(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let files: Vec<_> = std::fs::read_dir(dir.path().join("captures"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1);
+        let contents = std::fs::read_to_string(&files[0]).unwrap();
+        for secret in [
+            "client-secret-token",
+            "cookie-secret-value",
+            "upstream-key-1234",
+        ] {
+            assert!(
+                !contents.contains(secret),
+                "{} leaked: {}",
+                secret,
+                contents
+            );
+        }
+
+        let capture: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(capture["route"], "users");
+        let request = &capture["request"];
+        assert_eq!(request["method"], "POST");
+        assert_eq!(request["uri"], "/api/users?page=2");
+        assert_eq!(request["headers"]["authorization"], "****oken");
+        assert_eq!(request["headers"]["cookie"], "****alue");
+        assert_eq!(request["headers"]["x-upstream-key"], "****1234");
+        assert_eq!(request["headers"]["x-trace"], "visible");
+        assert_eq!(request["body"], "01234567");
+        assert_eq!(request["body_truncated"], true);
+        let response = &capture["response"];
+        assert_eq!(response["status"], 200);
+        assert_eq!(response["body"], "{\"body\":");
+        assert_eq!(response["body_truncated"], true);
+    }
+
+    #[tokio::test]
+    async fn test_capture_masks_route_headers_and_key_param() {
+        let addr = spawn_echo_upstream().await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = crate::GatewayConfig::parse(&format!(
+            r#"
+[[routes]]
+name = "users"
+path = "/api/*"
+target = "http://{}"
+api_key_pool = "query"
+headers = {{ "X-Internal-Token" = "internal-secret-1234" }}
+capture = {{ dir = "{}" }}
+
+[api_key_pools.query]
+keys = [{{ key = "pool-secret-0001" }}]
+query_param_name = "api_key"
+"#,
+            addr,
+            dir.path().display()
+        ))
+        .unwrap();
+        let selectors: HashMap<String, SharedApiKeySelector> = config
+            .api_key_pools
+            .iter()
+            .map(|(name, pool)| (name.clone(), crate::api_key::create_selector(pool)))
+            .collect();
+        let proxy = ProxyService::new(
+            ProxyService::routes_from_config(&config.routes, &selectors).unwrap(),
+            Arc::new(GatewayMetrics::new()),
+        )
+        .unwrap();
+
+        let req = Request::builder()
+            .uri("/api/users?api_key=client-secret-5678&page=2")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(proxy.forward(req).await.unwrap().status(), StatusCode::OK);
+
+        let files: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1);
+        let contents = std::fs::read_to_string(&files[0]).unwrap();
+        let capture: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        // The echoed response shows what the upstream saw; the request is masked
+        let request = &capture["request"];
+        assert!(!request.to_string().contains("secret"), "{}", request);
+        assert_eq!(request["uri"], "/api/users?api_key=****5678&page=2");
+        assert_eq!(request["headers"]["x-internal-token"], "****1234");
+    }
+
+    #[tokio::test]
+    async fn test_match_query_routing() {
+        let addr = spawn_echo_upstream().await;
+        let config = crate::GatewayConfig::parse(&format!(
+            r#"
+[[routes]]
+path = "/search"
+target = "http://{}"
+api_key_pool = "default"
+match_query = {{ deep = "true" }}
+
+[[routes]]
+path = "/search"
+target = "http://localhost:1"
+match_query = {{ debug = "*" }}
+mock = {{ body = "debug" }}
+
+[[routes]]
+path = "/search"
+target = "http://localhost:1"
+mock = {{ body = "shallow" }}
+
+[api_key_pools.default]
+query_param_name = "key"
+keys = [{{ key = "pool-key-1" }}]
+"#,
+            addr
+        ))
+        .unwrap();
+        let selectors = config
+            .api_key_pools
+            .iter()
+            .map(|(name, pool)| (name.clone(), crate::api_key::create_selector(pool)))
+            .collect();
+        let routes = ProxyService::routes_from_config(&config.routes, &selectors).unwrap();
+        let proxy = ProxyService::new(routes, Arc::new(GatewayMetrics::new())).unwrap();
+        let body = |uri: &'static str| {
+            let proxy = &proxy;
+            async move {
+                let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+                let response = proxy.forward(req).await.unwrap();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                String::from_utf8(bytes.to_vec()).unwrap()
+            }
+        };
+
+        // Value match reaches the upstream, with the pool's key still added
+        let json: serde_json::Value =
+            serde_json::from_str(&body("/search?q=x&deep=true").await).unwrap();
+        assert_eq!(json["uri"], "/search?q=x&deep=true&key=pool%2Dkey%2D1");
+
+        assert_eq!(body("/search?deep=false").await, "shallow");
+        assert_eq!(body("/search?debug").await, "debug");
+        assert_eq!(body("/search?debug=1&deep=no").await, "debug");
+        assert_eq!(body("/search").await, "shallow");
+        assert_eq!(body("/search?a=1;deep=true").await, "shallow");
+
+        // With `;` as a separator the route sees the same parameters the
+        // query rewriting does
+        let routes = ProxyService::routes_from_config(&config.routes, &selectors).unwrap();
+        let proxy = ProxyService::new(routes, Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_routing(crate::config::RoutingConfig {
+                query_semicolon_separator: true,
+                ..Default::default()
+            });
+        let req = Request::builder()
+            .uri("/search?a=1;deep=true")
+            .body(Body::empty())
+            .unwrap();
+        let json = forward_json(&proxy, req).await;
+        assert_eq!(json["uri"], "/search?a=1;deep=true&key=pool%2Dkey%2D1");
+    }
+
+    #[tokio::test]
+    async fn test_header_templates() {
+        let addr = spawn_echo_upstream().await;
+        let headers = [
+            ("X-Upstream-Path", "{path}"),
+            ("X-Request-Method", "{method}"),
+            ("X-Original-Host", "{host}"),
+            ("X-Route", "route={route_name}"),
+            ("X-Received", "{now_rfc3339}"),
+            ("X-Literal", "static-value"),
+            ("X-Braces", "{{path}} is {path}"),
+        ];
+        let route = ProxyRoute {
+            name: Some("users".to_string()),
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            ..route_to(addr)
+        };
+        let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new())).unwrap();
+
+        let req = Request::builder()
+            .method("PUT")
+            .uri("/api/users/42?page=2")
+            .header(header::HOST, "api.example.com")
+            .body(Body::empty())
+            .unwrap();
+        let json = forward_json(&proxy, req).await;
+        let sent = &json["headers"];
+        assert_eq!(sent["x-upstream-path"], "/api/users/42");
+        assert_eq!(sent["x-request-method"], "PUT");
+        assert_eq!(sent["x-original-host"], "api.example.com");
+        assert_eq!(sent["x-route"], "route=users");
+        assert_eq!(sent["x-literal"], "static-value");
+        assert_eq!(sent["x-braces"], "{path} is /api/users/42");
+        let received = sent["x-received"].as_str().unwrap();
+        assert!(
+            chrono::DateTime::parse_from_rfc3339(received).is_ok(),
+            "{}",
+            received
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prewarm_opens_reusable_connections() {
+        // Upstream counting the connections it accepts; responses are slow
+        // enough that concurrent requests need a connection each
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let service = hyper::service::service_fn(|_req| async {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        Ok::<_, std::convert::Infallible>(Response::new(http_body_util::Full::new(
+                            bytes::Bytes::from_static(b"ok"),
+                        )))
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(hyper_util::rt::TokioIo::new(socket), service)
+                        .await;
+                });
+            }
+        });
+        let connections = || accepted.load(std::sync::atomic::Ordering::SeqCst);
+        let get = || {
+            Request::builder()
+                .uri("/api/users")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let proxy = ProxyService::new(
+            vec![ProxyRoute {
+                prewarm_connections: 3,
+                ..route_to(addr)
+            }],
+            Arc::new(GatewayMetrics::new()),
+        )
+        .unwrap();
+        assert_eq!(proxy.prewarm().await, 3);
+        assert_eq!(connections(), 3);
+
+        // Concurrent requests use the warm connections
+        let responses = futures::future::join_all((0..3).map(|_| proxy.forward(get()))).await;
+        assert!(responses
+            .iter()
+            .all(|r| r.as_ref().unwrap().status() == StatusCode::OK));
+        assert_eq!(connections(), 3);
+
+        // A cold route connects on its first request
+        let cold =
+            ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new())).unwrap();
+        assert_eq!(cold.prewarm().await, 0);
+        cold.forward(get()).await.unwrap();
+        assert_eq!(connections(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_connection_max_lifetime() {
+        // Upstream counting the connections it accepts
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let service = hyper::service::service_fn(|_req| async {
+                        Ok::<_, std::convert::Infallible>(Response::new(http_body_util::Full::new(
+                            bytes::Bytes::from_static(b"ok"),
+                        )))
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(hyper_util::rt::TokioIo::new(socket), service)
+                        .await;
+                });
+            }
+        });
+        let connections = || accepted.load(std::sync::atomic::Ordering::SeqCst);
+
+        let proxy = Arc::new(
+            ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new()))
+                .unwrap()
+                .with_client(UpstreamClientConfig {
+                    connection_max_idle: Some(30),
+                    ..Default::default()
+                }),
+        );
+        let send = || async {
+            let req = Request::builder()
+                .uri("/api/users")
+                .body(Body::empty())
+                .unwrap();
+            let response = proxy.forward(req).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            response.into_body().collect().await.unwrap();
+        };
+
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let lifetime = Duration::from_millis(300);
+        tokio::spawn(run_connection_recycle(proxy.clone(), lifetime, shutdown_rx));
+
+        // Within the lifetime, the pooled connection is reused
+        send().await;
+        send().await;
+        assert_eq!(connections(), 1);
+
+        // Past it, the next request connects again
+        tokio::time::sleep(lifetime + Duration::from_millis(100)).await;
+        send().await;
+        assert_eq!(connections(), 2);
+        send().await;
+        assert_eq!(connections(), 2);
+
+        shutdown_tx.send(true).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_tls_sni_fails_route_build() {
+        for sni in ["not a host", "10.0.0.1"] {
+            let route: RouteConfig = toml::from_str(&format!(
+                "path = \"/api/*\"\ntarget = \"https://10.0.0.1\"\ntls_sni = {:?}",
+                sni
+            ))
+            .unwrap();
+            let Err(err) = ProxyService::routes_from_config(&[route], &HashMap::new()) else {
+                panic!("tls_sni '{}' was accepted", sni);
+            };
+            assert!(err.to_string().contains("is not a DNS name"), "{}", err);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tls_sni_override() {
+        use rustls::server::ResolvesServerCertUsingSni;
+        use rustls::sign::CertifiedKey;
+        use rustls_pki_types::pem::PemObject;
+        use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+
+        // HTTPS upstream holding a certificate for `upstream.internal` only:
+        // handshakes without that server name are rejected
+        let testdata = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src/tls/testdata");
+        let certs = CertificateDer::pem_file_iter(testdata.join("upstream.pem"))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let key = PrivateKeyDer::from_pem_file(testdata.join("upstream.key")).unwrap();
+        let signing_key = rustls::crypto::aws_lc_rs::sign::any_supported_type(&key).unwrap();
+        let mut resolver = ResolvesServerCertUsingSni::new();
+        resolver
+            .add("upstream.internal", CertifiedKey::new(certs, signing_key))
+            .unwrap();
+        let server_config = rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(resolver));
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(server_config));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let Ok(stream) = acceptor.accept(socket).await else {
+                        return;
+                    };
+                    let service = hyper::service::service_fn(|_req| async {
+                        Ok::<_, std::convert::Infallible>(Response::new(http_body_util::Full::new(
+                            bytes::Bytes::from_static(b"secure"),
+                        )))
+                    });
+                    let _ = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(hyper_util::rt::TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+
+        let tls = crate::tls::client_config_with(
+            &crate::config::TlsConfig {
+                native_roots: false,
+                ca_bundle: Some(testdata.join("ca.pem")),
+            },
+            rustls_native_certs::load_native_certs,
+        )
+        .unwrap();
+        let route = |tls_sni: Option<&str>| ProxyRoute {
+            target: format!("https://{}", addr),
+            tls_sni: tls_sni.map(|name| ServerName::try_from(name.to_string()).unwrap()),
+            ..create_test_route()
+        };
+        let get = || {
+            Request::builder()
+                .uri("/api/users")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let proxy = ProxyService::with_tls(
+            vec![route(Some("upstream.internal"))],
+            Arc::new(GatewayMetrics::new()),
+            tls.clone(),
+        );
+        let response = proxy.forward(get()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"secure");
+
+        // Connecting by IP alone sends no usable server name
+        let proxy = ProxyService::with_tls(vec![route(None)], Arc::new(GatewayMetrics::new()), tls);
+        assert!(proxy.forward(get()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_upstream_connection_close_not_reused() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Answers one request per connection with `Connection: close`, but
+        // leaves the socket open: a reused connection would never answer
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            let mut open = Vec::new();
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                let mut buf = vec![0u8; 8192];
+                let mut read = 0;
+                while !buf[..read].windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buf[read..]).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => read += n,
+                    }
+                }
+                let _ = socket
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\nok",
+                    )
+                    .await;
+                open.push(socket);
+            }
+        });
+
+        let proxy =
+            ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new())).unwrap();
+        for _ in 0..3 {
+            let req = Request::builder()
+                .uri("/api/users")
+                .body(Body::empty())
+                .unwrap();
+            let response = tokio::time::timeout(Duration::from_secs(5), proxy.forward(req))
+                .await
+                .expect("request reused a closed connection")
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(response.headers().get(header::CONNECTION).is_none());
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(&body[..], b"ok");
+        }
+        assert_eq!(accepted.load(std::sync::atomic::Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_set_buffered_framing() {
+        let mut headers = axum::http::HeaderMap::new();
+        headers.insert(
+            axum::http::header::TRANSFER_ENCODING,
+            "chunked".parse().unwrap(),
+        );
+        headers.insert(
+            axum::http::header::CONTENT_ENCODING,
+            "gzip".parse().unwrap(),
+        );
+        headers.insert(axum::http::header::CONTENT_LENGTH, "999".parse().unwrap());
+
+        set_buffered_framing(&mut headers, 42, false);
+        assert!(headers.get(axum::http::header::TRANSFER_ENCODING).is_none());
+        assert_eq!(headers[axum::http::header::CONTENT_ENCODING], "gzip");
+        assert_eq!(headers[axum::http::header::CONTENT_LENGTH], "42");
+
+        set_buffered_framing(&mut headers, 10, true);
+        assert!(headers.get(axum::http::header::CONTENT_ENCODING).is_none());
+        assert_eq!(headers[axum::http::header::CONTENT_LENGTH], "10");
+    }
+
+    #[test]
+    fn test_new_pool_gets_client_on_set_routes() {
+        let addr: std::net::SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let mut pools = HashMap::new();
+        pools.insert(
+            "late".to_string(),
+            UpstreamPoolConfig {
+                max_idle_per_host: Some(1),
+                idle_timeout: Some(5),
+            },
+        );
+        let proxy = ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_upstream_pools(&pools);
+        let default = proxy.clients.load().default.clone();
+
+        proxy.set_routes(vec![
+            route_to(addr),
+            ProxyRoute {
+                path_pattern: "/late/*".to_string(),
+                upstream_pool: Some("late".to_string()),
+                ..route_to(addr)
+            },
+            ProxyRoute {
+                path_pattern: "/new/*".to_string(),
+                upstream_pool: Some("new".to_string()),
+                ..route_to(addr)
+            },
+        ]);
+        let routes = proxy.get_routes();
+        assert!(Arc::ptr_eq(&proxy.client_for(&routes[0]), &default));
+        assert!(!Arc::ptr_eq(&proxy.client_for(&routes[1]), &default));
+        assert!(!Arc::ptr_eq(&proxy.client_for(&routes[2]), &default));
+
+        // Recycling keeps the pools the route table names
+        proxy.recycle_connections();
+        assert!(proxy.clients.load().pools.contains_key("new"));
+    }
+
+    #[tokio::test]
+    async fn test_upstream_pool_isolation() {
+        let addr = spawn_echo_upstream().await;
+        let isolated = |path: &str, pool: &str| ProxyRoute {
+            path_pattern: path.to_string(),
+            upstream_pool: Some(pool.to_string()),
+            ..route_to(addr)
+        };
+        let routes = vec![
+            ProxyRoute {
+                path_pattern: "/default-a/*".to_string(),
+                ..route_to(addr)
+            },
+            ProxyRoute {
+                path_pattern: "/default-b/*".to_string(),
+                ..route_to(addr)
+            },
+            isolated("/slow-a/*", "slow"),
+            isolated("/slow-b/*", "slow"),
+            isolated("/fast/*", "fast"),
+        ];
+        let mut pools = HashMap::new();
+        pools.insert(
+            "slow".to_string(),
+            UpstreamPoolConfig {
+                max_idle_per_host: Some(1),
+                idle_timeout: Some(5),
+            },
+        );
+        let proxy = ProxyService::new(routes, Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_upstream_pools(&pools);
+        let routes = proxy.get_routes();
+
+        // Default routes share the default client
+        let default = proxy.clients.load().default.clone();
+        assert!(Arc::ptr_eq(&proxy.client_for(&routes[0]), &default));
+        assert!(Arc::ptr_eq(&proxy.client_for(&routes[1]), &default));
+        // Routes with the same pool name share an isolated client
+        assert!(Arc::ptr_eq(
+            &proxy.client_for(&routes[2]),
+            &proxy.client_for(&routes[3])
+        ));
+        assert!(!Arc::ptr_eq(&proxy.client_for(&routes[2]), &default));
+        assert!(!Arc::ptr_eq(
+            &proxy.client_for(&routes[2]),
+            &proxy.client_for(&routes[4])
+        ));
+
+        // Smoke test: concurrent requests through isolated pools all succeed
+        let get = |path: &str| Request::builder().uri(path).body(Body::empty()).unwrap();
+        let (slow, fast, default) = tokio::join!(
+            proxy.forward(get("/slow-a/x")),
+            proxy.forward(get("/fast/x")),
+            proxy.forward(get("/default-a/x")),
+        );
+        for response in [slow, fast, default] {
+            assert_eq!(response.unwrap().status(), StatusCode::OK);
+        }
+    }
+
+    #[test]
+    fn test_title_case_and_sni_clients_per_pool() {
+        let addr: std::net::SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let route = |pool: Option<&str>, sni: Option<&str>| ProxyRoute {
+            upstream_pool: pool.map(str::to_string),
+            title_case_headers: sni.is_none(),
+            tls_sni: sni.map(|name| ServerName::try_from(name.to_string()).unwrap()),
+            ..route_to(addr)
+        };
+        let proxy = ProxyService::new(vec![], Arc::new(GatewayMetrics::new())).unwrap();
+        let client = |pool, sni| proxy.client_for(&route(pool, sni));
+
+        // Title-case routes share a client only within the same pool
+        assert!(Arc::ptr_eq(
+            &client(Some("slow"), None),
+            &client(Some("slow"), None)
+        ));
+        assert!(!Arc::ptr_eq(
+            &client(Some("slow"), None),
+            &client(None, None)
+        ));
+        assert!(!Arc::ptr_eq(
+            &client(Some("slow"), None),
+            &client(Some("fast"), None)
+        ));
+
+        // So do routes overriding the TLS server name
+        let sni = Some("api.example.com");
+        assert!(Arc::ptr_eq(
+            &client(Some("slow"), sni),
+            &client(Some("slow"), sni)
+        ));
+        assert!(!Arc::ptr_eq(&client(Some("slow"), sni), &client(None, sni)));
+        assert!(!Arc::ptr_eq(
+            &client(Some("slow"), sni),
+            &client(Some("slow"), Some("other.example.com"))
+        ));
+    }
+
+    /// Start an upstream that waits before answering
+    async fn spawn_slow_upstream(delay: Duration) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = axum::Router::new().fallback(move || async move {
+            tokio::time::sleep(delay).await;
+            "done"
+        });
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        addr
+    }
+
+    /// Spawn an upstream that counts requests and answers after `delay`
+    async fn spawn_counting_upstream(
+        delay: Duration,
+    ) -> (std::net::SocketAddr, Arc<std::sync::atomic::AtomicUsize>) {
+        let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = hits.clone();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = axum::Router::new().fallback(move || {
+            let counter = counter.clone();
+            async move {
+                let n = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                tokio::time::sleep(delay).await;
+                format!("response {}", n)
             }
+        });
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (addr, hits)
+    }
 
-            // Add custom headers
-            for (key, value) in &route.headers {
-                if let Ok(header_name) = key.parse::<axum::http::header::HeaderName>() {
-                    if let Ok(header_value) = value.parse::<axum::http::header::HeaderValue>() {
-                        headers.insert(header_name, header_value);
-                    }
+    /// Spawn an upstream serving `"v1"`-tagged content and honoring `If-None-Match`
+    async fn spawn_etag_upstream() -> (std::net::SocketAddr, Arc<std::sync::atomic::AtomicUsize>) {
+        let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = hits.clone();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = axum::Router::new().fallback(move |headers: axum::http::HeaderMap| {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                let etag = [(header::ETAG, "\"v1\"")];
+                if headers
+                    .get(header::IF_NONE_MATCH)
+                    .is_some_and(|v| v == "\"v1\"")
+                {
+                    (StatusCode::NOT_MODIFIED, etag, "").into_response()
+                } else {
+                    (StatusCode::OK, etag, "content").into_response()
                 }
             }
+        });
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (addr, hits)
+    }
 
-            // Inject API key as header if configured (only when query_param_name is NOT set)
-            if let Some(selector) = api_key_selector {
-                // Only inject as header if query_param_name is not set
-                if selector.query_param_name.is_none() {
-                    if let Some(ref key) = api_key {
-                        if let Ok(header_name) = selector
-                            .header_name
-                            .parse::<axum::http::header::HeaderName>()
-                        {
-                            if let Ok(header_value) = key.parse::<axum::http::header::HeaderValue>()
-                            {
-                                headers.insert(header_name, header_value);
-                            }
-                        }
-                    }
-                }
-            }
+    /// Spawn an upstream answering every request with `status` and a JSON body
+    async fn spawn_json_upstream(
+        status: StatusCode,
+        body: serde_json::Value,
+    ) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app =
+            axum::Router::new().fallback(move || async move { (status, axum::Json(body.clone())) });
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        addr
+    }
+
+    fn aggregate_route(targets: &[(&str, String)]) -> ProxyRoute {
+        ProxyRoute {
+            aggregate: targets
+                .iter()
+                .map(|(name, target)| AggregateTarget {
+                    name: name.to_string(),
+                    target: target.clone(),
+                    timeout_ms: 1000,
+                })
+                .collect(),
+            ..create_test_route()
         }
+    }
 
-        // Convert body to the expected type
-        let body_bytes = match axum::body::to_bytes(body, usize::MAX).await {
-            Ok(bytes) => bytes,
-            Err(e) => {
-                self.metrics
-                    .record_request(&method, &path, 500, start.elapsed());
-                return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("Failed to read request body: {}", e),
-                ));
-            }
+    /// Read the `_sum` sample of a histogram for a route from Prometheus output
+    fn histogram_sum(output: &str, metric: &str, route: &str) -> f64 {
+        let prefix = format!("{}_sum{{route=\"{}\"}} ", metric, route);
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix(&prefix))
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(|| panic!("missing {} for route {}", metric, route))
+    }
+
+    #[tokio::test]
+    async fn test_slow_upstream_opens_circuit() {
+        let addr = spawn_slow_upstream(Duration::from_millis(100)).await;
+        let route = ProxyRoute {
+            circuit_breaker: Some(Arc::new(CircuitBreaker::new(
+                crate::config::CircuitBreakerConfig {
+                    minimum_calls: 2,
+                    slow_call_threshold_ms: Some(50),
+                    ..Default::default()
+                },
+            ))),
+            ..route_to(addr)
         };
+        let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new())).unwrap();
 
-        let boxed_body = http_body_util::Full::new(body_bytes)
-            .map_err(|e| match e {})
-            .boxed();
+        for _ in 0..2 {
+            let req = Request::builder()
+                .uri("/api/slow")
+                .body(Body::empty())
+                .unwrap();
+            assert!(proxy.forward(req).await.is_ok());
+        }
 
-        let new_req = builder.body(boxed_body).map_err(|e| {
-            self.metrics
-                .record_request(&method, &path, 500, start.elapsed());
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to build request: {}", e),
-            )
-        })?;
+        let req = Request::builder()
+            .uri("/api/slow")
+            .body(Body::empty())
+            .unwrap();
+        let err = proxy.forward(req).await.unwrap_err();
+        assert!(matches!(err, ProxyError::CircuitOpen));
+    }
 
-        // Send request
-        let response = self.client.request(new_req).await.map_err(|e| {
-            self.metrics
-                .record_request(&method, &path, 502, start.elapsed());
-            (
-                StatusCode::BAD_GATEWAY,
-                format!("Failed to forward request: {}", e),
-            )
-        })?;
+    #[tokio::test]
+    async fn test_queue_timeout_not_counted_by_circuit_breaker() {
+        let addr = spawn_slow_upstream(Duration::ZERO).await;
+        let breaker = Arc::new(CircuitBreaker::new(crate::config::CircuitBreakerConfig {
+            minimum_calls: 1,
+            ..Default::default()
+        }));
+        let limit = Arc::new(Semaphore::new(1));
+        let route = ProxyRoute {
+            circuit_breaker: Some(breaker.clone()),
+            concurrency_limit: Some(limit.clone()),
+            timeout: Some(Duration::from_millis(100)),
+            ..route_to(addr)
+        };
+        let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new())).unwrap();
+        let get = || {
+            Request::builder()
+                .uri("/api/slow")
+                .body(Body::empty())
+                .unwrap()
+        };
 
-        let status = response.status().as_u16();
-        self.metrics
-            .record_request(&method, &path, status, start.elapsed());
+        // Requests time out waiting for the only permit, never reaching the
+        // upstream
+        let held = limit.clone().acquire_owned().await.unwrap();
+        for _ in 0..3 {
+            let err = proxy.forward(get()).await.unwrap_err();
+            assert!(matches!(err, ProxyError::Timeout(TimeoutPhase::Upstream)));
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
 
-        // Record API key usage if an API key was used
-        // This is recorded after successful proxy to ensure we only count
-        // requests that were successfully forwarded to the target
-        if let Some(ref key) = api_key {
-            let route_name = route.name.as_deref().unwrap_or(&path);
-            self.metrics.record_api_key_usage(key, route_name);
+        drop(held);
+        assert!(proxy.forward(get()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_response_body_timeout_counted_once() {
+        let (addr, _closed) = spawn_stalling_upstream(1).await;
+        let breaker = Arc::new(CircuitBreaker::new(crate::config::CircuitBreakerConfig {
+            minimum_calls: 2,
+            failure_rate: 1.0,
+            ..Default::default()
+        }));
+        let route = ProxyRoute {
+            circuit_breaker: Some(breaker.clone()),
+            timeout: Some(Duration::from_millis(300)),
+            ..route_to(addr)
+        };
+        let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new())).unwrap();
+
+        let req = Request::builder()
+            .uri("/api/events")
+            .body(Body::empty())
+            .unwrap();
+        let err = proxy.forward(req).await.unwrap_err();
+        assert!(matches!(
+            err,
+            ProxyError::Timeout(TimeoutPhase::ResponseBody)
+        ));
+
+        // One failure in the window: a second one opens the circuit
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.record(false, Duration::ZERO);
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_identical_gets_coalesced() {
+        let (addr, hits) = spawn_counting_upstream(Duration::from_millis(200)).await;
+        let route = ProxyRoute {
+            cache: Some(Arc::new(ResponseCache::new(
+                &crate::config::CacheConfig::default(),
+            ))),
+            ..route_to(addr)
+        };
+        let proxy =
+            Arc::new(ProxyService::new(vec![route], Arc::new(GatewayMetrics::new())).unwrap());
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for _ in 0..20 {
+            let proxy = proxy.clone();
+            tasks.spawn(async move {
+                let req = Request::builder()
+                    .uri("/api/items?page=1")
+                    .body(Body::empty())
+                    .unwrap();
+                let response = proxy.forward(req).await.unwrap();
+                axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap()
+            });
         }
+        while let Some(body) = tasks.join_next().await {
+            assert_eq!(body.unwrap(), "response 1");
+        }
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
 
-        // Convert response body
-        let (parts, body) = response.into_parts();
-        let body_bytes = match http_body_util::BodyExt::collect(body).await {
-            Ok(collected) => collected.to_bytes(),
-            Err(e) => {
-                return Err((
-                    StatusCode::BAD_GATEWAY,
-                    format!("Failed to read response body: {}", e),
-                ));
+        // Later requests are served from the cache; other keys are not
+        let req = Request::builder()
+            .uri("/api/items?page=1")
+            .body(Body::empty())
+            .unwrap();
+        proxy.forward(req).await.unwrap();
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        let req = Request::builder()
+            .uri("/api/items?page=2")
+            .body(Body::empty())
+            .unwrap();
+        proxy.forward(req).await.unwrap();
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_requests_with_cookies_not_cached() {
+        let (addr, hits) = spawn_counting_upstream(Duration::from_millis(100)).await;
+        let proxy = Arc::new(
+            ProxyService::new(vec![cached_route_to(addr)], Arc::new(GatewayMetrics::new()))
+                .unwrap(),
+        );
+        let get = |cookie: &'static str| {
+            let proxy = proxy.clone();
+            async move {
+                let req = Request::builder()
+                    .uri("/api/me")
+                    .header(header::COOKIE, cookie)
+                    .body(Body::empty())
+                    .unwrap();
+                let response = proxy.forward(req).await.unwrap();
+                axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap()
             }
         };
 
-        let response = Response::from_parts(parts, Body::from(body_bytes));
+        // Concurrent requests of two users aren't coalesced
+        let (alice, bob) = tokio::join!(get("session=alice"), get("session=bob"));
+        assert_ne!(alice, bob);
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
 
-        Ok(response)
+        // Nor served from the cache later
+        get("session=alice").await;
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 3);
     }
 
-    /// Get all configured routes
-    pub fn get_routes(&self) -> &[ProxyRoute] {
-        &self.routes
+    fn cached_route_to(addr: std::net::SocketAddr) -> ProxyRoute {
+        ProxyRoute {
+            cache: Some(Arc::new(ResponseCache::new(
+                &crate::config::CacheConfig::default(),
+            ))),
+            ..route_to(addr)
+        }
     }
-}
-
-/// Check if a header is a hop-by-hop header that should not be forwarded.
-///
-/// Note: While RFC 7230 doesn't classify "host" as a hop-by-hop header,
-/// we include it here because the proxy must replace the Host header with
-/// the target server's host for HTTPS targets to work correctly.
-/// The Host header will be explicitly set from the target URL after filtering.
-fn is_hop_by_hop_header(name: &str) -> bool {
-    matches!(
-        name.to_lowercase().as_str(),
-        "connection"
-            | "keep-alive"
-            | "proxy-authenticate"
-            | "proxy-authorization"
-            | "te"
-            | "trailers"
-            | "transfer-encoding"
-            | "upgrade"
-            | "host"
-    )
-}
 
-/// Extract host and optional port from a URL string
-fn extract_host_from_url(url: &str) -> Option<String> {
-    // Parse the URL to extract host
-    if let Ok(parsed) = url.parse::<axum::http::Uri>() {
-        if let Some(authority) = parsed.authority() {
-            return Some(authority.to_string());
+    fn conditional_get(if_none_match: Option<&str>) -> Request<Body> {
+        let mut req = Request::builder().uri("/api/items");
+        if let Some(tag) = if_none_match {
+            req = req.header(header::IF_NONE_MATCH, tag);
         }
+        req.body(Body::empty()).unwrap()
     }
-    None
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[tokio::test]
+    async fn test_not_modified_from_cache() {
+        let (addr, hits) = spawn_etag_upstream().await;
+        let proxy = ProxyService::new(vec![cached_route_to(addr)], Arc::new(GatewayMetrics::new()))
+            .unwrap();
 
-    fn create_test_route() -> ProxyRoute {
-        ProxyRoute {
-            name: None,
-            path_pattern: "/api/*".to_string(),
-            target: "http://localhost:8081".to_string(),
-            strip_prefix: true,
-            methods: vec![],
-            api_key_selector: None,
-            headers: HashMap::new(),
-            description: Some("Test route".to_string()),
-        }
+        let response = proxy.forward(conditional_get(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ETAG], "\"v1\"");
+
+        let response = proxy
+            .forward(conditional_get(Some("\"v1\"")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], "\"v1\"");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+
+        // A stale tag gets the full cached response
+        let response = proxy
+            .forward(conditional_get(Some("\"v0\"")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
     }
 
-    #[test]
-    fn test_route_matching() {
-        let route = create_test_route();
+    #[tokio::test]
+    async fn test_not_modified_relayed_from_upstream() {
+        let (addr, hits) = spawn_etag_upstream().await;
+        let route = cached_route_to(addr);
+        let cache = route.cache.clone().unwrap();
+        let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new())).unwrap();
+
+        let response = proxy
+            .forward(conditional_get(Some("\"v1\"")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(cache.is_empty());
 
-        assert!(route.matches("/api/users", "GET"));
-        assert!(route.matches("/api/users/1", "POST"));
-        assert!(route.matches("/api", "GET"));
-        assert!(!route.matches("/other/path", "GET"));
+        // The 304 isn't served to clients without the tag
+        let response = proxy.forward(conditional_get(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
     }
 
-    #[test]
-    fn test_method_filtering() {
+    #[tokio::test]
+    async fn test_mock_route_never_dials_upstream() {
+        let (addr, hits) = spawn_counting_upstream(Duration::ZERO).await;
         let route = ProxyRoute {
-            methods: vec!["GET".to_string(), "POST".to_string()],
-            ..create_test_route()
+            mock: Some(Arc::new(MockResponse::from_config(&MockConfig {
+                status: 201,
+                headers: HashMap::from([("X-Mock".to_string(), "yes".to_string())]),
+                body: Some(r#"{"id":1}"#.to_string()),
+                body_file: None,
+            }))),
+            ..route_to(addr)
         };
+        let metrics = Arc::new(GatewayMetrics::new());
+        let proxy = ProxyService::new(vec![route], metrics.clone()).unwrap();
 
-        assert!(route.matches("/api/users", "GET"));
-        assert!(route.matches("/api/users", "POST"));
-        assert!(!route.matches("/api/users", "DELETE"));
+        for _ in 0..2 {
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/items")
+                .body(Body::from("ignored"))
+                .unwrap();
+            let response = proxy.forward(req).await.unwrap();
+            assert_eq!(response.status(), StatusCode::CREATED);
+            assert_eq!(response.headers()["x-mock"], "yes");
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(&body[..], br#"{"id":1}"#);
+        }
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 0);
+        assert_eq!(metrics.snapshot().total_requests, 2);
     }
 
     #[test]
-    fn test_target_url_with_strip_prefix() {
-        let route = create_test_route();
+    fn test_mock_body_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, b"from file").unwrap();
+        let mock = MockResponse::from_config(&MockConfig {
+            status: 200,
+            headers: HashMap::new(),
+            body: None,
+            body_file: Some(file.path().to_path_buf()),
+        });
+        assert_eq!(&mock.body[..], b"from file");
+    }
 
+    #[tokio::test]
+    async fn test_aggregate_merges_responses() {
+        let users = spawn_json_upstream(StatusCode::OK, serde_json::json!([{"id": 1}])).await;
+        let stats = spawn_json_upstream(StatusCode::OK, serde_json::json!({"total": 7})).await;
+        let route = aggregate_route(&[
+            ("users", format!("http://{}/users", users)),
+            ("stats", format!("http://{}/stats", stats)),
+        ]);
+        let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new())).unwrap();
+
+        let req = Request::builder()
+            .uri("/api/dashboard")
+            .body(Body::empty())
+            .unwrap();
+        let response = proxy.forward(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let merged: serde_json::Value = serde_json::from_slice(&body).unwrap();
         assert_eq!(
-            route.get_target_url("/api/users", None),
-            "http://localhost:8081/users"
-        );
-        assert_eq!(
-            route.get_target_url("/api/users/1", None),
-            "http://localhost:8081/users/1"
+            merged,
+            serde_json::json!({"users": [{"id": 1}], "stats": {"total": 7}})
         );
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_partial_failure() {
+        let users = spawn_json_upstream(StatusCode::OK, serde_json::json!([{"id": 1}])).await;
+        let broken = spawn_json_upstream(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            serde_json::json!({"message": "boom"}),
+        )
+        .await;
+        let slow = spawn_slow_upstream(Duration::from_millis(500)).await;
+        let mut route = aggregate_route(&[
+            ("users", format!("http://{}/users", users)),
+            ("stats", format!("http://{}/stats", broken)),
+            ("feed", format!("http://{}/feed", slow)),
+        ]);
+        route.aggregate[2].timeout_ms = 50;
+        let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new())).unwrap();
+
+        let req = Request::builder()
+            .uri("/api/dashboard")
+            .body(Body::empty())
+            .unwrap();
+        let response = proxy.forward(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let merged: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(merged["users"], serde_json::json!([{"id": 1}]));
         assert_eq!(
-            route.get_target_url("/api/users", Some("page=1")),
-            "http://localhost:8081/users?page=1"
+            merged["stats"]["error"],
+            "upstream returned 500 Internal Server Error"
         );
+        assert_eq!(merged["feed"]["error"], "timed out after 50ms");
     }
 
-    #[test]
-    fn test_target_url_without_strip_prefix() {
+    #[tokio::test]
+    async fn test_timeout_during_request_body() {
+        let addr = spawn_echo_upstream().await;
         let route = ProxyRoute {
-            strip_prefix: false,
-            ..create_test_route()
+            timeout: Some(Duration::from_millis(100)),
+            ..route_to(addr)
         };
+        let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new())).unwrap();
 
-        assert_eq!(
-            route.get_target_url("/api/users", None),
-            "http://localhost:8081/api/users"
+        // A client that sends part of the body and then stalls
+        let stalled = futures::StreamExt::chain(
+            futures::stream::iter([Ok::<_, std::io::Error>(bytes::Bytes::from("partial"))]),
+            futures::stream::pending(),
         );
+        let req = Request::builder()
+            .method("POST")
+            .uri("/api/upload")
+            .body(Body::from_stream(stalled))
+            .unwrap();
+
+        let started = Instant::now();
+        let err = proxy.forward(req).await.unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(matches!(
+            err,
+            ProxyError::Timeout(TimeoutPhase::RequestBody)
+        ));
+        assert_eq!(err.status_code(), StatusCode::GATEWAY_TIMEOUT);
     }
 
-    #[test]
-    fn test_extract_host_from_url() {
-        // HTTP URL without port
-        assert_eq!(
-            extract_host_from_url("http://example.com/path"),
-            Some("example.com".to_string())
+    /// Spawn an upstream that streams `chunks` chunked parts 80ms apart and
+    /// then stalls; sends on the returned channel once the gateway closed the
+    /// upstream connection
+    async fn spawn_stalling_upstream(
+        chunks: usize,
+    ) -> (std::net::SocketAddr, tokio::sync::oneshot::Receiver<()>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (closed_tx, closed_rx) = tokio::sync::oneshot::channel();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 8192];
+            let _ = socket.read(&mut buf).await;
+            let head = "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ntransfer-encoding: chunked\r\n\r\n";
+            socket.write_all(head.as_bytes()).await.unwrap();
+            for i in 1..=chunks {
+                let data = format!("chunk-{}\n", i);
+                let chunk = format!("{:x}\r\n{}\r\n", data.len(), data);
+                socket.write_all(chunk.as_bytes()).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(80)).await;
+            }
+            // Stall without ending the body until the gateway hangs up
+            while !matches!(socket.read(&mut buf).await, Ok(0) | Err(_)) {}
+            let _ = closed_tx.send(());
+        });
+        (addr, closed_rx)
+    }
+
+    #[tokio::test]
+    async fn test_stream_idle_timeout_closes_stalled_stream() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (upstream, upstream_closed) = spawn_stalling_upstream(4).await;
+        let route = ProxyRoute {
+            // Shorter than the stream; only covers the response headers
+            timeout: Some(Duration::from_millis(100)),
+            stream_idle_timeout: Some(Duration::from_millis(300)),
+            ..route_to(upstream)
+        };
+        let proxy =
+            Arc::new(ProxyService::new(vec![route], Arc::new(GatewayMetrics::new())).unwrap());
+        let gateway = spawn_gateway(proxy).await;
+
+        let mut client = tokio::net::TcpStream::connect(gateway).await.unwrap();
+        client
+            .write_all(b"GET /api/events HTTP/1.1\r\nhost: gateway\r\n\r\n")
+            .await
+            .unwrap();
+        let received = read_until(&mut client, "chunk-4").await;
+        assert!(received.starts_with("HTTP/1.1 200 OK"));
+        let stalled_at = Instant::now();
+
+        // The client connection is closed once the stream has been idle
+        let mut buf = [0u8; 1024];
+        loop {
+            let read = tokio::time::timeout(Duration::from_secs(5), client.read(&mut buf))
+                .await
+                .expect("client connection was not closed");
+            if matches!(read, Ok(0) | Err(_)) {
+                break;
+            }
+        }
+        assert!(stalled_at.elapsed() >= Duration::from_millis(200));
+        tokio::time::timeout(Duration::from_secs(5), upstream_closed)
+            .await
+            .expect("upstream connection was not closed")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_uri_length_limit() {
+        let (addr, hits) = spawn_counting_upstream(Duration::ZERO).await;
+        let metrics = Arc::new(GatewayMetrics::new());
+        // "/users?q=" plus padding is forwarded once the prefix is stripped
+        let request = |length: usize| {
+            let uri = format!("/api/users?q={}", "x".repeat(length - "/users?q=".len()));
+            Request::builder().uri(uri).body(Body::empty()).unwrap()
+        };
+
+        // The route's limit overrides the global one
+        let proxy = ProxyService::new(
+            vec![ProxyRoute {
+                max_uri_bytes: Some(32),
+                ..route_to(addr)
+            }],
+            metrics.clone(),
+        )
+        .unwrap()
+        .with_limits(LimitsConfig {
+            max_uri_bytes: Some(16),
+            ..Default::default()
+        });
+        let response = proxy.forward(request(32)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let err = proxy.forward(request(33)).await.unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::URI_TOO_LONG);
+        assert_eq!(err.to_string(), "Request URI exceeds 32 bytes");
+
+        // Routes without a limit of their own use the global one
+        let proxy = ProxyService::new(vec![route_to(addr)], metrics.clone())
+            .unwrap()
+            .with_limits(LimitsConfig {
+                max_uri_bytes: Some(16),
+                ..Default::default()
+            });
+        let response = proxy.forward(request(16)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let err = proxy.forward(request(17)).await.unwrap_err();
+        assert_eq!(err.reason_label(), "uri_too_long");
+
+        // Rejected requests never reach the upstream
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert_eq!(metrics.uris_too_long("/api/*"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_buffered_response_rejected() {
+        const CHUNKED: &str = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                               8\r\n01234567\r\n8\r\n89abcdef\r\n0\r\n\r\n";
+        let limits = LimitsConfig {
+            max_response_bytes: Some(10),
+            ..Default::default()
+        };
+        for upstream in [
+            "HTTP/1.1 200 OK\r\nContent-Length: 16\r\n\r\n0123456789abcdef",
+            CHUNKED,
+        ] {
+            let addr = spawn_raw_upstream(upstream).await;
+            let metrics = Arc::new(GatewayMetrics::new());
+            let proxy = ProxyService::new(vec![route_to(addr)], metrics.clone())
+                .unwrap()
+                .with_limits(limits.clone());
+            let req = Request::builder()
+                .uri("/api/big")
+                .body(Body::empty())
+                .unwrap();
+            let err = proxy.forward(req).await.unwrap_err();
+            assert_eq!(err.status_code(), StatusCode::BAD_GATEWAY);
+            assert_eq!(err.reason_label(), "response_too_large");
+            assert_eq!(metrics.responses_too_large("/api/*"), 1);
+        }
+
+        // Responses within the limit are unaffected
+        let addr = spawn_raw_upstream(CHUNKED).await;
+        let proxy = ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new()))
+            .unwrap()
+            .with_limits(LimitsConfig {
+                max_response_bytes: Some(16),
+                ..Default::default()
+            });
+        let req = Request::builder()
+            .uri("/api/big")
+            .body(Body::empty())
+            .unwrap();
+        let response = proxy.forward(req).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"0123456789abcdef");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_streamed_response_cut_off() {
+        let addr = spawn_raw_upstream(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+             8\r\n01234567\r\n8\r\n89abcdef\r\n0\r\n\r\n",
+        )
+        .await;
+        let metrics = Arc::new(GatewayMetrics::new());
+        let route = ProxyRoute {
+            stream_idle_timeout: Some(Duration::from_secs(5)),
+            ..route_to(addr)
+        };
+        let proxy = ProxyService::new(vec![route], metrics.clone())
+            .unwrap()
+            .with_limits(LimitsConfig {
+                max_response_bytes: Some(10),
+                ..Default::default()
+            });
+        let req = Request::builder()
+            .uri("/api/events")
+            .body(Body::empty())
+            .unwrap();
+        let response = proxy.forward(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .is_err());
+        assert_eq!(metrics.responses_too_large("/api/*"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_buffer_threshold_streams_large_responses() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        /// Upstream announcing `len` bytes, sending half of them and the
+        /// rest 300ms later
+        async fn spawn_split_upstream(len: usize) -> std::net::SocketAddr {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0u8; 8192];
+                let _ = socket.read(&mut buf).await;
+                let head = format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n", len);
+                socket.write_all(head.as_bytes()).await.unwrap();
+                socket.write_all(&vec![b'a'; len / 2]).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                socket.write_all(&vec![b'b'; len - len / 2]).await.unwrap();
+            });
+            addr
+        }
+
+        // Returns the time until the response headers and the body length
+        let fetch = |len: usize, cached: bool| async move {
+            let route = ProxyRoute {
+                cache: cached
+                    .then(|| Arc::new(ResponseCache::new(&crate::config::CacheConfig::default()))),
+                ..route_to(spawn_split_upstream(len).await)
+            };
+            let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new()))
+                .unwrap()
+                .with_forwarding(ForwardingConfig {
+                    buffer_threshold_bytes: Some(1024),
+                    ..Default::default()
+                });
+            let req = Request::builder()
+                .uri("/api/file")
+                .body(Body::empty())
+                .unwrap();
+            let started = Instant::now();
+            let response = proxy.forward(req).await.unwrap();
+            let headers_after = started.elapsed();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (headers_after, body.len())
+        };
+
+        // Small responses are buffered: nothing is returned before the end
+        let (headers_after, len) = fetch(512, false).await;
+        assert!(
+            headers_after >= Duration::from_millis(250),
+            "{:?}",
+            headers_after
         );
+        assert_eq!(len, 512);
 
-        // HTTP URL with port
-        assert_eq!(
-            extract_host_from_url("http://localhost:8080/path"),
-            Some("localhost:8080".to_string())
+        // Large ones are passed on as they arrive
+        let (headers_after, len) = fetch(64 * 1024, false).await;
+        assert!(
+            headers_after < Duration::from_millis(250),
+            "{:?}",
+            headers_after
         );
+        assert_eq!(len, 64 * 1024);
 
-        // HTTPS URL without port
-        assert_eq!(
-            extract_host_from_url("https://api.example.com/v1/users"),
-            Some("api.example.com".to_string())
+        // Routes needing whole bodies buffer them whatever their size
+        let (headers_after, len) = fetch(64 * 1024, true).await;
+        assert!(
+            headers_after >= Duration::from_millis(250),
+            "{:?}",
+            headers_after
         );
+        assert_eq!(len, 64 * 1024);
+    }
 
-        // HTTPS URL with port
-        assert_eq!(
-            extract_host_from_url("https://api.example.com:443/v1/users"),
-            Some("api.example.com:443".to_string())
+    #[tokio::test]
+    async fn test_timeout_during_upstream_call() {
+        let addr = spawn_slow_upstream(Duration::from_millis(500)).await;
+        let route = ProxyRoute {
+            timeout: Some(Duration::from_millis(100)),
+            ..route_to(addr)
+        };
+        let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new())).unwrap();
+
+        let req = Request::builder()
+            .uri("/api/slow")
+            .body(Body::empty())
+            .unwrap();
+        let err = proxy.forward(req).await.unwrap_err();
+        assert!(matches!(err, ProxyError::Timeout(TimeoutPhase::Upstream)));
+        assert_eq!(err.reason_label(), "timeout_upstream");
+    }
+
+    #[tokio::test]
+    async fn test_request_deadline_shorter_than_route_timeout() {
+        let addr = spawn_slow_upstream(Duration::from_millis(500)).await;
+        let route = ProxyRoute {
+            timeout: Some(Duration::from_secs(5)),
+            ..route_to(addr)
+        };
+        let proxy = ProxyService::new(vec![route], Arc::new(GatewayMetrics::new())).unwrap();
+
+        let started = Instant::now();
+        let req = Request::builder()
+            .uri("/api/slow")
+            .header(REQUEST_DEADLINE_HEADER, "100")
+            .body(Body::empty())
+            .unwrap();
+        let err = proxy.forward(req).await.unwrap_err();
+        assert!(matches!(err, ProxyError::Timeout(TimeoutPhase::Upstream)));
+        assert!(started.elapsed() < Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn test_expired_request_deadline() {
+        let (addr, hits) = spawn_counting_upstream(Duration::ZERO).await;
+        let proxy =
+            ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new())).unwrap();
+
+        let past = chrono::Utc::now() - chrono::Duration::seconds(1);
+        let req = Request::builder()
+            .uri("/api/users")
+            .header(REQUEST_DEADLINE_HEADER, past.to_rfc3339())
+            .body(Body::empty())
+            .unwrap();
+        let err = proxy.forward(req).await.unwrap_err();
+        assert!(matches!(err, ProxyError::DeadlineExceeded));
+        assert_eq!(err.status_code(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_request_deadline_forwarded() {
+        let addr = spawn_echo_upstream().await;
+        let proxy =
+            ProxyService::new(vec![route_to(addr)], Arc::new(GatewayMetrics::new())).unwrap();
+
+        let req = Request::builder()
+            .uri("/api/users")
+            .header(REQUEST_DEADLINE_HEADER, "30000")
+            .header(header::CONTENT_TYPE, "application/grpc")
+            .body(Body::empty())
+            .unwrap();
+        let sent = forward_json(&proxy, req).await["headers"].clone();
+        assert!(sent.get(REQUEST_DEADLINE_HEADER).is_none());
+        let at =
+            chrono::DateTime::parse_from_rfc3339(sent[UPSTREAM_DEADLINE_HEADER].as_str().unwrap())
+                .unwrap();
+        let left = at.with_timezone(&chrono::Utc) - chrono::Utc::now();
+        assert!(left > chrono::Duration::seconds(25), "{}", left);
+        let grpc_timeout: u64 = sent["grpc-timeout"]
+            .as_str()
+            .unwrap()
+            .strip_suffix('m')
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((25_000..=30_000).contains(&grpc_timeout));
+
+        // Invalid deadlines are ignored
+        let req = Request::builder()
+            .uri("/api/users")
+            .header(REQUEST_DEADLINE_HEADER, "soon")
+            .body(Body::empty())
+            .unwrap();
+        let sent = forward_json(&proxy, req).await["headers"].clone();
+        assert!(sent.get(UPSTREAM_DEADLINE_HEADER).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_queue_wait_metric() {
+        let addr = spawn_slow_upstream(Duration::from_millis(200)).await;
+        let metrics = Arc::new(GatewayMetrics::new());
+        let routes = vec![
+            ProxyRoute {
+                name: Some("limited".to_string()),
+                concurrency_limit: Some(Arc::new(Semaphore::new(1))),
+                ..route_to(addr)
+            },
+            ProxyRoute {
+                name: Some("unlimited".to_string()),
+                path_pattern: "/other/*".to_string(),
+                ..route_to(addr)
+            },
+        ];
+        let proxy = ProxyService::new(routes, metrics.clone()).unwrap();
+
+        let get = |path: &str| Request::builder().uri(path).body(Body::empty()).unwrap();
+        let (a, b, c) = tokio::join!(
+            proxy.forward(get("/api/a")),
+            proxy.forward(get("/api/b")),
+            proxy.forward(get("/other/c")),
         );
+        for response in [a, b, c] {
+            assert_eq!(response.unwrap().status(), StatusCode::OK);
+        }
 
-        // Relative path (no authority)
-        assert_eq!(extract_host_from_url("/just/a/path"), None);
+        let output = metrics.prometheus_output().unwrap();
+        // The second limited request waited for the first to complete
+        assert!(histogram_sum(&output, "gateway_queue_wait_seconds", "limited") >= 0.1);
+        // Unlimited routes record a zero wait
+        assert!(histogram_sum(&output, "gateway_queue_wait_seconds", "unlimited") < 0.01);
     }
 
     #[test]
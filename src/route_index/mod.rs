@@ -0,0 +1,140 @@
+//! Route index module
+//!
+//! This module narrows down the routes that can match a request path:
+//! - Routes are grouped by the first segment of their path pattern
+//!   (`/api/v1/*` is stored under `api`)
+//! - Catch-all patterns (`/*`) are candidates for every path; the root
+//!   pattern `/` is only a candidate for paths with an empty first segment
+//! - Candidates keep the configured route order, so the first matching
+//!   candidate is the same route a linear scan would find
+//! - Pattern helpers tell how specific a pattern is and whether two
+//!   patterns can match the same path
+
+use std::collections::HashMap;
+
+/// Index from a path's first segment to the routes that may match it
+#[derive(Debug, Clone, Default)]
+pub struct RouteIndex {
+    /// Candidate route indices per first segment, in route order
+    by_segment: HashMap<String, Vec<usize>>,
+    /// Routes that may match any path, in route order
+    catch_all: Vec<usize>,
+}
+
+impl RouteIndex {
+    /// Build the index for route path patterns given in route order
+    pub fn new<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut keyed = Vec::new();
+        let mut catch_all = Vec::new();
+        for (i, pattern) in patterns.into_iter().enumerate() {
+            match pattern_segment(pattern) {
+                Some(segment) => keyed.push((segment.to_string(), i)),
+                None => catch_all.push(i),
+            }
+        }
+
+        let mut by_segment: HashMap<String, Vec<usize>> = HashMap::new();
+        for (segment, i) in keyed {
+            by_segment.entry(segment).or_default().push(i);
+        }
+        // Merge the catch-all routes into each bucket, keeping route order
+        for candidates in by_segment.values_mut() {
+            candidates.extend(&catch_all);
+            candidates.sort_unstable();
+        }
+
+        Self {
+            by_segment,
+            catch_all,
+        }
+    }
+
+    /// Get the indices of the routes that may match `path`, in route order
+    pub fn candidates(&self, path: &str) -> &[usize] {
+        path.strip_prefix('/')
+            .map(|rest| rest.split('/').next().unwrap_or(""))
+            .and_then(|segment| self.by_segment.get(segment))
+            .unwrap_or(&self.catch_all)
+    }
+}
+
+/// Get the literal prefix of a path pattern (`/api/*` -> `/api`)
+///
+/// Mirrors the pattern forms accepted by route matching: `prefix/*`,
+/// `prefix/` and exact `prefix` all match `prefix` and the paths below it.
+/// The root pattern `/` only matches `/`, so it is its own prefix.
+pub fn pattern_prefix(pattern: &str) -> &str {
+    if pattern == "/" {
+        return pattern;
+    }
+    pattern
+        .strip_suffix("/*")
+        .or_else(|| pattern.strip_suffix('/'))
+        .unwrap_or(pattern)
+}
+
+/// Check if some path is matched by both patterns
+pub fn patterns_overlap(a: &str, b: &str) -> bool {
+    let (a, b) = (pattern_prefix(a), pattern_prefix(b));
+    let below = |parent: &str, child: &str| {
+        parent.is_empty()
+            || child == parent
+            || child
+                .strip_prefix(parent)
+                .is_some_and(|rest| rest.starts_with('/'))
+    };
+    below(a, b) || below(b, a)
+}
+
+/// Get the first path segment every path matched by `pattern` starts with
+///
+/// Returns `None` for patterns that may match any path.
+fn pattern_segment(pattern: &str) -> Option<&str> {
+    let rest = pattern_prefix(pattern).strip_prefix('/')?;
+    Some(rest.split('/').next().unwrap_or(""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern_segment() {
+        assert_eq!(pattern_segment("/api/*"), Some("api"));
+        assert_eq!(pattern_segment("/api/v1/users"), Some("api"));
+        assert_eq!(pattern_segment("/api/"), Some("api"));
+        assert_eq!(pattern_segment("/*"), None);
+        assert_eq!(pattern_segment("/"), Some(""));
+        assert_eq!(pattern_segment("api/*"), None);
+    }
+
+    #[test]
+    fn test_patterns_overlap() {
+        assert!(patterns_overlap("/api/*", "/api/v1/*"));
+        assert!(patterns_overlap("/api/v1/users", "/api/"));
+        assert!(patterns_overlap("/*", "/users"));
+        assert!(patterns_overlap("/users", "/users/*"));
+        assert!(!patterns_overlap("/api/*", "/apis/*"));
+        assert!(!patterns_overlap("/api/v1/*", "/api/v2/*"));
+        // The root pattern only overlaps patterns matching the root
+        assert!(patterns_overlap("/", "/*"));
+        assert!(patterns_overlap("/", "/"));
+        assert!(!patterns_overlap("/", "/users"));
+        assert!(!patterns_overlap("/api/*", "/"));
+        assert_eq!(pattern_prefix("/api/v1/*"), "/api/v1");
+    }
+
+    #[test]
+    fn test_candidates_keep_route_order() {
+        let index = RouteIndex::new(["/users/*", "/*", "/api/v2/*", "/api/*"]);
+        assert_eq!(index.candidates("/api/v2/items"), &[1, 2, 3]);
+        assert_eq!(index.candidates("/users"), &[0, 1]);
+        assert_eq!(index.candidates("/other"), &[1]);
+        assert_eq!(index.candidates("/"), &[1]);
+
+        let index = RouteIndex::new(["/", "/api/*", "/*"]);
+        assert_eq!(index.candidates("/"), &[0, 2]);
+        assert_eq!(index.candidates("/api/users"), &[1, 2]);
+        assert_eq!(index.candidates("/other"), &[2]);
+    }
+}
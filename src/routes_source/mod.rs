@@ -0,0 +1,485 @@
+//! Remote route source module
+//!
+//! This module loads routes from a control-plane endpoint:
+//! - Routes are fetched as a JSON array at startup and on an interval
+//! - Remote routes are merged after the file routes, which take precedence
+//! - The merged configuration is validated before it is applied
+//! - Each server's route table is swapped in place without a restart, and
+//!   only when its routes changed
+
+use crate::api_key::SharedApiKeySelector;
+use crate::config::{GatewayConfig, RouteConfig, RoutesSourceConfig, ServerConfig};
+use crate::proxy::{ProxyRoute, ProxyService};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{debug, warn};
+
+/// A running server's proxy and the server config it was built from
+#[derive(Clone)]
+pub struct ServerProxy {
+    /// Server configuration (used to select its routes)
+    pub server: ServerConfig,
+    /// The server's proxy service
+    pub proxy: Arc<ProxyService>,
+    /// Enabled routes last applied to the proxy, each with the route built
+    /// from it
+    applied: Arc<Mutex<Vec<(serde_json::Value, ProxyRoute)>>>,
+}
+
+impl ServerProxy {
+    /// Track `proxy`, whose route table was built from `routes`
+    pub fn new(server: ServerConfig, proxy: Arc<ProxyService>, routes: &[RouteConfig]) -> Self {
+        let applied = routes
+            .iter()
+            .filter(|r| r.enabled)
+            .map(route_value)
+            .zip(proxy.get_routes().iter().cloned())
+            .collect();
+        Self {
+            server,
+            proxy,
+            applied: Arc::new(Mutex::new(applied)),
+        }
+    }
+
+    /// Replace the route table with `routes` if they changed
+    ///
+    /// Routes whose configuration and API key pool are unchanged keep their
+    /// state: circuit breaker, response cache, token cache and concurrency
+    /// permits. Returns whether the table was replaced; if a route can't be
+    /// built the current table is kept.
+    pub fn apply(
+        &self,
+        routes: &[RouteConfig],
+        api_key_selectors: &HashMap<String, SharedApiKeySelector>,
+    ) -> anyhow::Result<bool> {
+        let next = self.build(routes, api_key_selectors)?;
+        Ok(self.replace(next))
+    }
+
+    /// Build the route table for `routes`, or `None` if they're unchanged
+    fn build(
+        &self,
+        routes: &[RouteConfig],
+        api_key_selectors: &HashMap<String, SharedApiKeySelector>,
+    ) -> anyhow::Result<Option<Vec<(serde_json::Value, ProxyRoute)>>> {
+        let applied = self.applied.lock().unwrap();
+        let configs: Vec<(serde_json::Value, &RouteConfig)> = routes
+            .iter()
+            .filter(|r| r.enabled)
+            .map(|r| (route_value(r), r))
+            .collect();
+
+        let unchanged = configs.len() == applied.len()
+            && configs
+                .iter()
+                .zip(applied.iter())
+                .all(|((value, config), (old, route))| {
+                    value == old && same_selector(route, config, api_key_selectors)
+                });
+        if unchanged {
+            return Ok(None);
+        }
+
+        configs
+            .into_iter()
+            .map(|(value, config)| {
+                let kept = applied
+                    .iter()
+                    .find(|(old, route)| {
+                        *old == value && same_selector(route, config, api_key_selectors)
+                    })
+                    .map(|(_, route)| route.clone());
+                let route = match kept {
+                    Some(route) => route,
+                    None => ProxyService::routes_from_config(
+                        std::slice::from_ref(config),
+                        api_key_selectors,
+                    )?
+                    .remove(0),
+                };
+                Ok((value, route))
+            })
+            .collect::<anyhow::Result<_>>()
+            .map(Some)
+    }
+
+    /// Swap in a table from [`Self::build`], returning whether it changed
+    fn replace(&self, next: Option<Vec<(serde_json::Value, ProxyRoute)>>) -> bool {
+        let Some(next) = next else {
+            return false;
+        };
+        self.proxy
+            .set_routes(next.iter().map(|(_, route)| route.clone()).collect());
+        *self.applied.lock().unwrap() = next;
+        true
+    }
+}
+
+/// Comparable form of a route configuration
+fn route_value(route: &RouteConfig) -> serde_json::Value {
+    serde_json::to_value(route).unwrap_or(serde_json::Value::Null)
+}
+
+/// Whether `route` uses the selector `config` names in `api_key_selectors`
+fn same_selector(
+    route: &ProxyRoute,
+    config: &RouteConfig,
+    api_key_selectors: &HashMap<String, SharedApiKeySelector>,
+) -> bool {
+    let selector = config
+        .api_key_pool
+        .as_ref()
+        .and_then(|name| api_key_selectors.get(name));
+    match (&route.api_key_selector, selector) {
+        (None, None) => true,
+        (Some(current), Some(selector)) => Arc::ptr_eq(current, selector),
+        _ => false,
+    }
+}
+
+/// Fetch the route list from the control plane
+pub async fn fetch_routes(
+    client: &reqwest::Client,
+    source: &RoutesSourceConfig,
+) -> anyhow::Result<Vec<RouteConfig>> {
+    let mut request = client.get(&source.url);
+    if let Some(auth) = &source.auth_header {
+        request = request.header(reqwest::header::AUTHORIZATION, auth);
+    }
+    let response = request.send().await?.error_for_status()?;
+    Ok(response.json().await?)
+}
+
+/// Merge remote routes into the file configuration and validate the result
+///
+/// Remote routes that reuse the path or name of an existing route are
+/// ignored, so file routes always take precedence.
+pub fn merge_routes(
+    base: &GatewayConfig,
+    remote: Vec<RouteConfig>,
+) -> anyhow::Result<GatewayConfig> {
+    let mut merged = base.clone();
+    for route in remote {
+        let duplicate = merged.routes.iter().any(|existing| {
+            existing.path == route.path || (existing.name.is_some() && existing.name == route.name)
+        });
+        if duplicate {
+            warn!(
+                "Ignoring remote route '{}': duplicates an existing route",
+                route.name.as_deref().unwrap_or(&route.path)
+            );
+            continue;
+        }
+        merged.routes.push(route);
+    }
+    merged.normalize();
+    merged.validate()?;
+    Ok(merged)
+}
+
+/// Replace each server's route table with its routes from `config`
+///
+/// Servers whose routes are unchanged keep their current table. Every
+/// table is built before any is swapped, so if a route fails to build all
+/// servers keep their current routes.
+pub fn apply_routes(
+    config: &GatewayConfig,
+    proxies: &[ServerProxy],
+    api_key_selectors: &HashMap<String, SharedApiKeySelector>,
+) -> anyhow::Result<()> {
+    let tables = proxies
+        .iter()
+        .map(|server_proxy| {
+            let routes: Vec<RouteConfig> = config
+                .routes_for_server(&server_proxy.server)
+                .into_iter()
+                .cloned()
+                .collect();
+            server_proxy.build(&routes, api_key_selectors)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    for (server_proxy, next) in proxies.iter().zip(tables) {
+        server_proxy.replace(next);
+    }
+    Ok(())
+}
+
+/// Load the startup configuration, including remote routes if configured
+///
+/// Falls back to the file routes if the control plane can't be reached or
+/// returns invalid routes.
+pub async fn load_routes(base: &GatewayConfig) -> GatewayConfig {
+    let Some(source) = &base.routes_source else {
+        return base.clone();
+    };
+    let client = reqwest::Client::new();
+    let result = match fetch_routes(&client, source).await {
+        Ok(remote) => merge_routes(base, remote),
+        Err(e) => Err(e),
+    };
+    result.unwrap_or_else(|e| {
+        warn!(
+            "Failed to load routes from {}: {}; using file routes only",
+            source.url, e
+        );
+        base.clone()
+    })
+}
+
+/// Fetch remote routes once and apply them to the running servers
+///
+/// Returns the number of routes now active across the merged configuration.
+pub async fn refresh_routes(
+    client: &reqwest::Client,
+    base: &GatewayConfig,
+    proxies: &[ServerProxy],
+    api_key_selectors: &HashMap<String, SharedApiKeySelector>,
+) -> anyhow::Result<usize> {
+    let source = base
+        .routes_source
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("No routes_source configured"))?;
+    let remote = fetch_routes(client, source).await?;
+    let merged = merge_routes(base, remote)?;
+    apply_routes(&merged, proxies, api_key_selectors)?;
+    Ok(merged.routes.len())
+}
+
+/// Poll the control plane until shutdown
+///
+/// `base` is the configuration loaded from file; each poll merges the
+/// current remote routes into it. Failed polls keep the active routes.
+pub async fn poll_routes_source(
+    base: GatewayConfig,
+    proxies: Vec<ServerProxy>,
+    api_key_selectors: HashMap<String, SharedApiKeySelector>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    let Some(source) = base.routes_source.clone() else {
+        return;
+    };
+    if source.poll_interval == 0 {
+        return;
+    }
+
+    let client = reqwest::Client::new();
+    let mut interval = tokio::time::interval(Duration::from_secs(source.poll_interval));
+    // The first tick completes immediately; routes were loaded at startup
+    interval.tick().await;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                match refresh_routes(&client, &base, &proxies, &api_key_selectors).await {
+                    Ok(count) => debug!("Refreshed routes from {} ({} active)", source.url, count),
+                    Err(e) => warn!(
+                        "Failed to refresh routes from {}: {}; keeping current routes",
+                        source.url, e
+                    ),
+                }
+            }
+            changed = shutdown_rx.changed() => {
+                if changed.is_err() || *shutdown_rx.borrow() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::GatewayMetrics;
+    use axum::body::Body;
+    use axum::http::{HeaderMap, Request, StatusCode};
+
+    /// Spawn a control plane serving `routes` to clients sending `Bearer secret`
+    async fn spawn_control_plane(routes: serde_json::Value) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = axum::Router::new().route(
+            "/routes",
+            axum::routing::get(move |headers: HeaderMap| async move {
+                if headers.get("authorization").and_then(|v| v.to_str().ok())
+                    != Some("Bearer secret")
+                {
+                    return Err(StatusCode::UNAUTHORIZED);
+                }
+                Ok(axum::Json(routes.clone()))
+            }),
+        );
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        addr
+    }
+
+    /// Spawn an upstream answering every request with `body`
+    async fn spawn_upstream(body: &'static str) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = axum::Router::new().fallback(move || async move { body });
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        addr
+    }
+
+    fn base_config(control_plane: std::net::SocketAddr, upstream: std::net::SocketAddr) -> String {
+        format!(
+            r#"
+[routes_source]
+url = "http://{}/routes"
+auth_header = "Bearer secret"
+
+[[routes]]
+name = "files"
+path = "/files/*"
+target = "http://{}"
+"#,
+            control_plane, upstream
+        )
+    }
+
+    fn server_proxy(config: &GatewayConfig) -> ServerProxy {
+        let server = config.get_servers()[0].clone();
+        let routes: Vec<RouteConfig> = config
+            .routes_for_server(&server)
+            .into_iter()
+            .cloned()
+            .collect();
+        let proxy = ProxyService::new(
+            ProxyService::routes_from_config(&routes, &HashMap::new()).unwrap(),
+            Arc::new(GatewayMetrics::new()),
+        )
+        .unwrap();
+        ServerProxy::new(server, Arc::new(proxy), &routes)
+    }
+
+    async fn get(proxy: &ProxyService, uri: &str) -> Option<String> {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = proxy.forward(req).await.ok()?;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        Some(String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_remote_routes_become_active() {
+        let files = spawn_upstream("files").await;
+        let users = spawn_upstream("users").await;
+        let control_plane = spawn_control_plane(serde_json::json!([
+            { "name": "users", "path": "/users/*", "target": format!("http://{}", users), "strip_prefix": true },
+            // Conflicts with the file route and is ignored
+            { "name": "files", "path": "/other/*", "target": format!("http://{}", users) },
+        ]))
+        .await;
+
+        let base = GatewayConfig::parse(&base_config(control_plane, files)).unwrap();
+        let server_proxy = server_proxy(&base);
+        let proxy = server_proxy.proxy.clone();
+        assert_eq!(get(&proxy, "/users/1").await, None);
+
+        let count = refresh_routes(
+            &reqwest::Client::new(),
+            &base,
+            &[server_proxy],
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(count, 2);
+
+        assert_eq!(get(&proxy, "/users/1").await.as_deref(), Some("users"));
+        assert_eq!(get(&proxy, "/files/a").await.as_deref(), Some("files"));
+        assert_eq!(get(&proxy, "/other/a").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_routes_keep_table_and_state() {
+        let files = spawn_upstream("files").await;
+        let mut base = GatewayConfig::parse(&base_config(files, files)).unwrap();
+        base.routes[0].circuit_breaker = Some(Default::default());
+        let server_proxy = server_proxy(&base);
+        let proxy = server_proxy.proxy.clone();
+        let table = proxy.get_routes();
+
+        // Nothing changed: the table isn't swapped
+        let mut merged = merge_routes(&base, Vec::new()).unwrap();
+        assert!(!server_proxy.apply(&merged.routes, &HashMap::new()).unwrap());
+        assert!(Arc::ptr_eq(&table, &proxy.get_routes()));
+
+        // A new route leaves the unchanged one as it was
+        let mut users = merged.routes[0].clone();
+        users.name = Some("users".into());
+        users.path = "/users/*".into();
+        merged.routes.push(users);
+        assert!(server_proxy.apply(&merged.routes, &HashMap::new()).unwrap());
+        let routes = proxy.get_routes();
+        assert_eq!(routes.len(), 2);
+        let breaker = |route: &ProxyRoute| route.circuit_breaker.clone().unwrap();
+        assert!(Arc::ptr_eq(&breaker(&table[0]), &breaker(&routes[0])));
+        assert!(!Arc::ptr_eq(&breaker(&table[0]), &breaker(&routes[1])));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_remote_routes_keep_current_table() {
+        let files = spawn_upstream("files").await;
+        let control_plane = spawn_control_plane(serde_json::json!([
+            { "path": "/users/*", "target": "http://localhost:1", "api_key_pool": "missing" },
+        ]))
+        .await;
+
+        let base = GatewayConfig::parse(&base_config(control_plane, files)).unwrap();
+        let server_proxy = server_proxy(&base);
+        let proxy = server_proxy.proxy.clone();
+
+        let result = refresh_routes(
+            &reqwest::Client::new(),
+            &base,
+            &[server_proxy],
+            &HashMap::new(),
+        )
+        .await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("unknown API key pool 'missing'"));
+        assert_eq!(proxy.get_routes().len(), 1);
+        assert_eq!(get(&proxy, "/files/a").await.as_deref(), Some("files"));
+    }
+
+    #[tokio::test]
+    async fn test_unloadable_schema_keeps_current_tables() {
+        let files = spawn_upstream("files").await;
+        let base = GatewayConfig::parse(&base_config(files, files)).unwrap();
+        let proxies = [server_proxy(&base), server_proxy(&base)];
+        let tables: Vec<_> = proxies.iter().map(|p| p.proxy.get_routes()).collect();
+
+        // The schema was valid at load but is gone by the time routes apply
+        let mut merged = merge_routes(&base, Vec::new()).unwrap();
+        merged.routes[0].body_schema = Some("/nonexistent/schema.json".into());
+        let err = apply_routes(&merged, &proxies, &HashMap::new()).unwrap_err();
+        assert!(err.to_string().contains("Failed to read schema"), "{}", err);
+        for (server_proxy, table) in proxies.iter().zip(&tables) {
+            assert!(Arc::ptr_eq(table, &server_proxy.proxy.get_routes()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_routes_falls_back_to_file_routes() {
+        let files = spawn_upstream("files").await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed = listener.local_addr().unwrap();
+        drop(listener);
+
+        let base = GatewayConfig::parse(&base_config(closed, files)).unwrap();
+        let loaded = load_routes(&base).await;
+        assert_eq!(loaded.routes.len(), 1);
+    }
+}
@@ -0,0 +1,180 @@
+//! Runtime state overlay module
+//!
+//! This module keeps operator toggles that are not part of the config file:
+//! - Routes and API keys are disabled in a JSON sidecar file, set with
+//!   `[runtime_state] path`, that is read on every (re)load and restart
+//! - The overlay is re-applied to the configuration on every (re)load, so
+//!   toggles survive hot reloads until they are removed from the file
+
+use crate::config::GatewayConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::info;
+
+/// Operator toggles layered over the configuration
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuntimeState {
+    /// Disabled routes, by name or path
+    #[serde(default)]
+    pub disabled_routes: BTreeSet<String>,
+    /// Disabled API keys, by pool name
+    #[serde(default)]
+    pub disabled_keys: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl RuntimeState {
+    /// Check if no toggles are set
+    pub fn is_empty(&self) -> bool {
+        self.disabled_routes.is_empty() && self.disabled_keys.is_empty()
+    }
+
+    /// Disable the routes in `config` matched by the overlay
+    pub fn apply(&self, config: &mut GatewayConfig) {
+        for route in &mut config.routes {
+            let disabled = self.disabled_routes.contains(&route.path)
+                || route
+                    .name
+                    .as_ref()
+                    .is_some_and(|name| self.disabled_routes.contains(name));
+            if disabled {
+                route.enabled = false;
+            }
+        }
+        for (pool_name, keys) in &self.disabled_keys {
+            if let Some(pool) = config.api_key_pools.get_mut(pool_name) {
+                for key in pool.keys.iter_mut().filter(|k| keys.contains(&k.key)) {
+                    key.enabled = false;
+                }
+            }
+        }
+    }
+}
+
+/// Runtime state shared across reloads
+#[derive(Debug, Default)]
+pub struct RuntimeStateStore {
+    state: Mutex<RuntimeState>,
+}
+
+impl RuntimeStateStore {
+    /// Create an empty in-memory store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the sidecar file at `path`, if set and it exists
+    ///
+    /// Called on every (re)load; the file replaces the in-memory state so
+    /// edits made to it while the gateway runs are picked up.
+    pub fn attach(&self, path: Option<&Path>) -> anyhow::Result<()> {
+        if let Some(path) = path.filter(|p| p.exists()) {
+            let contents = std::fs::read_to_string(path)?;
+            let state: RuntimeState = serde_json::from_str(&contents).map_err(|e| {
+                anyhow::anyhow!("Invalid runtime state file {}: {}", path.display(), e)
+            })?;
+            if !state.is_empty() {
+                info!("Loaded runtime state from {}", path.display());
+            }
+            *self.state.lock().unwrap() = state;
+        }
+        Ok(())
+    }
+
+    /// Apply the current state to a freshly loaded configuration
+    pub fn apply(&self, config: &mut GatewayConfig) {
+        self.state.lock().unwrap().apply(config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_key::ApiKeySelector;
+
+    const CONFIG: &str = r#"
+[[routes]]
+name = "users"
+path = "/users/*"
+target = "http://localhost:8081"
+
+[[routes]]
+path = "/orders/*"
+target = "http://localhost:8082"
+
+[api_key_pools.default]
+keys = [{ key = "key-a" }, { key = "key-b" }]
+"#;
+
+    /// Load the config as a reload would and apply the overlay
+    fn reload(store: &RuntimeStateStore) -> GatewayConfig {
+        let mut config = GatewayConfig::parse(CONFIG).unwrap();
+        store.apply(&mut config);
+        config
+    }
+
+    fn enabled_paths(config: &GatewayConfig) -> Vec<&str> {
+        config
+            .enabled_routes()
+            .iter()
+            .map(|r| r.path.as_str())
+            .collect()
+    }
+
+    /// Write the sidecar file and attach a store to it
+    fn attach(store: &RuntimeStateStore, path: &Path, state: &str) {
+        std::fs::write(path, state).unwrap();
+        store.attach(Some(path)).unwrap();
+    }
+
+    #[test]
+    fn test_disabled_route_survives_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let store = RuntimeStateStore::new();
+        attach(&store, &path, r#"{ "disabled_routes": ["users"] }"#);
+
+        for _ in 0..2 {
+            let config = reload(&store);
+            assert_eq!(enabled_paths(&config), vec!["/orders/*"]);
+        }
+
+        // Emptying the file re-enables the route on the next reload
+        attach(&store, &path, "{}");
+        assert_eq!(enabled_paths(&reload(&store)).len(), 2);
+    }
+
+    #[test]
+    fn test_disabled_key_survives_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let store = RuntimeStateStore::new();
+        attach(
+            &store,
+            &path,
+            r#"{ "disabled_keys": { "default": ["key-a"] } }"#,
+        );
+
+        let config = reload(&store);
+        let selector = ApiKeySelector::new(&config.api_key_pools["default"]);
+        assert_eq!(selector.len(), 1);
+        assert_eq!(selector.get_key(), Some("key-b"));
+    }
+
+    #[test]
+    fn test_state_kept_without_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let store = RuntimeStateStore::new();
+        attach(&store, &path, r#"{ "disabled_routes": ["/orders/*"] }"#);
+        assert_eq!(enabled_paths(&reload(&store)), vec!["/users/*"]);
+
+        // A reload dropping the path, or with the file gone, keeps the toggles
+        store.attach(None).unwrap();
+        assert_eq!(enabled_paths(&reload(&store)), vec!["/users/*"]);
+        std::fs::remove_file(&path).unwrap();
+        store.attach(Some(&path)).unwrap();
+        assert_eq!(enabled_paths(&reload(&store)), vec!["/users/*"]);
+    }
+}
@@ -0,0 +1,1335 @@
+//! Gateway server module
+//!
+//! This module builds and runs the gateway's HTTP servers:
+//! - [`build_router`] assembles one server's router (health, readiness,
+//!   metrics and admin endpoints, the proxy fallback and the master access
+//!   token guard), so embedders can add their own layers before serving it
+//! - Requests for an alias of `routing.canonical_host` are redirected to the
+//!   canonical host, except for the health and metrics endpoints
+//! - `forwarding.server_header` is applied to every response the router sends
+//! - [`run_gateway`] starts every configured server on the caller's tokio
+//!   runtime and stops them when the shutdown channel turns `true`
+//! - Servers with `socket_activation` listen on sockets passed by systemd
+//!   instead of binding their addresses
+//! - Each server counts its open connections and, with `max_connections`
+//!   set, holds further connections until one closes
+//! - The CLI `start` command loads the config file and calls [`run_gateway`]
+
+use crate::admin::{admin_router, AdminState, INFO_PATH};
+use crate::alerts;
+use crate::api_key::{create_selector, SharedApiKeySelector};
+use crate::config::{GatewayConfig, RouteConfig};
+use crate::dns::{self, DnsRefresher, SystemResolver};
+use crate::error_pages::ErrorPages;
+use crate::health::{jittered_interval, run_self_checks, HealthChecker, ReadinessCheck};
+use crate::metrics::{GatewayMetrics, OPENMETRICS_CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE};
+use crate::proxy::{self, ProxyService};
+use crate::routes_source::{self, ServerProxy};
+use crate::socket_activation::InheritedListeners;
+use crate::tls;
+use arc_swap::ArcSwap;
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
+use tower::Service;
+use tower_http::trace::TraceLayer;
+use tracing::{error, info, warn};
+
+/// Route count above which a warning is logged at startup
+const LARGE_ROUTE_TABLE: usize = 1000;
+
+/// Application state shared across a server's handlers
+#[derive(Clone)]
+pub struct AppState {
+    /// Proxy for the server's routes
+    pub proxy: Arc<ProxyService>,
+    /// Shared gateway metrics
+    pub metrics: Arc<GatewayMetrics>,
+    /// Shared health checker
+    pub health: Arc<HealthChecker>,
+    /// Error pages rendered for failed proxy requests
+    pub error_pages: Arc<ErrorPages>,
+    /// Active configuration, replaced when routes are reloaded in place
+    pub config: Arc<ArcSwap<GatewayConfig>>,
+}
+
+/// Build the router for one server
+///
+/// Gateway endpoints sit under `routing.base_path`, like the routes; every
+/// other request is forwarded by the state's proxy.
+pub fn build_router(state: AppState) -> Router {
+    let config = state.config.load();
+    let health_path = config.routing.endpoint_path(&config.health.path);
+    let ready_path = config.routing.endpoint_path(&config.health.ready_path);
+    let metrics_path = config.routing.endpoint_path(&config.metrics.path);
+
+    let mut app = Router::new()
+        .route(&health_path, get(health_handler))
+        .route(&ready_path, get(ready_handler))
+        .route(&metrics_path, get(metrics_handler));
+    if config.admin.enabled {
+        app = app.merge(admin_router(AdminState {
+            config: state.config.clone(),
+            metrics: state.metrics.clone(),
+            health: state.health.clone(),
+        }));
+    }
+    app.fallback(proxy_handler)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            master_access_token_guard,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            canonical_host_redirect,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            server_header_layer,
+        ))
+        .layer(
+            TraceLayer::new_for_http().make_span_with(|req: &Request<Body>| {
+                tracing::info_span!(
+                    "request",
+                    method = %req.method(),
+                    uri = %req.uri(),
+                    version = ?req.version(),
+                    auth_label = tracing::field::Empty,
+                )
+            }),
+        )
+        .with_state(state)
+}
+
+/// Make-service counting a server's connections and capping how many are open
+///
+/// axum's accept loop waits for [`ConnectionLimiter::call`] before serving a
+/// connection, so once the cap is reached new connections wait (in the
+/// accept loop, then the listen backlog) until an open one closes.
+#[derive(Clone)]
+pub struct ConnectionLimiter {
+    router: Router,
+    permits: Option<Arc<Semaphore>>,
+    metrics: Arc<GatewayMetrics>,
+    server: String,
+}
+
+impl ConnectionLimiter {
+    /// Serve `router`, allowing at most `max_connections` open connections
+    pub fn new(
+        router: Router,
+        max_connections: Option<usize>,
+        metrics: Arc<GatewayMetrics>,
+        server: String,
+    ) -> Self {
+        Self {
+            router,
+            permits: max_connections.map(|max| Arc::new(Semaphore::new(max))),
+            metrics,
+            server,
+        }
+    }
+}
+
+impl<'a> Service<axum::serve::IncomingStream<'a>> for ConnectionLimiter {
+    type Response = LimitedConnection;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<LimitedConnection, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _stream: axum::serve::IncomingStream<'a>) -> Self::Future {
+        let limiter = self.clone();
+        Box::pin(async move {
+            let permit = match limiter.permits {
+                Some(permits) => permits.acquire_owned().await.ok(),
+                None => None,
+            };
+            limiter.metrics.inc_server_connections(&limiter.server);
+            Ok(LimitedConnection {
+                router: limiter.router,
+                _guard: Arc::new(ConnectionGuard {
+                    _permit: permit,
+                    metrics: limiter.metrics,
+                    server: limiter.server,
+                }),
+            })
+        })
+    }
+}
+
+/// Service for one connection, releasing its slot once the connection and
+/// its in-flight requests are done
+#[derive(Clone)]
+pub struct LimitedConnection {
+    router: Router,
+    _guard: Arc<ConnectionGuard>,
+}
+
+impl Service<Request<Body>> for LimitedConnection {
+    type Response = Response;
+    type Error = Infallible;
+    type Future = <Router as Service<Request<Body>>>::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Service::<Request<Body>>::poll_ready(&mut self.router, cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        self.router.call(req)
+    }
+}
+
+/// Connection slot held for the lifetime of a connection
+struct ConnectionGuard {
+    _permit: Option<OwnedSemaphorePermit>,
+    metrics: Arc<GatewayMetrics>,
+    server: String,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.metrics.dec_server_connections(&self.server);
+    }
+}
+
+/// Resolve the host of every route target, failing on the first that
+/// doesn't resolve
+///
+/// Literal IP addresses are skipped, as are mock routes (which have no
+/// upstream). Aggregate sub-request targets are resolved too.
+pub async fn resolve_upstreams(routes: &[RouteConfig]) -> anyhow::Result<()> {
+    for route in routes.iter().filter(|r| r.mock.is_none()) {
+        let targets = std::iter::once(route.target.as_str())
+            .filter(|target| !target.is_empty())
+            .chain(route.aggregate.iter().map(|sub| sub.target.as_str()));
+        for target in targets {
+            let uri: axum::http::Uri = target.parse().map_err(|e| {
+                anyhow::anyhow!(
+                    "Route '{}' target '{}' is invalid: {}",
+                    route.path,
+                    target,
+                    e
+                )
+            })?;
+            let Some(host) = uri.host() else {
+                continue;
+            };
+            let bare = host.trim_start_matches('[').trim_end_matches(']');
+            if bare.parse::<std::net::IpAddr>().is_ok() {
+                continue;
+            }
+            let port = uri.port_u16().unwrap_or(match uri.scheme_str() {
+                Some("https") => 443,
+                _ => 80,
+            });
+            let resolved = tokio::net::lookup_host((host, port))
+                .await
+                .map(|mut addrs| addrs.next().is_some());
+            match resolved {
+                Ok(true) => {}
+                Ok(false) => anyhow::bail!(
+                    "Route '{}' target host '{}' resolved to no addresses",
+                    route.path,
+                    host
+                ),
+                Err(e) => anyhow::bail!(
+                    "Route '{}' target host '{}' could not be resolved: {}",
+                    route.path,
+                    host,
+                    e
+                ),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Run all servers from a configuration until `shutdown` turns `true`
+///
+/// Routes from `routes_source` are merged in (and kept up to date) here, so
+/// `config` is the configuration as loaded from the file.
+pub async fn run_gateway(
+    config: GatewayConfig,
+    shutdown_rx: watch::Receiver<bool>,
+) -> anyhow::Result<()> {
+    let (_reload_tx, reload_rx) = watch::channel(config.clone());
+    run_gateway_with_reload(config, shutdown_rx, reload_rx).await
+}
+
+/// Run all servers like [`run_gateway`], swapping their route tables in
+/// place whenever `reload_rx` receives a new configuration
+///
+/// Only the routes, API key pools and upstream pools of a received
+/// configuration are applied to the proxies, and the gateway endpoints
+/// describe it from then on; use [`GatewayConfig::reloads_in_place`] to
+/// decide whether a change can be sent here or needs a restart. Requests in flight finish on
+/// the route table they started with.
+pub async fn run_gateway_with_reload(
+    config: GatewayConfig,
+    mut shutdown_rx: watch::Receiver<bool>,
+    reload_rx: watch::Receiver<GatewayConfig>,
+) -> anyhow::Result<()> {
+    let file_config = config;
+
+    // Merge routes from the control plane, if configured
+    let config = routes_source::load_routes(&file_config).await;
+
+    // Create API key selectors
+    let api_key_selectors: HashMap<String, SharedApiKeySelector> = config
+        .api_key_pools
+        .iter()
+        .map(|(name, pool)| (name.clone(), create_selector(pool)))
+        .collect();
+
+    // Create shared metrics
+    let metrics = Arc::new(GatewayMetrics::with_buckets(
+        config.metrics.latency_buckets.clone(),
+    ));
+
+    // Load error pages
+    let error_pages = Arc::new(ErrorPages::load(&config.errors)?);
+    if !error_pages.is_empty() {
+        info!("Error pages loaded: {}", error_pages.len());
+    }
+
+    // Load the trusted roots for HTTPS upstreams
+    let tls = tls::client_config(&config.tls)?;
+
+    // Create shared health checker; not ready until the self-checks have run
+    let health = Arc::new(HealthChecker::new());
+    health.set_ready(false);
+
+    // Get all servers to start, dependencies first
+    let servers = config.servers_in_startup_order()?;
+    info!("Starting {} server(s)", servers.len());
+    info!("Routes configured: {}", config.routes.len());
+    if config.routes.len() > LARGE_ROUTE_TABLE {
+        warn!(
+            "{} routes configured; large route tables increase memory use and reload time",
+            config.routes.len()
+        );
+    }
+    info!("API key pools configured: {}", config.api_key_pools.len());
+    if config.master_access_token.enabled {
+        info!(
+            "Master access token guard enabled (header: {})",
+            config.master_access_token.header_name
+        );
+    }
+
+    // Catch typos in upstream hostnames before any server starts
+    for server in servers.iter().filter(|s| s.resolve_upstreams_on_start) {
+        let routes: Vec<RouteConfig> = config
+            .routes_for_server(server)
+            .into_iter()
+            .cloned()
+            .collect();
+        resolve_upstreams(&routes).await?;
+    }
+
+    // Spawn a task for each server
+    let mut handles = Vec::new();
+    let mut server_proxies = Vec::new();
+    let mut prewarming = Vec::new();
+    let mut inherited_listeners = InheritedListeners::from_env();
+
+    // Configuration read by the servers' endpoints, updated on reload
+    let active_config = Arc::new(ArcSwap::from_pointee(config.clone()));
+
+    for server in servers {
+        // Get routes for this server
+        let server_routes: Vec<_> = config
+            .routes_for_server(server)
+            .into_iter()
+            .cloned()
+            .collect();
+
+        let proxy_routes = ProxyService::routes_from_config(&server_routes, &api_key_selectors)?;
+        let proxy = Arc::new(
+            ProxyService::with_tls(proxy_routes, metrics.clone(), tls.clone())
+                .with_upstream_pools(&config.upstream_pools)
+                .with_client(config.client.clone())
+                .with_forwarding(config.forwarding.clone())
+                .with_routing(config.routing.clone())
+                .with_debug_headers(config.debug_headers.clone())
+                .with_limits(config.limits.clone())
+                .with_access_log(config.access_log.clone()),
+        );
+
+        server_proxies.push(ServerProxy::new(
+            server.clone(),
+            proxy.clone(),
+            &server_routes,
+        ));
+
+        // Keep idle upstream connections open for routes asking for them
+        let prewarm = server_routes.iter().any(|r| r.prewarm_connections > 0);
+        if prewarm {
+            tokio::spawn(proxy::run_prewarm(proxy.clone(), shutdown_rx.clone()));
+        }
+        prewarming.push(prewarm);
+
+        // Follow upstreams whose addresses change behind short DNS TTLs
+        if let Some(interval) = config.client.dns_refresh_interval {
+            let refresher = DnsRefresher::new(
+                Arc::new(SystemResolver),
+                config.client.recycle_on_dns_change,
+            );
+            tokio::spawn(dns::run_dns_refresh(
+                proxy.clone(),
+                refresher,
+                std::time::Duration::from_secs(interval),
+                shutdown_rx.clone(),
+            ));
+        }
+
+        // Reconnect to upstreams periodically
+        if let Some(lifetime) = config.client.connection_max_lifetime {
+            tokio::spawn(proxy::run_connection_recycle(
+                proxy.clone(),
+                std::time::Duration::from_secs(lifetime),
+                shutdown_rx.clone(),
+            ));
+        }
+
+        let app = build_router(AppState {
+            proxy,
+            metrics: metrics.clone(),
+            health: health.clone(),
+            error_pages: error_pages.clone(),
+            config: active_config.clone(),
+        });
+
+        // Get server addresses
+        let addrs = server
+            .bind_addrs()
+            .iter()
+            .map(|addr| addr.parse())
+            .collect::<Result<Vec<SocketAddr>, _>>()?;
+        let server_name = server.display_name();
+
+        info!(
+            "Starting server '{}' on {} with {} route(s)",
+            server_name,
+            addrs
+                .iter()
+                .map(SocketAddr::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+            server_routes.len()
+        );
+
+        if let Some(max_connections) = server.max_connections {
+            info!("  At most {} open connection(s)", max_connections);
+        }
+        if config.health.enabled {
+            info!(
+                "  Health endpoints at {} and {}",
+                config.routing.endpoint_path(&config.health.path),
+                config.routing.endpoint_path(&config.health.ready_path)
+            );
+        }
+        if config.metrics.enabled {
+            info!(
+                "  Metrics endpoint at {}",
+                config.routing.endpoint_path(&config.metrics.path)
+            );
+        }
+        if config.admin.enabled {
+            info!(
+                "  Admin endpoints at {} and {}",
+                config.routing.endpoint_path("/admin/*"),
+                config.routing.endpoint_path(INFO_PATH)
+            );
+        }
+
+        // Bind before spawning so servers that depend on this one only
+        // start once it is accepting connections; socket-activated servers
+        // listen on the inherited sockets instead, while any are left
+        let mut listeners = Vec::new();
+        for addr in addrs {
+            let inherited = if server.socket_activation {
+                inherited_listeners.next_listener()
+            } else {
+                None
+            };
+            let listener = match inherited {
+                Some(listener) => {
+                    let listener = listener?;
+                    info!(
+                        "  Listening on inherited socket {} in place of {}",
+                        listener.local_addr()?,
+                        addr
+                    );
+                    listener
+                }
+                None => {
+                    if server.socket_activation {
+                        warn!("  No inherited socket left for {}, binding it", addr);
+                    }
+                    tokio::net::TcpListener::bind(addr).await?
+                }
+            };
+            listeners.push(listener);
+        }
+
+        // Spawn a task per listener with graceful shutdown support; they
+        // share the router and the connection limit
+        let connections = ConnectionLimiter::new(
+            app,
+            server.max_connections,
+            metrics.clone(),
+            server_name.clone(),
+        );
+        for listener in listeners {
+            let server_shutdown_rx = shutdown_rx.clone();
+            let connections = connections.clone();
+            let handle = tokio::spawn(async move {
+                axum::serve(listener, connections)
+                    .with_graceful_shutdown(async move {
+                        // A closed channel is reported by `run_gateway`
+                        let mut rx = server_shutdown_rx;
+                        let _ = shutdown_requested(&mut rx).await;
+                    })
+                    .await?;
+                Ok::<(), anyhow::Error>(())
+            });
+            handles.push(handle);
+        }
+    }
+
+    let server_count = server_proxies.len();
+
+    // Apply route and key pool changes without restarting the servers
+    tokio::spawn(run_route_reloads(
+        reload_rx.clone(),
+        active_config,
+        server_proxies.clone(),
+        prewarming,
+        shutdown_rx.clone(),
+    ));
+
+    // Keep remote routes up to date until the servers stop
+    if config.routes_source.is_some() {
+        tokio::spawn(routes_source::poll_routes_source(
+            file_config,
+            server_proxies,
+            api_key_selectors.clone(),
+            shutdown_rx.clone(),
+        ));
+    }
+
+    // Watch route error rates until the servers stop
+    if config.alerts.webhook_url.is_some() {
+        info!(
+            "Alerting on route error rates above {:.0}% over {}s",
+            config.alerts.error_rate_threshold * 100.0,
+            config.alerts.window_secs
+        );
+        tokio::spawn(alerts::run_alerts(
+            config.alerts.clone(),
+            metrics.clone(),
+            shutdown_rx.clone(),
+        ));
+    }
+
+    // All servers are bound; run the self-checks that gate readiness, then
+    // repeat them on the probe interval, if any
+    let mut checks_config = config.clone();
+    let mut checks_reload_rx = reload_rx;
+    let checks_health = health.clone();
+    let mut checks_shutdown_rx = shutdown_rx.clone();
+    tokio::spawn(async move {
+        loop {
+            // Check the routes reloaded in place, if any
+            if matches!(checks_reload_rx.has_changed(), Ok(true)) {
+                checks_config = checks_reload_rx.borrow_and_update().clone();
+            }
+            let mut checks = vec![ReadinessCheck::pass(
+                "servers",
+                format!("{} server(s) bound", server_count),
+            )];
+            checks.extend(run_self_checks(&checks_config).await);
+            for check in checks.iter().filter(|c| !c.passed) {
+                warn!(
+                    "Readiness check '{}' failed: {}",
+                    check.name,
+                    check.message.as_deref().unwrap_or("")
+                );
+            }
+            checks_health.set_checks(checks);
+            checks_health.set_ready(true);
+
+            let Some(interval) = checks_config.health.upstream.interval_secs else {
+                break;
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(jittered_interval(std::time::Duration::from_secs(interval))) => {}
+                changed = checks_shutdown_rx.changed() => {
+                    if changed.is_err() || *checks_shutdown_rx.borrow() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    // Wait for shutdown signal or server error
+    tokio::select! {
+        requested = shutdown_requested(&mut shutdown_rx) => {
+            if requested.is_err() {
+                error!("Shutdown channel closed without a shutdown signal, stopping servers");
+                anyhow::bail!("shutdown channel closed without a shutdown signal");
+            }
+            info!("Shutdown signal received, stopping servers...");
+        }
+        result = async {
+            for handle in handles {
+                handle.await??;
+            }
+            Ok::<(), anyhow::Error>(())
+        } => {
+            return result;
+        }
+    }
+
+    Ok(())
+}
+
+/// Wait until `shutdown_rx` turns `true`
+///
+/// Fails if the sender is dropped first: a closed channel is a bug in the
+/// caller, not a request to stop.
+async fn shutdown_requested(
+    shutdown_rx: &mut watch::Receiver<bool>,
+) -> Result<(), watch::error::RecvError> {
+    loop {
+        if *shutdown_rx.borrow_and_update() {
+            return Ok(());
+        }
+        shutdown_rx.changed().await?;
+    }
+}
+
+/// Swap each server's route table to the routes of every configuration
+/// `reload_rx` receives, until `shutdown_rx` turns `true`
+///
+/// Upstream pool clients are rebuilt when their settings changed, and the
+/// configuration is stored in `active_config` for the gateway endpoints.
+///
+/// `prewarming` tells, per proxy, whether its prewarm task is running; one
+/// is started when reloaded routes ask for prewarmed connections. Servers
+/// that resolve their upstreams on start keep the current routes when a
+/// reloaded target doesn't resolve.
+async fn run_route_reloads(
+    mut reload_rx: watch::Receiver<GatewayConfig>,
+    active_config: Arc<ArcSwap<GatewayConfig>>,
+    proxies: Vec<ServerProxy>,
+    mut prewarming: Vec<bool>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    // The initial configuration is already applied
+    reload_rx.borrow_and_update();
+    loop {
+        tokio::select! {
+            changed = reload_rx.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+            }
+            changed = shutdown_rx.changed() => {
+                if changed.is_err() || *shutdown_rx.borrow() {
+                    break;
+                }
+                continue;
+            }
+        }
+        let config = reload_rx.borrow_and_update().clone();
+
+        let mut unresolved = None;
+        for server_proxy in proxies
+            .iter()
+            .filter(|p| p.server.resolve_upstreams_on_start)
+        {
+            let routes: Vec<RouteConfig> = config
+                .routes_for_server(&server_proxy.server)
+                .into_iter()
+                .cloned()
+                .collect();
+            if let Err(e) = resolve_upstreams(&routes).await {
+                unresolved = Some(e);
+                break;
+            }
+        }
+        if let Some(e) = unresolved {
+            warn!("Not reloading routes: {}; keeping current routes", e);
+            continue;
+        }
+
+        let api_key_selectors: HashMap<String, SharedApiKeySelector> = config
+            .api_key_pools
+            .iter()
+            .map(|(name, pool)| (name.clone(), create_selector(pool)))
+            .collect();
+        if let Err(e) = routes_source::apply_routes(&config, &proxies, &api_key_selectors) {
+            warn!("Not reloading routes: {}; keeping current routes", e);
+            continue;
+        }
+        for server_proxy in &proxies {
+            server_proxy
+                .proxy
+                .set_upstream_pools(&config.upstream_pools);
+        }
+        active_config.store(Arc::new(config.clone()));
+        info!(
+            "Routes reloaded in place: {} route(s), {} API key pool(s)",
+            config.routes.len(),
+            config.api_key_pools.len()
+        );
+
+        for (server_proxy, prewarm) in proxies.iter().zip(prewarming.iter_mut()) {
+            let wanted = server_proxy
+                .proxy
+                .get_routes()
+                .iter()
+                .any(|r| r.prewarm_connections > 0);
+            if wanted && !*prewarm {
+                tokio::spawn(proxy::run_prewarm(
+                    server_proxy.proxy.clone(),
+                    shutdown_rx.clone(),
+                ));
+                *prewarm = true;
+            }
+        }
+    }
+}
+
+/// Master access token guard middleware
+///
+/// When enabled, this middleware validates that incoming requests include a valid
+/// access token in the configured header. This applies to ALL endpoints including
+/// health checks and metrics endpoints for maximum security.
+///
+/// If you need to exclude health/metrics from authentication, consider running
+/// a separate server instance without the guard for internal monitoring.
+async fn master_access_token_guard(
+    State(state): State<AppState>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let config = state.config.load();
+    let guard = &config.master_access_token;
+
+    // If guard is not enabled, pass through
+    if !guard.enabled {
+        return next.run(req).await;
+    }
+
+    // Get the token from the configured header
+    let token = req
+        .headers()
+        .get(&guard.header_name)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+
+    // Validate the token and attribute the request to its label
+    match guard.matching_token(token) {
+        Some(matched) => {
+            let label = matched.label();
+            tracing::Span::current().record("auth_label", label);
+            state.metrics.record_auth_request(label);
+            next.run(req).await
+        }
+        None => (StatusCode::UNAUTHORIZED, "Invalid or missing access token").into_response(),
+    }
+}
+
+/// Canonical host redirect middleware
+///
+/// Requests whose `Host` is one of `routing.canonical_host.aliases` are
+/// redirected to the canonical host with the same path and query. Health,
+/// readiness and metrics requests are served on any host, so probes and
+/// scrapers addressing the gateway by IP keep working.
+async fn canonical_host_redirect(
+    State(state): State<AppState>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let config = state.config.load();
+    let routing = &config.routing;
+    let Some(canonical) = &routing.canonical_host else {
+        return next.run(req).await;
+    };
+    let host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| req.uri().host());
+    if !host.is_some_and(|host| canonical.is_alias(host)) {
+        return next.run(req).await;
+    }
+    let path = req.uri().path();
+    let exempt = [
+        &config.health.path,
+        &config.health.ready_path,
+        &config.metrics.path,
+    ]
+    .into_iter()
+    .any(|endpoint| routing.endpoint_path(endpoint) == path);
+    if exempt {
+        return next.run(req).await;
+    }
+
+    let path_and_query = req
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+    let location = format!(
+        "{}://{}{}",
+        canonical.scheme, canonical.host, path_and_query
+    );
+    let status = StatusCode::from_u16(canonical.status).unwrap_or(StatusCode::PERMANENT_REDIRECT);
+    (status, [(header::LOCATION, location)]).into_response()
+}
+
+/// `Server` header middleware
+///
+/// Applies `forwarding.server_header` to every response: proxied ones, the
+/// gateway endpoints' and errors, including the guard's and redirects.
+async fn server_header_layer(
+    State(state): State<AppState>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let mut response = next.run(req).await;
+    if let Some(setting) = &state.config.load().forwarding.server_header {
+        let headers = response.headers_mut();
+        headers.remove(header::SERVER);
+        if let Some(value) = setting.value().and_then(|v| v.parse().ok()) {
+            headers.insert(header::SERVER, value);
+        }
+    }
+    response
+}
+
+/// Health check handler
+async fn health_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let health = state.health.liveness();
+    (health.status_code(), Json(health))
+}
+
+/// Readiness handler
+async fn ready_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let health = state.health.readiness();
+    (health.status_code(), Json(health))
+}
+
+/// Metrics handler
+///
+/// Serves the OpenMetrics format when the `Accept` header asks for it, and
+/// the Prometheus text format otherwise.
+async fn metrics_handler(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let (content_type, output) = if accepts_openmetrics(accept) {
+        (OPENMETRICS_CONTENT_TYPE, state.metrics.openmetrics_output())
+    } else {
+        match state.metrics.prometheus_output() {
+            Ok(output) => (PROMETHEUS_CONTENT_TYPE, output),
+            Err(e) => {
+                error!("{}", e);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to encode metrics",
+                )
+                    .into_response();
+            }
+        }
+    };
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, content_type)],
+        output,
+    )
+        .into_response()
+}
+
+/// Check if an `Accept` header lists the OpenMetrics format (with a non-zero q)
+fn accepts_openmetrics(accept: &str) -> bool {
+    accept.split(',').any(|range| {
+        let mut params = range.split(';').map(str::trim);
+        let media_type = params.next().unwrap_or("");
+        media_type.eq_ignore_ascii_case("application/openmetrics-text")
+            && !params.any(|param| {
+                param
+                    .strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .is_some_and(|q| q == 0.0)
+            })
+    })
+}
+
+/// Proxy handler - forwards requests to target services
+async fn proxy_handler(State(state): State<AppState>, req: Request<Body>) -> impl IntoResponse {
+    let accept = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    match state.proxy.forward(req).await {
+        Ok(response) => response.into_response(),
+        Err(err) => state.error_pages.render(&err, accept.as_deref()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admin::CONFIG_PATH;
+    use tower::ServiceExt;
+
+    /// Build a server router for a configuration, proxying to `upstream`
+    async fn router(config: &str) -> Router {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, Router::new().fallback(|| async { "upstream" }))
+                .await
+                .unwrap();
+        });
+
+        let config = GatewayConfig::parse(&format!(
+            "{}\n[[routes]]\npath = \"/api/*\"\ntarget = \"http://{}\"",
+            config, upstream
+        ))
+        .unwrap();
+        let metrics = Arc::new(GatewayMetrics::new());
+        let proxy = ProxyService::new(
+            ProxyService::routes_from_config(&config.routes, &HashMap::new()).unwrap(),
+            metrics.clone(),
+        )
+        .unwrap();
+        build_router(AppState {
+            proxy: Arc::new(proxy),
+            metrics,
+            health: Arc::new(HealthChecker::new()),
+            error_pages: Arc::new(ErrorPages::default()),
+            config: Arc::new(ArcSwap::from_pointee(config)),
+        })
+    }
+
+    async fn send(app: Router, uri: &str, token: Option<&str>) -> (StatusCode, String) {
+        let mut req = Request::builder().uri(uri);
+        if let Some(token) = token {
+            req = req.header(header::AUTHORIZATION, token);
+        }
+        let response = app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    #[tokio::test]
+    async fn test_build_router_serves_endpoints_and_routes() {
+        let app = router("").await;
+        assert_eq!(send(app.clone(), "/health", None).await.0, StatusCode::OK);
+        assert_eq!(
+            send(app.clone(), "/api/users", None).await,
+            (StatusCode::OK, "upstream".to_string())
+        );
+        assert_eq!(send(app, "/missing", None).await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_build_router_applies_master_access_token_guard() {
+        let app = router("[master_access_token]\nenabled = true\ntokens = [\"Bearer t\"]").await;
+        assert_eq!(
+            send(app.clone(), "/api/users", None).await.0,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            send(app, "/api/users", Some("Bearer t")).await.0,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_canonical_host_redirect() {
+        let app = router(
+            "[routing.canonical_host]\nhost = \"example.com\"\naliases = [\"www.example.com\", \"203.0.113.10\"]",
+        )
+        .await;
+        let send_to = |host: &'static str, uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let req = Request::builder()
+                    .uri(uri)
+                    .header(header::HOST, host)
+                    .body(Body::empty())
+                    .unwrap();
+                app.oneshot(req).await.unwrap()
+            }
+        };
+
+        for host in ["www.example.com", "WWW.example.com:8080", "203.0.113.10"] {
+            let response = send_to(host, "/api/users?page=2").await;
+            assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+            assert_eq!(
+                response.headers()[header::LOCATION],
+                "https://example.com/api/users?page=2"
+            );
+        }
+
+        // The canonical host is proxied, and probes work on any host
+        let response = send_to("example.com", "/api/users").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send_to("203.0.113.10", "/health").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send_to("203.0.113.10", "/metrics").await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_server_header_on_every_response() {
+        let server = |app: Router, uri: &'static str| async move {
+            let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let response = app.oneshot(req).await.unwrap();
+            let status = response.status();
+            let values: Vec<String> = response
+                .headers()
+                .get_all(header::SERVER)
+                .iter()
+                .map(|v| v.to_str().unwrap().to_string())
+                .collect();
+            (status, values)
+        };
+
+        let app = router("[forwarding]\nserver_header = \"edge/2\"").await;
+        for (uri, status) in [
+            ("/api/users", StatusCode::OK),
+            ("/missing", StatusCode::NOT_FOUND),
+            ("/health", StatusCode::OK),
+        ] {
+            assert_eq!(
+                server(app.clone(), uri).await,
+                (status, vec!["edge/2".to_string()])
+            );
+        }
+
+        // Errors from the guard get it too
+        let app = router(
+            "[forwarding]\nserver_header = true\n\n[master_access_token]\nenabled = true\ntokens = [\"Bearer t\"]",
+        )
+        .await;
+        assert_eq!(
+            server(app, "/api/users").await,
+            (StatusCode::UNAUTHORIZED, vec!["open-gateway".to_string()])
+        );
+
+        let app = router("").await;
+        assert!(server(app, "/missing").await.1.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_metrics_content_negotiation() {
+        let app = router("").await;
+        let scrape = |accept: &'static str| {
+            let app = app.clone();
+            async move {
+                let req = Request::builder()
+                    .uri("/metrics")
+                    .header(header::ACCEPT, accept)
+                    .body(Body::empty())
+                    .unwrap();
+                let response = app.oneshot(req).await.unwrap();
+                let content_type = response.headers()[header::CONTENT_TYPE].clone();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (content_type, String::from_utf8_lossy(&body).into_owned())
+            }
+        };
+
+        let (content_type, body) =
+            scrape("application/openmetrics-text;version=1.0.0,text/plain;q=0.5").await;
+        assert_eq!(content_type, OPENMETRICS_CONTENT_TYPE);
+        assert!(body.ends_with("# EOF\n"), "{}", body);
+
+        let (content_type, body) = scrape("text/plain").await;
+        assert_eq!(content_type, PROMETHEUS_CONTENT_TYPE);
+        assert!(!body.contains("# EOF"));
+
+        let (content_type, _) = scrape("application/openmetrics-text;q=0").await;
+        assert_eq!(content_type, PROMETHEUS_CONTENT_TYPE);
+    }
+
+    #[tokio::test]
+    async fn test_connections_over_limit_wait() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpStream;
+
+        async fn request(conn: &mut TcpStream) {
+            conn.write_all(b"GET / HTTP/1.1\r\nhost: gateway\r\n\r\n")
+                .await
+                .unwrap();
+        }
+        async fn response(conn: &mut TcpStream) -> String {
+            let mut received = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !received.ends_with(b"ok") {
+                let n = conn.read(&mut buf).await.unwrap();
+                assert!(n > 0, "connection closed");
+                received.extend_from_slice(&buf[..n]);
+            }
+            String::from_utf8_lossy(&received).into_owned()
+        }
+
+        let metrics = Arc::new(GatewayMetrics::new());
+        let app = Router::new().route("/", get(|| async { "ok" }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connections = ConnectionLimiter::new(app, Some(2), metrics.clone(), "edge".into());
+        tokio::spawn(async move { axum::serve(listener, connections).await });
+
+        let mut first = TcpStream::connect(addr).await.unwrap();
+        let mut second = TcpStream::connect(addr).await.unwrap();
+        for conn in [&mut first, &mut second] {
+            request(conn).await;
+            assert!(response(conn).await.starts_with("HTTP/1.1 200 OK"));
+        }
+        assert_eq!(metrics.server_connections("edge"), 2);
+
+        // The third connection isn't served while the cap is reached
+        let mut third = TcpStream::connect(addr).await.unwrap();
+        request(&mut third).await;
+        let waiting =
+            tokio::time::timeout(std::time::Duration::from_millis(300), response(&mut third)).await;
+        assert!(waiting.is_err());
+
+        // Closing a connection lets it through
+        drop(first);
+        let served =
+            tokio::time::timeout(std::time::Duration::from_secs(5), response(&mut third)).await;
+        assert!(served.unwrap().starts_with("HTTP/1.1 200 OK"));
+        assert_eq!(metrics.server_connections("edge"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_upstreams_on_start() {
+        let config = GatewayConfig::parse(
+            r#"
+[server]
+host = "127.0.0.1"
+port = 0
+resolve_upstreams_on_start = true
+
+[tls]
+native_roots = false
+
+[[routes]]
+path = "/ip/*"
+target = "http://127.0.0.1:9"
+
+[[routes]]
+path = "/typo/*"
+target = "http://upstream-typo.invalid:8080"
+"#,
+        )
+        .unwrap();
+        assert!(resolve_upstreams(&config.routes[..1]).await.is_ok());
+
+        let (_shutdown_tx, shutdown_rx) = watch::channel(false);
+        let err = tokio::time::timeout(
+            std::time::Duration::from_secs(10),
+            run_gateway(config, shutdown_rx),
+        )
+        .await
+        .expect("startup did not fail")
+        .unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("Route '/typo/*'"), "{}", message);
+        assert!(message.contains("upstream-typo.invalid"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_server_listens_on_every_host() {
+        let port = {
+            let probe = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            probe.local_addr().unwrap().port()
+        };
+        let config = GatewayConfig::parse(&format!(
+            "[server]\nhosts = [\"127.0.0.1\", \"127.0.0.2\"]\nport = {}\n\n[tls]\nnative_roots = false",
+            port
+        ))
+        .unwrap();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let gateway = tokio::spawn(run_gateway(config, shutdown_rx));
+
+        let client = reqwest::Client::new();
+        for host in ["127.0.0.1", "127.0.0.2"] {
+            let url = format!("http://{}:{}/health", host, port);
+            let mut response = client.get(&url).send().await;
+            for _ in 0..50 {
+                if response.is_ok() {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                response = client.get(&url).send().await;
+            }
+            assert_eq!(response.unwrap().status(), StatusCode::OK, "{}", url);
+        }
+
+        shutdown_tx.send(true).unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(5), gateway)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_admin_endpoints_follow_reloads() {
+        let port = {
+            let probe = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            probe.local_addr().unwrap().port()
+        };
+        let config = GatewayConfig::parse(&format!(
+            "[server]\nhost = \"127.0.0.1\"\nport = {}\n\n[tls]\nnative_roots = false\n\n[master_access_token]\nenabled = true\ntokens = [\"Bearer t\"]\n\n[admin]\nenabled = true\n\n[[routes]]\nname = \"api\"\npath = \"/api/*\"\ntarget = \"http://localhost:3000\"\n",
+            port
+        ))
+        .unwrap();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let (reload_tx, reload_rx) = watch::channel(config.clone());
+        let gateway = tokio::spawn(run_gateway_with_reload(
+            config.clone(),
+            shutdown_rx,
+            reload_rx,
+        ));
+
+        let client = reqwest::Client::new();
+        let fetch = |path: &'static str| {
+            let client = client.clone();
+            async move {
+                let url = format!("http://127.0.0.1:{}{}", port, path);
+                for _ in 0..50 {
+                    if let Ok(response) = client
+                        .get(&url)
+                        .header(header::AUTHORIZATION, "Bearer t")
+                        .send()
+                        .await
+                    {
+                        return response;
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                }
+                panic!("{} not reachable", url);
+            }
+        };
+        let target = |config: serde_json::Value| config["routes"][0]["target"].clone();
+        let current: serde_json::Value = fetch(CONFIG_PATH).await.json().await.unwrap();
+        assert_eq!(target(current), "http://localhost:3000");
+
+        let mut next = config.clone();
+        next.routes[0].target = "http://localhost:4000".to_string();
+        next.api_key_pools =
+            GatewayConfig::parse("[api_key_pools.added]\nkeys = [{ key = \"sk-added-key-0001\" }]")
+                .unwrap()
+                .api_key_pools;
+        assert!(config.reloads_in_place(&next));
+        reload_tx.send(next).unwrap();
+
+        let mut reloaded = serde_json::Value::Null;
+        for _ in 0..50 {
+            reloaded = fetch(CONFIG_PATH).await.json().await.unwrap();
+            if target(reloaded.clone()) != "http://localhost:3000" {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(target(reloaded), "http://localhost:4000");
+        let sample = fetch("/admin/pools/added/sample?n=10").await;
+        assert_eq!(sample.status(), StatusCode::OK);
+
+        shutdown_tx.send(true).unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(5), gateway)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_bind_addresses_rejected() {
+        let err = GatewayConfig::parse(
+            "[[servers]]\nname = \"a\"\nhosts = [\"10.0.0.1\", \"127.0.0.1\"]\nport = 8080\n\n[[servers]]\nname = \"b\"\nhost = \"127.0.0.1\"\nport = 8080\n",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("127.0.0.1:8080"), "{}", err);
+
+        let config =
+            GatewayConfig::parse("[server]\nhosts = [\"10.0.0.1\", \"::1\"]\nport = 8080\n")
+                .unwrap();
+        assert_eq!(config.server.bind_addrs(), ["10.0.0.1:8080", "[::1]:8080"]);
+    }
+
+    #[tokio::test]
+    async fn test_run_gateway_stops_on_shutdown() {
+        let config = GatewayConfig::parse(
+            "[server]\nhost = \"127.0.0.1\"\nport = 0\n\n[tls]\nnative_roots = false",
+        )
+        .unwrap();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let gateway = tokio::spawn(run_gateway(config, shutdown_rx));
+
+        shutdown_tx.send(true).unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(5), gateway)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dropped_shutdown_sender_is_an_error() {
+        let config = GatewayConfig::parse(
+            "[server]\nhost = \"127.0.0.1\"\nport = 0\n\n[tls]\nnative_roots = false",
+        )
+        .unwrap();
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let gateway = tokio::spawn(run_gateway(config, shutdown_rx));
+
+        drop(shutdown_tx);
+        let err = tokio::time::timeout(std::time::Duration::from_secs(5), gateway)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("shutdown channel closed"),
+            "{}",
+            err
+        );
+    }
+}
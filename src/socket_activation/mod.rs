@@ -0,0 +1,136 @@
+//! Socket activation module
+//!
+//! This module hands listening sockets opened by systemd to servers with
+//! `socket_activation = true`, instead of binding them:
+//! - Inherited sockets are found through `LISTEN_PID` and `LISTEN_FDS`, and
+//!   start at file descriptor 3; sockets passed to another process (a
+//!   different `LISTEN_PID`) are ignored
+//! - Socket-activated servers take the inherited sockets in config order,
+//!   one per bind address; once they run out, the rest bind as usual
+//! - Each server listens on a duplicate of the inherited descriptor, so
+//!   servers restarted after a config change can take the socket again
+//! - Socket activation is only supported on Linux; elsewhere every server
+//!   binds its own addresses
+
+use std::io;
+
+/// First file descriptor passed by systemd (`SD_LISTEN_FDS_START`)
+pub const LISTEN_FDS_START: i32 = 3;
+
+/// Listening sockets inherited from the service manager
+#[derive(Debug, Default)]
+pub struct InheritedListeners {
+    fds: std::vec::IntoIter<i32>,
+}
+
+impl InheritedListeners {
+    /// Find the sockets passed to this process through `LISTEN_PID` and
+    /// `LISTEN_FDS` (none outside Linux)
+    pub fn from_env() -> Self {
+        if !cfg!(target_os = "linux") {
+            return Self::default();
+        }
+        let pid_var = std::env::var("LISTEN_PID").ok();
+        let fds_var = std::env::var("LISTEN_FDS").ok();
+        Self::from_fds(listen_fds(
+            pid_var.as_deref(),
+            fds_var.as_deref(),
+            std::process::id(),
+        ))
+    }
+
+    /// Hand out the given file descriptors, in order
+    pub fn from_fds(fds: Vec<i32>) -> Self {
+        Self {
+            fds: fds.into_iter(),
+        }
+    }
+
+    /// Take the next inherited socket as a listener, if any is left
+    pub fn next_listener(&mut self) -> Option<io::Result<tokio::net::TcpListener>> {
+        self.fds.next().map(listener_from_fd)
+    }
+}
+
+/// File descriptors passed to process `pid`, given the values of
+/// `LISTEN_PID` and `LISTEN_FDS`
+fn listen_fds(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> Vec<i32> {
+    let for_us = listen_pid.and_then(|value| value.trim().parse::<u32>().ok()) == Some(pid);
+    let count = listen_fds
+        .and_then(|value| value.trim().parse::<i32>().ok())
+        .unwrap_or(0);
+    if !for_us || count <= 0 {
+        return Vec::new();
+    }
+    (LISTEN_FDS_START..LISTEN_FDS_START + count).collect()
+}
+
+/// Listen on a duplicate of the socket `fd`, leaving `fd` open
+#[cfg(target_os = "linux")]
+fn listener_from_fd(fd: i32) -> io::Result<tokio::net::TcpListener> {
+    use std::os::fd::BorrowedFd;
+
+    // SAFETY: inherited descriptors stay open for the life of the process;
+    // the listener owns its own duplicate
+    let owned = unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()?;
+    let listener = std::net::TcpListener::from(owned);
+    listener.set_nonblocking(true)?;
+    tokio::net::TcpListener::from_std(listener)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn listener_from_fd(_fd: i32) -> io::Result<tokio::net::TcpListener> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "socket activation is only supported on Linux",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listen_fds() {
+        assert_eq!(listen_fds(Some("42"), Some("2"), 42), [3, 4]);
+        // Sockets passed to another process are not ours
+        assert!(listen_fds(Some("41"), Some("2"), 42).is_empty());
+        assert!(listen_fds(None, Some("2"), 42).is_empty());
+        assert!(listen_fds(Some("42"), None, 42).is_empty());
+        assert!(listen_fds(Some("42"), Some("0"), 42).is_empty());
+        assert!(listen_fds(Some("42"), Some("two"), 42).is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_listener_from_inherited_fd() {
+        use std::os::fd::AsRawFd;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // A socket bound by "systemd" before the gateway started
+        let inherited = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = inherited.local_addr().unwrap();
+        let fd = inherited.as_raw_fd();
+
+        let mut listeners = InheritedListeners::from_fds(vec![fd]);
+        let listener = listeners.next_listener().unwrap().unwrap();
+        assert_eq!(listener.local_addr().unwrap(), addr);
+        assert!(listeners.next_listener().is_none());
+
+        let accept = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            socket.write_all(b"hello").await.unwrap();
+        });
+        let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut buf = Vec::new();
+        client.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, b"hello");
+        accept.await.unwrap();
+
+        // The inherited descriptor survives the listener, so a restarted
+        // server can take it again
+        let mut listeners = InheritedListeners::from_fds(vec![fd]);
+        let listener = listeners.next_listener().unwrap().unwrap();
+        assert_eq!(listener.local_addr().unwrap(), addr);
+    }
+}
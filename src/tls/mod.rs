@@ -0,0 +1,117 @@
+//! Upstream TLS module
+//!
+//! This module builds the TLS client configuration shared by upstream
+//! connections:
+//! - The operating system's root certificates are trusted by default
+//! - `[tls] ca_bundle` adds roots from a PEM file
+//! - Failing to load roots is reported as a configuration error with a
+//!   hint, instead of aborting the process
+//! - With `native_roots = false` the system store isn't read at all, so the
+//!   gateway runs in minimal containers (with only the bundle's roots, or none)
+
+use crate::config::TlsConfig;
+use rustls::{ClientConfig, RootCertStore};
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::CertificateDer;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Loads the operating system's root certificates
+pub type NativeRootsLoader = fn() -> rustls_native_certs::CertificateResult;
+
+/// Build the TLS client configuration for upstream connections
+pub fn client_config(config: &TlsConfig) -> anyhow::Result<Arc<ClientConfig>> {
+    client_config_with(config, rustls_native_certs::load_native_certs)
+}
+
+/// Build the TLS client configuration, reading native roots with `load_native`
+pub fn client_config_with(
+    config: &TlsConfig,
+    load_native: NativeRootsLoader,
+) -> anyhow::Result<Arc<ClientConfig>> {
+    let mut roots = RootCertStore::empty();
+
+    if config.native_roots {
+        let native = load_native();
+        for error in &native.errors {
+            warn!("Error loading native root certificates: {}", error);
+        }
+        let (added, _) = roots.add_parsable_certificates(native.certs);
+        if added == 0 {
+            anyhow::bail!(
+                "Failed to load native root certificates{}; install the system CA \
+                 certificates (e.g. the `ca-certificates` package), or set \
+                 `tls.ca_bundle` and `tls.native_roots = false`",
+                native
+                    .errors
+                    .first()
+                    .map(|e| format!(" ({})", e))
+                    .unwrap_or_default()
+            );
+        }
+    }
+
+    if let Some(path) = &config.ca_bundle {
+        let certs = CertificateDer::pem_file_iter(path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| {
+                anyhow::anyhow!("Failed to read tls.ca_bundle {}: {}", path.display(), e)
+            })?;
+        let (added, _) = roots.add_parsable_certificates(certs);
+        if added == 0 {
+            anyhow::bail!("tls.ca_bundle {} contains no certificates", path.display());
+        }
+        info!(
+            "Loaded {} root certificate(s) from {}",
+            added,
+            path.display()
+        );
+    }
+
+    if roots.is_empty() {
+        warn!("No trusted root certificates; HTTPS upstreams will fail verification");
+    }
+
+    Ok(Arc::new(
+        ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_native_roots() -> rustls_native_certs::CertificateResult {
+        rustls_native_certs::CertificateResult::default()
+    }
+
+    #[test]
+    fn test_missing_native_roots_is_an_error() {
+        let err = client_config_with(&TlsConfig::default(), no_native_roots).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("Failed to load native root certificates"));
+        assert!(message.contains("ca-certificates"));
+    }
+
+    #[test]
+    fn test_native_roots_can_be_disabled() {
+        let config = TlsConfig {
+            native_roots: false,
+            ca_bundle: None,
+        };
+        assert!(client_config_with(&config, no_native_roots).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_ca_bundle() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let config = TlsConfig {
+            native_roots: false,
+            ca_bundle: Some(file.path().to_path_buf()),
+        };
+        let err = client_config_with(&config, no_native_roots).unwrap_err();
+        assert!(err.to_string().contains("contains no certificates"));
+    }
+}
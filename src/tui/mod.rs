@@ -5,11 +5,18 @@
 //! - Health check status
 //! - Configuration overview
 //! - Route information
+//! - API key pool statistics
+//! - Live mode, reading a running gateway's `/gateway/info` and `/metrics`
+//!   instead of the local configuration
+//! - An error banner while the local configuration file is invalid, keeping
+//!   the last valid configuration on screen until the file is fixed
 
-use crate::config::GatewayConfig;
-use crate::health::HealthChecker;
-use crate::metrics::GatewayMetrics;
-use crate::proxy::ProxyRoute;
+use crate::admin::{GatewayInfo, INFO_PATH};
+use crate::api_key::{create_selector, mask_key};
+use crate::config::{GatewayConfig, RouteConfig};
+use crate::health::{format_uptime, HealthChecker, HealthResponse};
+use crate::metrics::{GatewayMetrics, MetricsSnapshot};
+use crate::proxy::{ProxyRoute, ProxyService};
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
     execute,
@@ -23,30 +30,54 @@ use ratatui::{
     widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Tabs, Wrap},
     Frame, Terminal,
 };
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::io;
 use std::sync::Arc;
-use tokio::time::Duration;
+use tokio::sync::watch;
+use tokio::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Interval between refreshes in live mode
+const LIVE_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Default interval between redraws, in milliseconds
+pub const DEFAULT_REFRESH_MS: u64 = 250;
+
+/// Get the selection one page below `selected`, stopping at the last item
+fn page_down(selected: usize, len: usize, page: usize) -> usize {
+    selected
+        .saturating_add(page.max(1))
+        .min(len.saturating_sub(1))
+}
+
+/// Get the selection one page above `selected`, stopping at the first item
+fn page_up(selected: usize, page: usize) -> usize {
+    selected.saturating_sub(page.max(1))
+}
 
 /// Tab selection
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Tab {
     Overview,
     Routes,
+    Pools,
     Config,
     Help,
 }
 
 impl Tab {
     fn titles() -> Vec<&'static str> {
-        vec!["Overview", "Routes", "Config", "Help"]
+        vec!["Overview", "Routes", "Pools", "Config", "Help"]
     }
 
     fn from_index(index: usize) -> Self {
         match index {
             0 => Tab::Overview,
             1 => Tab::Routes,
-            2 => Tab::Config,
-            3 => Tab::Help,
+            2 => Tab::Pools,
+            3 => Tab::Config,
+            4 => Tab::Help,
             _ => Tab::Overview,
         }
     }
@@ -55,23 +86,249 @@ impl Tab {
         match self {
             Tab::Overview => 0,
             Tab::Routes => 1,
-            Tab::Config => 2,
-            Tab::Help => 3,
+            Tab::Pools => 2,
+            Tab::Config => 3,
+            Tab::Help => 4,
         }
     }
 }
 
+impl std::str::FromStr for Tab {
+    type Err = String;
+
+    /// Parse a tab from its title, case-insensitively
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Tab::titles()
+            .iter()
+            .position(|title| title.eq_ignore_ascii_case(name.trim()))
+            .map(Tab::from_index)
+            .ok_or_else(|| {
+                let valid: Vec<String> = Tab::titles().iter().map(|t| t.to_lowercase()).collect();
+                format!(
+                    "unknown tab '{}', expected one of: {}",
+                    name,
+                    valid.join(", ")
+                )
+            })
+    }
+}
+
+/// Summary of an API key pool for display
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoolSummary {
+    pub name: String,
+    pub strategy: String,
+    pub enabled_keys: usize,
+    pub total_keys: usize,
+    pub keys: Vec<KeySummary>,
+}
+
+/// Summary of a single API key for display (the key value is masked)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeySummary {
+    pub masked_key: String,
+    pub weight: u32,
+    pub enabled: bool,
+    pub usage: u64,
+}
+
+/// Build pool summaries from the configuration and recorded metrics, sorted by pool name
+pub fn build_pool_summaries(config: &GatewayConfig, metrics: &GatewayMetrics) -> Vec<PoolSummary> {
+    let mut summaries: Vec<PoolSummary> = config
+        .api_key_pools
+        .iter()
+        .map(|(name, pool)| PoolSummary {
+            name: name.clone(),
+            strategy: pool.strategy.as_str().to_string(),
+            enabled_keys: pool.keys.iter().filter(|k| k.enabled).count(),
+            total_keys: pool.keys.len(),
+            keys: pool
+                .keys
+                .iter()
+                .map(|k| KeySummary {
+                    masked_key: mask_key(&k.key),
+                    weight: k.weight,
+                    enabled: k.enabled,
+                    usage: metrics.api_key_usage(&k.key),
+                })
+                .collect(),
+        })
+        .collect();
+    summaries.sort_by(|a, b| a.name.cmp(&b.name));
+    summaries
+}
+
+/// Build a metrics snapshot from a Prometheus text exposition
+///
+/// Sums `gateway_requests_total` over all series; series with a status of
+/// 400 or above count as errors.
+pub fn snapshot_from_prometheus(text: &str) -> MetricsSnapshot {
+    let mut total_requests = 0;
+    let mut total_errors = 0;
+    for line in text.lines() {
+        let Some(rest) = line.strip_prefix("gateway_requests_total") else {
+            continue;
+        };
+        let (labels, value) = match rest.strip_prefix('{').and_then(|r| r.split_once('}')) {
+            Some((labels, value)) => (labels, value),
+            None => ("", rest),
+        };
+        let Ok(count) = value.trim().parse::<f64>() else {
+            continue;
+        };
+        let count = count as u64;
+        total_requests += count;
+        let status = labels
+            .split(',')
+            .filter_map(|label| label.split_once('='))
+            .find(|(name, _)| name.trim() == "status")
+            .and_then(|(_, value)| value.trim_matches('"').parse::<u16>().ok());
+        if status.is_some_and(|status| status >= 400) {
+            total_errors += count;
+        }
+    }
+    MetricsSnapshot {
+        total_requests,
+        total_errors,
+        error_rate: if total_requests == 0 {
+            0.0
+        } else {
+            (total_errors as f64 / total_requests as f64) * 100.0
+        },
+    }
+}
+
+/// Get the counters accumulated since `baseline` was taken
+///
+/// If a counter went backwards (a live gateway restarted), the baseline is
+/// stale and the current values are returned as is.
+pub fn snapshot_since(current: &MetricsSnapshot, baseline: &MetricsSnapshot) -> MetricsSnapshot {
+    if current.total_requests < baseline.total_requests
+        || current.total_errors < baseline.total_errors
+    {
+        return current.clone();
+    }
+    let total_requests = current.total_requests - baseline.total_requests;
+    let total_errors = current.total_errors - baseline.total_errors;
+    MetricsSnapshot {
+        total_requests,
+        total_errors,
+        error_rate: if total_requests == 0 {
+            0.0
+        } else {
+            (total_errors as f64 / total_requests as f64) * 100.0
+        },
+    }
+}
+
+/// State of a running gateway as shown by the monitor in live mode
+#[derive(Clone)]
+pub struct LiveView {
+    pub config: GatewayConfig,
+    pub routes: Vec<ProxyRoute>,
+    pub pools: Vec<PoolSummary>,
+    pub health: HealthResponse,
+    pub metrics: MetricsSnapshot,
+}
+
+impl LiveView {
+    /// Map the gateway's info and metrics responses to the monitor's model
+    pub fn from_responses(info: GatewayInfo, metrics: MetricsSnapshot) -> anyhow::Result<Self> {
+        // Schema files live on the gateway's host; the monitor only lists routes
+        let display: Vec<_> = info
+            .config
+            .routes
+            .iter()
+            .cloned()
+            .map(|route| RouteConfig {
+                body_schema: None,
+                ..route
+            })
+            .collect();
+        let routes = ProxyService::routes_from_config(&display, &HashMap::new())?;
+        Ok(Self {
+            config: info.config,
+            routes,
+            pools: info.pools,
+            health: info.health,
+            metrics,
+        })
+    }
+}
+
+/// Client for a running gateway's info and metrics endpoints
+pub struct LiveGateway {
+    client: reqwest::Client,
+    url: String,
+    token: Option<(String, String)>,
+}
+
+impl LiveGateway {
+    /// Create a client for the gateway at `url` (including any base path)
+    ///
+    /// `token` is sent in `token_header` to pass the master access token guard.
+    pub fn new(url: &str, token_header: &str, token: Option<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            token: token.map(|token| (token_header.to_string(), token)),
+        }
+    }
+
+    /// Gateway URL
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    async fn get(&self, path: &str) -> anyhow::Result<reqwest::Response> {
+        let mut request = self.client.get(format!("{}{}", self.url, path));
+        if let Some((header, token)) = &self.token {
+            request = request.header(header, token);
+        }
+        Ok(request.send().await?.error_for_status()?)
+    }
+
+    /// Fetch the gateway's current state
+    pub async fn fetch(&self) -> anyhow::Result<LiveView> {
+        let info: GatewayInfo = self.get(INFO_PATH).await?.json().await?;
+        let metrics = if info.config.metrics.enabled {
+            let text = self.get(&info.config.metrics.path).await?.text().await?;
+            snapshot_from_prometheus(&text)
+        } else {
+            snapshot_from_prometheus("")
+        };
+        LiveView::from_responses(info, metrics)
+    }
+}
+
 /// TUI Monitor application
 pub struct MonitorApp {
     config: GatewayConfig,
     metrics: Arc<GatewayMetrics>,
     health: Arc<HealthChecker>,
     routes: Vec<ProxyRoute>,
+    live: Option<LiveGateway>,
+    live_view: Option<LiveView>,
+    last_refresh: Instant,
     current_tab: Tab,
     route_list_state: ListState,
+    route_page_size: usize,
+    refresh: Duration,
+    /// Overview counters when `r` was last pressed, and the time of the reset
+    counters_baseline: Option<(MetricsSnapshot, Instant)>,
+    config_file: Option<ConfigFile>,
+    /// Why the configuration file couldn't be loaded, if it is invalid
+    config_error: Option<String>,
     should_quit: bool,
 }
 
+/// Local configuration file reloaded whenever the watcher flags a change
+struct ConfigFile {
+    path: String,
+    profile: Option<String>,
+    changed: watch::Sender<bool>,
+}
+
 impl MonitorApp {
     /// Create a new monitor application
     pub fn new(
@@ -90,12 +347,169 @@ impl MonitorApp {
             metrics,
             health,
             routes,
+            live: None,
+            live_view: None,
+            last_refresh: Instant::now(),
             current_tab: Tab::Overview,
             route_list_state,
+            route_page_size: 1,
+            refresh: Duration::from_millis(DEFAULT_REFRESH_MS),
+            counters_baseline: None,
+            config_file: None,
+            config_error: None,
             should_quit: false,
         }
     }
 
+    /// Set the interval between redraws
+    pub fn with_refresh(mut self, refresh: Duration) -> Self {
+        self.refresh = refresh;
+        self
+    }
+
+    /// Open on the given tab instead of the Overview
+    pub fn with_tab(mut self, tab: Tab) -> Self {
+        self.current_tab = tab;
+        self
+    }
+
+    /// Show a running gateway instead of the local configuration
+    pub fn with_live(mut self, gateway: LiveGateway, view: LiveView) -> Self {
+        self.live = Some(gateway);
+        self.set_live_view(view);
+        self
+    }
+
+    /// Reload the configuration file each time `changed` turns `true`,
+    /// resetting it to `false` once reloaded
+    pub fn with_config_file(
+        mut self,
+        path: &str,
+        profile: Option<&str>,
+        changed: watch::Sender<bool>,
+    ) -> Self {
+        self.config_file = Some(ConfigFile {
+            path: path.to_string(),
+            profile: profile.map(str::to_string),
+            changed,
+        });
+        self
+    }
+
+    /// Show why the configuration couldn't be loaded
+    pub fn with_config_error(mut self, error: String) -> Self {
+        self.config_error = Some(error);
+        self
+    }
+
+    fn set_live_view(&mut self, view: LiveView) {
+        self.config = view.config.clone();
+        self.set_routes(view.routes.clone());
+        self.live_view = Some(view);
+        self.last_refresh = Instant::now();
+    }
+
+    fn set_routes(&mut self, routes: Vec<ProxyRoute>) {
+        self.routes = routes;
+        let selected = match self.route_list_state.selected() {
+            _ if self.routes.is_empty() => None,
+            Some(i) => Some(i.min(self.routes.len() - 1)),
+            None => Some(0),
+        };
+        self.route_list_state.select(selected);
+    }
+
+    /// Show a freshly loaded configuration, or keep the current one and
+    /// show why loading failed
+    fn apply_config(&mut self, result: anyhow::Result<GatewayConfig>) {
+        match result {
+            Ok(config) => {
+                let selectors: HashMap<_, _> = config
+                    .api_key_pools
+                    .iter()
+                    .map(|(name, pool)| (name.clone(), create_selector(pool)))
+                    .collect();
+                match ProxyService::routes_from_config(&config.routes, &selectors) {
+                    Ok(routes) => {
+                        self.set_routes(routes);
+                        self.config = config;
+                        self.config_error = None;
+                    }
+                    Err(e) => self.config_error = Some(format!("{:#}", e)),
+                }
+            }
+            Err(e) => self.config_error = Some(format!("{:#}", e)),
+        }
+    }
+
+    /// Reload the configuration file if the watcher flagged a change
+    fn reload_config(&mut self) {
+        let Some(file) = &self.config_file else {
+            return;
+        };
+        if !*file.changed.borrow() {
+            return;
+        }
+        let result = GatewayConfig::from_file_with_profile(&file.path, file.profile.as_deref());
+        file.changed.send_replace(false);
+        if result.is_ok() {
+            info!("Reloaded configuration from {}", file.path);
+        }
+        self.apply_config(result);
+    }
+
+    /// Re-fetch the live gateway's state once the refresh interval passed
+    async fn refresh_live(&mut self) {
+        let Some(gateway) = &self.live else {
+            return;
+        };
+        if self.last_refresh.elapsed() < LIVE_REFRESH_INTERVAL {
+            return;
+        }
+        match gateway.fetch().await {
+            Ok(view) => self.set_live_view(view),
+            Err(e) => {
+                warn!("Failed to refresh from {}: {}", gateway.url(), e);
+                self.last_refresh = Instant::now();
+            }
+        }
+    }
+
+    fn metrics_snapshot(&self) -> MetricsSnapshot {
+        match &self.live_view {
+            Some(view) => view.metrics.clone(),
+            None => self.metrics.snapshot(),
+        }
+    }
+
+    /// Counters shown in the Overview (since the last reset, if any)
+    fn overview_snapshot(&self) -> MetricsSnapshot {
+        let current = self.metrics_snapshot();
+        match &self.counters_baseline {
+            Some((baseline, _)) => snapshot_since(&current, baseline),
+            None => current,
+        }
+    }
+
+    /// Start counting the Overview counters from the current values
+    fn reset_counters(&mut self) {
+        self.counters_baseline = Some((self.metrics_snapshot(), Instant::now()));
+    }
+
+    fn health_response(&self) -> HealthResponse {
+        match &self.live_view {
+            Some(view) => view.health.clone(),
+            None => self.health.liveness(),
+        }
+    }
+
+    fn pool_summaries(&self) -> Vec<PoolSummary> {
+        match &self.live_view {
+            Some(view) => view.pools.clone(),
+            None => build_pool_summaries(&self.config, &self.metrics),
+        }
+    }
+
     /// Run the TUI application
     pub async fn run(&mut self) -> anyhow::Result<()> {
         enable_raw_mode()?;
@@ -119,9 +533,11 @@ impl MonitorApp {
 
     async fn run_app<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> anyhow::Result<()> {
         loop {
+            self.refresh_live().await;
+            self.reload_config();
             terminal.draw(|f| self.ui(f))?;
 
-            if event::poll(Duration::from_millis(250))? {
+            if event::poll(self.refresh)? {
                 if let Event::Key(key) = event::read()? {
                     if key.kind == KeyEventKind::Press {
                         self.handle_input(key.code);
@@ -143,12 +559,12 @@ impl MonitorApp {
                 self.should_quit = true;
             }
             KeyCode::Tab | KeyCode::Right => {
-                let next_index = (self.current_tab.index() + 1) % 4;
+                let next_index = (self.current_tab.index() + 1) % Tab::titles().len();
                 self.current_tab = Tab::from_index(next_index);
             }
             KeyCode::BackTab | KeyCode::Left => {
                 let prev_index = if self.current_tab.index() == 0 {
-                    3
+                    Tab::titles().len() - 1
                 } else {
                     self.current_tab.index() - 1
                 };
@@ -156,69 +572,118 @@ impl MonitorApp {
             }
             KeyCode::Char('1') => self.current_tab = Tab::Overview,
             KeyCode::Char('2') => self.current_tab = Tab::Routes,
-            KeyCode::Char('3') => self.current_tab = Tab::Config,
-            KeyCode::Char('4') | KeyCode::Char('h') => self.current_tab = Tab::Help,
-            KeyCode::Down | KeyCode::Char('j') => {
-                if self.current_tab == Tab::Routes && !self.routes.is_empty() {
-                    let i = match self.route_list_state.selected() {
-                        Some(i) => {
-                            if i >= self.routes.len() - 1 {
-                                0
-                            } else {
-                                i + 1
-                            }
+            KeyCode::Char('3') => self.current_tab = Tab::Pools,
+            KeyCode::Char('4') => self.current_tab = Tab::Config,
+            KeyCode::Char('5') | KeyCode::Char('h') => self.current_tab = Tab::Help,
+            KeyCode::Char('r') => self.reset_counters(),
+            KeyCode::Down | KeyCode::Char('j')
+                if self.current_tab == Tab::Routes && !self.routes.is_empty() =>
+            {
+                let i = match self.route_list_state.selected() {
+                    Some(i) => {
+                        if i >= self.routes.len() - 1 {
+                            0
+                        } else {
+                            i + 1
                         }
-                        None => 0,
-                    };
-                    self.route_list_state.select(Some(i));
-                }
+                    }
+                    None => 0,
+                };
+                self.route_list_state.select(Some(i));
             }
-            KeyCode::Up | KeyCode::Char('k') => {
-                if self.current_tab == Tab::Routes && !self.routes.is_empty() {
-                    let i = match self.route_list_state.selected() {
-                        Some(i) => {
-                            if i == 0 {
-                                self.routes.len() - 1
-                            } else {
-                                i - 1
-                            }
+            KeyCode::Up | KeyCode::Char('k')
+                if self.current_tab == Tab::Routes && !self.routes.is_empty() =>
+            {
+                let i = match self.route_list_state.selected() {
+                    Some(i) => {
+                        if i == 0 {
+                            self.routes.len() - 1
+                        } else {
+                            i - 1
                         }
-                        None => 0,
-                    };
-                    self.route_list_state.select(Some(i));
-                }
+                    }
+                    None => 0,
+                };
+                self.route_list_state.select(Some(i));
+            }
+            KeyCode::PageDown if self.current_tab == Tab::Routes && !self.routes.is_empty() => {
+                let i = self.route_list_state.selected().unwrap_or(0);
+                self.route_list_state.select(Some(page_down(
+                    i,
+                    self.routes.len(),
+                    self.route_page_size,
+                )));
+            }
+            KeyCode::PageUp if self.current_tab == Tab::Routes && !self.routes.is_empty() => {
+                let i = self.route_list_state.selected().unwrap_or(0);
+                self.route_list_state
+                    .select(Some(page_up(i, self.route_page_size)));
+            }
+            KeyCode::Char('g') if self.current_tab == Tab::Routes && !self.routes.is_empty() => {
+                self.route_list_state.select(Some(0));
+            }
+            KeyCode::Char('G') if self.current_tab == Tab::Routes && !self.routes.is_empty() => {
+                self.route_list_state.select(Some(self.routes.len() - 1));
             }
             _ => {}
         }
     }
 
     fn ui(&mut self, f: &mut Frame) {
+        // The error banner grows with the message, up to a few lines
+        let banner_height = self
+            .config_error
+            .as_ref()
+            .map_or(0, |e| e.lines().count().clamp(1, 4) as u16 + 2);
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .margin(1)
             .constraints([
-                Constraint::Length(3), // Title
-                Constraint::Length(3), // Tabs
-                Constraint::Min(0),    // Content
-                Constraint::Length(3), // Status bar
+                Constraint::Length(3),             // Title
+                Constraint::Length(banner_height), // Config error
+                Constraint::Length(3),             // Tabs
+                Constraint::Min(0),                // Content
+                Constraint::Length(3),             // Status bar
             ])
             .split(f.area());
 
         self.render_title(f, chunks[0]);
-        self.render_tabs(f, chunks[1]);
+        self.render_config_error(f, chunks[1]);
+        self.render_tabs(f, chunks[2]);
 
         match self.current_tab {
-            Tab::Overview => self.render_overview(f, chunks[2]),
-            Tab::Routes => self.render_routes(f, chunks[2]),
-            Tab::Config => self.render_config(f, chunks[2]),
-            Tab::Help => self.render_help(f, chunks[2]),
+            Tab::Overview => self.render_overview(f, chunks[3]),
+            Tab::Routes => self.render_routes(f, chunks[3]),
+            Tab::Pools => self.render_pools(f, chunks[3]),
+            Tab::Config => self.render_config(f, chunks[3]),
+            Tab::Help => self.render_help(f, chunks[3]),
         }
 
-        self.render_status_bar(f, chunks[3]);
+        self.render_status_bar(f, chunks[4]);
+    }
+
+    fn render_config_error(&self, f: &mut Frame, area: Rect) {
+        let Some(error) = &self.config_error else {
+            return;
+        };
+        let banner = Paragraph::new(error.as_str())
+            .style(Style::default().fg(Color::Red))
+            .wrap(Wrap { trim: true })
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Red))
+                    .title("⚠ Invalid configuration (showing the last valid one)"),
+            );
+        f.render_widget(banner, area);
     }
 
     fn render_title(&self, f: &mut Frame, area: Rect) {
-        let title = Paragraph::new("🚀 Open Gateway Monitor")
+        let title = match &self.live {
+            Some(gateway) => format!("🚀 Open Gateway Monitor (live: {})", gateway.url()),
+            None => "🚀 Open Gateway Monitor".to_string(),
+        };
+        let title = Paragraph::new(title)
             .style(
                 Style::default()
                     .fg(Color::Cyan)
@@ -263,10 +728,10 @@ impl MonitorApp {
             .split(area);
 
         // Left side: Metrics
-        let metrics = self.metrics.snapshot();
-        let health_response = self.health.liveness();
+        let metrics = self.overview_snapshot();
+        let health_response = self.health_response();
 
-        let metrics_text = vec![
+        let mut metrics_text = vec![
             Line::from(vec![
                 Span::styled("Total Requests: ", Style::default().fg(Color::Gray)),
                 Span::styled(
@@ -313,8 +778,24 @@ impl MonitorApp {
             ]),
         ];
 
+        let title = match &self.counters_baseline {
+            Some((_, reset_at)) => {
+                metrics_text.insert(
+                    0,
+                    Line::from(vec![
+                        Span::styled("Since Reset: ", Style::default().fg(Color::Gray)),
+                        Span::styled(
+                            format_uptime(reset_at.elapsed().as_secs()),
+                            Style::default().fg(Color::Cyan),
+                        ),
+                    ]),
+                );
+                "📊 Metrics (since reset)"
+            }
+            None => "📊 Metrics",
+        };
         let metrics_widget = Paragraph::new(metrics_text)
-            .block(Block::default().borders(Borders::ALL).title("📊 Metrics"))
+            .block(Block::default().borders(Borders::ALL).title(title))
             .wrap(Wrap { trim: true });
         f.render_widget(metrics_widget, chunks[0]);
 
@@ -345,7 +826,7 @@ impl MonitorApp {
             Line::from(vec![
                 Span::styled("Uptime: ", Style::default().fg(Color::Gray)),
                 Span::styled(
-                    self.health.uptime_formatted(),
+                    format_uptime(health_response.uptime_seconds),
                     Style::default().fg(Color::Cyan),
                 ),
             ]),
@@ -377,7 +858,7 @@ impl MonitorApp {
             Span::styled("Metrics: ", Style::default().fg(Color::Gray)),
             Span::styled(
                 if self.config.metrics.enabled {
-                    self.config.metrics.path.clone()
+                    self.config.routing.endpoint_path(&self.config.metrics.path)
                 } else {
                     "disabled".to_string()
                 },
@@ -416,6 +897,8 @@ impl MonitorApp {
             )
             .highlight_symbol(">> ");
 
+        // Page by the number of visible rows (inside the borders)
+        self.route_page_size = chunks[0].height.saturating_sub(2).max(1) as usize;
         f.render_stateful_widget(list, chunks[0], &mut self.route_list_state);
 
         // Right: Route details
@@ -457,6 +940,10 @@ impl MonitorApp {
                         Span::styled("API Key: ", Style::default().fg(Color::Gray)),
                         Span::styled(api_key, Style::default().fg(Color::Magenta)),
                     ]),
+                    Line::from(vec![
+                        Span::styled("Group: ", Style::default().fg(Color::Gray)),
+                        Span::styled(route.group.clone(), Style::default().fg(Color::White)),
+                    ]),
                     Line::from(""),
                     Line::from(vec![Span::styled(
                         route
@@ -483,6 +970,65 @@ impl MonitorApp {
         f.render_widget(detail, chunks[1]);
     }
 
+    fn render_pools(&self, f: &mut Frame, area: Rect) {
+        let summaries = self.pool_summaries();
+
+        let mut pool_text = Vec::new();
+        if summaries.is_empty() {
+            pool_text.push(Line::from("No API key pools configured"));
+        }
+
+        for pool in &summaries {
+            pool_text.push(Line::from(vec![
+                Span::styled(
+                    pool.name.clone(),
+                    Style::default()
+                        .fg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    format!(
+                        "  {} · {}/{} keys enabled",
+                        pool.strategy, pool.enabled_keys, pool.total_keys
+                    ),
+                    Style::default().fg(Color::Gray),
+                ),
+            ]));
+            for key in &pool.keys {
+                let (status, color) = if key.enabled {
+                    ("enabled", Color::Green)
+                } else {
+                    ("disabled", Color::Red)
+                };
+                pool_text.push(Line::from(vec![
+                    Span::styled(
+                        format!("  {:<12}", key.masked_key),
+                        Style::default().fg(Color::White),
+                    ),
+                    Span::styled(
+                        format!(" weight {:<4}", key.weight),
+                        Style::default().fg(Color::Gray),
+                    ),
+                    Span::styled(format!(" {:<9}", status), Style::default().fg(color)),
+                    Span::styled(
+                        format!(" {} request(s)", key.usage),
+                        Style::default().fg(Color::Magenta),
+                    ),
+                ]));
+            }
+            pool_text.push(Line::from(""));
+        }
+
+        let pools = Paragraph::new(pool_text)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("🔑 API Key Pools"),
+            )
+            .wrap(Wrap { trim: false });
+        f.render_widget(pools, area);
+    }
+
     fn render_config(&self, f: &mut Frame, area: Rect) {
         let mut config_text = vec![Line::from(Span::styled(
             "Servers Configuration",
@@ -501,8 +1047,8 @@ impl MonitorApp {
             let route_count = self.config.routes_for_server(server).len();
             config_text.push(Line::from(format!("  {}:", name)));
             config_text.push(Line::from(format!(
-                "    Address: {}:{}",
-                server.host, server.port
+                "    Address: {}",
+                server.bind_addrs().join(", ")
             )));
             config_text.push(Line::from(format!("    Timeout: {}s", server.timeout)));
             config_text.push(Line::from(format!("    Routes: {}", route_count)));
@@ -523,7 +1069,10 @@ impl MonitorApp {
                 "No"
             }
         )));
-        config_text.push(Line::from(format!("  Path: {}", self.config.metrics.path)));
+        config_text.push(Line::from(format!(
+            "  Path: {}",
+            self.config.routing.endpoint_path(&self.config.metrics.path)
+        )));
         config_text.push(Line::from(""));
         config_text.push(Line::from(Span::styled(
             "Health Configuration",
@@ -539,7 +1088,10 @@ impl MonitorApp {
                 "No"
             }
         )));
-        config_text.push(Line::from(format!("  Path: {}", self.config.health.path)));
+        config_text.push(Line::from(format!(
+            "  Path: {}",
+            self.config.routing.endpoint_path(&self.config.health.path)
+        )));
         config_text.push(Line::from(""));
         config_text.push(Line::from(Span::styled(
             "API Key Pools",
@@ -580,8 +1132,9 @@ impl MonitorApp {
             Line::from(""),
             Line::from("  Tab / →         Next tab"),
             Line::from("  Shift+Tab / ←   Previous tab"),
-            Line::from("  1-4             Jump to tab"),
+            Line::from("  1-5             Jump to tab"),
             Line::from("  h               Help tab"),
+            Line::from("  r               Reset Overview counters"),
             Line::from("  q / Esc         Quit"),
             Line::from(""),
             Line::from(Span::styled(
@@ -593,6 +1146,8 @@ impl MonitorApp {
             Line::from(""),
             Line::from("  ↑ / k           Previous route"),
             Line::from("  ↓ / j           Next route"),
+            Line::from("  PgUp / PgDn     Previous / next page"),
+            Line::from("  g / G           First / last route"),
             Line::from(""),
             Line::from(Span::styled(
                 "About",
@@ -620,7 +1175,9 @@ impl MonitorApp {
             Span::styled("q", Style::default().fg(Color::Yellow)),
             Span::raw(": Quit  "),
             Span::styled("h", Style::default().fg(Color::Yellow)),
-            Span::raw(": Help"),
+            Span::raw(": Help  "),
+            Span::styled("Refresh", Style::default().fg(Color::Yellow)),
+            Span::raw(format!(": {}ms", self.refresh.as_millis())),
         ]))
         .style(Style::default().fg(Color::Gray))
         .alignment(Alignment::Center)
@@ -628,3 +1185,291 @@ impl MonitorApp {
         f.render_widget(status, area);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_down_stops_at_last_route() {
+        assert_eq!(page_down(0, 50, 10), 10);
+        assert_eq!(page_down(45, 50, 10), 49);
+        assert_eq!(page_down(49, 50, 10), 49);
+        assert_eq!(page_down(0, 1, 10), 0);
+        // An unrendered list still moves by one row
+        assert_eq!(page_down(3, 50, 0), 4);
+    }
+
+    #[test]
+    fn test_page_up_stops_at_first_route() {
+        assert_eq!(page_up(25, 10), 15);
+        assert_eq!(page_up(4, 10), 0);
+        assert_eq!(page_up(0, 10), 0);
+        assert_eq!(page_up(3, 0), 2);
+    }
+
+    #[test]
+    fn test_home_and_end_keys() {
+        let routes = ProxyService::routes_from_config(
+            &(0..30)
+                .map(|i| {
+                    toml::from_str(&format!(
+                        "path = \"/r{}/*\"\ntarget = \"http://localhost:8080\"",
+                        i
+                    ))
+                    .unwrap()
+                })
+                .collect::<Vec<_>>(),
+            &HashMap::new(),
+        )
+        .unwrap();
+        let mut app = MonitorApp::new(
+            GatewayConfig::default(),
+            Arc::new(GatewayMetrics::new()),
+            Arc::new(HealthChecker::new()),
+            routes,
+        );
+        app.current_tab = Tab::Routes;
+        app.route_page_size = 10;
+
+        app.handle_input(KeyCode::Char('G'));
+        assert_eq!(app.route_list_state.selected(), Some(29));
+        app.handle_input(KeyCode::PageDown);
+        assert_eq!(app.route_list_state.selected(), Some(29));
+        app.handle_input(KeyCode::PageUp);
+        assert_eq!(app.route_list_state.selected(), Some(19));
+        app.handle_input(KeyCode::Char('g'));
+        assert_eq!(app.route_list_state.selected(), Some(0));
+        app.handle_input(KeyCode::PageDown);
+        assert_eq!(app.route_list_state.selected(), Some(10));
+    }
+
+    #[test]
+    fn test_build_pool_summaries() {
+        let toml = r#"
+[api_key_pools.zeta]
+strategy = "weight"
+keys = [
+    { key = "Bearer sk-zeta-secret-0001", weight = 3 },
+    { key = "Bearer sk-zeta-secret-0002", weight = 1, enabled = false },
+]
+
+[api_key_pools.alpha]
+keys = [{ key = "alpha-secret-key-9999" }]
+"#;
+        let config = GatewayConfig::parse(toml).unwrap();
+        let metrics = GatewayMetrics::new();
+        metrics.record_api_key_usage("Bearer sk-zeta-secret-0001", "/api");
+        metrics.record_api_key_usage("Bearer sk-zeta-secret-0001", "/api");
+
+        let summaries = build_pool_summaries(&config, &metrics);
+        assert_eq!(summaries.len(), 2);
+
+        assert_eq!(summaries[0].name, "alpha");
+        assert_eq!(summaries[0].strategy, "round_robin");
+        assert_eq!(summaries[0].enabled_keys, 1);
+        assert_eq!(summaries[0].total_keys, 1);
+
+        let zeta = &summaries[1];
+        assert_eq!(zeta.strategy, "weight");
+        assert_eq!(zeta.enabled_keys, 1);
+        assert_eq!(zeta.total_keys, 2);
+        assert_eq!(
+            zeta.keys[0],
+            KeySummary {
+                masked_key: "****0001".to_string(),
+                weight: 3,
+                enabled: true,
+                usage: 2,
+            }
+        );
+        assert!(!zeta.keys[1].enabled);
+        assert_eq!(zeta.keys[1].usage, 0);
+
+        // Key values are never exposed
+        for pool in &summaries {
+            for key in &pool.keys {
+                assert!(!key.masked_key.contains("secret"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_snapshot_from_prometheus() {
+        let metrics = GatewayMetrics::new();
+        metrics.record_request("GET", "/api/users", 200, Duration::from_millis(5));
+        metrics.record_request("GET", "/api/users", 200, Duration::from_millis(5));
+        metrics.record_request("POST", "/api/users", 502, Duration::from_millis(5));
+
+        let snapshot = snapshot_from_prometheus(&metrics.prometheus_output().unwrap());
+        assert_eq!(snapshot.total_requests, 3);
+        assert_eq!(snapshot.total_errors, 1);
+        assert!((snapshot.error_rate - 100.0 / 3.0).abs() < 1e-9);
+
+        assert_eq!(snapshot_from_prometheus("").total_requests, 0);
+    }
+
+    #[test]
+    fn test_snapshot_since() {
+        let snapshot = |total_requests, total_errors| MetricsSnapshot {
+            total_requests,
+            total_errors,
+            error_rate: 0.0,
+        };
+
+        let delta = snapshot_since(&snapshot(110, 12), &snapshot(100, 10));
+        assert_eq!((delta.total_requests, delta.total_errors), (10, 2));
+        assert!((delta.error_rate - 20.0).abs() < 1e-9);
+
+        let unchanged = snapshot_since(&snapshot(100, 10), &snapshot(100, 10));
+        assert_eq!((unchanged.total_requests, unchanged.total_errors), (0, 0));
+        assert_eq!(unchanged.error_rate, 0.0);
+
+        // Counters that went backwards start over from the current values
+        let restarted = snapshot_since(&snapshot(5, 1), &snapshot(100, 10));
+        assert_eq!((restarted.total_requests, restarted.total_errors), (5, 1));
+    }
+
+    #[test]
+    fn test_reset_key_counts_from_baseline() {
+        let metrics = Arc::new(GatewayMetrics::new());
+        let mut app = MonitorApp::new(
+            GatewayConfig::default(),
+            metrics.clone(),
+            Arc::new(HealthChecker::new()),
+            Vec::new(),
+        );
+        metrics.record_request("GET", "/api", 200, Duration::from_millis(5));
+        metrics.record_request("GET", "/api", 500, Duration::from_millis(5));
+        assert_eq!(app.overview_snapshot().total_requests, 2);
+
+        app.handle_input(KeyCode::Char('r'));
+        assert_eq!(app.overview_snapshot().total_requests, 0);
+
+        metrics.record_request("GET", "/api", 200, Duration::from_millis(5));
+        let since = app.overview_snapshot();
+        assert_eq!((since.total_requests, since.total_errors), (1, 0));
+        // The underlying counters keep counting
+        assert_eq!(app.metrics_snapshot().total_requests, 3);
+    }
+
+    #[test]
+    fn test_with_tab_sets_current_tab() {
+        let app = MonitorApp::new(
+            GatewayConfig::default(),
+            Arc::new(GatewayMetrics::new()),
+            Arc::new(HealthChecker::new()),
+            Vec::new(),
+        );
+        assert_eq!(app.current_tab, Tab::Overview);
+        let app = app.with_tab("Routes".parse().unwrap());
+        assert_eq!(app.current_tab, Tab::Routes);
+
+        assert_eq!("pools".parse::<Tab>(), Ok(Tab::Pools));
+        assert_eq!(
+            "metrics".parse::<Tab>().unwrap_err(),
+            "unknown tab 'metrics', expected one of: overview, routes, pools, config, help"
+        );
+    }
+
+    #[test]
+    fn test_invalid_config_shows_error_then_recovers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let path_str = path.to_str().unwrap();
+        std::fs::write(&path, "[[routes]]\npath = \"/api/*\"\n").unwrap();
+
+        // Started from an invalid file: empty config and an error banner
+        let error = GatewayConfig::from_file(path_str).unwrap_err();
+        let (changed, _) = watch::channel(false);
+        let mut app = MonitorApp::new(
+            GatewayConfig::default(),
+            Arc::new(GatewayMetrics::new()),
+            Arc::new(HealthChecker::new()),
+            Vec::new(),
+        )
+        .with_config_file(path_str, None, changed.clone())
+        .with_config_error(format!("{:#}", error));
+        assert!(app.config_error.is_some());
+        assert!(app.routes.is_empty());
+
+        // Nothing is reloaded until the watcher flags a change
+        std::fs::write(
+            &path,
+            "[[routes]]\npath = \"/api/*\"\ntarget = \"http://localhost:3000\"\n",
+        )
+        .unwrap();
+        app.reload_config();
+        assert!(app.config_error.is_some());
+
+        changed.send_replace(true);
+        app.reload_config();
+        assert!(app.config_error.is_none());
+        assert_eq!(app.routes.len(), 1);
+        assert_eq!(app.route_list_state.selected(), Some(0));
+        assert!(!*changed.borrow());
+
+        // Breaking the file again keeps the last valid routes on screen
+        std::fs::write(&path, "not toml").unwrap();
+        changed.send_replace(true);
+        app.reload_config();
+        assert!(app.config_error.is_some());
+        assert_eq!(app.routes.len(), 1);
+        assert_eq!(app.config.routes[0].path, "/api/*");
+    }
+
+    #[test]
+    fn test_live_view_from_responses() {
+        let info: GatewayInfo = serde_json::from_value(serde_json::json!({
+            "version": "1.2.3",
+            "config": {
+                "routes": [
+                    { "name": "users", "path": "/users/*", "target": "http://users" },
+                    { "name": "orders", "path": "/orders/*", "target": "http://orders", "enabled": false },
+                ],
+                "api_key_pools": { "default": { "keys": [{ "key": "****0001" }] } },
+            },
+            "pools": [{
+                "name": "default",
+                "strategy": "round_robin",
+                "enabled_keys": 1,
+                "total_keys": 1,
+                "keys": [{ "masked_key": "****0001", "weight": 1, "enabled": true, "usage": 7 }],
+            }],
+            "health": { "status": "unhealthy", "version": "1.2.3", "uptime_seconds": 90, "message": "Service is draining" },
+        }))
+        .unwrap();
+        let metrics = snapshot_from_prometheus(
+            "gateway_requests_total{method=\"GET\",path=\"/users\",status=\"200\"} 4",
+        );
+
+        let view = LiveView::from_responses(info, metrics).unwrap();
+        let app = MonitorApp::new(
+            GatewayConfig::default(),
+            Arc::new(GatewayMetrics::new()),
+            Arc::new(HealthChecker::new()),
+            Vec::new(),
+        )
+        .with_live(
+            LiveGateway::new("http://gateway/", "Authorization", None),
+            view,
+        );
+
+        // Disabled routes (including runtime toggles) aren't listed
+        assert_eq!(app.routes.len(), 1);
+        assert_eq!(app.routes[0].name.as_deref(), Some("users"));
+        assert_eq!(app.route_list_state.selected(), Some(0));
+        assert_eq!(app.pool_summaries()[0].keys[0].usage, 7);
+        assert_eq!(app.metrics_snapshot().total_requests, 4);
+        let health = app.health_response();
+        assert_eq!(health.status, crate::health::HealthStatus::Unhealthy);
+        assert_eq!(health.uptime_seconds, 90);
+        assert_eq!(app.live.as_ref().unwrap().url(), "http://gateway");
+    }
+
+    #[test]
+    fn test_tab_navigation_includes_pools() {
+        assert_eq!(Tab::from_index(Tab::Pools.index()), Tab::Pools);
+        assert_eq!(Tab::titles()[Tab::Pools.index()], "Pools");
+    }
+}
@@ -0,0 +1,344 @@
+//! Config file watcher module
+//!
+//! This module watches the configuration file for changes and signals the
+//! server loop to reload:
+//! - Bursts of file system events are debounced into a single reload
+//! - Events arriving while a reload is in progress are ignored
+//! - Atomic saves (write to a temp file, then rename over the config) are
+//!   detected, and the watch is re-established when the file is replaced
+//! - A deleted config file leaves the last valid configuration in place;
+//!   the file is polled for, and watched again once it reappears
+//! - New configuration is validated before a reload is triggered
+
+use crate::config::{profile_path, GatewayConfig};
+use notify::{Event, RecursiveMode, Watcher};
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tracing::{error, info, warn};
+
+/// How often a missing config file is checked for
+const MISSING_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Watch config file for changes and trigger reload
+///
+/// Sends `true` on `shutdown_tx` when the config file (or the profile file
+/// layered over it) changed and the new configuration is valid. The server
+/// loop resets the value to `false` once the reload has completed.
+pub async fn watch_config_file(
+    config_path: &str,
+    profile: Option<&str>,
+    debounce: Duration,
+    shutdown_tx: watch::Sender<bool>,
+) {
+    let path = Path::new(config_path);
+    let parent_dir = path.parent().unwrap_or(Path::new("."));
+    let file_name = |p: &Path| {
+        p.file_name()
+            .and_then(|f| f.to_str())
+            .unwrap_or("")
+            .to_string()
+    };
+    let mut config_file_names = vec![file_name(path)];
+    if let Some(profile) = profile {
+        config_file_names.push(file_name(&profile_path(path, profile)));
+    }
+
+    let (tx, mut rx) = mpsc::channel::<Result<Event, notify::Error>>(10);
+
+    let mut watcher = match notify::recommended_watcher(move |res| {
+        // Use try_send to avoid blocking the file system event thread
+        // If the channel is full, we drop the event (the next event will still trigger reload)
+        let _ = tx.try_send(res);
+    }) {
+        Ok(w) => w,
+        Err(e) => {
+            error!("Failed to create file watcher: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = watcher.watch(parent_dir, RecursiveMode::NonRecursive) {
+        error!("Failed to watch config directory: {}", e);
+        return;
+    }
+
+    info!("Watching {} for changes", config_path);
+
+    let mut current_identity = file_identity(path);
+    let mut missing = current_identity.is_none();
+
+    loop {
+        // While the file is missing, also poll for it: its directory may
+        // have been replaced too, taking the watch with it
+        let batch = if missing {
+            tokio::select! {
+                batch = next_batch(&mut rx, debounce) => batch,
+                _ = tokio::time::sleep(MISSING_POLL_INTERVAL) => Some(Vec::new()),
+            }
+        } else {
+            next_batch(&mut rx, debounce).await
+        };
+        let Some(batch) = batch else {
+            break;
+        };
+
+        let mut changed = false;
+        for result in batch {
+            match result {
+                Ok(event) => {
+                    if is_config_change(&event, &config_file_names)
+                        || is_config_removal(&event, &config_file_names)
+                    {
+                        changed = true;
+                    }
+                }
+                Err(e) => {
+                    error!("File watch error: {}", e);
+                }
+            }
+        }
+
+        let identity = file_identity(path);
+        if identity.is_none() {
+            if !missing {
+                warn!(
+                    "Config file {} is missing; serving the last valid configuration until it reappears",
+                    config_path
+                );
+                missing = true;
+            }
+            current_identity = None;
+            continue;
+        }
+
+        // Editors that save atomically replace the file with a new inode,
+        // and a recreated file may live in a recreated directory; re-arm the
+        // watch so we keep receiving events for the new file
+        if identity != current_identity {
+            if missing {
+                info!(
+                    "Config file {} reappeared, re-establishing watch",
+                    config_path
+                );
+                missing = false;
+            } else {
+                info!(
+                    "Config file {} was replaced, re-establishing watch",
+                    config_path
+                );
+            }
+            let _ = watcher.unwatch(parent_dir);
+            if let Err(e) = watcher.watch(parent_dir, RecursiveMode::NonRecursive) {
+                error!("Failed to re-watch config directory: {}", e);
+            }
+            changed = true;
+        }
+        current_identity = identity;
+
+        if !changed {
+            continue;
+        }
+
+        // A previous reload has not completed yet
+        if *shutdown_tx.borrow() {
+            info!("Config file changed during reload, ignoring");
+            continue;
+        }
+
+        // Validate new config before triggering reload
+        match GatewayConfig::from_file_with_profile(config_path, profile) {
+            Ok(_) => {
+                info!("Config file changed, triggering reload...");
+                let _ = shutdown_tx.send(true);
+            }
+            Err(e) => {
+                warn!("Config file changed but invalid: {}", e);
+                warn!("Keeping current configuration");
+            }
+        }
+    }
+}
+
+/// Check if the event concerns one of our config files
+fn is_config_file_event(event: &Event, config_file_names: &[String]) -> bool {
+    event.paths.iter().any(|p| {
+        p.file_name()
+            .and_then(|n| n.to_str())
+            .map(|n| config_file_names.iter().any(|name| name == n))
+            .unwrap_or(false)
+    })
+}
+
+/// Check if the event is a modification of our config files
+fn is_config_change(event: &Event, config_file_names: &[String]) -> bool {
+    is_config_file_event(event, config_file_names)
+        && matches!(
+            event.kind,
+            notify::EventKind::Modify(_) | notify::EventKind::Create(_)
+        )
+}
+
+/// Check if the event removes one of our config files
+fn is_config_removal(event: &Event, config_file_names: &[String]) -> bool {
+    matches!(event.kind, notify::EventKind::Remove(_))
+        && is_config_file_event(event, config_file_names)
+}
+
+/// Get an identifier for the file currently at `path`
+///
+/// On Unix this is the device and inode number, which changes when the file
+/// is replaced by a rename. Other platforms fall back to the modification time.
+fn file_identity(path: &Path) -> Option<(u64, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some((metadata.dev(), metadata.ino()))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let modified = metadata
+            .modified()
+            .ok()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?;
+        Some((modified.as_secs(), u64::from(modified.subsec_nanos())))
+    }
+}
+
+/// Wait for the next burst of events
+///
+/// Blocks until an event arrives, then keeps collecting events until no new
+/// event has been received for `window`. Returns `None` once the channel is
+/// closed and drained.
+pub async fn next_batch<T>(rx: &mut mpsc::Receiver<T>, window: Duration) -> Option<Vec<T>> {
+    let first = rx.recv().await?;
+    let mut batch = vec![first];
+
+    loop {
+        match tokio::time::timeout(window, rx.recv()).await {
+            Ok(Some(event)) => batch.push(event),
+            Ok(None) | Err(_) => return Some(batch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_burst_triggers_single_batch() {
+        let (tx, mut rx) = mpsc::channel::<u32>(16);
+
+        tokio::spawn(async move {
+            for i in 0..5 {
+                tx.send(i).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+
+        let mut triggers = Vec::new();
+        while let Some(batch) = next_batch(&mut rx, Duration::from_millis(100)).await {
+            triggers.push(batch);
+        }
+
+        assert_eq!(triggers.len(), 1);
+        assert_eq!(triggers[0], vec![0, 1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_separate_bursts_trigger_separately() {
+        let (tx, mut rx) = mpsc::channel::<u32>(16);
+
+        tokio::spawn(async move {
+            tx.send(1).await.unwrap();
+            tx.send(2).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            tx.send(3).await.unwrap();
+        });
+
+        let mut triggers = Vec::new();
+        while let Some(batch) = next_batch(&mut rx, Duration::from_millis(50)).await {
+            triggers.push(batch);
+        }
+
+        assert_eq!(triggers, vec![vec![1, 2], vec![3]]);
+    }
+
+    const VALID_CONFIG: &str = r#"
+[[routes]]
+path = "/api/*"
+target = "http://localhost:8081"
+"#;
+
+    #[tokio::test]
+    async fn test_reload_on_rename_replace() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, VALID_CONFIG).unwrap();
+
+        let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+        let path = config_path.to_str().unwrap().to_string();
+        let handle = tokio::spawn(async move {
+            watch_config_file(&path, None, Duration::from_millis(50), shutdown_tx).await;
+        });
+
+        // Give the watcher time to start
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        // Save atomically: write a temp file and rename it over the config
+        let temp_path = dir.path().join(".config.toml.tmp");
+        std::fs::write(&temp_path, VALID_CONFIG).unwrap();
+        std::fs::rename(&temp_path, &config_path).unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), shutdown_rx.changed())
+            .await
+            .expect("reload was not triggered")
+            .unwrap();
+        assert!(*shutdown_rx.borrow());
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_reload_when_deleted_config_reappears() {
+        let dir = tempfile::tempdir().unwrap();
+        // The directory itself is deleted too, taking the watch with it
+        let config_dir = dir.path().join("conf");
+        let config_path = config_dir.join("config.toml");
+        for recreate_dir in [false, true] {
+            std::fs::create_dir_all(&config_dir).unwrap();
+            std::fs::write(&config_path, VALID_CONFIG).unwrap();
+
+            let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+            let path = config_path.to_str().unwrap().to_string();
+            let handle = tokio::spawn(async move {
+                watch_config_file(&path, None, Duration::from_millis(50), shutdown_tx).await;
+            });
+            tokio::time::sleep(Duration::from_millis(200)).await;
+
+            if recreate_dir {
+                std::fs::remove_dir_all(&config_dir).unwrap();
+            } else {
+                std::fs::remove_file(&config_path).unwrap();
+            }
+            // Deleting the file keeps the current configuration
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            assert!(!shutdown_rx.has_changed().unwrap());
+
+            std::fs::create_dir_all(&config_dir).unwrap();
+            std::fs::write(&config_path, VALID_CONFIG).unwrap();
+            tokio::time::timeout(Duration::from_secs(5), shutdown_rx.changed())
+                .await
+                .expect("reload was not triggered")
+                .unwrap();
+            assert!(*shutdown_rx.borrow());
+
+            handle.abort();
+        }
+    }
+}